@@ -0,0 +1,27 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+fn main() {
+        /* The crash guard needs `sigsetjmp`, which can't be called from Rust. */
+        #[cfg(feature = "crash_guard")]
+        if std::env::var_os("CARGO_CFG_UNIX").is_some() {
+                println!("cargo:rerun-if-changed=src/isolation.c");
+                cc::Build::new()
+                        .file("src/isolation.c")
+                        .flag_if_supported("-fexceptions")
+                        .compile("vplugin_guard");
+        }
+}
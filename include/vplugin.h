@@ -0,0 +1,133 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+#ifndef VPLUGIN_H
+#define VPLUGIN_H
+
+/* Generated with cbindgen from src/capi.rs, don't edit it by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/*
+ ## PluginManager
+ A `PluginManager` is responsible for managing all loaded plugins,
+ like deploying them, attaching hooks, cleaning up the filesystem, etc.
+ You should have it as a singleton instance in your application.
+
+ */
+typedef struct vplugin_manager vplugin_manager;
+
+/*
+ ## PluginHandle
+ A lightweight, `Copy`-able reference to a plugin owned by a
+ [`PluginManager`](crate::plugin_manager::PluginManager). Handles can be
+ stored anywhere in the application and passed to the manager's functions
+ instead of `&mut Plugin`.
+
+ Every handle carries a generation: Once the plugin it refers to is removed,
+ the handle stays invalid even if a new plugin reuses the same slot.
+ */
+typedef struct vplugin_handle {
+  uint32_t index;
+  uint32_t generation;
+} vplugin_handle;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ Returns the error of the last function that failed on the calling thread, or `NULL`
+ if none did. The string stays valid until another function fails on the same thread.
+ */
+const char *vplugin_last_error(void);
+
+/*
+ Creates a plugin manager with the default options, see `PluginManager::new`. It has
+ to be freed with `vplugin_manager_free`.
+ */
+struct vplugin_manager *vplugin_manager_new(void);
+
+/*
+ Frees `manager`, unloading its plugins. `NULL` is ignored. If unloading a plugin panics,
+ the plugins left aren't unloaded.
+
+ # Safety
+ `manager` must come from `vplugin_manager_new`, and can't be used anymore.
+ */
+void vplugin_manager_free(struct vplugin_manager *manager);
+
+/*
+ Loads the plugin at `path` (A nul-terminated path) into `manager`, and writes its handle
+ to `handle`. See `PluginManager::load`.
+
+ # Safety
+ `manager` must be valid, `path` nul-terminated and `handle` writable.
+ */
+int vplugin_load(struct vplugin_manager *manager, const char *path, struct vplugin_handle *handle);
+
+/*
+ Starts the plugin referred to by `handle`, see `PluginManager::begin`.
+
+ # Safety
+ `manager` must be valid.
+ */
+int vplugin_begin(struct vplugin_manager *manager, struct vplugin_handle handle);
+
+/*
+ Returns the hook `name` (Nul-terminated) of the started plugin referred to by `handle`,
+ or `NULL` if it doesn't export it. The caller casts it to the signature of the hook.
+
+ Unlike the `HookGuard`s of the Rust API, the returned pointer doesn't keep the library of
+ the plugin loaded: it dangles once the plugin is unloaded (Or its manager freed), and
+ nothing stops the caller from calling it then.
+
+ # Safety
+ `manager` must be valid and `name` nul-terminated. The hook can't be called once the
+ plugin is terminated, unloaded or its manager freed.
+ */
+void *vplugin_get_hook(const struct vplugin_manager *manager,
+                       struct vplugin_handle handle,
+                       const char *name);
+
+/*
+ Terminates the plugin referred to by `handle`, which stays loaded and can be started
+ again. See `PluginManager::terminate`.
+
+ # Safety
+ `manager` must be valid.
+ */
+int vplugin_terminate(struct vplugin_manager *manager, struct vplugin_handle handle);
+
+/*
+ Unloads the plugin referred to by `handle`, and removes it from `manager`: the handle
+ becomes invalid. Started plugins should be terminated first, as their destructor isn't
+ called.
+
+ # Safety
+ `manager` must be valid.
+ */
+int vplugin_unload(struct vplugin_manager *manager, struct vplugin_handle handle);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* VPLUGIN_H */
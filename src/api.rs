@@ -0,0 +1,210 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! The handshake through which a host and its plugins agree on the version of the
+//! host's API they use.
+
+use std::ffi::c_void;
+use std::fmt;
+use std::str::FromStr;
+use serde_derive::{Deserialize, Serialize};
+use crate::diagnostics;
+use crate::error::VPluginError;
+use crate::plugin::Plugin;
+use vplugin_core::PluginMetadata;
+
+/// The symbol plugins export to take part in the handshake.
+pub const QUERY_API_SYMBOL: &str = "vplugin_query_api";
+
+/// The symbol plugins export to receive the API table of the version they negotiated.
+pub const SET_API_SYMBOL: &str = "vplugin_set_api";
+
+/// ## HostApiVersion
+/// The version of the API a host exposes to its plugins, declared with
+/// [`PluginManager::set_host_api_version`](crate::plugin_manager::PluginManager::set_host_api_version).
+///
+/// When a manager declares one, every plugin it loads is asked which version it wants
+/// through its `vplugin_query_api` export:
+/// ```c
+/// int32_t vplugin_query_api(uint16_t host_major, uint16_t host_minor);
+/// ```
+/// The plugin returns the version it will use, encoded as `(major << 16) | minor`, which
+/// can't be newer than the host's (A plugin built against an older API downgrades), or a
+/// negative value to refuse the host. The negotiated version is then available from
+/// [`Plugin::api_version`](crate::plugin::Plugin::api_version).
+///
+/// Plugins can also declare the version they were built against in the `api_version` field
+/// of their metadata (`"1.2"`), which is checked before their library is even loaded, and
+/// again when they're started: they are refused with `VPluginError::IncompatibleVersion`
+/// unless the host provides the same major version, at least as new. Plugins that neither declare a version nor export
+/// `vplugin_query_api` are loaded without checking anything.
+///
+/// ## Multiple versions
+/// Hosts can keep supporting older versions of their API after breaking it, by registering
+/// a table (A struct of function pointers) for every version with
+/// [`PluginManager::register_api`](crate::plugin_manager::PluginManager::register_api). The
+/// plugin then receives the table of the newest version with the major version it negotiated
+/// through its `vplugin_set_api` export:
+/// ```c
+/// void vplugin_set_api(uint16_t major, uint16_t minor, const void *table);
+/// ```
+/// Tables of old versions are usually adapters, implementing the old functions on top of
+/// the current ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct HostApiVersion {
+        pub major: u16,
+        pub minor: u16,
+}
+
+impl HostApiVersion {
+        /// Creates a new version.
+        pub const fn new(major: u16, minor: u16) -> Self {
+                Self { major, minor }
+        }
+
+        /* Decodes the value returned by `vplugin_query_api`, `None` if the plugin refused. */
+        fn decode(value: i32) -> Option<Self> {
+                if value < 0 {
+                        return None;
+                }
+                Some(Self::new((value >> 16) as u16, (value & 0xFFFF) as u16))
+        }
+}
+
+impl FromStr for HostApiVersion {
+        type Err = VPluginError;
+
+        /// Parses a version written as `major.minor` (`"1.2"`), like the `api_version`
+        /// field of the metadata of plugins.
+        fn from_str(version: &str) -> Result<Self, Self::Err> {
+                let parsed = version
+                        .split_once('.')
+                        .and_then(|(major, minor)| Some(Self::new(major.parse().ok()?, minor.parse().ok()?)));
+                parsed.ok_or_else(|| VPluginError::InvalidVersion { version: version.to_owned() })
+        }
+}
+
+impl fmt::Display for HostApiVersion {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}.{}", self.major, self.minor)
+        }
+}
+
+type QueryApi = unsafe extern "C" fn(u16, u16) -> i32;
+type SetApi   = unsafe extern "C" fn(u16, u16, *const c_void);
+
+/// A version of the host's API, registered with
+/// [`PluginManager::register_api`](crate::plugin_manager::PluginManager::register_api).
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ApiTable {
+        pub(crate) version: HostApiVersion,
+        pub(crate) table  : *const c_void,
+        /* Whether the table implements an old version on top of a newer one. */
+        pub(crate) adapter: bool,
+}
+
+/* The tables are `&'static` references to `Sync` values. */
+unsafe impl Send for ApiTable {}
+unsafe impl Sync for ApiTable {}
+
+/// Returns the table to hand to a plugin that negotiated `version`: the newest one with the
+/// same major version, that is at least as new.
+fn select(tables: &[ApiTable], version: HostApiVersion) -> Option<&ApiTable> {
+        tables
+                .iter()
+                .filter(|table| table.version.major == version.major && table.version >= version)
+                .max_by_key(|table| table.version)
+}
+
+/// Hands `plugin`, which negotiated `version`, the matching table out of `tables`. Returns
+/// the table handed to the plugin.
+pub(crate) fn attach(plugin: &Plugin, version: HostApiVersion, tables: &[ApiTable]) -> Result<ApiTable, VPluginError> {
+        let table = match select(tables, version) {
+                Some(table) => *table,
+                None        => {
+                        diagnostics::error!("The host doesn't provide version {} of its API anymore.", version);
+                        return Err(VPluginError::IncompatibleApi { host: version.to_string() });
+                }
+        };
+        if table.adapter {
+                diagnostics::trace!("Plugin uses version {} of the API through an adapter.", table.version);
+        }
+
+        match unsafe { plugin.symbol::<SetApi>(SET_API_SYMBOL) } {
+                Ok (set_api) => unsafe { set_api(table.version.major, table.version.minor, table.table) },
+                Err(_)       => diagnostics::trace!("Plugin doesn't export {}, not handing it an API table.", SET_API_SYMBOL)
+        }
+        Ok(table)
+}
+
+/// Checks the version of the host's API the plugin described by `metadata` declares it was
+/// built against (Its `api_version` field), if any, against the host's version `host`. The
+/// plugin works with a host of the same major version, that is at least as new.
+pub(crate) fn check_declared(metadata: &PluginMetadata, host: HostApiVersion) -> Result<(), VPluginError> {
+        if let Err(e) = verify_declared(metadata, host) {
+                diagnostics::error!("Refusing plugin: {}.", e);
+                return Err(e);
+        }
+        Ok(())
+}
+
+/// Like [`check_declared`], without reporting anything. Returns the version the plugin
+/// declares, if any.
+pub(crate) fn verify_declared(metadata: &PluginMetadata, host: HostApiVersion) -> Result<Option<HostApiVersion>, VPluginError> {
+        match declared(metadata) {
+                Some(declared) if declared.major != host.major || declared > host => {
+                        Err(VPluginError::IncompatibleVersion { expected: host.to_string(), found: declared.to_string() })
+                },
+                declared => Ok(declared)
+        }
+}
+
+/* The version of the API the plugin declares in its metadata, if it declares a valid one. */
+fn declared(metadata: &PluginMetadata) -> Option<HostApiVersion> {
+        metadata.api_version.as_deref()?.parse().ok()
+}
+
+/// Asks `plugin` which version of the host's API it uses. Plugins that don't take part in
+/// the handshake use the version they declare in their metadata, if any (See [`check_declared`]).
+pub(crate) fn negotiate(plugin: &Plugin, host: HostApiVersion) -> Result<Option<HostApiVersion>, VPluginError> {
+        let query = match unsafe { plugin.symbol::<QueryApi>(QUERY_API_SYMBOL) } {
+                Ok (query) => query,
+                Err(_)     => {
+                        diagnostics::trace!("Plugin doesn't export {}, not negotiating the API version.", QUERY_API_SYMBOL);
+                        return Ok(declared(plugin.get_metadata()));
+                }
+        };
+
+        let answer = unsafe { query(host.major, host.minor) };
+        match HostApiVersion::decode(answer) {
+                Some(version) if version <= host => {
+                        diagnostics::trace!("Negotiated API version {} (Host version {}).", version, host);
+                        Ok(Some(version))
+                },
+                Some(version) => {
+                        diagnostics::error!(
+                                "Plugin requires API version {}, but the host only provides {}.",
+                                version,
+                                host
+                        );
+                        Err(VPluginError::IncompatibleApi { host: host.to_string() })
+                },
+                None => {
+                        diagnostics::error!("Plugin refused host API version {} (Returned {}).", host, answer);
+                        Err(VPluginError::IncompatibleApi { host: host.to_string() })
+                }
+        }
+}
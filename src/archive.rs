@@ -0,0 +1,376 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Extraction of `.vpl` archives. Only available with the `archive` feature,
+//! hosts that only load unpacked plugins can disable it to drop the `zip`
+//! dependency.
+//!
+//! Archives are ZIP files, or with the `tar` feature tarballs compressed with gzip
+//! (`.tar.gz`) or zstd (`.tar.zst`). The format is told apart from the first bytes of
+//! the archive, whatever its extension is.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+use crate::error::VPluginError;
+use crate::diagnostics;
+use crate::progress::{LoadPhase, Reporter};
+use crate::workspace;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// The formats of archives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+        Zip,
+        #[cfg(feature = "tar")]
+        TarGz,
+        #[cfg(feature = "tar")]
+        TarZstd,
+}
+
+impl Format {
+        /* Tells the format of `file` from its first bytes, rewinding it afterwards. */
+        fn sniff<R: Read + Seek>(file: &mut R) -> Result<Self, VPluginError> {
+                let mut header = Vec::with_capacity(ZSTD_MAGIC.len());
+                let read = file
+                        .by_ref()
+                        .take(ZSTD_MAGIC.len() as u64)
+                        .read_to_end(&mut header)
+                        .and_then(|_| file.rewind());
+                if let Err(e) = read {
+                        diagnostics::error!("Couldn't read the archive: {}", e);
+                        return Err(VPluginError::from_io(&e));
+                }
+
+                #[cfg(feature = "tar")]
+                if header.starts_with(GZIP_MAGIC) {
+                        return Ok(Format::TarGz);
+                }
+                #[cfg(feature = "tar")]
+                if header.starts_with(ZSTD_MAGIC) {
+                        return Ok(Format::TarZstd);
+                }
+                #[cfg(not(feature = "tar"))]
+                if header.starts_with(GZIP_MAGIC) || header.starts_with(ZSTD_MAGIC) {
+                        diagnostics::error!(
+                                "The archive is a compressed tarball, but VPlugin was built without the `tar` feature. \
+                                Not extracting plugin."
+                        );
+                        return Err(VPluginError::InvalidPlugin);
+                }
+                /* Anything else is refused by the ZIP reader, with its own error. */
+                Ok(Format::Zip)
+        }
+}
+
+/// An entry of an archive, see [`for_each_entry`].
+pub(crate) struct Entry<'a> {
+        /// The path of the entry, relative to the root of the archive.
+        pub(crate) path    : PathBuf,
+        pub(crate) is_dir  : bool,
+        /// The size of the entry once extracted, as the archive says.
+        pub(crate) size    : u64,
+        pub(crate) contents: &'a mut dyn Read,
+}
+
+impl Entry<'_> {
+        /// Returns the path of the entry with `/` as separator, whatever the platform is.
+        pub(crate) fn name(&self) -> String {
+                self.path
+                        .components()
+                        .map(|c| c.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/")
+        }
+}
+
+/// Calls `f` with every entry of the archive `file`, in the order they're stored, stopping
+/// at the first error. Entries whose path leads outside of the archive (`../`, absolute
+/// paths, ...) are skipped, and so are the links and special files of tarballs.
+pub(crate) fn for_each_entry<R, F>(mut file: R, mut f: F) -> Result<(), VPluginError>
+where
+        R: Read + Seek,
+        F: FnMut(Entry<'_>) -> Result<(), VPluginError>
+{
+        match Format::sniff(&mut file)? {
+                Format::Zip     => zip_entries(file, &mut f),
+                #[cfg(feature = "tar")]
+                Format::TarGz   => tar_entries(flate2::read::GzDecoder::new(file), &mut f),
+                #[cfg(feature = "tar")]
+                Format::TarZstd => match zstd::stream::read::Decoder::new(file) {
+                        Ok (decoder) => tar_entries(decoder, &mut f),
+                        Err(e)       => {
+                                diagnostics::error!("Archive error: {}. Not extracting plugin.", e);
+                                Err(VPluginError::InvalidPlugin)
+                        }
+                }
+        }
+}
+
+fn zip_entries<R: Read + Seek>(file: R, f: &mut dyn FnMut(Entry<'_>) -> Result<(), VPluginError>) -> Result<(), VPluginError> {
+        let mut archive = match ZipArchive::new(file) {
+                Ok (v) => v,
+                Err(e) => {
+                        diagnostics::error!("Archive error: {}. Not extracting plugin.", e);
+                        return Err(VPluginError::InvalidPlugin)
+                }
+        };
+        for i in 0..archive.len() {
+                let mut file = match archive.by_index(i) {
+                        Ok (file) => file,
+                        Err(e)    => {
+                                diagnostics::error!("Archive entry {} is invalid: {}. Not extracting plugin.", i, e);
+                                return Err(VPluginError::InvalidPlugin);
+                        }
+                };
+                let path = match file.enclosed_name() {
+                        Some(path) => path.to_owned(),
+                        None       => continue
+                };
+                let is_dir = file.is_dir();
+                let size   = file.size();
+                f(Entry { path, is_dir, size, contents: &mut file })?;
+        }
+        Ok(())
+}
+
+#[cfg(feature = "tar")]
+fn tar_entries<R: Read>(file: R, f: &mut dyn FnMut(Entry<'_>) -> Result<(), VPluginError>) -> Result<(), VPluginError> {
+        let invalid = |e: io::Error| {
+                diagnostics::error!("Archive error: {}. Not extracting plugin.", e);
+                VPluginError::InvalidPlugin
+        };
+        let mut archive = tar::Archive::new(file);
+        for entry in archive.entries().map_err(invalid)? {
+                let mut entry = entry.map_err(invalid)?;
+                let is_dir = match entry.header().entry_type() {
+                        tar::EntryType::Directory => true,
+                        tar::EntryType::Regular | tar::EntryType::Continuous => false,
+                        _ => continue
+                };
+                let path = match entry.path().ok().and_then(|path| enclosed(&path)) {
+                        Some(path) => path,
+                        None       => continue
+                };
+                let size = entry.header().size().unwrap_or(0);
+                f(Entry { path, is_dir, size, contents: &mut entry })?;
+        }
+        Ok(())
+}
+
+/* The path of a tarball entry relative to the root of the archive, if it stays inside of it. */
+#[cfg(feature = "tar")]
+fn enclosed(path: &Path) -> Option<PathBuf> {
+        use std::path::Component;
+
+        let mut enclosed = PathBuf::new();
+        for component in path.components() {
+                match component {
+                        Component::Normal(part) => enclosed.push(part),
+                        Component::CurDir       => (),
+                        _                       => return None
+                }
+        }
+        (!enclosed.as_os_str().is_empty()).then_some(enclosed)
+}
+
+/// Extracts the archive `file` inside `destination`, reporting the progress to `progress`
+/// if given.
+/// 
+/// The archive is untrusted input: whatever it contains, an error is returned
+/// instead of panicking.
+pub(crate) fn extract<R: Read + Seek>(file: R, destination: &Path, progress: Option<&Reporter<'_>>) -> Result<(), VPluginError> {
+        extract_archive(file, destination, None, progress)
+}
+
+/// Extracts the archive `file` inside `destination` like [`extract`], except the entry
+/// named `skip`, which is left out.
+pub(crate) fn extract_except<R: Read + Seek>(file: R, destination: &Path, skip: &str, progress: Option<&Reporter<'_>>) -> Result<(), VPluginError> {
+        extract_archive(file, destination, Some(skip), progress)
+}
+
+/// Returns the name and contents of the entry of the archive `file` at `path`, compared
+/// case-insensitively like [`workspace::find`], or `None` if there is no such entry or
+/// the archive can't be read.
+pub(crate) fn read_entry<R: Read + Seek>(file: R, path: &str) -> Option<(String, Vec<u8>)> {
+        let mut found = None;
+        for_each_entry(file, |entry| {
+                let name = entry.name();
+                if found.is_some() || entry.is_dir || !name.eq_ignore_ascii_case(path) {
+                        return Ok(());
+                }
+                let mut contents = Vec::new();
+                entry.contents.read_to_end(&mut contents).map_err(|e| VPluginError::from_io(&e))?;
+                found = Some((name, contents));
+                Ok(())
+        }).ok()?;
+        found
+}
+
+fn extract_archive<R: Read + Seek>(mut file: R, destination: &Path, skip: Option<&str>, progress: Option<&Reporter<'_>>) -> Result<(), VPluginError> {
+        let (files, bytes) = check_case_collisions(&mut file, skip)?;
+        file.rewind().map_err(|e| VPluginError::from_io(&e))?;
+        if let Some(progress) = progress {
+                progress.phase(LoadPhase::Extracting, Some(files), Some(bytes))?;
+        }
+
+        for_each_entry(file, |entry| {
+                if Some(entry.name().as_str()) == skip {
+                        return Ok(());
+                }
+                let outpath = workspace::join(destination, &entry.path);
+
+                let written = if entry.is_dir {
+                        fs::create_dir_all(&outpath)
+                } else {
+                        let created = match outpath.parent() {
+                                Some(p) if !p.exists() => fs::create_dir_all(p),
+                                _                      => Ok(())
+                        };
+                        created
+                                .and_then(|_| File::create(&outpath))
+                                .and_then(|outfile| io::copy(entry.contents, &mut Counting { inner: outfile, reporter: progress }))
+                                .map(|_| ())
+                };
+                if progress.is_some_and(Reporter::cancelled) {
+                        return Err(VPluginError::Cancelled);
+                }
+                if let Err(e) = written {
+                        diagnostics::error!("Couldn't extract {}: {}", outpath.display(), e);
+                        return Err(match e.kind() {
+                                /* Corrupted compressed data. */
+                                io::ErrorKind::InvalidData | io::ErrorKind::InvalidInput | io::ErrorKind::UnexpectedEof
+                                                => VPluginError::InvalidPlugin,
+                                _               => VPluginError::from_io(&e)
+                        });
+                }
+                match (progress, entry.is_dir) {
+                        (Some(progress), false) => progress.file(0),
+                        _                       => Ok(())
+                }
+        })
+}
+
+/*
+ * Entries whose paths only differ by case would overwrite each other on case-insensitive
+ * filesystems (The default on Windows and macOS), so such archives are rejected everywhere.
+ * Returns how many files would be extracted (Leaving out `skip`), and their total size.
+ */
+fn check_case_collisions<R: Read + Seek>(file: R, skip: Option<&str>) -> Result<(usize, u64), VPluginError> {
+        let mut seen: HashMap<String, String> = HashMap::new();
+        let mut files = 0;
+        let mut bytes = 0;
+        for_each_entry(file, |entry| {
+                if !entry.is_dir && Some(entry.name().as_str()) != skip {
+                        files += 1;
+                        bytes += entry.size;
+                }
+                let mut prefix = PathBuf::new();
+                for component in entry.path.components() {
+                        prefix.push(component);
+                        let original = prefix.to_string_lossy().into_owned();
+                        match seen.get(&original.to_lowercase()) {
+                                Some(existing) if *existing != original => {
+                                        diagnostics::error!(
+                                                "Archive contains both '{}' and '{}', which only differ by case.",
+                                                existing,
+                                                original
+                                        );
+                                        return Err(VPluginError::ConflictingEntries {
+                                                first : existing.clone(),
+                                                second: original
+                                        });
+                                },
+                                Some(_) => (),
+                                None    => { seen.insert(original.to_lowercase(), original); }
+                        }
+                }
+                Ok(())
+        })?;
+        Ok((files, bytes))
+}
+
+/// Packs every file inside `directory` into the archive `destination`, the
+/// reverse of [`extract`].
+#[cfg(feature = "testing")]
+pub(crate) fn pack(directory: &Path, destination: &Path) -> Result<(), VPluginError> {
+        use std::io::Write;
+        use zip::ZipWriter;
+        use zip::write::FileOptions;
+
+        let file = match File::create(destination) {
+                Ok (f) => f,
+                Err(e) => {
+                        diagnostics::error!("Couldn't create {}: {}", destination.display(), e);
+                        return Err(VPluginError::from_io(&e));
+                }
+        };
+        let mut archive = ZipWriter::new(file);
+        let mut pending = vec![directory.to_path_buf()];
+        let failed = |e: &dyn std::fmt::Display| {
+                diagnostics::error!("Couldn't pack {}: {}", directory.display(), e);
+                VPluginError::InternalError { err: e.to_string() }
+        };
+
+        while let Some(dir) = pending.pop() {
+                for entry in fs::read_dir(&dir).map_err(|e| failed(&e))? {
+                        let path = entry.map_err(|e| failed(&e))?.path();
+                        if path.is_dir() {
+                                pending.push(path);
+                                continue;
+                        }
+                        let name = path
+                                .strip_prefix(directory)
+                                .unwrap_or(&path)
+                                .components()
+                                .map(|c| c.as_os_str().to_string_lossy())
+                                .collect::<Vec<_>>()
+                                .join("/");
+                        let contents = fs::read(&path).map_err(|e| failed(&e))?;
+                        archive.start_file(name, FileOptions::default()).map_err(|e| failed(&e))?;
+                        archive.write_all(&contents).map_err(|e| failed(&e))?;
+                }
+        }
+        archive.finish().map_err(|e| failed(&e))?;
+        Ok(())
+}
+
+/* A writer reporting what goes through it to a `Reporter`. Writes fail once loading is cancelled. */
+struct Counting<'r, 'a, W> {
+        inner   : W,
+        reporter: Option<&'r Reporter<'a>>,
+}
+
+impl<W: io::Write> io::Write for Counting<'_, '_, W> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                let written = self.inner.write(buf)?;
+                if let Some(reporter) = self.reporter {
+                        if reporter.bytes(written as u64).is_err() {
+                                /* Not `Interrupted`, which `io::copy` retries. */
+                                return Err(io::Error::other("Loading was cancelled"));
+                        }
+                }
+                Ok(written)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+                self.inner.flush()
+        }
+}
@@ -0,0 +1,143 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! An async front for [`PluginManager`], for applications that can't block the thread
+//! they run on (Like the UI thread). Only available with the `tokio` feature.
+//!
+//! Extracting archives, loading libraries, running entry points and destructors, and
+//! calling hooks all block, so [`AsyncPluginManager`] runs them on Tokio's blocking
+//! thread pool and lets the application await them:
+//! ```rust,no_run
+//! # async fn run() -> Result<(), vplugin::VPluginError> {
+//! let manager = vplugin::AsyncPluginManager::new(vplugin::PluginManager::new());
+//! let plugin  = manager.load("plugin.vpl").await?;
+//! manager.begin(plugin).await?;
+//! let status  = unsafe {
+//!     manager.call_hook(plugin, "on_frame", |on_frame: unsafe extern "C" fn(f32) -> i32| on_frame(0.016))
+//! }.await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::Path;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use crate::error::VPluginError;
+use crate::handle::PluginHandle;
+use crate::plugin_manager::PluginManager;
+use crate::startup::StartupSummary;
+
+/// ## AsyncPluginManager
+/// A [`PluginManager`] shared between tasks, whose blocking operations can be awaited.
+/// See the [module documentation](crate::async_manager).
+///
+/// Cloning it gives another reference to the same manager. Hooks of different plugins
+/// (Or of the same one) can run at the same time, while loading, starting and terminating
+/// plugins waits for the hooks running to return.
+#[derive(Debug, Clone)]
+pub struct AsyncPluginManager {
+        manager: Arc<RwLock<PluginManager>>,
+}
+
+impl AsyncPluginManager {
+        /// Wraps `manager`, which can still be configured through
+        /// [`write`](crate::async_manager::AsyncPluginManager::write).
+        pub fn new(manager: PluginManager) -> Self {
+                Self { manager: Arc::new(RwLock::new(manager)) }
+        }
+
+        /// Locks the manager for reading, to use the rest of its API. This blocks until the
+        /// operations changing the manager return, so avoid it from async code while they run.
+        pub fn read(&self) -> RwLockReadGuard<'_, PluginManager> {
+                self.manager.read().unwrap_or_else(|e| e.into_inner())
+        }
+
+        /// Locks the manager for writing, to use the rest of its API. This blocks until every
+        /// operation running returns, so avoid it from async code while they run.
+        pub fn write(&self) -> RwLockWriteGuard<'_, PluginManager> {
+                self.manager.write().unwrap_or_else(|e| e.into_inner())
+        }
+
+        /// Loads a plugin, see [`PluginManager::load`].
+        pub async fn load(&self, filename: impl AsRef<Path>) -> Result<PluginHandle, VPluginError> {
+                let filename = filename.as_ref().to_path_buf();
+                self.blocking_write(move |manager| manager.load(filename)).await
+        }
+
+        /// Starts a plugin, see [`PluginManager::begin`].
+        pub async fn begin(&self, handle: PluginHandle) -> Result<(), VPluginError> {
+                self.blocking_write(move |manager| manager.begin(handle)).await
+        }
+
+        /// Starts every plugin that isn't started yet, see [`PluginManager::begin_all`].
+        pub async fn begin_all(&self) -> Result<StartupSummary, VPluginError> {
+                self.blocking_write(|manager| manager.begin_all()).await
+        }
+
+        /// Terminates a plugin, see [`PluginManager::terminate`].
+        pub async fn terminate(&self, handle: PluginHandle) -> Result<(), VPluginError> {
+                self.blocking_write(move |manager| manager.terminate(handle)).await
+        }
+
+        /// Calls the hook `name` of a plugin on the blocking thread pool, see
+        /// [`PluginManager::call_hook`].
+        ///
+        /// ## Safety
+        /// `F` must be the actual type of the symbol, and `call` must not keep it around.
+        pub async unsafe fn call_hook<F, R>(
+                &self,
+                handle: PluginHandle,
+                name: impl Into<String>,
+                call: impl FnOnce(F) -> R + Send + 'static,
+        ) -> Result<R, VPluginError>
+        where
+                F: Copy + Send + 'static,
+                R: Send + 'static,
+        {
+                let name = name.into();
+                self.blocking_read(move |manager| unsafe { manager.call_hook(handle, name, call) }).await
+        }
+
+        async fn blocking_read<R: Send + 'static>(
+                &self,
+                f: impl FnOnce(&PluginManager) -> Result<R, VPluginError> + Send + 'static,
+        ) -> Result<R, VPluginError> {
+                let manager = Arc::clone(&self.manager);
+                run(move || f(&manager.read().unwrap_or_else(|e| e.into_inner()))).await
+        }
+
+        async fn blocking_write<R: Send + 'static>(
+                &self,
+                f: impl FnOnce(&mut PluginManager) -> Result<R, VPluginError> + Send + 'static,
+        ) -> Result<R, VPluginError> {
+                let manager = Arc::clone(&self.manager);
+                run(move || f(&mut manager.write().unwrap_or_else(|e| e.into_inner()))).await
+        }
+}
+
+impl From<PluginManager> for AsyncPluginManager {
+        fn from(manager: PluginManager) -> Self {
+                Self::new(manager)
+        }
+}
+
+/* Runs `f` on the blocking thread pool. Panics are carried over to the task awaiting it. */
+async fn run<R: Send + 'static>(f: impl FnOnce() -> Result<R, VPluginError> + Send + 'static) -> Result<R, VPluginError> {
+        match tokio::task::spawn_blocking(f).await {
+                Ok (result)              => result,
+                Err(e) if e.is_panic()   => std::panic::resume_unwind(e.into_panic()),
+                Err(e)                   => Err(VPluginError::InternalError { err: e.to_string() })
+        }
+}
@@ -0,0 +1,173 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+use std::ffi::c_void;
+use std::fmt;
+use libloading::Library;
+
+/// ## PluginBackend
+/// Where the symbols of a [`Plugin`](crate::plugin::Plugin) (Its entry point, destructor
+/// and hooks) are looked up. For plugins loaded from the disk this is the shared library
+/// itself ([`libloading::Library`]).
+///
+/// Other backends can be used with [`Plugin::with_backend`](crate::plugin::Plugin::with_backend),
+/// for example [`FakeBackend`](crate::testing::FakeBackend) (With the `testing` feature) which
+/// lets tests provide the symbols directly, without loading anything. Every lookup VPlugin
+/// does (Entry points, destructors, hooks, [`is_symbol_present`](crate::plugin::Plugin::is_symbol_present))
+/// goes through this trait, so it's also the source of symbols unit tests script.
+#[doc(alias = "SymbolSource")]
+pub trait PluginBackend: fmt::Debug + Send + Sync {
+        /// Returns the address of the symbol `name`, which is nul-terminated, or
+        /// a message explaining why it couldn't be found.
+        fn symbol(&self, name: &[u8]) -> Result<*mut c_void, String>;
+}
+
+impl PluginBackend for Library {
+        fn symbol(&self, name: &[u8]) -> Result<*mut c_void, String> {
+                match unsafe { self.get::<*mut c_void>(name) } {
+                        Ok (symbol) => Ok(*symbol),
+                        Err(e)      => Err(e.to_string())
+                }
+        }
+}
+
+/// Opens the shared library at `path`, with the given visibility of its symbols.
+#[cfg(not(target_os = "ios"))]
+pub(crate) fn open(path: &std::path::Path, symbols: crate::options::SymbolVisibility) -> Result<Box<dyn PluginBackend>, String> {
+        #[cfg(unix)]
+        let library = {
+                use libloading::os::unix::{RTLD_GLOBAL, RTLD_LAZY, RTLD_LOCAL};
+                use crate::options::SymbolVisibility;
+                let scope = match symbols {
+                        #[cfg(all(target_os = "linux", target_env = "gnu"))]
+                        SymbolVisibility::Isolated => return open_isolated(path),
+                        #[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+                        SymbolVisibility::Isolated => {
+                                crate::diagnostics::warning!("Linker namespaces are only available on Linux with glibc, loading the plugin with local symbols.");
+                                RTLD_LOCAL
+                        },
+                        SymbolVisibility::Local    => RTLD_LOCAL,
+                        SymbolVisibility::Global   => RTLD_GLOBAL
+                };
+                unsafe { libloading::os::unix::Library::open(Some(path), RTLD_LAZY | scope) }.map(Library::from)
+        };
+        #[cfg(windows)]
+        let library = {
+                if symbols == crate::options::SymbolVisibility::Isolated {
+                        crate::diagnostics::warning!("Linker namespaces are only available on Linux with glibc, loading the plugin with local symbols.");
+                }
+                crate::platform::load_library(path)
+        };
+        match library {
+                Ok (l) => Ok(Box::new(l)),
+                Err(e) => Err(e.to_string())
+        }
+}
+
+/// Opens the shared library `data`, without writing it to the disk: it's copied into an
+/// anonymous file (`memfd_create`), which is loaded through `/proc/self/fd`. `name` only
+/// shows up in `/proc/<pid>/maps`. Only available on Linux.
+#[cfg(target_os = "linux")]
+pub(crate) fn open_memory(name: &str, data: &[u8], symbols: crate::options::SymbolVisibility) -> Result<Box<dyn PluginBackend>, String> {
+        use std::ffi::{c_char, c_int, c_uint, CString};
+        use std::fs::File;
+        use std::io::Write;
+        use std::os::unix::io::{AsRawFd, FromRawFd};
+
+        /* Not exposed by the standard library. */
+        extern "C" {
+                fn memfd_create(name: *const c_char, flags: c_uint) -> c_int;
+        }
+        const MFD_CLOEXEC: c_uint = 1;
+
+        let name = CString::new(name.replace('\0', "")).map_err(|e| e.to_string())?;
+        let fd = unsafe { memfd_create(name.as_ptr(), MFD_CLOEXEC) };
+        if fd < 0 {
+                return Err(format!("memfd_create failed: {}", std::io::Error::last_os_error()));
+        }
+        /* Closed once the library is loaded, which keeps its own mapping. */
+        let mut file = unsafe { File::from_raw_fd(fd) };
+        file.write_all(data).map_err(|e| e.to_string())?;
+        open(std::path::Path::new(&format!("/proc/self/fd/{}", file.as_raw_fd())), symbols)
+}
+
+/* Opens the shared library at `path` in a new linker namespace. */
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+fn open_isolated(path: &std::path::Path) -> Result<Box<dyn PluginBackend>, String> {
+        use std::ffi::{c_char, c_int, c_long, CStr, CString};
+        use std::os::unix::ffi::OsStrExt;
+        use libloading::os::unix::{RTLD_LAZY, RTLD_LOCAL};
+
+        /* Not exposed by libloading. */
+        extern "C" {
+                fn dlmopen(namespace: c_long, filename: *const c_char, flags: c_int) -> *mut c_void;
+                fn dlerror() -> *mut c_char;
+        }
+        const LM_ID_NEWLM: c_long = -1;
+
+        let filename = CString::new(path.as_os_str().as_bytes()).map_err(|e| e.to_string())?;
+        let handle = unsafe { dlmopen(LM_ID_NEWLM, filename.as_ptr(), RTLD_LAZY | RTLD_LOCAL) };
+        if handle.is_null() {
+                let error = unsafe { dlerror() };
+                return Err(match error.is_null() {
+                        true  => "dlmopen failed".into(),
+                        false => unsafe { CStr::from_ptr(error) }.to_string_lossy().into_owned()
+                });
+        }
+        let library: Library = unsafe { libloading::os::unix::Library::from_raw(handle) }.into();
+        Ok(Box::new(library))
+}
+
+/// Returns the libraries out of `needs` (The `needs` field of a plugin's metadata) that
+/// can be found neither inside the package extracted in `workdir`, nor by the dynamic
+/// linker. Libraries for other platforms (`.dll` files on Linux, ...) are skipped.
+///
+/// Nothing is loaded: the libraries are looked for in the directories the dynamic linker
+/// searches (See [`find_library`](crate::platform::find_library)), since `needs` comes from
+/// the plugin and loading a library runs its code.
+#[cfg(not(target_os = "ios"))]
+pub(crate) fn missing_libraries(needs: &[String], workdir: &std::path::Path) -> Vec<String> {
+        needs
+                .iter()
+                .filter(|library| for_this_platform(library))
+                .filter(|library| crate::platform::find_library(library, workdir).is_none())
+                .cloned()
+                .collect()
+}
+
+/* Whether `library` is meant for the host's platform, judging from its extension. */
+#[cfg(not(target_os = "ios"))]
+fn for_this_platform(library: &str) -> bool {
+        /* Versioned libraries keep their version after the extension (`libssl.so.3`). */
+        let library = library.to_ascii_lowercase();
+        match library.rsplit('.').find(|part| ["dll", "dylib", "so"].contains(part)) {
+                Some("dll")   => cfg!(windows),
+                Some("dylib") => cfg!(target_os = "macos"),
+                Some(_)       => cfg!(all(unix, not(target_os = "macos"))),
+                None          => true
+        }
+}
+
+/// The symbols of the running application, used by builtin plugins.
+pub(crate) fn this_program() -> Result<Box<dyn PluginBackend>, String> {
+        #[cfg(unix)]
+        let library: Library = libloading::os::unix::Library::this().into();
+        #[cfg(windows)]
+        let library: Library = libloading::os::windows::Library::this()
+                .map_err(|e| e.to_string())?
+                .into();
+        Ok(Box::new(library))
+}
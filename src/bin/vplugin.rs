@@ -0,0 +1,265 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+
+//! `vplugin`, a command line tool to check plugins without writing a host:
+//! ```text
+//! vplugin inspect  <plugin>                  Prints the metadata of the plugin.
+//! vplugin validate <plugin>                  Runs every compatibility check, without loading the plugin.
+//! vplugin run      <plugin> [--entry NAME]   Loads, starts and terminates the plugin.
+//! vplugin console  <plugin> [--entry NAME]   Starts the plugin and lets you call its hooks.
+//! ```
+//! Only built with the `cli` feature.
+
+use std::ffi::c_int;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use vplugin::{Diagnostic, DiagnosticsSink, Plugin, PluginManager, Severity, VPluginError};
+
+const USAGE: &str = "\
+Usage: vplugin <command> <plugin> [options]
+
+Commands:
+    inspect  <plugin>                  Print the metadata of the plugin, without loading it
+    validate <plugin>                  Run every compatibility check, without loading the plugin
+    run      <plugin> [--entry NAME]   Load, start and terminate the plugin
+    console  <plugin> [--entry NAME]   Start the plugin and call its hooks interactively
+
+Options:
+    -v, --verbose                      Print everything VPlugin does, not only warnings and errors
+
+<plugin> is a .vpl archive, an extracted plugin directory or an object file.";
+
+/* Prints the warnings and errors of VPlugin, and everything else with `--verbose`. */
+struct Stderr {
+        verbose: bool,
+}
+
+impl DiagnosticsSink for Stderr {
+        fn report(&self, diagnostic: &Diagnostic<'_>) {
+                let level = match diagnostic.severity {
+                        Severity::Error   => "error",
+                        Severity::Warning => "warning",
+                        _ if self.verbose => "note",
+                        _                 => return
+                };
+                eprintln!("{}: {}", level, diagnostic.message);
+        }
+}
+
+enum Command {
+        Inspect,
+        Validate,
+        Run { entry: Option<String> },
+        Console { entry: Option<String> },
+}
+
+struct Arguments {
+        command: Command,
+        plugin : PathBuf,
+        verbose: bool,
+}
+
+fn parse(mut args: impl Iterator<Item = String>) -> Result<Arguments, String> {
+        let command = args.next().ok_or("No command given.")?;
+        let mut plugin  = None;
+        let mut entry   = None;
+        let mut verbose = false;
+        while let Some(arg) = args.next() {
+                match arg.as_str() {
+                        "--entry"                 => entry = Some(args.next().ok_or("--entry needs the name of the entry point.")?),
+                        "-v" | "--verbose"        => verbose = true,
+                        _ if arg.starts_with('-') => return Err(format!("Unknown option '{}'.", arg)),
+                        _ if plugin.is_none()     => plugin = Some(PathBuf::from(arg)),
+                        _                         => return Err(format!("Unexpected argument '{}'.", arg))
+                }
+        }
+
+        let command = match command.as_str() {
+                "inspect"  => Command::Inspect,
+                "validate" => Command::Validate,
+                "run"      => Command::Run { entry: entry.take() },
+                "console"  => Command::Console { entry: entry.take() },
+                _          => return Err(format!("Unknown command '{}'.", command))
+        };
+        if entry.is_some() {
+                return Err("--entry is only accepted by 'run'.".into());
+        }
+        Ok(Arguments {
+                command,
+                plugin: plugin.ok_or("No plugin given.")?,
+                verbose,
+        })
+}
+
+fn inspect(manager: &PluginManager, plugin: &Path) -> Result<bool, VPluginError> {
+        let metadata = manager.inspect(plugin)?;
+        println!("{:#}", metadata);
+        if !metadata.settings.is_empty() {
+                println!("  Settings:");
+                for (key, setting) in &metadata.settings.settings {
+                        println!("    {} ({}, default {})", key, setting.kind, setting.default);
+                }
+        }
+        for (key, value) in metadata.extra_iter() {
+                println!("  {}: {}", key, value);
+        }
+        Ok(true)
+}
+
+fn validate(manager: &PluginManager, plugin: &Path) -> Result<bool, VPluginError> {
+        let symbols = manager.symbol_report(plugin)?;
+        if !symbols.is_clean() {
+                println!("{}", symbols);
+        }
+        let report = manager.compatibility_report(plugin)?;
+        print!("{}", report);
+        Ok(report.is_compatible())
+}
+
+/* Loads and starts the plugin at `path`. */
+fn start(manager: &mut PluginManager, path: &Path) -> Result<Plugin, VPluginError> {
+        let mut plugin = manager.load_plugin(path)?;
+        manager.begin_plugin(&mut plugin)?;
+        println!("{} started.", plugin.get_metadata());
+        Ok(plugin)
+}
+
+fn stop(plugin: &mut Plugin) -> Result<(), VPluginError> {
+        /* Checked beforehand, terminating the plugin may unload its library. */
+        let has_destructor = plugin.is_symbol_present::<(), _>("vplugin_exit");
+        match plugin.terminate() {
+                Ok (()) => println!("{} terminated.", plugin.get_metadata()),
+                /* The destructor is optional, VPlugin already warned about it. */
+                Err(_) if !has_destructor => (),
+                Err(e) => return Err(e)
+        }
+        Ok(())
+}
+
+fn run(manager: &mut PluginManager, path: &Path, entry: Option<&str>) -> Result<bool, VPluginError> {
+        if let Some(entry) = entry {
+                manager.set_entry_point(entry);
+        }
+        let mut plugin = start(manager, path)?;
+        stop(&mut plugin)?;
+        Ok(true)
+}
+
+const CONSOLE_HELP: &str = "\
+Commands:
+    exports, e     List the symbols the plugin exports
+    call, c NAME   Call the hook NAME, which takes no arguments, and print what it returns
+    reload, r      Terminate the plugin, then load and start it again (After rebuilding it)
+    info, i        Print what is known about the plugin
+    help, h        Print this message
+    quit, q        Terminate the plugin and exit";
+
+fn console(manager: &mut PluginManager, path: &Path, entry: Option<&str>) -> Result<bool, VPluginError> {
+        if let Some(entry) = entry {
+                manager.set_entry_point(entry);
+        }
+        let mut plugin = Some(start(manager, path)?);
+        println!("{}", CONSOLE_HELP);
+
+        let mut lines = io::stdin().lock().lines();
+        loop {
+                print!("> ");
+                let _ = io::stdout().flush();
+                let line = match lines.next() {
+                        Some(Ok(line)) => line,
+                        _              => break
+                };
+                let mut words = line.split_whitespace();
+                let loaded    = plugin.as_ref();
+                match (words.next(), words.next()) {
+                        (None, _)                 => continue,
+                        (Some("quit" | "q"), _)   => break,
+                        (Some("help" | "h"), _)   => println!("{}", CONSOLE_HELP),
+                        (Some("reload" | "r"), _) => {
+                                if let Some(Err(e)) = plugin.take().map(|mut old| stop(&mut old)) {
+                                        eprintln!("error: {}", e);
+                                }
+                                plugin = start(manager, path)
+                                        .map_err(|e| eprintln!("error: {}", e))
+                                        .ok();
+                        },
+                        (Some("exports" | "e"), _) => match manager.exports(path) {
+                                Ok (exports) => exports.iter().for_each(|name| println!("    {}", name)),
+                                Err(e)       => eprintln!("error: {}", e)
+                        },
+                        (Some("info" | "i" | "call" | "c"), _) if loaded.is_none() => {
+                                eprintln!("error: The plugin isn't loaded, 'reload' it first.")
+                        },
+                        (Some("info" | "i"), _) => {
+                                let plugin = loaded.unwrap();
+                                let memory = manager.memory_usage(plugin);
+                                println!("{:#}", plugin);
+                                println!("  Memory     : {} bytes (Peak {})", memory.live, memory.peak);
+                        },
+                        (Some("call" | "c"), Some(name)) => {
+                                /* Hooks returning nothing print whatever was left in the return register. */
+                                match unsafe { loaded.unwrap().get_symbol::<unsafe extern "C" fn() -> c_int>(name) } {
+                                        Ok (hook) => println!("{} returned {}", name, unsafe { hook() }),
+                                        Err(e)    => eprintln!("error: {}", e)
+                                }
+                        },
+                        (Some("call" | "c"), None) => eprintln!("error: Which hook? (call NAME)"),
+                        (Some(command), _)         => eprintln!("error: Unknown command '{}', see 'help'.", command)
+                }
+        }
+
+        match plugin {
+                Some(mut plugin) => stop(&mut plugin).map(|_| true),
+                None             => Ok(true)
+        }
+}
+
+fn main() -> ExitCode {
+        let arguments = match parse(std::env::args().skip(1)) {
+                Ok (arguments) => arguments,
+                Err(e)         => {
+                        eprintln!("{}\n\n{}", e, USAGE);
+                        return ExitCode::from(2);
+                }
+        };
+
+        let mut manager = match PluginManager::try_new() {
+                Ok (manager) => manager,
+                Err(e)       => {
+                        eprintln!("error: {}", e);
+                        return ExitCode::FAILURE;
+                }
+        };
+        manager.set_diagnostics_sink(Stderr { verbose: arguments.verbose });
+
+        let plugin = arguments.plugin.as_path();
+        let result = match &arguments.command {
+                Command::Inspect           => inspect(&manager, plugin),
+                Command::Validate          => validate(&manager, plugin),
+                Command::Run { entry }     => run(&mut manager, plugin, entry.as_deref()),
+                Command::Console { entry } => console(&mut manager, plugin, entry.as_deref()),
+        };
+        match result {
+                Ok (true)  => ExitCode::SUCCESS,
+                Ok (false) => ExitCode::FAILURE,
+                Err(e)     => {
+                        eprintln!("error: {}", e);
+                        ExitCode::FAILURE
+                }
+        }
+}
@@ -0,0 +1,57 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+use std::sync::Mutex;
+
+/// ## BuiltinPlugin
+/// A plugin that is statically linked into the application instead of being
+/// loaded from a `.vpl` archive at runtime.
+///
+/// Some platforms (iOS in particular) forbid loading code at runtime, so plugins
+/// have to be linked into the application. Their symbols are then looked up in
+/// the application itself, prefixed by `symbol_prefix` so that multiple builtin
+/// plugins can each have their own `vplugin_init` (Exported as `myplugin_vplugin_init`).
+///
+/// On iOS, [`Plugin::load`](crate::plugin::Plugin::load) automatically uses the builtin
+/// plugin with the same name as the one found in the archive's metadata. On other platforms,
+/// builtin plugins can be loaded with [`Plugin::load_builtin`](crate::plugin::Plugin::load_builtin).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuiltinPlugin {
+        /// The name of the plugin, as it would appear in `metadata.toml`.
+        pub name         : String,
+        /// The version of the plugin.
+        pub version      : String,
+        /// The plugin's description.
+        pub description  : Option<String>,
+        /// Prefix of every symbol exported by the plugin.
+        pub symbol_prefix: String,
+}
+
+static BUILTIN_PLUGINS: Mutex<Vec<BuiltinPlugin>> = Mutex::new(Vec::new());
+
+/// Registers a builtin plugin so that it can be loaded later. Registering a plugin
+/// with the same name as an already registered one replaces it.
+pub fn register_builtin(plugin: BuiltinPlugin) {
+        let mut plugins = BUILTIN_PLUGINS.lock().unwrap_or_else(|e| e.into_inner());
+        plugins.retain(|p| p.name != plugin.name);
+        plugins.push(plugin);
+}
+
+/// Returns the builtin plugin registered under `name`, if any.
+pub(crate) fn find(name: &str) -> Option<BuiltinPlugin> {
+        let plugins = BUILTIN_PLUGINS.lock().unwrap_or_else(|e| e.into_inner());
+        plugins.iter().find(|p| p.name == name).cloned()
+}
@@ -0,0 +1,223 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! The C API of VPlugin, so applications written in C, C++ (Or any language able to call
+//! C functions) can host plugins. Only available with the `capi` feature, which exports
+//! these functions from the `cdylib` VPlugin builds.
+//!
+//! Their declarations are in `include/vplugin.h`, generated with
+//! [cbindgen](https://github.com/mozilla/cbindgen) from this module:
+//! ```sh
+//! cbindgen --config cbindgen.toml --output include/vplugin.h
+//! ```
+//!
+//! Functions returning an `int` return `0` on success, and `-1` if they failed, in which
+//! case [`vplugin_last_error`] describes the error. Panics never unwind into the caller:
+//! functions that panic fail instead (Returning `-1` or `NULL`), with the panic as their error.
+//!
+//! ## Example
+//! ```c
+//! vplugin_manager *manager = vplugin_manager_new();
+//! vplugin_handle   plugin;
+//! if (vplugin_load(manager, "plugins/spellcheck.vpl", &plugin) != 0 || vplugin_begin(manager, plugin) != 0) {
+//!     fprintf(stderr, "Couldn't start the plugin: %s\n", vplugin_last_error());
+//! }
+//! int (*check)(const char *) = (int (*)(const char *))vplugin_get_hook(manager, plugin, "check_word");
+//! ...
+//! vplugin_terminate(manager, plugin);
+//! vplugin_manager_free(manager);
+//! ```
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr;
+use crate::error::VPluginError;
+use crate::handle::PluginHandle;
+use crate::isolation::Unguarded;
+use crate::plugin_manager::PluginManager;
+
+thread_local! {
+        /* The error of the last function that failed on this thread. */
+        static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/* Records `error` as the last error of the thread, returning -1. */
+fn failed(error: VPluginError) -> c_int {
+        let message = CString::new(error.to_string().replace('\0', "")).unwrap_or_default();
+        LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+        -1
+}
+
+fn succeeded(result: Result<(), VPluginError>) -> c_int {
+        match result {
+                Ok (()) => 0,
+                Err(e)  => failed(e)
+        }
+}
+
+/*
+ * Runs `f`, recording a panic as the last error and returning `fallback` instead of unwinding into C.
+ * Plugins may call the C API from their entry point, the crash guard is suspended meanwhile (See `isolation`).
+ */
+fn guarded<R>(fallback: R, f: impl FnOnce() -> R) -> R {
+        let _unguarded = Unguarded::enter();
+        match panic::catch_unwind(AssertUnwindSafe(f)) {
+                Ok (result)  => result,
+                Err(payload) => {
+                        let message = payload
+                                .downcast_ref::<&str>()
+                                .copied()
+                                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                                .unwrap_or("Box<dyn Any>");
+                        failed(VPluginError::InternalError { err: format!("VPlugin panicked: {}", message) });
+                        fallback
+                }
+        }
+}
+
+/* The string at `string`, which is nul-terminated, or `None` if it's NULL. */
+unsafe fn c_str<'a>(string: *const c_char) -> Option<&'a CStr> {
+        match string.is_null() {
+                true  => None,
+                false => Some(CStr::from_ptr(string))
+        }
+}
+
+/// Returns the error of the last function that failed on the calling thread, or `NULL`
+/// if none did. The string stays valid until another function fails on the same thread.
+#[no_mangle]
+pub extern "C" fn vplugin_last_error() -> *const c_char {
+        guarded(ptr::null(), || LAST_ERROR.with(|last| match &*last.borrow() {
+                Some(message) => message.as_ptr(),
+                None          => ptr::null()
+        }))
+}
+
+/// Creates a plugin manager with the default options, see `PluginManager::new`. It has
+/// to be freed with `vplugin_manager_free`.
+#[no_mangle]
+pub extern "C" fn vplugin_manager_new() -> *mut PluginManager {
+        guarded(ptr::null_mut(), || Box::into_raw(Box::new(PluginManager::new())))
+}
+
+/// Frees `manager`, unloading its plugins. `NULL` is ignored. If unloading a plugin panics,
+/// the plugins left aren't unloaded.
+///
+/// # Safety
+/// `manager` must come from `vplugin_manager_new`, and can't be used anymore.
+#[no_mangle]
+pub unsafe extern "C" fn vplugin_manager_free(manager: *mut PluginManager) {
+        guarded((), || {
+                if !manager.is_null() {
+                        drop(Box::from_raw(manager));
+                }
+        })
+}
+
+/// Loads the plugin at `path` (A nul-terminated path) into `manager`, and writes its handle
+/// to `handle`. See `PluginManager::load`.
+///
+/// # Safety
+/// `manager` must be valid, `path` nul-terminated and `handle` writable.
+#[no_mangle]
+pub unsafe extern "C" fn vplugin_load(manager: *mut PluginManager, path: *const c_char, handle: *mut PluginHandle) -> c_int {
+        guarded(-1, || {
+                let (manager, path) = match (manager.as_mut(), c_str(path)) {
+                        (Some(manager), Some(path)) if !handle.is_null() => (manager, path),
+                        _ => return failed(VPluginError::ParametersError)
+                };
+                #[cfg(unix)]
+                let path = PathBuf::from(<std::ffi::OsStr as std::os::unix::ffi::OsStrExt>::from_bytes(path.to_bytes()));
+                #[cfg(not(unix))]
+                let path = match path.to_str() {
+                        Ok (path) => PathBuf::from(path),
+                        Err(_)    => return failed(VPluginError::ParametersError)
+                };
+                succeeded(manager.load(path).map(|loaded| *handle = loaded))
+        })
+}
+
+/// Starts the plugin referred to by `handle`, see `PluginManager::begin`.
+///
+/// # Safety
+/// `manager` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn vplugin_begin(manager: *mut PluginManager, handle: PluginHandle) -> c_int {
+        guarded(-1, || match manager.as_mut() {
+                Some(manager) => succeeded(manager.begin(handle)),
+                None          => failed(VPluginError::ParametersError)
+        })
+}
+
+/// Returns the hook `name` (Nul-terminated) of the started plugin referred to by `handle`,
+/// or `NULL` if it doesn't export it. The caller casts it to the signature of the hook.
+///
+/// Unlike the `HookGuard`s of the Rust API, the returned pointer doesn't keep the library of
+/// the plugin loaded: it dangles once the plugin is unloaded (Or its manager freed), and
+/// nothing stops the caller from calling it then.
+///
+/// # Safety
+/// `manager` must be valid and `name` nul-terminated. The hook can't be called once the
+/// plugin is terminated, unloaded or its manager freed.
+#[no_mangle]
+pub unsafe extern "C" fn vplugin_get_hook(manager: *const PluginManager, handle: PluginHandle, name: *const c_char) -> *mut c_void {
+        guarded(ptr::null_mut(), || {
+                let (manager, name) = match (manager.as_ref(), c_str(name).map(CStr::to_str)) {
+                        (Some(manager), Some(Ok(name))) => (manager, name),
+                        _ => {
+                                failed(VPluginError::ParametersError);
+                                return ptr::null_mut();
+                        }
+                };
+                match manager.custom_hook::<(), ()>(handle, name) {
+                        Ok (hook) => hook as *mut c_void,
+                        Err(e)    => {
+                                failed(e);
+                                ptr::null_mut()
+                        }
+                }
+        })
+}
+
+/// Terminates the plugin referred to by `handle`, which stays loaded and can be started
+/// again. See `PluginManager::terminate`.
+///
+/// # Safety
+/// `manager` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn vplugin_terminate(manager: *mut PluginManager, handle: PluginHandle) -> c_int {
+        guarded(-1, || match manager.as_mut() {
+                Some(manager) => succeeded(manager.terminate(handle)),
+                None          => failed(VPluginError::ParametersError)
+        })
+}
+
+/// Unloads the plugin referred to by `handle`, and removes it from `manager`: the handle
+/// becomes invalid. Started plugins should be terminated first, as their destructor isn't
+/// called.
+///
+/// # Safety
+/// `manager` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn vplugin_unload(manager: *mut PluginManager, handle: PluginHandle) -> c_int {
+        guarded(-1, || match manager.as_mut().map(|manager| manager.remove(handle)) {
+                Some(Some(_)) => 0,
+                Some(None)    => failed(VPluginError::InvalidHandle),
+                None          => failed(VPluginError::ParametersError)
+        })
+}
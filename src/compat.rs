@@ -0,0 +1,373 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Reports of the compatibility checks VPlugin runs on plugins, for hosts to show
+//! to their users.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use serde_derive::{Deserialize, Serialize};
+use vplugin_core::PluginMetadata;
+use vplugin_core::manifest::{HookManifest, HOOK_MANIFEST_FILE};
+use vplugin_core::sdk::{SdkInfo, LAYOUT_HASH_SYMBOL, SDK_VERSION_SYMBOL};
+use crate::api::{self, HostApiVersion, QUERY_API_SYMBOL};
+use crate::error::VPluginError;
+use crate::license::LicensePolicy;
+use crate::plugin::Plugin;
+use crate::plugin_manager::{self, LEGACY_ENTRY_POINT};
+#[cfg(feature = "preflight")]
+use crate::preflight;
+use crate::sdk;
+use crate::version::Constraints;
+use crate::workspace;
+
+/// ## CompatibilityReport
+/// The outcome of every compatibility check on a plugin, returned by
+/// [`PluginManager::compatibility_report`](crate::plugin_manager::PluginManager::compatibility_report).
+///
+/// Hosts can show it in a plugin details panel before enabling the plugin, or use
+/// [`is_compatible`](CompatibilityReport::is_compatible) to decide whether to enable it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompatibilityReport {
+        /// The name of the plugin.
+        pub plugin : String,
+        /// The version of the plugin.
+        pub version: String,
+        /// Every check, in the order they were run.
+        pub checks : Vec<CompatibilityCheck>,
+}
+
+/// The result of [`PluginManager::check_compatibility`](crate::plugin_manager::PluginManager::check_compatibility):
+/// every plugin path, with either its report or the error that prevented reading it.
+pub type CompatibilityMatrix = Vec<(PathBuf, Result<CompatibilityReport, VPluginError>)>;
+
+/// A single check inside a [`CompatibilityReport`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompatibilityCheck {
+        /// What was checked.
+        pub kind   : CheckKind,
+        /// Whether the check passed, and why.
+        pub outcome: CheckOutcome,
+}
+
+/// What a [`CompatibilityCheck`] verifies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum CheckKind {
+        /// The object file was built for the host's platform.
+        Platform,
+        /// The version requirements between the application and the plugin are met: the
+        /// plugin's `requires_host` range and the versions the application accepts.
+        HostVersion,
+        /// The native libraries the plugin needs can be found.
+        NativeLibraries,
+        /// The license of the plugin is accepted by the host's license policy.
+        License,
+        /// The plugin was built against a compatible version of VPlugin.
+        SdkVersion,
+        /// The plugin was built with the host's compiler (Rust ABI plugins only).
+        Toolchain,
+        /// The plugin supports the host's API version.
+        HostApi,
+        /// The plugin exports the host's entry point.
+        EntryPoint,
+        /// The signatures the package declares (Of its entry point, and of its hooks in
+        /// `hooks.toml`) are valid, match what the host calls, and the hooks are exported.
+        Signatures,
+}
+
+/// The result of a [`CompatibilityCheck`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum CheckOutcome {
+        /// The check passed. `detail` describes what was found (For example the
+        /// version of the SDK).
+        Passed { detail: String },
+        /// The check failed, the plugin can't be used.
+        Failed { reason: String },
+        /// The check doesn't apply to this plugin or host.
+        Skipped { reason: String },
+}
+
+/* What the compatibility checks need to know about the host. */
+pub(crate) struct Host<'a> {
+        pub(crate) versions: &'a Constraints,
+        pub(crate) licenses: Option<&'a LicensePolicy>,
+        pub(crate) api     : Option<HostApiVersion>,
+        pub(crate) entry   : &'a str,
+}
+
+/* What the checks read from the object file of the plugin, without loading it. */
+#[cfg_attr(not(feature = "preflight"), allow(dead_code))]
+struct ObjectFile {
+        /* The architecture it was built for, if it isn't the host's. */
+        foreign_architecture: Option<String>,
+        sdk                 : Option<SdkInfo>,
+        exports             : Vec<String>,
+        /* Whether the host's entry point is a function, `None` if it isn't exported. */
+        entry               : Option<bool>,
+}
+
+impl CompatibilityReport {
+        /// Returns whether no check failed.
+        pub fn is_compatible(&self) -> bool {
+                self.failures().next().is_none()
+        }
+
+        /// Returns the checks that failed.
+        pub fn failures(&self) -> impl Iterator<Item = &CompatibilityCheck> {
+                self.checks.iter().filter(|check| matches!(check.outcome, CheckOutcome::Failed { .. }))
+        }
+
+        /// Runs every check on the plugin described by `metadata`, whose package is in the
+        /// directory `package`, for `host`. Nothing is loaded: what the plugin exports is read
+        /// from its object file, which needs the `preflight` feature (Those checks are skipped
+        /// without it).
+        pub(crate) fn new(metadata: &PluginMetadata, package: &Path, host: &Host<'_>) -> Self {
+                let mut report = Self {
+                        plugin : metadata.name.clone(),
+                        version: metadata.version.clone(),
+                        checks : Vec::new(),
+                };
+                let objfile = workspace::find(package, Path::new(&Plugin::resolve_objfile(package, &metadata.objfile)));
+                let object  = ObjectFile::read(&objfile, host.entry);
+                report.push(CheckKind::Platform, platform(metadata, &object));
+                report.push(CheckKind::HostVersion, host_version(metadata, host.versions));
+                report.push(CheckKind::License, license(metadata, host.licenses));
+                report.push(CheckKind::NativeLibraries, native_libraries(metadata, package));
+                report.push(CheckKind::SdkVersion, sdk_version(&object));
+                report.push(CheckKind::Toolchain, toolchain(&object));
+                report.push(CheckKind::HostApi, host_api_version(metadata, &object, host.api));
+                report.push(CheckKind::EntryPoint, entry_point(&object, host.entry));
+                report.push(CheckKind::Signatures, signatures(metadata, package, &object));
+                report
+        }
+
+        fn push(&mut self, kind: CheckKind, outcome: CheckOutcome) {
+                self.checks.push(CompatibilityCheck { kind, outcome });
+        }
+}
+
+impl ObjectFile {
+        #[cfg(feature = "preflight")]
+        fn read(objfile: &Path, entry: &str) -> Result<Self, String> {
+                let read = || -> Result<Self, VPluginError> {
+                        Ok(Self {
+                                foreign_architecture: preflight::foreign_architecture(objfile)?,
+                                sdk                 : preflight::sdk_info(objfile)?,
+                                exports             : preflight::exports(objfile)?,
+                                entry               : preflight::is_function(objfile, entry)?,
+                        })
+                };
+                read().map_err(|e| format!("Couldn't read '{}': {}", objfile.display(), e))
+        }
+
+        #[cfg(not(feature = "preflight"))]
+        fn read(_objfile: &Path, _entry: &str) -> Result<Self, String> {
+                Err("Reading object files without loading them needs the `preflight` feature".into())
+        }
+
+        fn exports(&self, symbol: &str) -> bool {
+                self.exports.iter().any(|export| export == symbol)
+        }
+}
+
+impl fmt::Display for CompatibilityReport {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                writeln!(f, "{} v{}:", self.plugin, self.version)?;
+                for check in &self.checks {
+                        writeln!(f, "  {}", check)?;
+                }
+                Ok(())
+        }
+}
+
+impl fmt::Display for CompatibilityCheck {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match &self.outcome {
+                        CheckOutcome::Passed  { detail } => write!(f, "[passed]  {:?}: {}", self.kind, detail),
+                        CheckOutcome::Failed  { reason } => write!(f, "[failed]  {:?}: {}", self.kind, reason),
+                        CheckOutcome::Skipped { reason } => write!(f, "[skipped] {:?}: {}", self.kind, reason),
+                }
+        }
+}
+
+fn passed(detail: impl Into<String>) -> CheckOutcome {
+        CheckOutcome::Passed { detail: detail.into() }
+}
+
+fn failed(reason: impl Into<String>) -> CheckOutcome {
+        CheckOutcome::Failed { reason: reason.into() }
+}
+
+fn skipped(reason: impl Into<String>) -> CheckOutcome {
+        CheckOutcome::Skipped { reason: reason.into() }
+}
+
+fn platform(metadata: &PluginMetadata, object: &Result<ObjectFile, String>) -> CheckOutcome {
+        match Path::new(&metadata.objfile).extension().and_then(|e| e.to_str()) {
+                Some(extension) if ["so", "dll", "dylib"].contains(&extension) && extension != std::env::consts::DLL_EXTENSION => {
+                        return failed(format!("'{}' is a library for another platform", metadata.objfile));
+                },
+                _ => ()
+        }
+        match object {
+                Ok (ObjectFile { foreign_architecture: Some(architecture), .. }) => {
+                        failed(format!("'{}' was built for {}, not {}", metadata.objfile, architecture, std::env::consts::ARCH))
+                },
+                Ok (_) => passed(format!("Built for {} on {}", std::env::consts::OS, std::env::consts::ARCH)),
+                Err(_) => passed(format!("Built for {}", std::env::consts::OS))
+        }
+}
+
+fn host_version(metadata: &PluginMetadata, versions: &Constraints) -> CheckOutcome {
+        let requires_host = versions.host.is_some() && metadata.requires_host.is_some();
+        if !requires_host && versions.accepts.is_empty() {
+                return skipped("Neither the host nor the plugin have version requirements");
+        }
+        match versions.resolve(metadata) {
+                Ok (_) => match (&versions.host, &metadata.requires_host) {
+                        (Some(host), Some(requirement)) => passed(format!("Version {} matches '{}'", host, requirement)),
+                        _                               => passed("The host accepts this version of the plugin")
+                },
+                Err(e) => failed(e.to_string())
+        }
+}
+
+fn license(metadata: &PluginMetadata, licenses: Option<&LicensePolicy>) -> CheckOutcome {
+        let policy = match licenses {
+                Some(policy) => policy,
+                None         => return skipped("The host doesn't restrict licenses")
+        };
+        match policy.evaluate(metadata) {
+                Ok (Ok(_))             => passed(format!("Licensed under '{}'", metadata.license.as_deref().unwrap_or("NONE"))),
+                Ok (Err((_, reason))) => failed(reason),
+                Err(e)                 => failed(e.to_string())
+        }
+}
+
+#[cfg(not(target_os = "ios"))]
+fn native_libraries(metadata: &PluginMetadata, workdir: &Path) -> CheckOutcome {
+        if metadata.needs.is_empty() {
+                return skipped("The plugin doesn't declare the libraries it needs");
+        }
+        let missing = crate::backend::missing_libraries(&metadata.needs, workdir);
+        if missing.is_empty() {
+                passed("Every library the plugin needs was found")
+        } else {
+                failed(VPluginError::MissingLibraries { libraries: missing }.to_string())
+        }
+}
+
+#[cfg(target_os = "ios")]
+fn native_libraries(_metadata: &PluginMetadata, _workdir: &Path) -> CheckOutcome {
+        skipped("Plugins are builtin on iOS")
+}
+
+fn sdk_version(object: &Result<ObjectFile, String>) -> CheckOutcome {
+        let object = match object {
+                Ok (object) => object,
+                Err(reason) => return skipped(reason)
+        };
+        match &object.sdk {
+                Some(info) => match sdk::verify_sdk(&info.sdk_version()) {
+                        Ok (_) => passed(format!("Built with VPlugin {}", info.sdk_version())),
+                        Err(e) => failed(e.to_string())
+                },
+                None if object.exports(SDK_VERSION_SYMBOL) => skipped("The plugin only reports its SDK version once loaded"),
+                None                                       => skipped("The plugin doesn't report its SDK version")
+        }
+}
+
+fn toolchain(object: &Result<ObjectFile, String>) -> CheckOutcome {
+        let object = match object {
+                Ok (object) => object,
+                Err(reason) => return skipped(reason)
+        };
+        match object.sdk.as_ref().and_then(SdkInfo::toolchain) {
+                Some((rustc, layout)) => match sdk::verify_toolchain(&rustc, layout) {
+                        Ok (_) => passed(format!("Built with '{}'", rustc)),
+                        Err(e) => failed(e.to_string())
+                },
+                None if object.sdk.is_none() && object.exports(LAYOUT_HASH_SYMBOL) => {
+                        skipped("The plugin only reports its toolchain once loaded")
+                },
+                None => skipped("The plugin doesn't use the Rust ABI")
+        }
+}
+
+fn host_api_version(metadata: &PluginMetadata, object: &Result<ObjectFile, String>, host: Option<HostApiVersion>) -> CheckOutcome {
+        let host = match host {
+                Some(host) => host,
+                None       => return skipped("The host doesn't declare an API version")
+        };
+        let negotiates = matches!(object, Ok(object) if object.exports(QUERY_API_SYMBOL));
+        match api::verify_declared(metadata, host) {
+                Ok (Some(declared)) => passed(format!("Built against API version {} (Host provides {})", declared, host)),
+                Ok (None) if negotiates => skipped("The plugin negotiates the API version once loaded"),
+                Ok (None) => skipped("The plugin doesn't declare an API version"),
+                Err(e)    => failed(e.to_string())
+        }
+}
+
+fn entry_point(object: &Result<ObjectFile, String>, entry: &str) -> CheckOutcome {
+        let object = match object {
+                Ok (object) => object,
+                Err(reason) => return skipped(reason)
+        };
+        match object.entry {
+                Some(true)  => passed(format!("Exports '{}'", entry)),
+                Some(false) => failed(format!("'{}' isn't a function", entry)),
+                None if entry != LEGACY_ENTRY_POINT && object.exports(LEGACY_ENTRY_POINT) => failed(format!(
+                        "The plugin only exports the legacy '{}' instead of '{}', which is deprecated",
+                        LEGACY_ENTRY_POINT,
+                        entry
+                )),
+                None => failed(format!("The plugin doesn't export '{}'", entry))
+        }
+}
+
+fn signatures(metadata: &PluginMetadata, package: &Path, object: &Result<ObjectFile, String>) -> CheckOutcome {
+        if metadata.entry_signature.is_some() {
+                if let Err(reason) = plugin_manager::verify_entry_signature(metadata) {
+                        return failed(format!("The entry point can't be called: {}", reason));
+                }
+        }
+        let path = workspace::find(package, Path::new(HOOK_MANIFEST_FILE));
+        let manifest = match std::fs::read_to_string(&path) {
+                Ok (contents) => match HookManifest::parse(&contents) {
+                        Ok (manifest) => manifest,
+                        Err(e)        => return failed(e.to_string())
+                },
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => HookManifest::default(),
+                Err(e) => return failed(format!("Couldn't read {}: {}", HOOK_MANIFEST_FILE, e))
+        };
+        if let Ok(object) = object {
+                let missing: Vec<&str> = manifest.hooks
+                        .keys()
+                        .filter(|hook| !object.exports(hook))
+                        .map(String::as_str)
+                        .collect();
+                if !missing.is_empty() {
+                        return failed(format!("Declares hooks it doesn't export: {}", missing.join(", ")));
+                }
+        }
+        match (metadata.entry_signature.is_some(), manifest.hooks.len()) {
+                (false, 0) => skipped("The plugin doesn't declare any signature"),
+                (_, hooks) => passed(format!("The declared signatures match ({} hooks)", hooks))
+        }
+}
@@ -0,0 +1,228 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Routing of the diagnostics VPlugin produces (Errors, warnings, ...).
+//!
+//! Every message goes through the [`DiagnosticsSink`] installed on the manager
+//! that is currently doing the work, or [`LogSink`] if there is none. The sinks
+//! in use are kept on a per-thread stack: managers and plugins push their own
+//! sink (And the name of the plugin involved) while running, so messages coming
+//! from deep inside the loader still end up in the right place.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::Arc;
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+        /// Detailed information about what VPlugin is doing.
+        Trace,
+        /// Information useful when debugging the application.
+        Debug,
+        /// Something noteworthy happened, but nothing went wrong.
+        Info,
+        /// Something unexpected happened, but VPlugin could carry on.
+        Warning,
+        /// An operation failed. An error is returned to the caller as well.
+        Error,
+}
+
+/// A single message reported by VPlugin.
+#[derive(Debug, Clone, Copy)]
+pub struct Diagnostic<'a> {
+        /// How serious the message is.
+        pub severity: Severity,
+        /// The name of the plugin the message is about, if any.
+        pub plugin  : Option<&'a str>,
+        /// The message itself. It's only formatted if the sink asks for it
+        /// (`to_string()`, `write!`, ...).
+        pub message : fmt::Arguments<'a>,
+        /// Set when the message reports that the plugin relies on something deprecated,
+        /// so hosts can tell plugin authors what to migrate to.
+        pub deprecation: Option<Deprecation<'a>>,
+        /// The backtrace of a plugin that panicked, captured by the plugin itself in its
+        /// panic hook (See [`PanicReport::backtrace`](crate::panics::PanicReport::backtrace)).
+        /// It's meant for the author of the plugin, which hosts can ask users to send it.
+        pub backtrace  : Option<&'a str>,
+}
+
+/// What a [`Diagnostic`] about a deprecation refers to.
+///
+/// VPlugin reports one, as a warning, when a plugin only exports the legacy `vplugin_init`
+/// entry point while the host configured its own, and when a plugin exports a symbol the
+/// host marked with [`deprecate_symbol`](crate::plugin_manager::PluginManager::deprecate_symbol).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deprecation<'a> {
+        /// The deprecated symbol the plugin uses.
+        pub symbol     : &'a str,
+        /// What the plugin should use instead, if anything.
+        pub replacement: Option<&'a str>,
+}
+
+/// ## DiagnosticsSink
+/// Receives the diagnostics of a [`PluginManager`](crate::plugin_manager::PluginManager)
+/// and of the plugins it loads. Install one with
+/// [`set_diagnostics_sink`](crate::plugin_manager::PluginManager::set_diagnostics_sink)
+/// to show them somewhere else than the log, for example in the notification area of a
+/// GUI application.
+///
+/// ## Example
+/// ```rust
+/// use vplugin::{Diagnostic, DiagnosticsSink, PluginManager, Severity};
+///
+/// struct Notifications;
+///
+/// impl DiagnosticsSink for Notifications {
+///     fn report(&self, diagnostic: &Diagnostic<'_>) {
+///         if diagnostic.severity >= Severity::Warning {
+///             let plugin = diagnostic.plugin.unwrap_or("VPlugin");
+///             println!("{}: {}", plugin, diagnostic.message);
+///         }
+///     }
+/// }
+///
+/// let mut manager = PluginManager::new();
+/// manager.set_diagnostics_sink(Notifications);
+/// ```
+pub trait DiagnosticsSink: Send + Sync {
+        /// Called for every diagnostic. This may be called from any thread that
+        /// uses the manager or its plugins.
+        fn report(&self, diagnostic: &Diagnostic<'_>);
+}
+
+/// The default [`DiagnosticsSink`], forwarding everything to the [`log`] crate under
+/// the `vplugin` target. Applications using `tracing` receive these messages through
+/// its `log` compatibility layer.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogSink;
+
+impl DiagnosticsSink for LogSink {
+        fn report(&self, diagnostic: &Diagnostic<'_>) {
+                let level = match diagnostic.severity {
+                        Severity::Trace   => log::Level::Trace,
+                        Severity::Debug   => log::Level::Debug,
+                        Severity::Info    => log::Level::Info,
+                        Severity::Warning => log::Level::Warn,
+                        Severity::Error   => log::Level::Error,
+                };
+                match diagnostic.backtrace {
+                        Some(backtrace) => log::log!(target: "vplugin", level, "{}\nBacktrace:\n{}", diagnostic.message, backtrace),
+                        None            => log::log!(target: "vplugin", level, "{}", diagnostic.message)
+                }
+        }
+}
+
+/// The sink a manager or plugin reports to. `None` means the sink of the
+/// enclosing scope (Or [`LogSink`]) is used.
+#[derive(Clone, Default)]
+pub(crate) struct Sink(Option<Arc<dyn DiagnosticsSink>>);
+
+impl Sink {
+        pub(crate) fn new(sink: impl DiagnosticsSink + 'static) -> Self {
+                Self(Some(Arc::new(sink)))
+        }
+
+        /// Makes this sink (And `plugin`) the current one of the thread, until
+        /// the returned [`Scope`] is dropped.
+        pub(crate) fn scope(&self, plugin: Option<&str>) -> Scope {
+                SCOPES.with(|scopes| scopes.borrow_mut().push(Context {
+                        sink  : self.0.clone(),
+                        plugin: plugin.map(str::to_owned),
+                }));
+                Scope(())
+        }
+}
+
+impl fmt::Debug for Sink {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self.0 {
+                        Some(_) => f.write_str("Sink(custom)"),
+                        None    => f.write_str("Sink(default)")
+                }
+        }
+}
+
+struct Context {
+        sink  : Option<Arc<dyn DiagnosticsSink>>,
+        plugin: Option<String>,
+}
+
+thread_local! {
+        static SCOPES: RefCell<Vec<Context>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Restores the previous sink of the thread when dropped.
+pub(crate) struct Scope(());
+
+impl Drop for Scope {
+        fn drop(&mut self) {
+                SCOPES.with(|scopes| scopes.borrow_mut().pop());
+        }
+}
+
+/// Reports a message to the current sink of the thread.
+pub(crate) fn emit(severity: Severity, message: fmt::Arguments<'_>) {
+        report(severity, None, None, message)
+}
+
+/// Reports, as a warning, that the plugin of the current scope uses something deprecated.
+pub(crate) fn deprecated(deprecation: Deprecation<'_>, message: fmt::Arguments<'_>) {
+        report(Severity::Warning, Some(deprecation), None, message)
+}
+
+/// Reports, as an error, that the plugin of the current scope failed, with the backtrace
+/// captured when it did (If any).
+pub(crate) fn failed(backtrace: Option<&str>, message: fmt::Arguments<'_>) {
+        report(Severity::Error, None, backtrace, message)
+}
+
+fn report(severity: Severity, deprecation: Option<Deprecation<'_>>, backtrace: Option<&str>, message: fmt::Arguments<'_>) {
+        /* The sink may use VPlugin itself, so the stack can't stay borrowed while it runs. */
+        let (sink, plugin) = SCOPES.with(|scopes| {
+                let scopes = scopes.borrow();
+                let sink   = scopes.iter().rev().find_map(|c| c.sink.clone());
+                let plugin = scopes.iter().rev().find_map(|c| c.plugin.clone());
+                (sink, plugin)
+        });
+
+        let diagnostic = Diagnostic { severity, plugin: plugin.as_deref(), message, deprecation, backtrace };
+        match sink {
+                Some(sink) => sink.report(&diagnostic),
+                None       => LogSink.report(&diagnostic)
+        }
+}
+
+/* Drop-in replacements for the `log` macros, reporting to the current sink instead. */
+macro_rules! error {
+        ($($arg:tt)+) => {
+                $crate::diagnostics::emit($crate::diagnostics::Severity::Error, format_args!($($arg)+))
+        };
+}
+
+macro_rules! warning {
+        ($($arg:tt)+) => {
+                $crate::diagnostics::emit($crate::diagnostics::Severity::Warning, format_args!($($arg)+))
+        };
+}
+
+macro_rules! trace {
+        ($($arg:tt)+) => {
+                $crate::diagnostics::emit($crate::diagnostics::Severity::Trace, format_args!($($arg)+))
+        };
+}
+
+pub(crate) use {error, warning, trace};
@@ -0,0 +1,144 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Finding the packages inside a directory of plugins, see
+//! [`PluginManager::load_from_directory`](crate::plugin_manager::PluginManager::load_from_directory).
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::diagnostics;
+use crate::error::VPluginError;
+use crate::plugin::Plugin;
+
+/// The extension of the packages found.
+pub const PACKAGE_EXTENSION: &str = "vpl";
+
+/// ## DiscoveryReport
+/// What [`PluginManager::load_from_directory`](crate::plugin_manager::PluginManager::load_from_directory)
+/// found and loaded. One plugin failing doesn't stop the others from being loaded, the
+/// failures are all collected here instead.
+///
+/// ## Example
+/// ```rust,no_run
+/// # mod my_app {
+/// #     pub fn plugins_dir() -> &'static str { "plugins" }
+/// #     pub fn notify(_message: String) {}
+/// # }
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let mut manager = vplugin::PluginManager::new();
+/// let report = manager.load_from_directory(my_app::plugins_dir(), true)?;
+/// for (path, error) in report.failed() {
+///     my_app::notify(format!("Couldn't load {}: {}", path.display(), error));
+/// }
+/// let plugins = report.into_plugins();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct DiscoveryReport {
+        /// Every package found, in the (Bytewise) order of their paths, with the result of
+        /// loading it. Subdirectories that couldn't be read are listed as well, with the
+        /// error that occurred.
+        pub plugins: Vec<(PathBuf, Result<Plugin, VPluginError>)>,
+}
+
+impl DiscoveryReport {
+        /// Returns how many plugins were loaded.
+        pub fn loaded(&self) -> usize {
+                self.plugins.iter().filter(|(_, result)| result.is_ok()).count()
+        }
+
+        /// Returns the packages (Or subdirectories) that failed, with their error.
+        pub fn failed(&self) -> impl Iterator<Item = (&Path, &VPluginError)> {
+                self.plugins
+                        .iter()
+                        .filter_map(|(path, result)| result.as_ref().err().map(|e| (path.as_path(), e)))
+        }
+
+        /// Returns whether everything found was loaded.
+        pub fn is_complete(&self) -> bool {
+                self.failed().next().is_none()
+        }
+
+        /// Returns the plugins that were loaded, dropping the failures.
+        pub fn into_plugins(self) -> Vec<Plugin> {
+                self.plugins
+                        .into_iter()
+                        .filter_map(|(_, result)| result.ok())
+                        .collect()
+        }
+}
+
+impl fmt::Display for DiscoveryReport {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let failed = self.plugins.len() - self.loaded();
+                write!(f, "{} plugin(s) loaded, {} failed", self.loaded(), failed)?;
+                for (path, error) in self.failed() {
+                        write!(f, "\n  {}: {}", path.display(), error)?;
+                }
+                Ok(())
+        }
+}
+
+/// What [`find_packages`] found.
+#[derive(Debug, Default)]
+pub(crate) struct Found {
+        /// The packages, sorted by path.
+        pub(crate) packages  : Vec<PathBuf>,
+        /// The subdirectories that couldn't be read, sorted by path.
+        pub(crate) unreadable: Vec<(PathBuf, VPluginError)>,
+}
+
+/// Returns the packages inside `directory`, and its subdirectories if `recursive` is set.
+/// Symbolic links to directories aren't followed, so links pointing back up the tree
+/// aren't an issue.
+pub(crate) fn find_packages(directory: &Path, recursive: bool) -> Result<Found, VPluginError> {
+        let mut found   = Found::default();
+        let mut pending = vec![directory.to_path_buf()];
+
+        while let Some(dir) = pending.pop() {
+                let entries = match fs::read_dir(&dir) {
+                        Ok (entries) => entries,
+                        Err(e) if dir == directory => {
+                                diagnostics::error!("Couldn't read the plugin directory {}: {}", dir.display(), e);
+                                return Err(VPluginError::from_io(&e));
+                        },
+                        Err(e) => {
+                                diagnostics::warning!("Skipping {}, which couldn't be read: {}", dir.display(), e);
+                                found.unreadable.push((dir, VPluginError::from_io(&e)));
+                                continue;
+                        }
+                };
+                for entry in entries.flatten() {
+                        let path = entry.path();
+                        match entry.file_type() {
+                                Ok (kind) if kind.is_dir() && recursive => pending.push(path),
+                                Ok (_) if is_package(&path) => found.packages.push(path),
+                                _ => ()
+                        }
+                }
+        }
+        found.packages.sort();
+        found.unreadable.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(found)
+}
+
+/* Whether `path` is a file (Or a link to one) with the extension of packages. */
+fn is_package(path: &Path) -> bool {
+        let extension = path.extension().map(|extension| extension.eq_ignore_ascii_case(PACKAGE_EXTENSION));
+        extension == Some(true) && path.is_file()
+}
@@ -0,0 +1,177 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Extension points: hooks the host lets several plugins implement, called one after
+//! the other like middleware.
+//!
+//! The host names an extension point after the symbol plugins export to implement it,
+//! and gets the implementations of the started plugins with
+//! [`PluginManager::extensions`](crate::plugin_manager::PluginManager::extensions).
+//! The returned [`ExtensionChain`] calls them in the order the plugins were added to the
+//! manager:
+//! ```rust,no_run
+//! # use std::ffi::{c_char, CString};
+//! # let mut manager = vplugin::PluginManager::new();
+//! # let text = CString::new("some text").unwrap();
+//! use vplugin::extension::ExtensionPoint;
+//!
+//! /* Plugins implement it by exporting `int32_t filter_text(const char *text)`. */
+//! static FILTER_TEXT: ExtensionPoint<unsafe extern "C" fn(*const c_char) -> i32> =
+//!     unsafe { ExtensionPoint::new("filter_text") };
+//!
+//! let chain   = manager.extensions(&FILTER_TEXT);
+//! let blocked = chain.first(|filter| (unsafe { filter(text.as_ptr()) } != 0).then_some(()));
+//! ```
+
+use std::fmt;
+use std::marker::PhantomData;
+use uuid::Uuid;
+use crate::guard::HookGuard;
+use crate::handle::PluginHandle;
+use crate::intercept::{Call, CallKind, Interceptors};
+
+/// ## ExtensionPoint
+/// A hook several plugins can implement, named after the symbol they export and typed
+/// with the signature of that symbol (`F`, usually an `unsafe extern "C" fn` pointer).
+/// See the [module documentation](crate::extension).
+pub struct ExtensionPoint<F: Copy> {
+        name : &'static str,
+        _hook: PhantomData<F>,
+}
+
+impl<F: Copy> ExtensionPoint<F> {
+        /// Declares the extension point `name`.
+        ///
+        /// ## Safety
+        /// `F` must be the actual type of the symbol `name` in every plugin exporting it.
+        pub const unsafe fn new(name: &'static str) -> Self {
+                Self { name, _hook: PhantomData }
+        }
+
+        /// Returns the name of the extension point, the symbol plugins export.
+        pub fn name(&self) -> &'static str {
+                self.name
+        }
+}
+
+impl<F: Copy> fmt::Debug for ExtensionPoint<F> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_tuple("ExtensionPoint").field(&self.name).finish()
+        }
+}
+
+/// A single implementation of an extension point, inside an [`ExtensionChain`].
+/// It pins its plugin like a [`HookGuard`], which it dereferences to.
+#[derive(Debug)]
+pub struct Extension<F: Copy> {
+        /// The plugin implementing the extension point.
+        pub plugin: PluginHandle,
+        pub(crate) name        : String,
+        pub(crate) instance    : Uuid,
+        pub(crate) hook        : HookGuard<F>,
+        pub(crate) interceptors: Interceptors,
+}
+
+impl<F: Copy> Extension<F> {
+        /*
+         * Calls `call` with the hook, wrapped by the interceptors of the plugin. Returns `Err`
+         * with `value` back if the call was refused, so the chain can go on without it.
+         */
+        fn call<A, R>(&self, point: &str, value: A, call: impl FnOnce(A, F) -> R) -> Result<R, A> {
+                let description = Call { plugin: &self.name, instance: self.instance, kind: CallKind::Hook(point) };
+                let _intercepted = match self.interceptors.before(&description) {
+                        Ok (intercepted) => intercepted,
+                        Err(_)           => return Err(value)
+                };
+                Ok(call(value, *self.hook))
+        }
+}
+
+impl<F: Copy> std::ops::Deref for Extension<F> {
+        type Target = F;
+
+        fn deref(&self) -> &F {
+                &self.hook
+        }
+}
+
+/// ## ExtensionChain
+/// The implementations of an extension point by the started plugins of a manager, in
+/// the order the plugins were added, returned by
+/// [`PluginManager::extensions`](crate::plugin_manager::PluginManager::extensions).
+///
+/// The chain pins the plugins, so they can't be terminated while it's alive. Don't keep
+/// it around: get a new one every time the extension point is invoked, so plugins started
+/// or terminated in the meantime are taken into account.
+///
+/// Every call is wrapped by the interceptors of the manager (See [`intercept`](crate::intercept)),
+/// implementations whose call is refused are skipped.
+#[derive(Debug)]
+pub struct ExtensionChain<F: Copy> {
+        point: &'static str,
+        links: Vec<Extension<F>>,
+}
+
+impl<F: Copy> ExtensionChain<F> {
+        pub(crate) fn new(point: &'static str, links: Vec<Extension<F>>) -> Self {
+                Self { point, links }
+        }
+
+        /// Returns how many plugins implement the extension point.
+        pub fn len(&self) -> usize {
+                self.links.len()
+        }
+
+        /// Returns whether no plugin implements the extension point.
+        pub fn is_empty(&self) -> bool {
+                self.links.is_empty()
+        }
+
+        /// Returns an iterator over the implementations, in order.
+        pub fn iter(&self) -> impl Iterator<Item = &Extension<F>> {
+                self.links.iter()
+        }
+
+        /// Calls `call` with every implementation in order, until it returns `Some`, which
+        /// is returned. The remaining implementations aren't called.
+        pub fn first<T>(&self, mut call: impl FnMut(F) -> Option<T>) -> Option<T> {
+                self.links.iter().find_map(|link| link.call(self.point, (), |(), hook| call(hook)).ok().flatten())
+        }
+
+        /// Calls `call` with every implementation in order, and returns what each of them
+        /// returned (Leaving out the calls that were refused).
+        pub fn all<T>(&self, mut call: impl FnMut(F) -> T) -> Vec<T> {
+                self.links.iter().filter_map(|link| link.call(self.point, (), |(), hook| call(hook)).ok()).collect()
+        }
+
+        /// Threads `init` through every implementation in order: `call` is given the value
+        /// returned for the previous implementation (`init` for the first one), and the
+        /// value returned for the last one is returned.
+        pub fn fold<A>(&self, init: A, mut call: impl FnMut(A, F) -> A) -> A {
+                self.links.iter().fold(init, |acc, link| {
+                        link.call(self.point, acc, &mut call).unwrap_or_else(|acc| acc)
+                })
+        }
+}
+
+impl<'a, F: Copy> IntoIterator for &'a ExtensionChain<F> {
+        type Item     = &'a Extension<F>;
+        type IntoIter = std::slice::Iter<'a, Extension<F>>;
+
+        fn into_iter(self) -> Self::IntoIter {
+                self.links.iter()
+        }
+}
@@ -0,0 +1,62 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! The points where a [`FaultInjector`](crate::testing::FaultInjector) can make
+//! VPlugin fail. Without the `testing` feature, checking them does nothing.
+
+use crate::error::VPluginError;
+
+/// A point where a fault can be injected.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(not(feature = "testing"), allow(dead_code))]
+pub(crate) enum Stage<'a> {
+        #[cfg_attr(not(feature = "archive"), allow(dead_code))]
+        Extract,
+        Metadata,
+        Open,
+        EntryPoint,
+        Hook(&'a str),
+        Destructor,
+}
+
+/// The fault injector of a manager, and of the plugins it loads.
+#[cfg(feature = "testing")]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Faults(pub(crate) Option<std::sync::Arc<crate::testing::FaultInjector>>);
+
+#[cfg(feature = "testing")]
+impl Faults {
+        /// Returns the error injected at `stage`, if any, after waiting for the
+        /// delay injected there.
+        pub(crate) fn check(&self, stage: Stage<'_>) -> Result<(), VPluginError> {
+                match &self.0 {
+                        Some(injector) => injector.trigger(stage),
+                        None           => Ok(())
+                }
+        }
+}
+
+#[cfg(not(feature = "testing"))]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Faults(());
+
+#[cfg(not(feature = "testing"))]
+impl Faults {
+        #[inline(always)]
+        pub(crate) fn check(&self, _stage: Stage<'_>) -> Result<(), VPluginError> {
+                Ok(())
+        }
+}
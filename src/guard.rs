@@ -0,0 +1,131 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use crate::backend::PluginBackend;
+use crate::plugin::Plugin;
+
+/// ## HookGuard
+/// A hook that keeps its plugin pinned while it's alive. As long as a guard exists,
+/// [`Plugin::terminate`](crate::plugin::Plugin::terminate) refuses to terminate the plugin
+/// (Or waits for the guards to be dropped, see [`PinPolicy`]), and the guard holds a
+/// reference to the plugin's library: if the plugin is dropped or unloaded anyway, its
+/// library is only unloaded once the last guard is dropped. This way the function pointer
+/// inside can't end up pointing to unloaded code.
+/// 
+/// Guards are returned by [`Plugin::hook_guard`](crate::plugin::Plugin::hook_guard) and
+/// [`Plugin::hook`](crate::plugin::Plugin::hook), and dereference to the function pointer itself:
+/// ```rust,no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let mut plugin = vplugin::Plugin::load("plugin.vpl")?;
+/// let add_one = plugin.hook_guard::<i32, i32>("add_one")?;
+/// let result  = unsafe { (*add_one)(41) };
+/// # Ok(())
+/// # }
+/// ```
+pub struct HookGuard<F: Copy> {
+        hook: F,
+        pins: Arc<AtomicUsize>,
+        /* Keeps the code `hook` points to loaded. */
+        library: Option<Arc<dyn PluginBackend>>,
+}
+
+impl<F: Copy> HookGuard<F> {
+        pub(crate) fn new(hook: F, plugin: &Plugin) -> Self {
+                Self::pinning(hook, &plugin.pins, plugin.raw.clone())
+        }
+
+        fn pinning(hook: F, pins: &Arc<AtomicUsize>, library: Option<Arc<dyn PluginBackend>>) -> Self {
+                pins.fetch_add(1, Ordering::AcqRel);
+                Self {
+                        hook,
+                        pins: Arc::clone(pins),
+                        library,
+                }
+        }
+
+        /// Returns the function pointer held by the guard. The pointer stays valid
+        /// only as long as the guard is alive, don't keep it around afterwards.
+        pub fn get(&self) -> F {
+                self.hook
+        }
+}
+
+impl<F: Copy> Deref for HookGuard<F> {
+        type Target = F;
+
+        fn deref(&self) -> &F {
+                &self.hook
+        }
+}
+
+impl<F: Copy> Clone for HookGuard<F> {
+        fn clone(&self) -> Self {
+                Self::pinning(self.hook, &self.pins, self.library.clone())
+        }
+}
+
+impl<F: Copy> Drop for HookGuard<F> {
+        fn drop(&mut self) {
+                self.pins.fetch_sub(1, Ordering::AcqRel);
+        }
+}
+
+impl<F: Copy> fmt::Debug for HookGuard<F> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct("HookGuard")
+                        .field("pins", &self.pins.load(Ordering::Acquire))
+                        .finish()
+        }
+}
+
+/// ## PinPolicy
+/// What [`Plugin::terminate`](crate::plugin::Plugin::terminate) does if
+/// [`HookGuard`]s of the plugin are still alive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PinPolicy {
+        /// Fail immediately with `VPluginError::PluginInUse` (The default).
+        #[default]
+        Fail,
+        /// Wait up to the given duration for the guards to be dropped (By other
+        /// threads), then fail with `VPluginError::PluginInUse`.
+        Wait(Duration),
+}
+
+/// Waits for every pin to be released according to `policy`. Returns the
+/// number of pins still held if they weren't released in time.
+pub(crate) fn wait_unpinned(pins: &AtomicUsize, policy: PinPolicy) -> Result<(), usize> {
+        let timeout = match policy {
+                PinPolicy::Fail          => Duration::ZERO,
+                PinPolicy::Wait(timeout) => timeout,
+        };
+        let start = std::time::Instant::now();
+
+        loop {
+                let held = pins.load(Ordering::Acquire);
+                if held == 0 {
+                        return Ok(());
+                }
+                if start.elapsed() >= timeout {
+                        return Err(held);
+                }
+                std::thread::sleep(Duration::from_millis(1));
+        }
+}
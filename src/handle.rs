@@ -0,0 +1,141 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+use std::fmt;
+
+/// ## PluginHandle
+/// A lightweight, `Copy`-able reference to a plugin owned by a
+/// [`PluginManager`](crate::plugin_manager::PluginManager). Handles can be
+/// stored anywhere in the application and passed to the manager's functions
+/// instead of `&mut Plugin`.
+/// 
+/// Every handle carries a generation: Once the plugin it refers to is removed,
+/// the handle stays invalid even if a new plugin reuses the same slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(C)]
+pub struct PluginHandle {
+        pub(crate) index     : u32,
+        pub(crate) generation: u32,
+}
+
+impl fmt::Display for PluginHandle {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "#{}.{}", self.index, self.generation)
+        }
+}
+
+/* A generational arena, where removed slots are reused with a new generation. */
+#[derive(Debug)]
+pub(crate) struct Slots<T> {
+        entries: Vec<Entry<T>>,
+        free   : Vec<u32>,
+}
+
+#[derive(Debug)]
+struct Entry<T> {
+        generation: u32,
+        value     : Option<T>,
+}
+
+impl<T> Default for Slots<T> {
+        fn default() -> Self {
+                Self {
+                        entries: Vec::new(),
+                        free   : Vec::new(),
+                }
+        }
+}
+
+impl<T> Slots<T> {
+        pub(crate) fn insert(&mut self, value: T) -> PluginHandle {
+                match self.free.pop() {
+                        Some(index) => {
+                                let entry = &mut self.entries[index as usize];
+                                entry.generation = entry.generation.wrapping_add(1);
+                                entry.value      = Some(value);
+                                PluginHandle { index, generation: entry.generation }
+                        },
+                        None => {
+                                let index = self.entries.len() as u32;
+                                self.entries.push(Entry { generation: 0, value: Some(value) });
+                                PluginHandle { index, generation: 0 }
+                        }
+                }
+        }
+
+        pub(crate) fn get(&self, handle: PluginHandle) -> Option<&T> {
+                match self.entries.get(handle.index as usize) {
+                        Some(entry) if entry.generation == handle.generation => entry.value.as_ref(),
+                        _ => None
+                }
+        }
+
+        pub(crate) fn get_mut(&mut self, handle: PluginHandle) -> Option<&mut T> {
+                match self.entries.get_mut(handle.index as usize) {
+                        Some(entry) if entry.generation == handle.generation => entry.value.as_mut(),
+                        _ => None
+                }
+        }
+
+        pub(crate) fn remove(&mut self, handle: PluginHandle) -> Option<T> {
+                let value = match self.entries.get_mut(handle.index as usize) {
+                        Some(entry) if entry.generation == handle.generation => entry.value.take(),
+                        _ => None
+                };
+                if value.is_some() {
+                        self.free.push(handle.index);
+                }
+                value
+        }
+
+        /* Takes the value out without freeing the slot, see restore(). */
+        pub(crate) fn take(&mut self, handle: PluginHandle) -> Option<T> {
+                match self.entries.get_mut(handle.index as usize) {
+                        Some(entry) if entry.generation == handle.generation => entry.value.take(),
+                        _ => None
+                }
+        }
+
+        /* Puts a value taken with take() back into its slot. */
+        pub(crate) fn restore(&mut self, handle: PluginHandle, value: T) {
+                if let Some(entry) = self.entries.get_mut(handle.index as usize) {
+                        if entry.generation == handle.generation {
+                                entry.value = Some(value);
+                        }
+                }
+        }
+
+        /* Whether a value was ever inserted in the slot of `handle`, whatever its generation. */
+        pub(crate) fn was_used(&self, handle: PluginHandle) -> bool {
+                (handle.index as usize) < self.entries.len()
+        }
+
+        pub(crate) fn iter(&self) -> impl Iterator<Item = (PluginHandle, &T)> {
+                self.entries.iter().enumerate().filter_map(|(index, entry)| {
+                        entry.value.as_ref().map(|value| {
+                                (PluginHandle { index: index as u32, generation: entry.generation }, value)
+                        })
+                })
+        }
+
+        pub(crate) fn clear(&mut self) {
+                for (index, entry) in self.entries.iter_mut().enumerate() {
+                        if entry.value.take().is_some() {
+                                self.free.push(index as u32);
+                        }
+                }
+        }
+}
@@ -0,0 +1,112 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+use crate::error::VPluginError;
+use crate::plugin::Plugin;
+
+/// ## Hooks
+/// A set of hooks resolved from a plugin all at once. This is implemented by the
+/// structs generated with [`declare_hooks!`](crate::declare_hooks), and resolved
+/// with [`Plugin::hooks`](crate::plugin::Plugin::hooks) or
+/// [`PluginManager::begin_with_hooks`](crate::plugin_manager::PluginManager::begin_with_hooks).
+pub trait Hooks: Sized {
+        /// Looks up every hook of the set in `plugin`, which must be started.
+        /// `VPluginError::MissingSymbol` is returned if any of them is missing.
+        fn resolve(plugin: &Plugin) -> Result<Self, VPluginError>;
+
+        /// The names of the hooks of the set, checked before the entry point of plugins runs
+        /// when the set is required with
+        /// [`PluginManager::require_hooks`](crate::plugin_manager::PluginManager::require_hooks).
+        /// Sets that don't list them are only checked once resolved.
+        fn names() -> &'static [&'static str] {
+                &[]
+        }
+}
+
+/// Declares a struct holding typed hooks of a plugin.
+///
+/// Every hook is declared like a function, and gets an `unsafe` method with the same
+/// signature calling into the plugin. The hooks are all resolved together (See
+/// [`Hooks`](crate::hooks::Hooks)), so a plugin missing any of them is detected when
+/// it starts rather than the first time the hook is needed, and calling a hook with the
+/// wrong arguments is a compile error instead of a bad `transmute`.
+///
+/// The struct pins the plugin (Like a [`HookGuard`](crate::guard::HookGuard)), so the
+/// plugin can't be terminated while the hooks are alive. Hosts can also require the set
+/// from every plugin with [`PluginManager::require_hooks`](crate::plugin_manager::PluginManager::require_hooks),
+/// so plugins missing a hook are refused before their entry point runs.
+///
+/// ## Example
+/// ```rust,no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// vplugin::declare_hooks! {
+///     pub struct EditorHooks {
+///         fn on_frame(dt: f32) -> i32;
+///         fn on_close();
+///     }
+/// }
+///
+/// let mut manager = vplugin::PluginManager::new();
+/// let plugin = manager.load("plugin.vpl")?;
+/// let hooks  = manager.begin_with_hooks::<EditorHooks>(plugin)?;
+/// let status = unsafe { hooks.on_frame(0.016) };
+/// # Ok(())
+/// # }
+/// ```
+#[macro_export]
+macro_rules! declare_hooks {
+        (
+                $(#[$attr:meta])*
+                $vis:vis struct $name:ident {
+                        $(
+                                $(#[$hook_attr:meta])*
+                                fn $hook:ident ( $($arg:ident : $ty:ty),* $(,)? ) $(-> $ret:ty)? ;
+                        )*
+                }
+        ) => {
+                $(#[$attr])*
+                $vis struct $name {
+                        $( $hook: unsafe extern "C" fn($($ty),*) $(-> $ret)?, )*
+                        _pin: $crate::HookGuard<()>,
+                }
+
+                impl $crate::Hooks for $name {
+                        fn resolve(plugin: &$crate::Plugin) -> ::std::result::Result<Self, $crate::VPluginError> {
+                                ::std::result::Result::Ok(Self {
+                                        $( $hook: unsafe { plugin.get_symbol(stringify!($hook))? }, )*
+                                        _pin: plugin.pin(),
+                                })
+                        }
+
+                        fn names() -> &'static [&'static str] {
+                                &[$(stringify!($hook)),*]
+                        }
+                }
+
+                impl $name {
+                        $(
+                                $(#[$hook_attr])*
+                                ///
+                                /// # Safety
+                                /// The plugin must export this hook with the declared signature.
+                                #[allow(clippy::too_many_arguments)]
+                                $vis unsafe fn $hook(&self, $($arg: $ty),*) $(-> $ret)? {
+                                        (self.$hook)($($arg),*)
+                                }
+                        )*
+                }
+        };
+}
@@ -0,0 +1,173 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Localized strings bundled in plugin packages.
+//!
+//! Packages can contain one file per locale in `assets/i18n`, named after the locale
+//! (`assets/i18n/fr.toml`, `assets/i18n/pt-BR.toml`, ...). Every string of the file is a
+//! translation, nested tables are flattened into dotted keys:
+//! ```toml
+//! title = "Correcteur"
+//!
+//! [menu]
+//! check = "Vérifier l'orthographe" # menu.check
+//! ```
+
+use std::collections::HashMap;
+use std::ffi::{c_char, CStr};
+use std::fs;
+use std::path::Path;
+use crate::diagnostics;
+use crate::error::VPluginError;
+use crate::workspace;
+use crate::isolation::Unguarded;
+
+/// Where the translations are inside a package.
+pub const I18N_DIRECTORY: &str = "assets/i18n";
+
+/// ## Translations
+/// The strings of a plugin translated in one locale, returned by
+/// [`Plugin::translations`](crate::plugin::Plugin::translations).
+///
+/// The host looks strings up with [`get`](Translations::get). To let the plugin itself use
+/// them, the host can put [`translate`] in its API table (See
+/// [`HostApiVersion`](crate::api::HostApiVersion)), along with a pointer to the `Translations`.
+#[derive(Debug, Clone, Default)]
+pub struct Translations {
+        locale : String,
+        /* The values are nul-terminated, so `translate` can return them as is. */
+        strings: HashMap<String, String>,
+}
+
+impl Translations {
+        /// Loads the translations for `locale` (`pt-BR`, `fr`, ...) from the `assets/i18n`
+        /// directory of the package extracted in `directory`. If there is no file for `locale`,
+        /// the file of its language (`pt`) is used instead.
+        ///
+        /// `VPluginError::NoSuchFile` is returned if neither exists.
+        pub fn load(directory: &Path, locale: &str) -> Result<Self, VPluginError> {
+                let i18n = workspace::find(directory, Path::new(I18N_DIRECTORY));
+                let language = locale.split(['-', '_']).next().unwrap_or(locale);
+                let candidates = [locale, language];
+
+                let found = candidates.iter().find_map(|candidate| {
+                        let file = workspace::find(&i18n, Path::new(&format!("{}.toml", candidate)));
+                        file.is_file().then(|| (candidate.to_string(), file))
+                });
+                let (locale, file) = match found {
+                        Some(found) => found,
+                        None        => {
+                                diagnostics::trace!("No translations for locale '{}' in {}.", locale, i18n.display());
+                                return Err(VPluginError::NoSuchFile);
+                        }
+                };
+
+                let contents = match fs::read_to_string(&file) {
+                        Ok (contents) => contents,
+                        Err(e)        => {
+                                diagnostics::error!("Couldn't read {}: {}", file.display(), e);
+                                return Err(VPluginError::from_io(&e));
+                        }
+                };
+                let table: toml::Table = match toml::from_str(&contents) {
+                        Ok (table) => table,
+                        Err(e)     => {
+                                diagnostics::error!("Invalid translations in {}: {}", file.display(), e);
+                                return Err(VPluginError::InvalidPlugin);
+                        }
+                };
+
+                let mut strings = HashMap::new();
+                flatten(&mut strings, String::new(), table);
+                Ok(Self { locale, strings })
+        }
+
+        /// Returns the locales a package has translations for, from the names of the files
+        /// inside its `assets/i18n` directory.
+        pub fn available(directory: &Path) -> Vec<String> {
+                let i18n = workspace::find(directory, Path::new(I18N_DIRECTORY));
+                let mut locales: Vec<String> = match fs::read_dir(i18n) {
+                        Ok (entries) => entries
+                                .filter_map(Result::ok)
+                                .map(|entry| entry.path())
+                                .filter(|path| path.extension().is_some_and(|e| e == "toml"))
+                                .filter_map(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+                                .collect(),
+                        Err(_)       => Vec::new()
+                };
+                locales.sort();
+                locales
+        }
+
+        /// The locale the translations were loaded from. This is the language alone
+        /// (`pt`) when there was no file for the requested locale (`pt-BR`).
+        pub fn locale(&self) -> &str {
+                &self.locale
+        }
+
+        /// Returns the translation of `key`, if any.
+        pub fn get(&self, key: &str) -> Option<&str> {
+                self.strings.get(key).map(|value| &value[..value.len() - 1])
+        }
+
+        /// Returns the translation of `key`, or `key` itself if there is none, so
+        /// untranslated strings are still shown.
+        pub fn get_or_key<'a>(&'a self, key: &'a str) -> &'a str {
+                self.get(key).unwrap_or(key)
+        }
+}
+
+/* Adds every string of `table` to `strings`, prefixing their keys with `prefix`. */
+fn flatten(strings: &mut HashMap<String, String>, prefix: String, table: toml::Table) {
+        for (key, value) in table {
+                let key = if prefix.is_empty() { key } else { format!("{}.{}", prefix, key) };
+                match value {
+                        toml::Value::String(mut value) => {
+                                /* A nul inside would cut the string short for plugins. */
+                                value.retain(|c| c != '\0');
+                                value.push('\0');
+                                strings.insert(key, value);
+                        },
+                        toml::Value::Table(table) => flatten(strings, key, table),
+                        other => diagnostics::warning!(
+                                "Ignoring translation '{}': expected a string, found {}.",
+                                key,
+                                other.type_str()
+                        )
+                }
+        }
+}
+
+/// Looks up `key` in `translations`, for plugins: the host can add this function to its API
+/// table, along with a pointer to the [`Translations`] of the plugin. Returns the translation
+/// as a nul-terminated string, valid as long as `translations` is, or null if there is none.
+///
+/// ## Safety
+/// `translations` must point to a valid `Translations`, and `key` to a nul-terminated string.
+pub unsafe extern "C" fn translate(translations: *const Translations, key: *const c_char) -> *const c_char {
+        let _unguarded = Unguarded::enter();
+        if translations.is_null() || key.is_null() {
+                return std::ptr::null();
+        }
+        let key = match CStr::from_ptr(key).to_str() {
+                Ok (key) => key,
+                Err(_)   => return std::ptr::null()
+        };
+        match (*translations).strings.get(key) {
+                Some(value) => value.as_ptr().cast(),
+                None        => std::ptr::null()
+        }
+}
@@ -0,0 +1,121 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! What identifies a single load of a plugin: a random instance ID, and the hash
+//! of the package it was loaded from.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use sha2::{Digest, Sha256};
+use crate::plugin::Plugin;
+
+/// Returns the SHA-256 hash of the package at `package`, in lowercase hexadecimal.
+///
+/// Archives and object files are hashed as they are. Directories are hashed file by
+/// file, in the order of their paths: every file adds its path relative to the directory
+/// (With `/` as separator), a nul byte, its length as 8 little-endian bytes and its
+/// contents. Two directories with the same files thus have the same hash, wherever
+/// they are.
+pub(crate) fn content_hash(package: &Path) -> io::Result<String> {
+        let mut hasher = Sha256::new();
+        if package.is_dir() {
+                let mut files = Vec::new();
+                list_files(package, package, &mut files)?;
+                files.sort();
+                for relative in files {
+                        let path = relative.split('/').fold(package.to_path_buf(), |path, part| path.join(part));
+                        hasher.update(relative.as_bytes());
+                        hasher.update([0]);
+                        hasher.update(fs::metadata(&path)?.len().to_le_bytes());
+                        io::copy(&mut File::open(&path)?, &mut hasher)?;
+                }
+        } else {
+                io::copy(&mut File::open(package)?, &mut hasher)?;
+        }
+        Ok(hex(&hasher.finalize()))
+}
+
+/// Returns what tells the package of `plugin` apart from any other, whatever the plugin
+/// calls itself: its content hash, or `builtin:<name>` for builtin plugins, which can't be
+/// replaced. `None` if the package of the plugin wasn't hashed.
+pub(crate) fn of(plugin: &Plugin) -> Option<String> {
+        match plugin.content_hash() {
+                Some(hash)                                     => Some(hash.to_owned()),
+                None if plugin.filename.as_os_str().is_empty() => Some(format!("builtin:{}", plugin.metadata.name)),
+                None                                           => None
+        }
+}
+
+/// Returns the SHA-256 hash of a package held in memory, the same as [`content_hash`]
+/// would return for the same archive on the disk.
+#[cfg(feature = "archive")]
+pub(crate) fn bytes_hash(package: &[u8]) -> String {
+        hex(&Sha256::digest(package))
+}
+
+fn hex(hash: &[u8]) -> String {
+        hash
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect()
+}
+
+/*
+ * Adds the paths of the files inside `dir` to `files`, relative to `root` and with `/` as separator.
+ * Symbolic links aren't followed into, so a link to one of its parents doesn't loop forever: they're
+ * listed as files, and hashed as the file they point to.
+ */
+pub(crate) fn list_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+                let entry = entry?;
+                let path  = entry.path();
+                if entry.file_type()?.is_dir() {
+                        list_files(root, &path, files)?;
+                        continue;
+                }
+                let relative = path
+                        .strip_prefix(root)
+                        .unwrap_or(&path)
+                        .components()
+                        .map(|c| c.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/");
+                files.push(relative);
+        }
+        Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+        use super::*;
+
+        #[cfg(unix)]
+        #[test]
+        fn symlinks_to_parents_are_not_followed() {
+                let dir = std::env::temp_dir().join(format!("vplugin-identity-{}", std::process::id()));
+                let _ = fs::remove_dir_all(&dir);
+                fs::create_dir_all(dir.join("lib")).unwrap();
+                fs::write(dir.join("lib/plugin.so"), "plugin").unwrap();
+                std::os::unix::fs::symlink("..", dir.join("lib/loop")).unwrap();
+
+                let mut files = Vec::new();
+                list_files(&dir, &dir, &mut files).unwrap();
+                files.sort();
+                assert_eq!(files, ["lib/loop", "lib/plugin.so"]);
+                let _ = fs::remove_dir_all(&dir);
+        }
+}
@@ -0,0 +1,182 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Interceptors wrapping the calls into plugins, to time, log, authorize or trace them
+//! in one place instead of around every call site of the application.
+//!
+//! Interceptors are registered with
+//! [`PluginManager::add_interceptor`](crate::plugin_manager::PluginManager::add_interceptor)
+//! and apply to every plugin of the manager, including those already loaded. They wrap:
+//! - The entry point of the plugins, when they're started.
+//! - Their destructor, when they're terminated. Interceptors only observe it, as a plugin
+//!   being terminated can't be kept running.
+//! - `vplugin_configure` and `vplugin_stop`, when they're handed their configuration and
+//!   asked to stop.
+//! - Their hooks, when called through
+//!   [`PluginManager::call_hook`](crate::plugin_manager::PluginManager::call_hook)
+//!   (Or [`Plugin::call_hook`](crate::plugin::Plugin::call_hook)) and
+//!   [extension chains](crate::extension::ExtensionChain).
+//!
+//! Hooks the application calls through a function pointer it resolved itself
+//! ([`Plugin::get_custom_hook`](crate::plugin::Plugin::get_custom_hook), [`HookGuard`](crate::guard::HookGuard),
+//! [`declare_hooks!`](crate::declare_hooks)) are called directly and aren't intercepted.
+//!
+//! ## Example
+//! ```rust,no_run
+//! # let mut manager = vplugin::PluginManager::new();
+//! struct Timing;
+//!
+//! impl vplugin::CallInterceptor for Timing {
+//!     fn after(&self, call: &vplugin::Call<'_>, elapsed: std::time::Duration) {
+//!         println!("{} of '{}' took {:?}", call.kind, call.plugin, elapsed);
+//!     }
+//! }
+//!
+//! manager.add_interceptor(Timing);
+//! ```
+
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+use crate::diagnostics;
+use crate::error::VPluginError;
+
+/// What a [`Call`] into a plugin is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallKind<'a> {
+        /// The entry point, starting the plugin.
+        EntryPoint,
+        /// The destructor, terminating the plugin.
+        Destructor,
+        /// `vplugin_configure`, handing the plugin its configuration before it's started.
+        Configure,
+        /// `vplugin_stop`, asking the plugin to stop (See [`Plugin::request_stop`](crate::plugin::Plugin::request_stop)).
+        Stop,
+        /// The hook with this name.
+        Hook(&'a str),
+}
+
+impl fmt::Display for CallKind<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self {
+                        CallKind::EntryPoint => write!(f, "entry point"),
+                        CallKind::Destructor => write!(f, "destructor"),
+                        CallKind::Configure  => write!(f, "configuration"),
+                        CallKind::Stop       => write!(f, "stop request"),
+                        CallKind::Hook(name) => write!(f, "hook '{}'", name)
+                }
+        }
+}
+
+/// A call into a plugin, as seen by [`CallInterceptor`]s.
+#[derive(Debug, Clone, Copy)]
+pub struct Call<'a> {
+        /// The name of the plugin called.
+        pub plugin  : &'a str,
+        /// The instance of the plugin called, see [`Plugin::instance_id`](crate::plugin::Plugin::instance_id).
+        pub instance: Uuid,
+        /// What's called.
+        pub kind    : CallKind<'a>,
+}
+
+/// ## CallInterceptor
+/// Logic running around the calls into plugins. See the [module documentation](crate::intercept).
+///
+/// When several interceptors are registered, `before` runs in the order they were
+/// registered and `after` in the reverse order, so each of them wraps the ones
+/// registered after it.
+pub trait CallInterceptor: Send + Sync {
+        /// Runs before the call. Returning an error refuses it: the plugin isn't called
+        /// (And no `after` runs), and the call fails with `VPluginError::CallRefused`
+        /// carrying the returned reason. Hooks refused inside an extension chain are
+        /// skipped instead. Destructors can't be refused, the error is only reported and
+        /// the destructor is called anyway.
+        fn before(&self, call: &Call<'_>) -> Result<(), String> {
+                let _ = call;
+                Ok(())
+        }
+
+        /// Runs after the call returned, with the time it took. It also runs if the call
+        /// panicked, while the panic unwinds, so it must not panic itself.
+        fn after(&self, call: &Call<'_>, elapsed: Duration) {
+                let _ = (call, elapsed);
+        }
+}
+
+/// The interceptors of a manager, shared with its plugins.
+#[derive(Clone, Default)]
+pub(crate) struct Interceptors(Arc<RwLock<Vec<Arc<dyn CallInterceptor>>>>);
+
+impl Interceptors {
+        pub(crate) fn add(&self, interceptor: Arc<dyn CallInterceptor>) {
+                self.0.write().unwrap_or_else(|e| e.into_inner()).push(interceptor);
+        }
+
+        /// Runs the `before` of every interceptor. The returned [`Intercepted`] runs their
+        /// `after` once dropped, which has to be once the plugin returned.
+        pub(crate) fn before<'a>(&self, call: &Call<'a>) -> Result<Intercepted<'a>, VPluginError> {
+                let interceptors = self.0.read().unwrap_or_else(|e| e.into_inner()).clone();
+                for interceptor in &interceptors {
+                        let reason = match interceptor.before(call) {
+                                Ok (())     => continue,
+                                Err(reason) => reason
+                        };
+                        if call.kind == CallKind::Destructor {
+                                diagnostics::warning!("Interceptor refused the destructor of '{}' ({}), calling it anyway.", call.plugin, reason);
+                                continue;
+                        }
+                        diagnostics::error!("Call to the {} of '{}' refused: {}", call.kind, call.plugin, reason);
+                        return Err(VPluginError::CallRefused {
+                                plugin: call.plugin.to_owned(),
+                                call  : call.kind.to_string(),
+                                reason
+                        });
+                }
+                Ok(Intercepted { interceptors, call: *call, started: Instant::now() })
+        }
+
+        /// Runs `f` wrapped by every interceptor.
+        pub(crate) fn run<R>(&self, call: &Call<'_>, f: impl FnOnce() -> R) -> Result<R, VPluginError> {
+                let _intercepted = self.before(call)?;
+                Ok(f())
+        }
+}
+
+impl fmt::Debug for Interceptors {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let count = self.0.read().unwrap_or_else(|e| e.into_inner()).len();
+                f.debug_tuple("Interceptors").field(&count).finish()
+        }
+}
+
+/// A call the interceptors let through, see [`Interceptors::before`].
+pub(crate) struct Intercepted<'a> {
+        interceptors: Vec<Arc<dyn CallInterceptor>>,
+        call        : Call<'a>,
+        started     : Instant,
+}
+
+/* Dropped when the call returns, or while it unwinds if it panicked. */
+impl Drop for Intercepted<'_> {
+        /* Runs the `after` of every interceptor, in reverse order. */
+        fn drop(&mut self) {
+                let elapsed = self.started.elapsed();
+                for interceptor in self.interceptors.iter().rev() {
+                        interceptor.after(&self.call, elapsed);
+                }
+        }
+}
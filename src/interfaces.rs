@@ -0,0 +1,205 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Interfaces plugins provide to each other.
+//!
+//! A plugin can provide named tables of functions (Its interfaces, like `"dsp.fft.v1"`) to
+//! the other plugins of the same manager, which look them up by name and call into it
+//! directly. Plugins reach the [`InterfaceRegistry`] of their manager through their
+//! `vplugin_set_interfaces` export:
+//! ```c
+//! typedef struct vplugin_interfaces {
+//!     int         (*provide)(const struct vplugin_interfaces *self, const char *name, const void *table);
+//!     const void *(*lookup) (const struct vplugin_interfaces *self, const char *name);
+//! } vplugin_interfaces;
+//!
+//! void vplugin_set_interfaces(const vplugin_interfaces *interfaces);
+//! ```
+//! `provide` returns [`INTERFACE_OK`], [`INTERFACE_TAKEN`] if another plugin already provides
+//! an interface with that name, or [`INTERFACE_INVALID`]. `lookup` returns `NULL` if nothing
+//! provides the interface. The layout of every table is agreed on by the plugins themselves,
+//! which is why names should carry a version.
+//!
+//! A plugin provides its interfaces from its entry point, and they're withdrawn once it's
+//! terminated or unloaded. Tables looked up must not be used afterwards: plugins using an
+//! interface should list its provider in their `[dependencies]`, so that it's started
+//! before them and ([`PluginManager::shutdown`](crate::plugin_manager::PluginManager::shutdown))
+//! terminated after them.
+
+use std::collections::HashMap;
+use std::ffi::{c_char, c_int, c_void, CStr};
+use std::sync::{Arc, Mutex, MutexGuard};
+use uuid::Uuid;
+use crate::diagnostics;
+use crate::plugin::Plugin;
+use crate::isolation::Unguarded;
+
+/// The symbol plugins export to receive the interface registry.
+pub const SET_INTERFACES_SYMBOL: &str = "vplugin_set_interfaces";
+
+/// Returned when the interface was provided.
+pub const INTERFACE_OK: c_int = 0;
+/// Returned when another plugin already provides an interface with that name.
+pub const INTERFACE_TAKEN: c_int = 1;
+/// Returned when the arguments are invalid (A `NULL` pointer, a name that isn't UTF-8, ...).
+pub const INTERFACE_INVALID: c_int = -1;
+
+/* An interface and the plugin providing it. */
+#[derive(Debug)]
+struct Provided {
+        plugin  : String,
+        instance: Uuid,
+        /* The address of the table. */
+        table   : usize,
+}
+
+/// ## InterfaceRegistry
+/// The interfaces the plugins of a manager provide, by name. See the
+/// [module documentation](crate::interfaces).
+///
+/// ## Example
+/// ```rust
+/// let manager = vplugin::PluginManager::new();
+/// for name in manager.interfaces().names() {
+///     println!("{} (From {})", name, manager.interfaces().provider(&name).unwrap());
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct InterfaceRegistry {
+        interfaces: Mutex<HashMap<String, Provided>>,
+}
+
+impl InterfaceRegistry {
+        /// Returns the table of the interface `name`, or `None` if no plugin provides it.
+        /// The table is only valid while its provider is started.
+        pub fn lookup(&self, name: &str) -> Option<*const c_void> {
+                self.lock().get(name).map(|provided| provided.table as *const c_void)
+        }
+
+        /// Returns the name of the plugin providing the interface `name`, if any.
+        pub fn provider(&self, name: &str) -> Option<String> {
+                self.lock().get(name).map(|provided| provided.plugin.clone())
+        }
+
+        /// Returns the names of the interfaces provided, sorted.
+        pub fn names(&self) -> Vec<String> {
+                let mut names: Vec<String> = self.lock().keys().cloned().collect();
+                names.sort();
+                names
+        }
+
+        /* Registers `table` as the interface `name` of `plugin`, unless another plugin provides it. */
+        fn provide(&self, name: &str, plugin: &str, instance: Uuid, table: *const c_void) -> c_int {
+                let mut interfaces = self.lock();
+                if let Some(provided) = interfaces.get(name) {
+                        if provided.instance != instance {
+                                diagnostics::error!(
+                                        "Plugin '{}' can't provide interface '{}', plugin '{}' already does.",
+                                        plugin,
+                                        name,
+                                        provided.plugin
+                                );
+                                return INTERFACE_TAKEN;
+                        }
+                }
+                diagnostics::trace!("Plugin '{}' provides interface '{}'.", plugin, name);
+                interfaces.insert(name.to_owned(), Provided { plugin: plugin.to_owned(), instance, table: table as usize });
+                INTERFACE_OK
+        }
+
+        /// Withdraws the interfaces provided by the instance `instance` of a plugin.
+        pub(crate) fn withdraw(&self, instance: Uuid) {
+                self.lock().retain(|_, provided| provided.instance != instance);
+        }
+
+        /* A registry stays usable even if a thread panicked while holding it. */
+        fn lock(&self) -> MutexGuard<'_, HashMap<String, Provided>> {
+                self.interfaces.lock().unwrap_or_else(|e| e.into_inner())
+        }
+}
+
+/// ## InterfaceTable
+/// The interface registry as seen by a single plugin, handed to it as a
+/// `const vplugin_interfaces *`. See the [module documentation](crate::interfaces).
+#[repr(C)]
+#[derive(Debug)]
+pub struct InterfaceTable {
+        /// Provides an interface.
+        pub provide: unsafe extern "C" fn(*const InterfaceTable, *const c_char, *const c_void) -> c_int,
+        /// Looks up an interface.
+        pub lookup : unsafe extern "C" fn(*const InterfaceTable, *const c_char) -> *const c_void,
+        registry   : Arc<InterfaceRegistry>,
+        plugin     : String,
+        instance   : Uuid,
+}
+
+impl Default for InterfaceTable {
+        fn default() -> Self {
+                Self::new(Arc::default(), "", Uuid::nil())
+        }
+}
+
+impl InterfaceTable {
+        fn new(registry: Arc<InterfaceRegistry>, plugin: &str, instance: Uuid) -> Self {
+                Self {
+                        provide: interface_provide,
+                        lookup : interface_lookup,
+                        registry,
+                        plugin : plugin.to_owned(),
+                        instance,
+                }
+        }
+
+        /// Withdraws the interfaces the plugin provided.
+        pub(crate) fn withdraw(&self) {
+                self.registry.withdraw(self.instance);
+        }
+}
+
+/* The name at `name`, or `None` if it's NULL or not UTF-8. */
+unsafe fn name<'a>(name: *const c_char) -> Option<&'a str> {
+        match name.is_null() {
+                true  => None,
+                false => CStr::from_ptr(name).to_str().ok()
+        }
+}
+
+unsafe extern "C" fn interface_provide(table: *const InterfaceTable, interface: *const c_char, provided: *const c_void) -> c_int {
+        let _unguarded = Unguarded::enter();
+        match (table.as_ref(), name(interface), provided.is_null()) {
+                (Some(table), Some(interface), false) => table.registry.provide(interface, &table.plugin, table.instance, provided),
+                _                                     => INTERFACE_INVALID
+        }
+}
+
+unsafe extern "C" fn interface_lookup(table: *const InterfaceTable, interface: *const c_char) -> *const c_void {
+        let _unguarded = Unguarded::enter();
+        match (table.as_ref(), name(interface)) {
+                (Some(table), Some(interface)) => table.registry.lookup(interface).unwrap_or(std::ptr::null()),
+                _                              => std::ptr::null()
+        }
+}
+
+/// Hands `plugin` the interface registry `registry`, if it exports `vplugin_set_interfaces`.
+pub(crate) fn attach(plugin: &mut Plugin, registry: &Arc<InterfaceRegistry>) {
+        match unsafe { plugin.symbol::<unsafe extern "C" fn(*const InterfaceTable)>(SET_INTERFACES_SYMBOL) } {
+                Ok (set_interfaces) => {
+                        plugin.interfaces = Arc::new(InterfaceTable::new(registry.clone(), &plugin.metadata.name, plugin.instance_id));
+                        unsafe { set_interfaces(&*plugin.interfaces) }
+                },
+                Err(_) => diagnostics::trace!("Plugin doesn't export {}, it can't use the interfaces of other plugins.", SET_INTERFACES_SYMBOL)
+        }
+}
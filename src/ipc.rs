@@ -0,0 +1,256 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! The messages exchanged between the host and the processes running sandboxed plugins.
+//!
+//! Every message is a frame: its length (`u32`, little endian), a tag saying what the
+//! message is, then its fields, each prefixed by its own length.
+
+use std::io::{self, Read, Write};
+
+/* Sent by the sandbox process once it's ready, before anything else. */
+pub(crate) const HANDSHAKE: &[u8] = b"VPLUGIN-SANDBOX/1";
+
+/* Frames larger than this are rejected instead of being allocated. */
+const MAX_FRAME: usize = 256 * 1024 * 1024;
+
+/// A message from the host to the sandbox process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Request {
+        /// Load the plugin at `path`, starting it with the entry point `entry`.
+        Load { entry: String, path: String },
+        /// Start the plugin.
+        Begin,
+        /// Call the hook `hook` with `input`.
+        Call { hook: String, input: Vec<u8> },
+        /// Terminate the plugin.
+        Terminate,
+}
+
+/// A message from the sandbox process to the host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Response {
+        /// The sandbox process is ready, with the handshake it sent.
+        Ready(Vec<u8>),
+        /// The request succeeded, with what the plugin returned (If anything).
+        Ok(Vec<u8>),
+        /// The request failed, with why.
+        Err(String),
+}
+
+impl Request {
+        pub(crate) fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+                match self {
+                        Request::Load { entry, path } => write_frame(w, 1, &[entry.as_bytes(), path.as_bytes()]),
+                        Request::Begin                => write_frame(w, 2, &[]),
+                        Request::Call { hook, input } => write_frame(w, 3, &[hook.as_bytes(), input]),
+                        Request::Terminate            => write_frame(w, 4, &[])
+                }
+        }
+
+        /// Reads the next request, or `None` once the host closed the pipe.
+        pub(crate) fn read_from(r: &mut impl Read) -> io::Result<Option<Self>> {
+                let (tag, mut fields) = match read_frame(r)? {
+                        Some(frame) => frame,
+                        None        => return Ok(None)
+                };
+                let request = match (tag, fields.len()) {
+                        (1, 2) => {
+                                let path  = string(fields.pop())?;
+                                let entry = string(fields.pop())?;
+                                Request::Load { entry, path }
+                        },
+                        (2, 0) => Request::Begin,
+                        (3, 2) => {
+                                let input = fields.pop().unwrap_or_default();
+                                let hook  = string(fields.pop())?;
+                                Request::Call { hook, input }
+                        },
+                        (4, 0) => Request::Terminate,
+                        _      => return Err(invalid(format!("Invalid request (tag {}, {} fields)", tag, fields.len())))
+                };
+                Ok(Some(request))
+        }
+}
+
+impl Response {
+        pub(crate) fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+                match self {
+                        Response::Ready(handshake) => write_frame(w, 0, &[handshake]),
+                        Response::Ok   (output)    => write_frame(w, 1, &[output]),
+                        Response::Err  (reason)    => write_frame(w, 2, &[reason.as_bytes()])
+                }
+        }
+
+        /// Reads the next response, or `None` once the sandbox process closed the pipe.
+        pub(crate) fn read_from(r: &mut impl Read) -> io::Result<Option<Self>> {
+                let (tag, mut fields) = match read_frame(r)? {
+                        Some(frame) => frame,
+                        None        => return Ok(None)
+                };
+                let response = match (tag, fields.pop(), fields.is_empty()) {
+                        (0, Some(handshake), true) => Response::Ready(handshake),
+                        (1, Some(output), true)    => Response::Ok(output),
+                        (2, Some(reason), true)    => Response::Err(string(Some(reason))?),
+                        _                          => return Err(invalid(format!("Invalid response (tag {})", tag)))
+                };
+                Ok(Some(response))
+        }
+}
+
+fn write_frame(w: &mut impl Write, tag: u8, fields: &[&[u8]]) -> io::Result<()> {
+        let mut frame = vec![tag];
+        for field in fields {
+                frame.extend_from_slice(&(field.len() as u32).to_le_bytes());
+                frame.extend_from_slice(field);
+        }
+        if frame.len() > MAX_FRAME {
+                return Err(invalid(format!("Message too large ({} bytes)", frame.len())));
+        }
+        w.write_all(&(frame.len() as u32).to_le_bytes())?;
+        w.write_all(&frame)?;
+        w.flush()
+}
+
+/* Reads a frame, or `None` if the pipe was closed before it started. */
+fn read_frame(r: &mut impl Read) -> io::Result<Option<(u8, Vec<Vec<u8>>)>> {
+        let mut len = [0u8; 4];
+        match r.read_exact(&mut len) {
+                Ok (())                                            => (),
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e)                                             => return Err(e)
+        }
+        let len = u32::from_le_bytes(len) as usize;
+        if len == 0 || len > MAX_FRAME {
+                return Err(invalid(format!("Invalid message length ({} bytes)", len)));
+        }
+        let mut frame = vec![0u8; len];
+        r.read_exact(&mut frame)?;
+
+        let tag = frame[0];
+        let mut fields = Vec::new();
+        let mut rest   = &frame[1..];
+        while !rest.is_empty() {
+                if rest.len() < 4 {
+                        return Err(invalid("Truncated message".into()));
+                }
+                let (field_len, tail) = rest.split_at(4);
+                let field_len = u32::from_le_bytes([field_len[0], field_len[1], field_len[2], field_len[3]]) as usize;
+                if field_len > tail.len() {
+                        return Err(invalid("Truncated message".into()));
+                }
+                let (field, tail) = tail.split_at(field_len);
+                fields.push(field.to_vec());
+                rest = tail;
+        }
+        Ok(Some((tag, fields)))
+}
+
+fn string(field: Option<Vec<u8>>) -> io::Result<String> {
+        String::from_utf8(field.unwrap_or_default()).map_err(|e| invalid(e.to_string()))
+}
+
+fn invalid(message: String) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+        use super::*;
+
+        fn frame(tag: u8, fields: &[&[u8]]) -> Vec<u8> {
+                let mut buffer = Vec::new();
+                write_frame(&mut buffer, tag, fields).unwrap();
+                buffer
+        }
+
+        fn invalid_data<T: std::fmt::Debug>(result: io::Result<T>) -> bool {
+                matches!(result, Err(e) if e.kind() == io::ErrorKind::InvalidData)
+        }
+
+        #[test]
+        fn requests_round_trip() {
+                let requests = [
+                        Request::Load { entry: "vplugin_init".into(), path: "/plugins/ünïcødé.vpl".into() },
+                        Request::Begin,
+                        Request::Call { hook: "render".into(), input: vec![0, 1, 2, 255] },
+                        Request::Call { hook: String::new(), input: Vec::new() },
+                        Request::Terminate,
+                ];
+                let mut buffer = Vec::new();
+                for request in &requests {
+                        request.write_to(&mut buffer).unwrap();
+                }
+                let mut reader = buffer.as_slice();
+                for request in requests {
+                        assert_eq!(Request::read_from(&mut reader).unwrap(), Some(request));
+                }
+                assert_eq!(Request::read_from(&mut reader).unwrap(), None);
+        }
+
+        #[test]
+        fn responses_round_trip() {
+                let responses = [
+                        Response::Ready(HANDSHAKE.to_vec()),
+                        Response::Ok(vec![42; 1000]),
+                        Response::Ok(Vec::new()),
+                        Response::Err("the plugin refused".into()),
+                ];
+                let mut buffer = Vec::new();
+                for response in &responses {
+                        response.write_to(&mut buffer).unwrap();
+                }
+                let mut reader = buffer.as_slice();
+                for response in responses {
+                        assert_eq!(Response::read_from(&mut reader).unwrap(), Some(response));
+                }
+                assert_eq!(Response::read_from(&mut reader).unwrap(), None);
+        }
+
+        #[test]
+        fn truncated_frames() {
+                let buffer = frame(3, &[b"render", b"abc"]);
+                /* The pipe closed in the middle of the frame. */
+                let cut = Request::read_from(&mut &buffer[..buffer.len() - 1]);
+                assert!(matches!(cut, Err(e) if e.kind() == io::ErrorKind::UnexpectedEof));
+
+                /* A field longer than what's left of the frame, or a length cut short. */
+                let mut long_field = buffer.clone();
+                let last = long_field.len() - 7;
+                long_field[last..last + 4].copy_from_slice(&255u32.to_le_bytes());
+                assert!(invalid_data(Request::read_from(&mut long_field.as_slice())));
+                let short_length = [3, 0, 0, 0, 3, 2, 0];
+                assert!(invalid_data(Request::read_from(&mut &short_length[..])));
+        }
+
+        #[test]
+        fn frame_lengths() {
+                assert!(invalid_data(Request::read_from(&mut &0u32.to_le_bytes()[..])));
+                let oversized = (MAX_FRAME as u32 + 1).to_le_bytes();
+                assert!(invalid_data(Response::read_from(&mut &oversized[..])));
+        }
+
+        #[test]
+        fn wrong_fields() {
+                assert!(invalid_data(Request::read_from(&mut frame(1, &[b"vplugin_init"]).as_slice())));
+                assert!(invalid_data(Request::read_from(&mut frame(2, &[b"unexpected"]).as_slice())));
+                assert!(invalid_data(Request::read_from(&mut frame(9, &[]).as_slice())));
+                assert!(invalid_data(Request::read_from(&mut frame(3, &[&[0xff, 0xfe], b""]).as_slice())));
+                assert!(invalid_data(Response::read_from(&mut frame(1, &[]).as_slice())));
+                assert!(invalid_data(Response::read_from(&mut frame(1, &[b"a", b"b"]).as_slice())));
+                assert!(invalid_data(Response::read_from(&mut frame(7, &[b"a"]).as_slice())));
+        }
+}
@@ -0,0 +1,144 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+/*
+ * The crash guard of VPlugin (The `crash_guard` feature), see src/isolation.rs.
+ *
+ * The exports of plugins taking no or plain arguments (Their entry point, destructor, ...) are
+ * called through vplugin_guard_call(), which
+ * returns to where it was called with the number of the signal if the plugin faults during
+ * the call. This has to be done in C: `sigsetjmp` returns twice, which Rust doesn't support.
+ * The export of the plugin is called right from here, and the guard is suspended while the
+ * plugin calls back into the application (vplugin_guard_suspend()), so the frames `siglongjmp`
+ * skips are those of the plugin, not those of the application.
+ *
+ * This file is built with -fexceptions, so a builtin plugin panicking unwinds through
+ * vplugin_guard_call() back to Rust, restoring the outer guard on the way.
+ */
+
+#include <setjmp.h>
+#include <signal.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <string.h>
+
+static const int SIGNALS[] = { SIGSEGV, SIGBUS, SIGILL, SIGFPE };
+#define SIGNAL_COUNT (sizeof(SIGNALS) / sizeof(SIGNALS[0]))
+
+/* The handlers installed before the guard's, for faults outside of guarded calls. */
+static struct sigaction previous[SIGNAL_COUNT];
+/* Where the guarded call running on this thread returns to, if any. */
+static _Thread_local sigjmp_buf *current = NULL;
+
+static void handler(int signal, siginfo_t *info, void *context) {
+        if (current != NULL) {
+                siglongjmp(*current, signal);
+        }
+
+        for (size_t i = 0; i < SIGNAL_COUNT; i++) {
+                if (SIGNALS[i] != signal) {
+                        continue;
+                }
+                if (previous[i].sa_flags & SA_SIGINFO) {
+                        previous[i].sa_sigaction(signal, info, context);
+                } else if (previous[i].sa_handler == SIG_DFL) {
+                        /* The faulting instruction runs again once this returns, and gets the default behavior. */
+                        sigaction(signal, &previous[i], NULL);
+                } else if (previous[i].sa_handler != SIG_IGN) {
+                        previous[i].sa_handler(signal);
+                }
+                return;
+        }
+}
+
+/* Installs the handlers of the guard. Only called once. */
+void vplugin_guard_install(void) {
+        struct sigaction action;
+        memset(&action, 0, sizeof(action));
+        action.sa_sigaction = handler;
+        action.sa_flags     = SA_SIGINFO | SA_ONSTACK;
+        sigemptyset(&action.sa_mask);
+
+        for (size_t i = 0; i < SIGNAL_COUNT; i++) {
+                sigaction(SIGNALS[i], &action, &previous[i]);
+        }
+}
+
+/* Makes the guard of the caller current again, even while unwinding. */
+static void restore(sigjmp_buf **outer) {
+        current = *outer;
+}
+
+/* The kinds of exports vplugin_guard_call() calls, see `Export` in src/isolation.rs. */
+enum export_kind {
+        EXPORT_STATUS    = 0, /* int (*)(void) */
+        EXPORT_UNIT      = 1, /* void (*)(void) */
+        EXPORT_CONFIGURE = 2, /* int (*)(const void *config, size_t length), `second` being the length */
+        EXPORT_STOP      = 3  /* void (*)(void (*acknowledge)(void *), void *context) */
+};
+
+/*
+ * Calls `export`, of the kind `kind`, with `first` and `second` as its arguments (If it takes
+ * any), writing the status it returns to `result`. Returns 0 once it returns, or the signal it
+ * raised.
+ */
+int vplugin_guard_call(int kind, void (*export)(void), void *first, void *second, int *result) {
+        sigjmp_buf buffer;
+        sigjmp_buf *outer __attribute__((cleanup(restore))) = current;
+
+        int signal = sigsetjmp(buffer, 1);
+        if (signal == 0) {
+                current = &buffer;
+                switch (kind) {
+                        case EXPORT_STATUS:
+                                *result = ((int (*)(void)) export)();
+                                break;
+                        case EXPORT_UNIT:
+                                ((void (*)(void)) export)();
+                                break;
+                        case EXPORT_CONFIGURE:
+                                *result = ((int (*)(const void *, size_t)) export)(first, (size_t) (uintptr_t) second);
+                                break;
+                        case EXPORT_STOP:
+                                ((void (*)(void (*)(void *), void *)) export)((void (*)(void *)) first, second);
+                                break;
+                }
+        }
+        return signal;
+}
+
+/* Suspends the guard of the calling thread, returning it for vplugin_guard_resume(). */
+void *vplugin_guard_suspend(void) {
+        sigjmp_buf *guard = current;
+        current = NULL;
+        return guard;
+}
+
+/* Makes `guard`, returned by vplugin_guard_suspend(), current again. */
+void vplugin_guard_resume(void *guard) {
+        current = guard;
+}
+
+/* The name of `signal`, one of those the guard handles. */
+const char *vplugin_guard_signal_name(int signal) {
+        switch (signal) {
+                case SIGSEGV: return "SIGSEGV";
+                case SIGBUS:  return "SIGBUS";
+                case SIGILL:  return "SIGILL";
+                case SIGFPE:  return "SIGFPE";
+                default:      return "an unknown signal";
+        }
+}
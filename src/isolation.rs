@@ -0,0 +1,208 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Isolation of the calls into plugins, so a plugin panicking or crashing doesn't take
+//! the application down with it.
+//!
+//! The entry point, the destructor, `vplugin_configure`, `vplugin_stop` and the hooks called with
+//! [`Plugin::call_hook`](crate::plugin::Plugin::call_hook) are called through [`guarded`]
+//! (Or [`guarded_export`]): a panic unwinding out of them is caught and returned as
+//! `VPluginError::PluginPanicked`. This covers builtin plugins (See [`builtin`](crate::builtin))
+//! exporting `extern "C-unwind"` functions, and the closures given to `call_hook`. Rust plugins
+//! loaded from a library have their own standard library, whose panics the application can't
+//! catch: they have to catch them themselves, see `vplugin_core::panic`.
+//!
+//! With the `crash_guard` feature, faults raised by those exports of the plugin on Unix
+//! (`SIGSEGV`, `SIGBUS`, `SIGILL` and `SIGFPE`) are returned as `VPluginError::PluginCrashed`
+//! instead of killing the process. The guard jumps back out of the faulting call with
+//! `siglongjmp`, which is only sound over frames that don't need to be unwound: the C code of
+//! the guard calls the export of the plugin itself, and the functions VPlugin hands to plugins
+//! (Its allocator, object and interface registries, permission prompts, ...) suspend the guard
+//! while they run ([`Unguarded`]), so a fault there isn't mistaken for one of the plugin. The
+//! tables of the application ([`PluginManager::register_api`](crate::plugin_manager::PluginManager::register_api))
+//! are the application's own code: their functions have to suspend the guard themselves with
+//! [`Unguarded::enter`], or the guard may jump over their frames, skipping destructors and
+//! leaving locks held. Hooks called with `call_hook` go through a closure of the application,
+//! so they're only guarded against panics.
+//!
+//! Either way the plugin is quarantined afterwards ([`PluginState::Quarantined`](crate::state::PluginState::Quarantined)):
+//! it can't be used or terminated anymore, and its library is kept loaded
+//! ([`LeakReason::Crashed`](crate::unload::LeakReason::Crashed)). Hooks the application
+//! calls itself (Through [`HookGuard`](crate::guard::HookGuard)s, [`declare_hooks!`](crate::declare_hooks)
+//! or extension chains) aren't guarded.
+
+use std::any::Any;
+use std::ffi::{c_int, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::error::VPluginError;
+use crate::diagnostics;
+
+/// An export of a plugin along with its arguments, which the crash guard can call directly.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Export {
+        /// Returns a status, like the entry point.
+        Status(unsafe extern "C-unwind" fn() -> c_int),
+        /// Returns nothing, like the destructor.
+        Unit(unsafe extern "C-unwind" fn()),
+        /// `vplugin_configure`, with the configuration and its length.
+        Configure(unsafe extern "C-unwind" fn(*const u8, usize) -> c_int, *const u8, usize),
+        /// `vplugin_stop`, with the function acknowledging and its context.
+        Stop(crate::stop::Stop, crate::stop::Acknowledge, *mut c_void),
+}
+
+/// ## Unguarded
+/// Suspends the crash guard of the calling thread until it's dropped, for the functions of
+/// the application plugins call (Those of the tables given to [`PluginManager::register_api`](crate::plugin_manager::PluginManager::register_api)).
+/// A fault while it's alive is handled like anywhere else in the application, instead of the
+/// guard jumping over frames of the application. Without the `crash_guard` feature, or
+/// outside of guarded calls, this does nothing.
+///
+/// ## Example
+/// ```rust,no_run
+/// use vplugin::isolation::Unguarded;
+///
+/// extern "C" fn log(message: *const std::ffi::c_char) {
+///     let _unguarded = Unguarded::enter();
+///     /* Locks, allocations, ... */
+/// }
+/// ```
+#[derive(Debug)]
+#[must_use = "The guard is resumed once this is dropped"]
+pub struct Unguarded {
+        #[cfg(all(feature = "crash_guard", unix))]
+        guard: *mut c_void,
+}
+
+impl Unguarded {
+        /// Suspends the crash guard of the calling thread, if it's in a guarded call.
+        pub fn enter() -> Self {
+                #[cfg(all(feature = "crash_guard", unix))]
+                {
+                        extern "C" {
+                                fn vplugin_guard_suspend() -> *mut c_void;
+                        }
+                        Self { guard: unsafe { vplugin_guard_suspend() } }
+                }
+                #[cfg(not(all(feature = "crash_guard", unix)))]
+                Self {}
+        }
+}
+
+#[cfg(all(feature = "crash_guard", unix))]
+impl Drop for Unguarded {
+        fn drop(&mut self) {
+                extern "C" {
+                        fn vplugin_guard_resume(guard: *mut c_void);
+                }
+                unsafe { vplugin_guard_resume(self.guard) }
+        }
+}
+
+/// Runs `f`, a call into the plugin named `plugin`. If it panics, `crashed` is set and
+/// `VPluginError::PluginPanicked` is returned.
+pub(crate) fn guarded<F, R>(plugin: &str, crashed: &AtomicBool, f: F) -> Result<R, VPluginError>
+where
+        F: FnOnce() -> R
+{
+        quarantine(plugin, crashed, catch(f))
+}
+
+/// Calls `export` of the plugin named `plugin`, returning its status (0 for the exports
+/// returning nothing).
+/// If it panics or crashes, `crashed` is set and `VPluginError::PluginPanicked` or
+/// `VPluginError::PluginCrashed` is returned.
+pub(crate) fn guarded_export(plugin: &str, crashed: &AtomicBool, export: Export) -> Result<c_int, VPluginError> {
+        quarantine(plugin, crashed, call(export))
+}
+
+/* Sets `crashed` and reports the error if the call didn't return. */
+fn quarantine<R>(plugin: &str, crashed: &AtomicBool, result: Result<R, VPluginError>) -> Result<R, VPluginError> {
+        let error = match result {
+                Ok (result) => return Ok(result),
+                Err(error)  => error
+        };
+        crashed.store(true, Ordering::Release);
+        diagnostics::error!("{} (Plugin '{}'), quarantining it.", error, plugin);
+        Err(error)
+}
+
+/* Runs `f`, returning the panic if it didn't return. */
+fn catch<F: FnOnce() -> R, R>(f: F) -> Result<R, VPluginError> {
+        panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| panicked(&*payload))
+}
+
+/* Calls `export`, returning what went wrong if it didn't return. */
+#[cfg(not(all(feature = "crash_guard", unix)))]
+fn call(export: Export) -> Result<c_int, VPluginError> {
+        catch(|| unsafe {
+                match export {
+                        Export::Status(status)                    => status(),
+                        Export::Unit(unit)                        => {
+                                unit();
+                                0
+                        },
+                        Export::Configure(configure, config, len) => configure(config, len),
+                        Export::Stop(stop, acknowledge, context)  => {
+                                stop(acknowledge, context);
+                                0
+                        }
+                }
+        })
+}
+
+#[cfg(all(feature = "crash_guard", unix))]
+fn call(export: Export) -> Result<c_int, VPluginError> {
+        use std::ffi::{c_char, CStr};
+        use std::sync::Once;
+
+        /* See src/isolation.c, built with -fexceptions so panics of builtin plugins can unwind through it. */
+        extern "C-unwind" {
+                fn vplugin_guard_install();
+                fn vplugin_guard_call(kind: c_int, export: *const c_void, first: *mut c_void, second: *mut c_void, result: *mut c_int) -> c_int;
+                fn vplugin_guard_signal_name(signal: c_int) -> *const c_char;
+        }
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| unsafe { vplugin_guard_install() });
+
+        /* The kinds of exports of isolation.c, and their arguments as pointers. */
+        let null = std::ptr::null_mut();
+        let (kind, export, first, second) = match export {
+                Export::Status(status)                    => (0, status as *const c_void, null, null),
+                Export::Unit(unit)                        => (1, unit as *const c_void, null, null),
+                Export::Configure(configure, config, len) => (2, configure as *const c_void, config as *mut c_void, len as *mut c_void),
+                Export::Stop(stop, acknowledge, context)  => (3, stop as *const c_void, acknowledge as *mut c_void, context)
+        };
+        let mut result = 0;
+        match catch(|| unsafe { vplugin_guard_call(kind, export, first, second, &mut result) })? {
+                0      => Ok(result),
+                /* What the plugin was holding is leaked, its frames were skipped. */
+                signal => {
+                        let name = unsafe { CStr::from_ptr(vplugin_guard_signal_name(signal)) };
+                        Err(VPluginError::PluginCrashed { signal: name.to_string_lossy().into_owned() })
+                }
+        }
+}
+
+/* The error for the panic whose payload is `payload`. Where it happened isn't known. */
+fn panicked(payload: &(dyn Any + Send)) -> VPluginError {
+        let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "Box<dyn Any>".into());
+        VPluginError::PluginPanicked { message, location: String::new() }
+}
@@ -30,7 +30,7 @@
 //! First, creating a skeleton app for the plugin:
 //! `**main.rs**`:
 //! 
-//! ```rust
+//! ```rust,no_run
 //! extern crate vplugin;
 //! use vplugin::PluginManager;
 //! use std::path::PathBuf;
@@ -40,7 +40,7 @@
 //!     let mut plugin_manager = PluginManager::new();
 //!     plugin_manager.set_entry_point("app_entry");
 //! 
-//!     let mut plugin = plugin_manager.load(plugin_path).expect("Plugin cannot be loaded!");
+//!     let mut plugin = plugin_manager.load_plugin(plugin_path).expect("Plugin cannot be loaded!");
 //!     plugin_manager.begin_plugin(&mut plugin).expect("Plugin couldn't be started!");
 //! }
 //!
@@ -90,13 +90,126 @@
 
 mod plugin;
 mod plugin_manager;
-mod error;
+pub mod error;
+mod workspace;
+mod platform;
+mod diagnostics;
+mod hooks;
+mod options;
+mod version;
+mod source;
+mod fault;
+pub mod api;
+mod sdk;
+mod compat;
+mod settings;
+mod store;
+pub mod i18n;
+pub mod permissions;
+pub mod license;
+pub mod memory;
+pub mod objects;
+pub mod threads;
+pub mod state_store;
+pub mod interfaces;
+pub mod progress;
+pub mod telemetry;
+pub mod backend;
+pub mod extension;
+pub mod intercept;
+pub mod lifecycle;
+pub mod discovery;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "archive")]
+mod archive;
+#[cfg(feature = "preflight")]
+pub mod preflight;
+#[cfg(feature = "sandbox")]
+pub mod sandbox;
+#[cfg(feature = "sandbox")]
+mod ipc;
+#[cfg(feature = "tokio")]
+pub mod async_manager;
+#[cfg(feature = "signing")]
+pub mod signing;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod builtin;
+pub mod state;
+mod guard;
+mod handle;
+mod identity;
+mod snapshot;
+mod startup;
+mod shutdown;
+mod unload;
+mod stop;
+mod panics;
+pub mod isolation;
+mod probe;
+#[cfg(target_os = "macos")]
+mod macos;
 pub mod shareable; // Are you happy `rustc`?
+pub mod prelude;
 
 /// Reexports of VPlugin's types.
 pub use plugin_manager::*;
 pub use plugin::*;
 pub use shareable::Shareable;
+#[cfg(feature = "derive")]
+pub use vplugin_derive::Shareable;
+#[cfg(feature = "serde_shareable")]
+pub use shareable::SerdeShareable;
+pub use vplugin_core::{MetadataDiagnostic, PluginMetadata, PluginState, Result, VPluginError};
+pub use vplugin_core::{Setting, SettingKind, SettingsSchema};
+pub use vplugin_core::{HookDeclaration, HookManifest, HookSignature};
+pub use guard::{HookGuard, PinPolicy};
+pub use handle::PluginHandle;
+pub use hooks::Hooks;
+pub use extension::{Extension, ExtensionChain, ExtensionPoint};
+pub use intercept::{Call, CallInterceptor, CallKind};
+pub use lifecycle::LifecycleObserver;
+pub use discovery::DiscoveryReport;
+pub use source::PluginSource;
+pub use backend::PluginBackend;
+pub use api::HostApiVersion;
+pub use i18n::Translations;
+pub use license::LicensePolicy;
+pub use memory::MemoryUsage;
+pub use objects::{ObjectHandle, ObjectRegistry};
+pub use state_store::PluginStateStore;
+pub use interfaces::InterfaceRegistry;
+pub use progress::{LoadPhase, LoadProgress};
+pub use panics::PanicReport;
+pub use telemetry::{TelemetryEvent, TelemetryRecord, TelemetrySink};
+#[cfg(feature = "preflight")]
+pub use preflight::{SymbolReport, UnresolvedSymbol};
+#[cfg(feature = "sandbox")]
+pub use sandbox::SandboxedPlugin;
+#[cfg(feature = "tokio")]
+pub use async_manager::AsyncPluginManager;
+pub use permissions::{PermissionDecision, PermissionPolicy, PermissionRequest, Permissions};
+pub use compat::{CheckKind, CheckOutcome, CompatibilityCheck, CompatibilityMatrix, CompatibilityReport};
+pub use options::{CleanupPolicy, ManagerOptions, Requirement, StartPolicy, SymbolVisibility, UnloadPolicy};
+pub use diagnostics::{Deprecation, Diagnostic, DiagnosticsSink, LogSink, Severity};
+pub use snapshot::{ManagerSnapshot, PluginSnapshot};
+pub use startup::{StartupFailure, StartupSummary};
+pub use shutdown::{ShutdownFailure, ShutdownReport};
+pub use unload::{LeakReason, LeakedLibrary};
+pub use builtin::{BuiltinPlugin, register_builtin};
+pub use probe::dynamic_loading_available;
+pub use vplugin_core::export_plugin;
+/// `#[repr(C)]` types for exchanging data with plugins.
+pub use vplugin_core::ffi;
 
 /// Reexporting libloading to assist projects that need the library.
-pub use libloading;
\ No newline at end of file
+pub use libloading;
+/// Reexporting semver, used for plugin versions.
+pub use semver;
+/// Reexporting toml, used for the values of plugin settings.
+pub use toml;
+/// Reexporting uuid, used for the instance IDs of plugins.
+pub use uuid;
+/// Reexporting the types shared with plugins.
+pub use vplugin_core;
\ No newline at end of file
@@ -0,0 +1,154 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! The licenses of plugins, and the licenses a host accepts.
+
+use std::fmt;
+use std::sync::Arc;
+use serde_derive::{Deserialize, Serialize};
+use spdx::{Expression, LicenseReq, Licensee};
+use vplugin_core::PluginMetadata;
+use crate::diagnostics;
+use crate::error::VPluginError;
+
+/// ## LicensePolicy
+/// The licenses a host accepts plugins under, checked against the `license` field of
+/// their metadata, an [SPDX expression](https://spdx.github.io/spdx-spec/v2.3/SPDX-license-expressions/)
+/// (`"MIT OR Apache-2.0"`). A plugin offered under several licenses (`OR`) is accepted
+/// if one of them is, one requiring several (`AND`) only if all of them are.
+///
+/// Licenses are SPDX identifiers, GNU licenses without their `-only` or `-or-later`
+/// suffix (`"GPL-3.0"` matches both `GPL-3.0-only` and `GPL-3.0-or-later`). Exceptions
+/// (`WITH`) are ignored, since they only grant more permissions.
+///
+/// ## Example
+/// A proprietary application refusing copyleft plugins:
+/// ```toml
+/// [plugins.license_policy]
+/// deny = ["GPL-2.0", "GPL-3.0", "AGPL-3.0"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LicensePolicy {
+        /// The only licenses accepted. Empty to accept every license that isn't denied.
+        pub allow           : Vec<String>,
+        /// Licenses that are never accepted.
+        pub deny            : Vec<String>,
+        /// Whether plugins that don't declare a license are accepted.
+        pub allow_unlicensed: bool,
+}
+
+impl LicensePolicy {
+        /// Checks that every license of the policy is a valid SPDX identifier.
+        pub fn validate(&self) -> Result<(), VPluginError> {
+                self.licensees().map(|_| ())
+        }
+
+        /// Checks that the plugin described by `metadata` can be used under the policy,
+        /// returning `VPluginError::LicensePolicyViolation` otherwise.
+        pub fn check(&self, metadata: &PluginMetadata) -> Result<(), VPluginError> {
+                match self.evaluate(metadata)? {
+                        Ok (_)                     => Ok(()),
+                        Err((expression, reason)) => {
+                                diagnostics::error!("Refusing plugin: {}.", reason);
+                                Err(VPluginError::LicensePolicyViolation { plugin: metadata.name.clone(), expression })
+                        }
+                }
+        }
+
+        /*
+         * Evaluates the license of the plugin against the policy, returning the offending
+         * expression and why it's refused if it is.
+         */
+        pub(crate) fn evaluate(&self, metadata: &PluginMetadata) -> Result<Result<(), (String, String)>, VPluginError> {
+                let license = match metadata.license.as_deref() {
+                        Some(license)                 => license,
+                        None if self.allow_unlicensed => return Ok(Ok(())),
+                        None                          => return Ok(Err((
+                                "NONE".into(),
+                                "Plugin doesn't declare a license, which the license policy requires".into()
+                        )))
+                };
+                let expression = match Expression::parse(license) {
+                        Ok (expression) => expression,
+                        Err(e)          => return Ok(Err((
+                                license.into(),
+                                format!("The license of the plugin isn't a valid SPDX expression ({})", e.reason)
+                        )))
+                };
+
+                let (allow, deny) = self.licensees()?;
+                let accepted = |req: &LicenseReq| {
+                        let req = LicenseReq { license: req.license.clone(), exception: None };
+                        !deny.iter().any(|licensee| licensee.satisfies(&req))
+                                && (allow.is_empty() || allow.iter().any(|licensee| licensee.satisfies(&req)))
+                };
+                Ok(match expression.evaluate_with_failures(accepted) {
+                        Ok (_)        => Ok(()),
+                        Err(failures) => {
+                                let refused: Vec<&str> = failures
+                                        .iter()
+                                        .map(|failure| &license[failure.span.start as usize..failure.span.end as usize])
+                                        .collect();
+                                Err((
+                                        license.into(),
+                                        format!("Plugin is licensed under '{}', and the license policy refuses {}", license, refused.join(", "))
+                                ))
+                        }
+                })
+        }
+
+        /* The allowed and denied licenses, parsed. */
+        fn licensees(&self) -> Result<(Vec<Licensee>, Vec<Licensee>), VPluginError> {
+                let parse = |licenses: &[String]| -> Result<Vec<Licensee>, VPluginError> {
+                        licenses
+                                .iter()
+                                .map(|license| Licensee::parse(license).map_err(|e| {
+                                        diagnostics::error!("Invalid license in the license policy: {}", e);
+                                        VPluginError::ParametersError
+                                }))
+                                .collect()
+                };
+                Ok((parse(&self.allow)?, parse(&self.deny)?))
+        }
+}
+
+type EntitlementFn = dyn Fn(&PluginMetadata) -> Result<(), String> + Send + Sync;
+
+/// The entitlement check installed on a manager, see
+/// [`PluginManager::set_entitlement_check`](crate::plugin_manager::PluginManager::set_entitlement_check).
+#[derive(Clone)]
+pub(crate) struct EntitlementCheck(pub(crate) Arc<EntitlementFn>);
+
+impl EntitlementCheck {
+        /// Asks the application whether the user is entitled to the plugin described by
+        /// `metadata`, returning `VPluginError::NotEntitled` otherwise.
+        pub(crate) fn check(&self, metadata: &PluginMetadata) -> Result<(), VPluginError> {
+                match (self.0)(metadata) {
+                        Ok (()) => Ok(()),
+                        Err(reason) => {
+                                diagnostics::error!("Refusing plugin, the user isn't entitled to it: {}.", reason);
+                                Err(VPluginError::NotEntitled { plugin: metadata.name.clone(), reason })
+                        }
+                }
+        }
+}
+
+impl fmt::Debug for EntitlementCheck {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("EntitlementCheck")
+        }
+}
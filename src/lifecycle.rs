@@ -0,0 +1,130 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Observers notified as the plugins of a manager are loaded, started and terminated, for
+//! what concerns every plugin (Telemetry, updating the UI, audit logs, ...) instead of being
+//! handled around every call site of the application.
+//!
+//! Observers are registered with
+//! [`PluginManager::add_observer`](crate::plugin_manager::PluginManager::add_observer) and
+//! apply to every plugin of the manager, including those already loaded. They're notified:
+//! - [`on_load`](LifecycleObserver::on_load) once a plugin was loaded by the manager. Plugins
+//!   that fail to load aren't observed, as there is no plugin to hand to the observers.
+//! - [`on_start`](LifecycleObserver::on_start) once the entry point of a plugin, started by
+//!   the manager, succeeded.
+//! - [`on_terminate`](LifecycleObserver::on_terminate) once a plugin was terminated, even if it
+//!   was removed from the manager since.
+//! - [`on_error`](LifecycleObserver::on_error) when starting or terminating a plugin failed,
+//!   instead of `on_start` or `on_terminate`.
+//!
+//! Observers run on the thread using the manager, in the order they were registered, and
+//! can't refuse anything (See [`intercept`](crate::intercept) for that).
+//!
+//! ## Example
+//! ```rust,no_run
+//! # mod my_app {
+//! #     pub fn audit(_message: String) {}
+//! # }
+//! # let mut manager = vplugin::PluginManager::new();
+//! struct AuditLog;
+//!
+//! impl vplugin::LifecycleObserver for AuditLog {
+//!     fn on_start(&self, plugin: &vplugin::Plugin) {
+//!         my_app::audit(format!("started {} {}", plugin.get_metadata().name, plugin.instance_id()));
+//!     }
+//!
+//!     fn on_error(&self, plugin: &vplugin::Plugin, error: &vplugin::VPluginError) {
+//!         my_app::audit(format!("{} failed: {}", plugin.get_metadata().name, error));
+//!     }
+//! }
+//!
+//! manager.add_observer(AuditLog);
+//! ```
+
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use crate::error::VPluginError;
+use crate::plugin::Plugin;
+
+/// ## LifecycleObserver
+/// Notified of the lifecycle of the plugins of a manager. See the
+/// [module documentation](crate::lifecycle).
+pub trait LifecycleObserver: Send + Sync {
+        /// Called once `plugin` was loaded.
+        fn on_load(&self, plugin: &Plugin) {
+                let _ = plugin;
+        }
+
+        /// Called once the entry point of `plugin` succeeded.
+        fn on_start(&self, plugin: &Plugin) {
+                let _ = plugin;
+        }
+
+        /// Called once `plugin` was terminated.
+        fn on_terminate(&self, plugin: &Plugin) {
+                let _ = plugin;
+        }
+
+        /// Called when starting or terminating `plugin` failed with `error`.
+        fn on_error(&self, plugin: &Plugin, error: &VPluginError) {
+                let _ = (plugin, error);
+        }
+}
+
+/// The observers of a manager, shared with its plugins.
+#[derive(Clone, Default)]
+pub(crate) struct Observers(Arc<RwLock<Vec<Arc<dyn LifecycleObserver>>>>);
+
+impl Observers {
+        pub(crate) fn add(&self, observer: Arc<dyn LifecycleObserver>) {
+                self.0.write().unwrap_or_else(|e| e.into_inner()).push(observer);
+        }
+
+        pub(crate) fn loaded(&self, plugin: &Plugin) {
+                self.notify(|observer| observer.on_load(plugin));
+        }
+
+        /// Notifies the observers of the result of starting `plugin`.
+        pub(crate) fn started(&self, plugin: &Plugin, result: Result<(), &VPluginError>) {
+                match result {
+                        Ok (()) => self.notify(|observer| observer.on_start(plugin)),
+                        Err(e)  => self.notify(|observer| observer.on_error(plugin, e))
+                }
+        }
+
+        /// Notifies the observers of the result of terminating `plugin`.
+        pub(crate) fn terminated(&self, plugin: &Plugin, result: Result<(), &VPluginError>) {
+                match result {
+                        Ok (()) => self.notify(|observer| observer.on_terminate(plugin)),
+                        Err(e)  => self.notify(|observer| observer.on_error(plugin, e))
+                }
+        }
+
+        /* The lock isn't held while the observers run, so they can use the plugin freely. */
+        fn notify(&self, f: impl Fn(&dyn LifecycleObserver)) {
+                let observers = self.0.read().unwrap_or_else(|e| e.into_inner()).clone();
+                for observer in &observers {
+                        f(observer.as_ref());
+                }
+        }
+}
+
+impl fmt::Debug for Observers {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let count = self.0.read().unwrap_or_else(|e| e.into_inner()).len();
+                f.debug_tuple("Observers").field(&count).finish()
+        }
+}
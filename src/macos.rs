@@ -0,0 +1,93 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! macOS specific parts of loading a plugin: code signature verification
+//! and translating Gatekeeper / AMFI rejections into useful errors.
+
+use std::path::Path;
+use std::process::Command;
+use crate::error::VPluginError;
+use crate::diagnostics;
+
+/* Fragments of dyld error messages caused by code signing policies. */
+const SIGNING_FAILURES: &[&str] = &[
+        "code signature",
+        "not valid for use in process",
+        "library load disallowed by system policy",
+        "mapping process and mapped file (non-platform) have different Team IDs",
+];
+
+/// Checks whether a dyld error was caused by Gatekeeper or AMFI refusing the
+/// library, and if so logs what the user can do about it.
+pub(crate) fn diagnose_load_error(objfile: &Path, error: &str) -> Option<VPluginError> {
+        if !SIGNING_FAILURES.iter().any(|f| error.contains(f)) {
+                return None;
+        }
+
+        diagnostics::error!(
+                "macOS refused to load {} because of its code signature. Sign the plugin with the \
+                same team identifier as the host (codesign --sign <identity> {}), or ad-hoc sign it \
+                for local testing (codesign --force --sign - {}).",
+                objfile.display(),
+                objfile.display(),
+                objfile.display()
+        );
+        Some(VPluginError::CodeSignatureRejected { reason: error.to_owned() })
+}
+
+/// Verifies the code signature of `objfile` and makes sure it was signed by
+/// one of the `allowed` team identifiers.
+pub(crate) fn verify_team_identifier(objfile: &Path, allowed: &[String]) -> Result<(), VPluginError> {
+        let verify = Command::new("codesign")
+                .args(["--verify", "--strict"])
+                .arg(objfile)
+                .output();
+        match verify {
+                Ok(out) if out.status.success() => (),
+                Ok(out) => {
+                        let reason = String::from_utf8_lossy(&out.stderr).trim().to_owned();
+                        diagnostics::error!("Code signature of {} is not valid: {}", objfile.display(), reason);
+                        return Err(VPluginError::CodeSignatureRejected { reason });
+                },
+                Err(e) => {
+                        diagnostics::error!("Couldn't run codesign: {}", e);
+                        return Err(VPluginError::InternalError { err: format!("Couldn't run codesign: {}", e) });
+                }
+        }
+
+        /* `codesign -dv` prints the signature details to stderr. */
+        let details = match Command::new("codesign").arg("-dv").arg(objfile).output() {
+                Ok (out) => String::from_utf8_lossy(&out.stderr).into_owned(),
+                Err(e)   => return Err(VPluginError::InternalError { err: format!("Couldn't run codesign: {}", e) }),
+        };
+        let team_id = details
+                .lines()
+                .find_map(|line| line.strip_prefix("TeamIdentifier="))
+                .unwrap_or("not set");
+
+        if allowed.iter().any(|id| id == team_id) {
+                Ok(())
+        } else {
+                diagnostics::error!(
+                        "{} is signed by team '{}', which is not in the list of allowed teams.",
+                        objfile.display(),
+                        team_id
+                );
+                Err(VPluginError::CodeSignatureRejected {
+                        reason: format!("Team identifier '{}' is not allowed", team_id)
+                })
+        }
+}
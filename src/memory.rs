@@ -0,0 +1,280 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+
+//! Accounting of the memory plugins allocate through the host.
+//!
+//! Every plugin loaded from the disk gets its own [`Allocator`], handed to it right after
+//! the API table (See [`HostApiVersion`](crate::api::HostApiVersion)) through its
+//! `vplugin_set_allocator` export:
+//! ```c
+//! typedef struct vplugin_allocator {
+//!     void *(*alloc)  (const struct vplugin_allocator *self, size_t size);
+//!     void *(*realloc)(const struct vplugin_allocator *self, void *ptr, size_t size);
+//!     void  (*free)   (const struct vplugin_allocator *self, void *ptr);
+//! } vplugin_allocator;
+//!
+//! void vplugin_set_allocator(const vplugin_allocator *allocator);
+//! ```
+//! The functions behave like `malloc`, `realloc` and `free` (Memory is aligned to 16 bytes),
+//! but count the memory of the plugin, which the host reads with
+//! [`PluginManager::memory_usage`](crate::plugin_manager::PluginManager::memory_usage) and
+//! can cap with [`PluginManager::set_memory_limit`](crate::plugin_manager::PluginManager::set_memory_limit).
+//! Memory allocated any other way isn't counted.
+
+use std::alloc::{self, Layout};
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use serde_derive::{Deserialize, Serialize};
+use crate::diagnostics;
+use crate::plugin::Plugin;
+use crate::isolation::Unguarded;
+
+/// The symbol plugins export to receive their allocator.
+pub const SET_ALLOCATOR_SYMBOL: &str = "vplugin_set_allocator";
+
+/* Every allocation starts with a header holding its size, which keeps it aligned. */
+const HEADER: usize = 16;
+const ALIGN : usize = 16;
+
+/// The memory a plugin allocated through its [`Allocator`], returned by
+/// [`PluginManager::memory_usage`](crate::plugin_manager::PluginManager::memory_usage).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryUsage {
+        /// The bytes currently allocated.
+        pub live       : usize,
+        /// The most bytes that were allocated at once.
+        pub peak       : usize,
+        /// How many allocations were made, including reallocations.
+        pub allocations: u64,
+        /// How many allocations were refused because of the limit.
+        pub refused    : u64,
+        /// The most bytes the plugin may allocate, if limited.
+        pub limit      : Option<usize>,
+}
+
+/// ## Allocator
+/// The allocator of a single plugin, handed to it as a `const vplugin_allocator *`. Every
+/// function receives the allocator itself, which is how allocations are attributed to the
+/// plugin. See the [module documentation](crate::memory).
+#[repr(C)]
+#[derive(Debug)]
+pub struct Allocator {
+        /// Allocates `size` bytes, like `malloc`.
+        pub alloc  : unsafe extern "C" fn(*const Allocator, usize) -> *mut c_void,
+        /// Resizes an allocation, like `realloc`.
+        pub realloc: unsafe extern "C" fn(*const Allocator, *mut c_void, usize) -> *mut c_void,
+        /// Frees an allocation, like `free`.
+        pub free   : unsafe extern "C" fn(*const Allocator, *mut c_void),
+        live       : AtomicUsize,
+        peak       : AtomicUsize,
+        allocations: AtomicU64,
+        refused    : AtomicU64,
+        /* `usize::MAX` when there's no limit. */
+        limit      : AtomicUsize,
+}
+
+impl Default for Allocator {
+        fn default() -> Self {
+                Self {
+                        alloc      : allocate,
+                        realloc    : reallocate,
+                        free       : deallocate,
+                        live       : AtomicUsize::new(0),
+                        peak       : AtomicUsize::new(0),
+                        allocations: AtomicU64::new(0),
+                        refused    : AtomicU64::new(0),
+                        limit      : AtomicUsize::new(usize::MAX),
+                }
+        }
+}
+
+impl Allocator {
+        /// Returns the memory allocated through this allocator.
+        pub fn usage(&self) -> MemoryUsage {
+                let limit = self.limit.load(Ordering::Relaxed);
+                MemoryUsage {
+                        live       : self.live.load(Ordering::Relaxed),
+                        peak       : self.peak.load(Ordering::Relaxed),
+                        allocations: self.allocations.load(Ordering::Relaxed),
+                        refused    : self.refused.load(Ordering::Relaxed),
+                        limit      : (limit != usize::MAX).then_some(limit),
+                }
+        }
+
+        /// Limits the memory allocated through this allocator to `limit` bytes, or removes
+        /// the limit. Allocations going past it return `NULL`, memory already allocated is
+        /// left alone.
+        pub fn set_limit(&self, limit: Option<usize>) {
+                self.limit.store(limit.unwrap_or(usize::MAX), Ordering::Relaxed);
+        }
+
+        /* Accounts for `size` more bytes, unless that goes past the limit. */
+        fn reserve(&self, size: usize) -> bool {
+                let limit = self.limit.load(Ordering::Relaxed);
+                let reserved = self.live.fetch_update(Ordering::AcqRel, Ordering::Acquire, |live| {
+                        live.checked_add(size).filter(|total| *total <= limit)
+                });
+                match reserved {
+                        Ok (live) => {
+                                self.peak.fetch_max(live + size, Ordering::Relaxed);
+                                self.allocations.fetch_add(1, Ordering::Relaxed);
+                                true
+                        },
+                        Err(_) => {
+                                self.refused.fetch_add(1, Ordering::Relaxed);
+                                false
+                        }
+                }
+        }
+
+        fn release(&self, size: usize) {
+                self.live.fetch_sub(size, Ordering::AcqRel);
+        }
+}
+
+fn layout(size: usize) -> Option<Layout> {
+        Layout::from_size_align(size.checked_add(HEADER)?, ALIGN).ok()
+}
+
+/* The start of the block holding `ptr`, and the size it was allocated with. */
+unsafe fn header(ptr: *mut c_void) -> (*mut u8, usize) {
+        let block = (ptr as *mut u8).sub(HEADER);
+        (block, *(block as *const usize))
+}
+
+unsafe extern "C" fn allocate(allocator: *const Allocator, size: usize) -> *mut c_void {
+        let _unguarded = Unguarded::enter();
+        let (allocator, layout) = match (allocator.as_ref(), layout(size)) {
+                (Some(allocator), Some(layout)) => (allocator, layout),
+                _                               => return std::ptr::null_mut()
+        };
+        if !allocator.reserve(size) {
+                return std::ptr::null_mut();
+        }
+        let block = alloc::alloc(layout);
+        if block.is_null() {
+                allocator.release(size);
+                return std::ptr::null_mut();
+        }
+        *(block as *mut usize) = size;
+        block.add(HEADER) as *mut c_void
+}
+
+unsafe extern "C" fn reallocate(allocator: *const Allocator, ptr: *mut c_void, size: usize) -> *mut c_void {
+        let _unguarded = Unguarded::enter();
+        if ptr.is_null() {
+                return allocate(allocator, size);
+        }
+        if size == 0 {
+                deallocate(allocator, ptr);
+                return std::ptr::null_mut();
+        }
+        let allocator = match allocator.as_ref() {
+                Some(allocator) => allocator,
+                None            => return std::ptr::null_mut()
+        };
+        let (block, old_size) = header(ptr);
+        let new_layout = match layout(size) {
+                Some(layout) => layout,
+                None         => return std::ptr::null_mut()
+        };
+        /* The block is only accounted for its new size once it has been resized. */
+        if size > old_size && !allocator.reserve(size - old_size) {
+                return std::ptr::null_mut();
+        }
+        let resized = alloc::realloc(block, layout(old_size).unwrap(), new_layout.size());
+        if resized.is_null() {
+                if size > old_size {
+                        allocator.release(size - old_size);
+                }
+                return std::ptr::null_mut();
+        }
+        if size <= old_size {
+                allocator.release(old_size - size);
+                allocator.allocations.fetch_add(1, Ordering::Relaxed);
+        }
+        *(resized as *mut usize) = size;
+        resized.add(HEADER) as *mut c_void
+}
+
+unsafe extern "C" fn deallocate(allocator: *const Allocator, ptr: *mut c_void) {
+        let _unguarded = Unguarded::enter();
+        if ptr.is_null() {
+                return;
+        }
+        let (block, size) = header(ptr);
+        alloc::dealloc(block, layout(size).unwrap());
+        if let Some(allocator) = allocator.as_ref() {
+                allocator.release(size);
+        }
+}
+
+/// Hands `plugin` its allocator, if it exports `vplugin_set_allocator`.
+pub(crate) fn attach(plugin: &Plugin) {
+        match unsafe { plugin.symbol::<unsafe extern "C" fn(*const Allocator)>(SET_ALLOCATOR_SYMBOL) } {
+                Ok (set_allocator) => unsafe { set_allocator(&*plugin.memory) },
+                Err(_)             => diagnostics::trace!("Plugin doesn't export {}, its memory isn't accounted.", SET_ALLOCATOR_SYMBOL)
+        }
+}
+
+#[cfg(test)]
+mod tests {
+        use super::*;
+
+        #[test]
+        fn accounts_allocations() {
+                let allocator = Allocator::default();
+                unsafe {
+                        let block = (allocator.alloc)(&allocator, 100);
+                        assert!(!block.is_null());
+                        assert_eq!(block as usize % ALIGN, 0);
+                        std::ptr::write_bytes(block as *mut u8, 0xab, 100);
+
+                        let block = (allocator.realloc)(&allocator, block, 1000);
+                        assert_eq!(*(block as *const u8).add(99), 0xab);
+                        assert_eq!(allocator.usage().live, 1000);
+                        let block = (allocator.realloc)(&allocator, block, 10);
+                        assert_eq!(allocator.usage(), MemoryUsage { live: 10, peak: 1000, allocations: 3, refused: 0, limit: None });
+
+                        (allocator.free)(&allocator, block);
+                        (allocator.free)(&allocator, std::ptr::null_mut());
+                }
+                assert_eq!(allocator.usage().live, 0);
+                assert_eq!(allocator.usage().peak, 1000);
+        }
+
+        #[test]
+        fn refuses_past_the_limit() {
+                let allocator = Allocator::default();
+                allocator.set_limit(Some(64));
+                unsafe {
+                        let block = (allocator.alloc)(&allocator, 64);
+                        assert!(!block.is_null());
+                        assert!((allocator.alloc)(&allocator, 1).is_null());
+                        /* Growing fails as well, leaving the block as it was. */
+                        assert!((allocator.realloc)(&allocator, block, 65).is_null());
+                        assert_eq!(allocator.usage(), MemoryUsage { live: 64, peak: 64, allocations: 1, refused: 2, limit: Some(64) });
+                        /* Sizes that can't be allocated at all aren't counted as refused. */
+                        assert!((allocator.alloc)(&allocator, usize::MAX).is_null());
+                        assert_eq!(allocator.usage().refused, 2);
+
+                        (allocator.free)(&allocator, block);
+                }
+                allocator.set_limit(None);
+                assert_eq!(allocator.usage().limit, None);
+        }
+}
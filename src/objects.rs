@@ -0,0 +1,287 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Handing objects of the host to plugins as opaque handles, instead of pointers.
+//!
+//! The host moves a `Box<T>` into the [`ObjectRegistry`] of its manager with
+//! [`insert`](ObjectRegistry::insert) and passes the [`ObjectHandle`] it gets back to the
+//! plugin. The object stays owned by the host: the plugin can only reach it for the
+//! duration of a callback, and a handle that was released is refused instead of pointing
+//! to freed memory. Plugins receive the registry through their `vplugin_set_objects` export:
+//! ```c
+//! typedef struct vplugin_objects {
+//!     int (*borrow) (const struct vplugin_objects *self, uint64_t handle,
+//!                    void (*callback)(void *object, void *user), void *user);
+//!     int (*release)(const struct vplugin_objects *self, uint64_t handle);
+//!     int (*valid)  (const struct vplugin_objects *self, uint64_t handle);
+//! } vplugin_objects;
+//!
+//! void vplugin_set_objects(const vplugin_objects *objects);
+//! ```
+//! `borrow` calls `callback` with the object, `release` drops it once the plugin doesn't
+//! need it anymore, and `valid` returns `1` if the handle still refers to an object. The
+//! first two return [`OBJECT_OK`], [`OBJECT_INVALID`], [`OBJECT_BORROWED`] or [`OBJECT_STALE`].
+//!
+//! ## Generations
+//! Handles are generational indices: the slot of a released object is reused by the next
+//! one, with a new generation. A handle kept after its object was released, by a plugin or
+//! the host, is detected as stale (`VPluginError::StaleHandle`) even once its slot is reused,
+//! instead of reaching the new object.
+
+use std::any::Any;
+use std::ffi::{c_int, c_void};
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+use crate::diagnostics;
+use crate::error::VPluginError;
+use crate::handle::{PluginHandle, Slots};
+use crate::plugin::Plugin;
+use crate::isolation::Unguarded;
+
+/// The symbol plugins export to receive the object registry.
+pub const SET_OBJECTS_SYMBOL: &str = "vplugin_set_objects";
+
+/// Returned when the operation succeeded.
+pub const OBJECT_OK: c_int = 0;
+/// Returned when the handle was never given out by the registry.
+pub const OBJECT_INVALID: c_int = -1;
+/// Returned when the object is already borrowed.
+pub const OBJECT_BORROWED: c_int = -2;
+/// Returned when the object the handle referred to was released.
+pub const OBJECT_STALE: c_int = -3;
+
+/// ## ObjectHandle
+/// An opaque reference to an object in an [`ObjectRegistry`], passed to plugins as a
+/// `uint64_t`: the generation of the object in the upper 32 bits, and its index plus one
+/// in the lower ones. `0` is never a valid handle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(transparent)]
+pub struct ObjectHandle(u64);
+
+impl ObjectHandle {
+        /// Returns the value passed to plugins.
+        pub const fn as_raw(self) -> u64 {
+                self.0
+        }
+
+        /// Creates a handle from the value a plugin passed back.
+        pub const fn from_raw(raw: u64) -> Self {
+                Self(raw)
+        }
+
+        fn new(slot: PluginHandle) -> Self {
+                Self(((slot.generation as u64) << 32) | (slot.index as u64 + 1))
+        }
+
+        /* The slot of the object, `None` for handles that can't have been given out. */
+        fn slot(self) -> Option<PluginHandle> {
+                match (self.0 & 0xFFFF_FFFF) as u32 {
+                        0     => None,
+                        index => Some(PluginHandle { index: index - 1, generation: (self.0 >> 32) as u32 })
+                }
+        }
+}
+
+impl fmt::Display for ObjectHandle {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self.slot() {
+                        Some(slot) => write!(f, "object {}", slot),
+                        None       => write!(f, "object #{}", self.0)
+                }
+        }
+}
+
+/* An object, `None` while it's borrowed. */
+type Slot = Option<Box<dyn Any + Send>>;
+
+/// ## ObjectRegistry
+/// The objects a host handed to its plugins, handed to them as a `const vplugin_objects *`.
+/// See the [module documentation](crate::objects).
+#[repr(C)]
+pub struct ObjectRegistry {
+        /// Calls a function with the object, see the module documentation.
+        pub borrow : unsafe extern "C" fn(*const ObjectRegistry, u64, Option<unsafe extern "C" fn(*mut c_void, *mut c_void)>, *mut c_void) -> c_int,
+        /// Drops the object.
+        pub release: unsafe extern "C" fn(*const ObjectRegistry, u64) -> c_int,
+        /// Returns `1` if the handle refers to an object, `0` otherwise.
+        pub valid  : unsafe extern "C" fn(*const ObjectRegistry, u64) -> c_int,
+        objects    : Mutex<Slots<Slot>>,
+}
+
+impl Default for ObjectRegistry {
+        fn default() -> Self {
+                Self {
+                        borrow : borrow_object,
+                        release: release_object,
+                        valid  : valid_object,
+                        objects: Mutex::default(),
+                }
+        }
+}
+
+impl fmt::Debug for ObjectRegistry {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct("ObjectRegistry").field("objects", &self.len()).finish()
+        }
+}
+
+impl ObjectRegistry {
+        /// Moves `object` into the registry, returning the handle to pass to plugins.
+        pub fn insert<T: Send + 'static>(&self, object: Box<T>) -> ObjectHandle {
+                ObjectHandle::new(self.lock().insert(Some(object)))
+        }
+
+        /// Takes the object `handle` refers to back out of the registry, invalidating the
+        /// handle. `VPluginError::ParametersError` is returned if the object isn't a `T`.
+        pub fn retrieve<T: Send + 'static>(&self, handle: ObjectHandle) -> Result<Box<T>, VPluginError> {
+                let mut objects = self.lock();
+                match lookup(&mut objects, handle)? {
+                        Some(object) if object.is::<T>() => (),
+                        Some(_)                          => return Err(VPluginError::ParametersError),
+                        None                             => return Err(VPluginError::HandleBorrowed { handle: handle.0 })
+                }
+                let slot = handle.slot().ok_or(VPluginError::InvalidHandle)?;
+                match objects.remove(slot).flatten().map(|object| object.downcast::<T>()) {
+                        Some(Ok(object)) => Ok(object),
+                        _                => Err(VPluginError::InvalidHandle)
+                }
+        }
+
+        /// Calls `f` with the object `handle` refers to. `VPluginError::ParametersError` is
+        /// returned if the object isn't a `T`.
+        pub fn borrow<T: Send + 'static, R>(&self, handle: ObjectHandle, f: impl FnOnce(&mut T) -> R) -> Result<R, VPluginError> {
+                let mut object = self.take(handle)?;
+                let result = object.downcast_mut::<T>().map(f);
+                self.restore(handle, object);
+                result.ok_or(VPluginError::ParametersError)
+        }
+
+        /// Drops the object `handle` refers to, invalidating the handle. Objects that are
+        /// borrowed can't be released.
+        pub fn release(&self, handle: ObjectHandle) -> Result<(), VPluginError> {
+                let mut objects = self.lock();
+                if lookup(&mut objects, handle)?.is_none() {
+                        return Err(VPluginError::HandleBorrowed { handle: handle.0 });
+                }
+                let removed = handle.slot().and_then(|slot| objects.remove(slot));
+                /* The object may use the registry when it's dropped. */
+                drop(objects);
+                drop(removed);
+                Ok(())
+        }
+
+        /// Returns whether `handle` refers to an object.
+        pub fn contains(&self, handle: ObjectHandle) -> bool {
+                lookup(&mut self.lock(), handle).is_ok()
+        }
+
+        /// Returns how many objects are in the registry.
+        pub fn len(&self) -> usize {
+                self.lock().iter().count()
+        }
+
+        /// Returns whether the registry is empty.
+        pub fn is_empty(&self) -> bool {
+                self.len() == 0
+        }
+
+        fn lock(&self) -> MutexGuard<'_, Slots<Slot>> {
+                self.objects.lock().unwrap_or_else(|e| e.into_inner())
+        }
+
+        /*
+         * Takes the object out while it's borrowed, so the lock isn't held while the
+         * plugin or the host use it (Which may call into the registry again).
+         */
+        fn take(&self, handle: ObjectHandle) -> Result<Box<dyn Any + Send>, VPluginError> {
+                lookup(&mut self.lock(), handle)?
+                        .take()
+                        .ok_or(VPluginError::HandleBorrowed { handle: handle.0 })
+        }
+
+        fn restore(&self, handle: ObjectHandle, object: Box<dyn Any + Send>) {
+                if let Ok(slot) = lookup(&mut self.lock(), handle) {
+                        *slot = Some(object);
+                }
+        }
+}
+
+/* The slot of the object `handle` refers to, telling stale handles from made up ones. */
+fn lookup(objects: &mut Slots<Slot>, handle: ObjectHandle) -> Result<&mut Slot, VPluginError> {
+        let slot = handle.slot().ok_or(VPluginError::InvalidHandle)?;
+        if !objects.was_used(slot) {
+                return Err(VPluginError::InvalidHandle);
+        }
+        objects.get_mut(slot).ok_or(VPluginError::StaleHandle { handle: handle.0 })
+}
+
+/* The status returned to plugins for `result`. */
+fn status(result: Result<(), VPluginError>) -> c_int {
+        match result {
+                Ok (())                                 => OBJECT_OK,
+                Err(VPluginError::StaleHandle { .. })    => OBJECT_STALE,
+                Err(VPluginError::HandleBorrowed { .. }) => OBJECT_BORROWED,
+                Err(_)                                   => OBJECT_INVALID
+        }
+}
+
+unsafe extern "C" fn borrow_object(
+        registry: *const ObjectRegistry,
+        handle  : u64,
+        callback: Option<unsafe extern "C" fn(*mut c_void, *mut c_void)>,
+        user    : *mut c_void
+) -> c_int {
+        let _unguarded = Unguarded::enter();
+        let (registry, callback) = match (registry.as_ref(), callback) {
+                (Some(registry), Some(callback)) => (registry, callback),
+                _                                => return OBJECT_INVALID
+        };
+        let handle = ObjectHandle(handle);
+        let mut object = match registry.take(handle) {
+                Ok (object) => object,
+                Err(e)      => {
+                        diagnostics::trace!("A plugin borrowed {}: {}", handle, e);
+                        return status(Err(e));
+                }
+        };
+        callback(&mut *object as *mut dyn Any as *mut c_void, user);
+        registry.restore(handle, object);
+        OBJECT_OK
+}
+
+unsafe extern "C" fn release_object(registry: *const ObjectRegistry, handle: u64) -> c_int {
+        let _unguarded = Unguarded::enter();
+        match registry.as_ref() {
+                Some(registry) => status(registry.release(ObjectHandle(handle))),
+                None           => OBJECT_INVALID
+        }
+}
+
+unsafe extern "C" fn valid_object(registry: *const ObjectRegistry, handle: u64) -> c_int {
+        let _unguarded = Unguarded::enter();
+        match registry.as_ref() {
+                Some(registry) => registry.contains(ObjectHandle(handle)) as c_int,
+                None           => 0
+        }
+}
+
+/// Hands `plugin` the object registry, if it exports `vplugin_set_objects`.
+pub(crate) fn attach(plugin: &Plugin) {
+        match unsafe { plugin.symbol::<unsafe extern "C" fn(*const ObjectRegistry)>(SET_OBJECTS_SYMBOL) } {
+                Ok (set_objects) => unsafe { set_objects(&*plugin.objects) },
+                Err(_)           => diagnostics::trace!("Plugin doesn't export {}, it can't use the objects of the host.", SET_OBJECTS_SYMBOL)
+        }
+}
@@ -0,0 +1,209 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use serde_derive::{Deserialize, Serialize};
+use crate::api::HostApiVersion;
+use crate::license::LicensePolicy;
+use crate::permissions::PermissionPolicy;
+
+/// ## ManagerOptions
+/// Everything that can be configured on a [`PluginManager`](crate::plugin_manager::PluginManager),
+/// passed to [`PluginManager::with_options`](crate::plugin_manager::PluginManager::with_options).
+///
+/// Every field has a default, so the options can be deserialized straight from a section
+/// of the application's configuration file, specifying only what differs:
+/// ```toml
+/// [plugins]
+/// entry_point = "myapp_init"
+/// workspace   = "/var/cache/myapp/plugins"
+/// start       = "on_load"
+/// host_version = "2.4.0"
+///
+/// [plugins.accepts_plugins]
+/// "*"      = ">=1.0"
+/// markdown = ">=1.2, <2"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ManagerOptions {
+        /// The name of the entry point of plugins. Defaults to `vplugin_init`.
+        /// See [`set_entry_point`](crate::plugin_manager::PluginManager::set_entry_point).
+        pub entry_point     : String,
+        /// The directory plugins are extracted into. Every manager still creates its own
+        /// directory inside it. Defaults to a VPlugin directory inside the temporary
+        /// (Or cache) directory.
+        pub workspace       : Option<PathBuf>,
+        /// When the plugins loaded by the manager are started.
+        pub start           : StartPolicy,
+        /// Whether `.vpl` archives can be loaded. When disabled, only directories and bare
+        /// object files are accepted. Defaults to `true`.
+        pub allow_archives  : bool,
+        /// Team identifiers allowed to sign plugins (macOS only). See
+        /// [`set_allowed_team_ids`](crate::plugin_manager::PluginManager::set_allowed_team_ids).
+        pub allowed_team_ids: Vec<String>,
+        /// Whether extracted plugins are removed from the disk once unloaded.
+        pub cleanup         : CleanupPolicy,
+        /// The version of the API the host exposes to plugins. See
+        /// [`set_host_api_version`](crate::plugin_manager::PluginManager::set_host_api_version).
+        pub host_api        : Option<HostApiVersion>,
+        /// The version of the application, checked against the `requires_host` field of
+        /// plugins. See [`set_host_version`](crate::plugin_manager::PluginManager::set_host_version).
+        pub host_version    : Option<String>,
+        /// The versions of plugins the application accepts, by plugin name (`"*"` for every
+        /// plugin). See [`accept_plugins`](crate::plugin_manager::PluginManager::accept_plugins).
+        pub accepts_plugins : BTreeMap<String, String>,
+        /// The directory plugins keep their data (Like their settings) in. See
+        /// [`set_data_dir`](crate::plugin_manager::PluginManager::set_data_dir).
+        pub data_dir        : Option<PathBuf>,
+        /// The licenses plugins are accepted under. See
+        /// [`set_license_policy`](crate::plugin_manager::PluginManager::set_license_policy).
+        pub license_policy  : Option<LicensePolicy>,
+        /// The capabilities plugins may declare. See
+        /// [`set_permission_policy`](crate::plugin_manager::PluginManager::set_permission_policy).
+        pub permission_policy: Option<PermissionPolicy>,
+        /// Whether the symbols of plugins are global. See
+        /// [`set_symbol_visibility`](crate::plugin_manager::PluginManager::set_symbol_visibility).
+        pub symbols         : SymbolVisibility,
+        /// Whether the libraries of plugins are unloaded once the plugins are dropped. See
+        /// [`set_unload_policy`](crate::plugin_manager::PluginManager::set_unload_policy).
+        pub unload          : UnloadPolicy,
+}
+
+impl Default for ManagerOptions {
+        fn default() -> Self {
+                Self {
+                        entry_point     : "vplugin_init".into(),
+                        workspace       : None,
+                        start           : StartPolicy::default(),
+                        allow_archives  : true,
+                        allowed_team_ids: Vec::new(),
+                        cleanup         : CleanupPolicy::default(),
+                        host_api        : None,
+                        host_version    : None,
+                        accepts_plugins : BTreeMap::new(),
+                        data_dir        : None,
+                        license_policy  : None,
+                        permission_policy: None,
+                        symbols         : SymbolVisibility::default(),
+                        unload          : UnloadPolicy::default(),
+                }
+        }
+}
+
+/// When the plugins loaded by a manager are started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartPolicy {
+        /// Plugins are only started when the application asks for it (The default).
+        #[default]
+        Manual,
+        /// Plugins are started as soon as they're loaded. A plugin whose entry point
+        /// fails is not returned, the error is.
+        OnLoad,
+}
+
+/// Whether the application can run without a plugin, which decides what
+/// [`PluginManager::begin_all`](crate::plugin_manager::PluginManager::begin_all) does when
+/// the plugin fails to start. Set with
+/// [`set_requirement`](crate::plugin_manager::PluginManager::set_requirement).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Requirement {
+        /// The application runs without the plugin if it fails, in a degraded mode
+        /// (The default).
+        #[default]
+        Optional,
+        /// The application can't run without the plugin, starting the plugins is
+        /// aborted if it fails.
+        Required,
+}
+
+/// Whether the directories plugins were extracted into are removed when the plugins
+/// (And their manager) are dropped. Directories and object files that were loaded in
+/// place are never removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CleanupPolicy {
+        /// Extracted plugins are always removed (The default).
+        #[default]
+        Always,
+        /// Extracted plugins are removed, unless something went wrong with them (They
+        /// failed to load, their entry point or destructor failed, ...). Those are kept
+        /// on the disk so they can be inspected.
+        OnSuccess,
+        /// Extracted plugins are never removed. Useful when debugging plugins, or when
+        /// the extracted files are cached by the application.
+        Never,
+}
+
+impl CleanupPolicy {
+        /* Whether files have to be removed, given whether something went wrong with them. */
+        pub(crate) fn removes(self, failed: bool) -> bool {
+                match self {
+                        Self::Always    => true,
+                        Self::OnSuccess => !failed,
+                        Self::Never     => false
+                }
+        }
+}
+
+/// Whether the symbols of a plugin can be used by the libraries loaded after it (Unix
+/// only, Windows always keeps them to the library).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SymbolVisibility {
+        /// The symbols of the plugin are only found through the plugin itself (`RTLD_LOCAL`,
+        /// the default), so plugins exporting the same names don't interfere.
+        #[default]
+        Local,
+        /// The symbols of the plugin are added to the global scope of the process
+        /// (`RTLD_GLOBAL`), where they can shadow the symbols of the application and of
+        /// the plugins loaded later. Only needed by plugins other libraries link against.
+        Global,
+        /// Every plugin is loaded in its own linker namespace (`dlmopen`), along with its
+        /// own copy of the libraries it links to, so plugins needing different builds of
+        /// the same library (Two versions of libcurl, ...) can be used together. Plugins
+        /// then can't use the symbols of the application by name, only what it hands them
+        /// (Like an API table), and the number of namespaces is limited (16 with glibc).
+        ///
+        /// Only available on Linux with glibc, other platforms load plugins with
+        /// [`Local`](SymbolVisibility::Local) symbols instead, with a warning.
+        Isolated,
+}
+
+/// Whether the library of a plugin is unloaded when the plugin is dropped. Unloading a
+/// library that started threads, or registered thread-local destructors, crashes the
+/// process as soon as they run code that is no longer mapped. Keeping the library loaded
+/// (Leaking it) avoids that, at the cost of its memory.
+///
+/// Leaked libraries are listed by
+/// [`PluginManager::leaked_libraries`](crate::plugin_manager::PluginManager::leaked_libraries),
+/// and the directories of their plugins are kept on the disk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnloadPolicy {
+        /// The library is unloaded (The default).
+        #[default]
+        Unload,
+        /// The library is never unloaded.
+        Leak,
+        /// The library is kept loaded if threads were started since it was loaded, as it
+        /// may have started them. Threads are only listed on Linux and Android, elsewhere
+        /// the library is always kept loaded.
+        LeakIfThreadsDetected,
+}
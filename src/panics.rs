@@ -0,0 +1,119 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! The panics Rust plugins report through `vplugin_set_panic_reporter`, see
+//! [`vplugin_core::panic`].
+
+use std::ffi::c_void;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use serde_derive::{Deserialize, Serialize};
+use vplugin_core::ffi::VStr;
+use vplugin_core::panic::{PanicReporter, SET_PANIC_REPORTER_SYMBOL};
+use crate::diagnostics;
+use crate::plugin::Plugin;
+use crate::isolation::Unguarded;
+
+/// ## PanicReport
+/// A panic of a plugin, returned by [`Plugin::last_panic`](crate::plugin::Plugin::last_panic).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PanicReport {
+        /// The panic message.
+        pub message  : String,
+        /// Where the plugin panicked, as `file:line:column`, if known.
+        pub location : Option<String>,
+        /// The backtrace of the plugin when it panicked, if it was built with the `backtrace`
+        /// feature of `vplugin-core`.
+        pub backtrace: Option<String>,
+}
+
+impl fmt::Display for PanicReport {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match &self.location {
+                        Some(location) => write!(f, "'{}' at {}", self.message, location),
+                        None           => write!(f, "'{}'", self.message)
+                }
+        }
+}
+
+/// The panics reported by a single plugin, the context of its reporter.
+#[derive(Debug, Default)]
+pub(crate) struct Panics {
+        /* Where the reports are sent to, the sink of the plugin. */
+        diagnostics: diagnostics::Sink,
+        plugin     : String,
+        last       : Mutex<Option<PanicReport>>,
+        /* Taken by `take()`, so a report is only attached to a single error. */
+        pending    : Mutex<Option<PanicReport>>,
+}
+
+impl Panics {
+        pub(crate) fn new(diagnostics: diagnostics::Sink, plugin: &str) -> Self {
+                Self { diagnostics, plugin: plugin.to_owned(), ..Self::default() }
+        }
+
+        /// The last panic reported.
+        pub(crate) fn last(&self) -> Option<PanicReport> {
+                self.last.lock().unwrap_or_else(|e| e.into_inner()).clone()
+        }
+
+        /// The panic reported since the last call, if any.
+        pub(crate) fn take(&self) -> Option<PanicReport> {
+                self.pending.lock().unwrap_or_else(|e| e.into_inner()).take()
+        }
+
+        fn record(&self, report: PanicReport) {
+                *self.last.lock().unwrap_or_else(|e| e.into_inner()) = Some(report.clone());
+                *self.pending.lock().unwrap_or_else(|e| e.into_inner()) = Some(report);
+        }
+}
+
+unsafe extern "C" fn report(
+        context  : *mut c_void,
+        message  : VStr<'_>,
+        file     : VStr<'_>,
+        line     : u32,
+        column   : u32,
+        backtrace: VStr<'_>
+) {
+        let _unguarded = Unguarded::enter();
+        let panics = match (context as *const Panics).as_ref() {
+                Some(panics) => panics,
+                None         => return
+        };
+        let file = String::from_utf8_lossy(file.as_bytes());
+        let report = PanicReport {
+                message  : String::from_utf8_lossy(message.as_bytes()).into_owned(),
+                location : (!file.is_empty()).then(|| format!("{}:{}:{}", file, line, column)),
+                backtrace: match backtrace.as_bytes() {
+                        []        => None,
+                        backtrace => Some(String::from_utf8_lossy(backtrace).into_owned())
+                },
+        };
+        /* Reported right away, the plugin may abort the process once the hook returns. */
+        let _scope = panics.diagnostics.scope(Some(&panics.plugin));
+        diagnostics::failed(report.backtrace.as_deref(), format_args!("Plugin panicked: {}", report));
+        panics.record(report);
+}
+
+/// Hands `plugin` the panic reporter of the host, if it exports `vplugin_set_panic_reporter`.
+pub(crate) fn attach(plugin: &mut Plugin) {
+        plugin.panics = Arc::new(Panics::new(plugin.diagnostics.clone(), &plugin.metadata.name));
+        match unsafe { plugin.symbol::<unsafe extern "C" fn(PanicReporter, *mut c_void)>(SET_PANIC_REPORTER_SYMBOL) } {
+                Ok (set_reporter) => unsafe { set_reporter(report, &*plugin.panics as *const Panics as *mut c_void) },
+                Err(_)            => diagnostics::trace!("Plugin doesn't export {}, its panics aren't reported.", SET_PANIC_REPORTER_SYMBOL)
+        }
+}
@@ -0,0 +1,298 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Capabilities plugins ask for at runtime, and the decisions of the user about them.
+//!
+//! VPlugin doesn't know what the capabilities are (`"network"`, `"filesystem"`, ...),
+//! that's up to the application. It asks the user through the prompt the application
+//! installs with [`PluginManager::set_permission_prompt`](crate::plugin_manager::PluginManager::set_permission_prompt),
+//! and remembers the permanent decisions in the data directory of the plugin. They're
+//! remembered for the package the user answered about (By its [content hash](crate::plugin::Plugin::content_hash)),
+//! not for its name: another package calling itself the same doesn't get them, and
+//! the user is asked again when the plugin is updated.
+//!
+//! Plugins also declare the capabilities they need in the `[permissions]` section of
+//! their metadata. Applications can refuse plugins asking for capabilities they never
+//! grant before loading them, with a [`PermissionPolicy`]. Capabilities a plugin didn't
+//! declare, or that the policy doesn't allow, are refused at runtime as well, without
+//! asking the user.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::{c_char, c_int, CStr};
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use serde_derive::{Deserialize, Serialize};
+use vplugin_core::PluginMetadata;
+use crate::diagnostics;
+use crate::error::VPluginError;
+use crate::store;
+use crate::isolation::Unguarded;
+
+/// The file the permanent decisions are kept in, inside the data directory of the plugin.
+/// It holds a table per package, named after its content hash.
+pub const PERMISSIONS_FILE: &str = "permissions.toml";
+
+/// A capability a plugin asked for, handed to the permission prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PermissionRequest<'a> {
+        /// The name of the plugin asking.
+        pub plugin    : &'a str,
+        /// The capability it asks for.
+        pub capability: &'a str,
+}
+
+/// What the user answered to a [`PermissionRequest`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionDecision {
+        /// Grant the capability, and don't ask again.
+        AllowAlways,
+        /// Grant the capability this time only.
+        AllowOnce,
+        /// Refuse the capability this time only.
+        DenyOnce,
+        /// Refuse the capability, and don't ask again.
+        DenyAlways,
+}
+
+impl PermissionDecision {
+        /// Returns whether the capability is granted.
+        pub fn is_allowed(self) -> bool {
+                matches!(self, PermissionDecision::AllowAlways | PermissionDecision::AllowOnce)
+        }
+
+        /* Whether the decision outlives the request. */
+        fn is_permanent(self) -> bool {
+                matches!(self, PermissionDecision::AllowAlways | PermissionDecision::DenyAlways)
+        }
+}
+
+/// ## PermissionPolicy
+/// The capabilities plugins may declare in the `[permissions]` section of their metadata,
+/// set with [`PluginManager::set_permission_policy`](crate::plugin_manager::PluginManager::set_permission_policy).
+/// Plugins declaring any other capability are refused before their library is loaded.
+///
+/// The policy only looks at what plugins declare: granting a capability while the plugin
+/// runs is still up to [`Permissions`].
+///
+/// ## Example
+/// An application whose plugins can't go online:
+/// ```toml
+/// [plugins.permission_policy]
+/// deny = ["network"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PermissionPolicy {
+        /// The only capabilities plugins may declare. Empty to allow every capability that isn't denied.
+        pub allow: Vec<String>,
+        /// Capabilities plugins may never declare.
+        pub deny : Vec<String>,
+}
+
+impl PermissionPolicy {
+        /// Returns whether plugins may declare `capability`.
+        pub fn allows(&self, capability: &str) -> bool {
+                !self.deny.iter().any(|denied| denied == capability)
+                        && (self.allow.is_empty() || self.allow.iter().any(|allowed| allowed == capability))
+        }
+
+        /// Checks that the plugin described by `metadata` only declares capabilities the
+        /// policy allows, returning `VPluginError::CapabilitiesRefused` with those it doesn't
+        /// otherwise.
+        pub fn check(&self, metadata: &PluginMetadata) -> Result<(), VPluginError> {
+                let refused: Vec<String> = metadata.permissions
+                        .keys()
+                        .filter(|capability| !self.allows(capability))
+                        .cloned()
+                        .collect();
+                if refused.is_empty() {
+                        return Ok(());
+                }
+                diagnostics::error!(
+                        "Refusing plugin: It asks for capabilities the application doesn't grant ({}).",
+                        refused.join(", ")
+                );
+                Err(VPluginError::CapabilitiesRefused { plugin: metadata.name.clone(), capabilities: refused })
+        }
+}
+
+type PromptFn = dyn Fn(&PermissionRequest<'_>) -> PermissionDecision + Send + Sync;
+
+/// The prompt installed on a manager, shared with the permissions of its plugins.
+#[derive(Clone)]
+pub(crate) struct Prompt(pub(crate) Arc<PromptFn>);
+
+impl fmt::Debug for Prompt {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("Prompt")
+        }
+}
+
+/// ## Permissions
+/// The capabilities of a single plugin, returned by
+/// [`PluginManager::permissions`](crate::plugin_manager::PluginManager::permissions).
+///
+/// The host checks capabilities with [`request`](Permissions::request). To let the plugin ask
+/// for them itself, the host can put [`request_capability`] in its API table (See
+/// [`HostApiVersion`](crate::api::HostApiVersion)), along with a pointer to the `Permissions`.
+#[derive(Debug)]
+pub struct Permissions {
+        plugin     : String,
+        /* What the permanent decisions are stored under: the content hash of the package. */
+        identity   : String,
+        /* The capabilities the plugin declares in its metadata, the only ones it can be granted. */
+        declared   : BTreeSet<String>,
+        policy     : Option<PermissionPolicy>,
+        path       : PathBuf,
+        prompt     : Option<Prompt>,
+        diagnostics: diagnostics::Sink,
+        /* The decisions that only last as long as this struct. */
+        session    : Mutex<BTreeMap<String, bool>>,
+}
+
+impl Permissions {
+        pub(crate) fn new(
+                metadata: &PluginMetadata,
+                identity: &str,
+                policy: Option<PermissionPolicy>,
+                dir: PathBuf,
+                prompt: Option<Prompt>,
+                diagnostics: diagnostics::Sink
+        ) -> Self {
+                Self {
+                        plugin  : metadata.name.clone(),
+                        identity: identity.into(),
+                        declared: metadata.permissions.keys().cloned().collect(),
+                        policy,
+                        path    : dir.join(PERMISSIONS_FILE),
+                        prompt,
+                        diagnostics,
+                        session: Mutex::new(BTreeMap::new()),
+                }
+        }
+
+        /// Returns whether the plugin may use `capability`. The user is asked through the
+        /// prompt of the manager unless they already made a permanent decision, which is then
+        /// stored. Without a prompt, capabilities that were never granted are refused.
+        ///
+        /// Capabilities the plugin doesn't declare in its metadata, or that the permission
+        /// policy of the manager doesn't allow, are refused without asking the user.
+        pub fn request(&self, capability: &str) -> Result<bool, VPluginError> {
+                let _scope = self.diagnostics.scope(Some(&self.plugin));
+                if !self.declared.contains(capability) {
+                        diagnostics::warning!("Plugin doesn't declare capability '{}' in its metadata, refusing it.", capability);
+                        return Ok(false);
+                }
+                if self.policy.as_ref().is_some_and(|policy| !policy.allows(capability)) {
+                        diagnostics::warning!("The permission policy doesn't allow capability '{}', refusing it.", capability);
+                        return Ok(false);
+                }
+                if let Some(granted) = self.granted(capability)? {
+                        return Ok(granted);
+                }
+                let prompt = match &self.prompt {
+                        Some(prompt) => prompt,
+                        None         => {
+                                diagnostics::warning!("No permission prompt was set, refusing capability '{}'.", capability);
+                                return Ok(false);
+                        }
+                };
+
+                let decision = (prompt.0)(&PermissionRequest { plugin: &self.plugin, capability });
+                diagnostics::trace!("Capability '{}' answered with {:?}.", capability, decision);
+                if decision.is_permanent() {
+                        self.update(|grants| {
+                                grants.insert(capability.into(), decision.is_allowed().into());
+                                true
+                        })?;
+                } else {
+                        self.session().insert(capability.into(), decision.is_allowed());
+                }
+                Ok(decision.is_allowed())
+        }
+
+        /// Returns the decision already made about `capability`, if any, without asking.
+        pub fn granted(&self, capability: &str) -> Result<Option<bool>, VPluginError> {
+                if let Some(granted) = self.session().get(capability) {
+                        return Ok(Some(*granted));
+                }
+                Ok(self.grants()?.get(capability).copied())
+        }
+
+        /// Returns every permanent decision about this package, by capability.
+        pub fn grants(&self) -> Result<BTreeMap<String, bool>, VPluginError> {
+                Ok(match store::read(&self.path)?.remove(&self.identity) {
+                        Some(toml::Value::Table(grants)) => grants
+                                .into_iter()
+                                .filter_map(|(capability, granted)| granted.as_bool().map(|granted| (capability, granted)))
+                                .collect(),
+                        _ => BTreeMap::new()
+                })
+        }
+
+        /// Forgets every decision about `capability`, so the user is asked again next time.
+        pub fn revoke(&self, capability: &str) -> Result<(), VPluginError> {
+                self.session().remove(capability);
+                self.update(|grants| grants.remove(capability).is_some())
+        }
+
+        /* The decisions of this session. They stay usable if a prompt panicked while the lock was held. */
+        fn session(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, bool>> {
+                self.session.lock().unwrap_or_else(|e| e.into_inner())
+        }
+
+        /* Changes the permanent decisions about this package with `f`, which returns whether it changed any. */
+        fn update(&self, f: impl FnOnce(&mut toml::Table) -> bool) -> Result<(), VPluginError> {
+                let mut packages = store::read(&self.path)?;
+                let mut grants = match packages.remove(&self.identity) {
+                        Some(toml::Value::Table(grants)) => grants,
+                        _                                => toml::Table::new()
+                };
+                if !f(&mut grants) {
+                        return Ok(());
+                }
+                packages.insert(self.identity.clone(), grants.into());
+                store::write(&self.path, &packages)
+        }
+}
+
+/// Asks for `capability` on behalf of a plugin, see [`Permissions::request`]. The host can add
+/// this function to its API table, along with a pointer to the [`Permissions`] of the plugin.
+/// Returns 1 if the capability is granted, 0 if it's refused and -1 on errors (Including
+/// a panic of the permission prompt, which never unwinds into the plugin).
+///
+/// ## Safety
+/// `permissions` must point to a valid `Permissions`, and `capability` to a nul-terminated string.
+pub unsafe extern "C" fn request_capability(permissions: *const Permissions, capability: *const c_char) -> c_int {
+        let _unguarded = Unguarded::enter();
+        if permissions.is_null() || capability.is_null() {
+                return -1;
+        }
+        let capability = match CStr::from_ptr(capability).to_str() {
+                Ok (capability) => capability,
+                Err(_)          => return -1
+        };
+        match panic::catch_unwind(AssertUnwindSafe(|| (*permissions).request(capability))) {
+                Ok (Ok(granted)) => granted as c_int,
+                Ok (Err(_))      => -1,
+                Err(_)           => {
+                        diagnostics::error!("The permission prompt panicked while asked for capability '{}'.", capability);
+                        -1
+                }
+        }
+}
@@ -0,0 +1,354 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! What differs between the platforms VPlugin runs on when it comes to paths and loading
+//! libraries.
+//!
+//! On Windows:
+//! - Libraries are loaded with `LoadLibraryExW`, searching the directory of the library
+//!   for the DLLs it links to rather than the one of the application or the current
+//!   directory. The libraries bundled with a plugin (Its `needs`) are loaded by their
+//!   absolute path beforehand, without changing the search path of the process.
+//! - Paths inside the workspace are extended-length (`\\?\`) paths, so they aren't limited
+//!   to `MAX_PATH` characters.
+//! - A DLL can't be deleted or replaced while it's loaded, so directories and object files
+//!   are loaded from a copy in the workspace instead of in place. Rebuilding a plugin while
+//!   the application runs then works like on the other platforms.
+
+use std::path::{Path, PathBuf};
+
+/// Whether the system locks the libraries it loads, so directories and object files are
+/// loaded from a copy (Like with [`PluginManager::load_instance`](crate::plugin_manager::PluginManager::load_instance)).
+pub(crate) const LOCKS_LOADED_LIBRARIES: bool = cfg!(windows);
+
+/// Resolves `path` against the current directory, so the plugin keeps pointing at the
+/// same package if the application changes directory afterwards.
+pub(crate) fn absolute(path: &Path) -> PathBuf {
+        if path.is_absolute() {
+                return path.to_path_buf();
+        }
+        match std::env::current_dir() {
+                Ok (current) => current.join(path),
+                Err(_)       => path.to_path_buf()
+        }
+}
+
+/// Converts `path` to an extended-length path on Windows. Other platforms
+/// don't have a path length limit to work around, so the path is returned as is.
+#[cfg(windows)]
+pub(crate) fn extended_length(path: &Path) -> PathBuf {
+        use std::ffi::OsString;
+
+        let raw = path.as_os_str().to_string_lossy();
+        if raw.starts_with(r"\\?\") {
+                return path.to_path_buf();
+        }
+        /* canonicalize() already returns a verbatim path for existing files. */
+        if let Ok(canonical) = std::fs::canonicalize(path) {
+                return canonical;
+        }
+
+        let mut extended = OsString::from(if raw.starts_with(r"\\") { r"\\?\UNC\" } else { r"\\?\" });
+        extended.push(raw.trim_start_matches('\\').replace('/', "\\"));
+        PathBuf::from(extended)
+}
+
+#[cfg(not(windows))]
+pub(crate) fn extended_length(path: &Path) -> PathBuf {
+        path.to_path_buf()
+}
+
+/// ## Library
+/// Where the dynamic linker would find a library, see [`find_library`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Library {
+        /// The file of the library.
+        File(PathBuf),
+        /// A library of the system that isn't a file (Those in the dyld shared cache on
+        /// macOS, API sets on Windows), which is always there.
+        System,
+}
+
+/// Looks for the library `name` (A file name like `libssl.so.3`, or a path) the way the
+/// dynamic linker would, without loading it: inside the package extracted in `workdir`
+/// first, then in the directories the system searches. `None` is returned if it can't be
+/// found.
+///
+/// The search paths are read from the environment and the configuration of the dynamic
+/// linker, since nothing may be loaded to check them: loading a library runs its
+/// initializers.
+pub(crate) fn find_library(name: &str, workdir: &Path) -> Option<Library> {
+        if let Some(bundled) = bundled_library(workdir, name) {
+                return Some(Library::File(bundled));
+        }
+        if is_system_library(name) {
+                return Some(Library::System);
+        }
+        let path = Path::new(name);
+        if path.components().count() > 1 {
+                return path.is_file().then(|| Library::File(path.to_path_buf()));
+        }
+        search_paths()
+                .into_iter()
+                .map(|directory| crate::workspace::find(&directory, path))
+                .find(|candidate| candidate.is_file())
+                .map(Library::File)
+}
+
+/* Libraries the system provides without a file to read. */
+fn is_system_library(name: &str) -> bool {
+        if cfg!(target_os = "macos") {
+                let path = Path::new(name);
+                let system = path.starts_with("/usr/lib") || path.starts_with("/System/Library");
+                return system && !path.is_file();
+        }
+        if cfg!(windows) {
+                let name = name.to_ascii_lowercase();
+                return name.starts_with("api-ms-win-") || name.starts_with("ext-ms-");
+        }
+        false
+}
+
+/* The directories the dynamic linker searches, in order. */
+#[cfg(all(unix, not(target_os = "macos")))]
+fn search_paths() -> Vec<PathBuf> {
+        let mut directories = env_paths("LD_LIBRARY_PATH");
+        read_ld_so_conf(Path::new("/etc/ld.so.conf"), &mut directories, 0);
+        let multiarch = format!("{}-linux-gnu", std::env::consts::ARCH);
+        for directory in ["/lib", "/usr/lib", "/lib64", "/usr/lib64"] {
+                directories.push(Path::new(directory).join(&multiarch));
+                directories.push(PathBuf::from(directory));
+        }
+        directories
+}
+
+/* Adds the directories listed in `conf` (An `ld.so.conf` file) to `directories`, following `include`s. */
+#[cfg(all(unix, not(target_os = "macos")))]
+fn read_ld_so_conf(conf: &Path, directories: &mut Vec<PathBuf>, depth: usize) {
+        let contents = match std::fs::read_to_string(conf) {
+                Ok (contents) => contents,
+                Err(_)        => return
+        };
+        for line in contents.lines().map(|line| line.split('#').next().unwrap_or("").trim()) {
+                match line.strip_prefix("include") {
+                        Some(pattern) if depth < 8 => {
+                                for included in glob_conf(pattern.trim()) {
+                                        read_ld_so_conf(&included, directories, depth + 1);
+                                }
+                        },
+                        Some(_)                    => (),
+                        None if line.is_empty()    => (),
+                        None                       => directories.push(PathBuf::from(line))
+                }
+        }
+}
+
+/* The files matching `pattern`, an `include` of `ld.so.conf` (Like `/etc/ld.so.conf.d/` followed by `*.conf`). Only a `*` in the file name is supported. */
+#[cfg(all(unix, not(target_os = "macos")))]
+fn glob_conf(pattern: &str) -> Vec<PathBuf> {
+        let pattern = Path::new(pattern);
+        let (directory, name) = match (pattern.parent(), pattern.file_name().and_then(|name| name.to_str())) {
+                (Some(directory), Some(name)) => (directory, name),
+                _                             => return Vec::new()
+        };
+        let (prefix, suffix) = match name.split_once('*') {
+                Some(parts) => parts,
+                None        => return vec![pattern.to_path_buf()]
+        };
+        let mut files: Vec<PathBuf> = std::fs::read_dir(directory)
+                .into_iter()
+                .flatten()
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| {
+                        let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+                        name.len() >= prefix.len() + suffix.len() && name.starts_with(prefix) && name.ends_with(suffix)
+                })
+                .collect();
+        files.sort();
+        files
+}
+
+#[cfg(target_os = "macos")]
+fn search_paths() -> Vec<PathBuf> {
+        let mut directories = env_paths("DYLD_LIBRARY_PATH");
+        match env_paths("DYLD_FALLBACK_LIBRARY_PATH") {
+                fallback if !fallback.is_empty() => directories.extend(fallback),
+                _                                => directories.extend([PathBuf::from("/usr/local/lib"), PathBuf::from("/usr/lib")])
+        }
+        directories
+}
+
+#[cfg(windows)]
+fn search_paths() -> Vec<PathBuf> {
+        let mut directories = Vec::new();
+        if let Some(application) = std::env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf)) {
+                directories.push(application);
+        }
+        if let Some(root) = std::env::var_os("SystemRoot").map(PathBuf::from) {
+                directories.push(root.join("System32"));
+                directories.push(root);
+        }
+        directories.extend(env_paths("PATH"));
+        directories
+}
+
+#[cfg(not(any(unix, windows)))]
+fn search_paths() -> Vec<PathBuf> {
+        Vec::new()
+}
+
+/* The directories listed in the environment variable `variable`. */
+#[cfg(any(unix, windows))]
+fn env_paths(variable: &str) -> Vec<PathBuf> {
+        match std::env::var_os(variable) {
+                Some(paths) => std::env::split_paths(&paths).filter(|path| !path.as_os_str().is_empty()).collect(),
+                None        => Vec::new()
+        }
+}
+
+/// Loads the DLL at `path`, looking for the DLLs it links to in its own directory and the
+/// system directories. The libraries a plugin bundles are loaded beforehand by
+/// [`BundledLibraries`], so they're found among the loaded modules.
+#[cfg(windows)]
+pub(crate) fn load_library(path: &Path) -> Result<libloading::Library, libloading::Error> {
+        use libloading::os::windows::{Library, LOAD_LIBRARY_SEARCH_DEFAULT_DIRS, LOAD_LIBRARY_SEARCH_DLL_LOAD_DIR};
+
+        /* LOAD_LIBRARY_SEARCH_DLL_LOAD_DIR only accepts absolute paths. */
+        let path = extended_length(&absolute(path));
+        unsafe { Library::load_with_flags(&path, LOAD_LIBRARY_SEARCH_DLL_LOAD_DIR | LOAD_LIBRARY_SEARCH_DEFAULT_DIRS) }
+                .map(libloading::Library::from)
+}
+
+/// Returns the file of the library `name` (An entry of `needs`) inside the package
+/// extracted in `workdir`, if it's there. Files that are outside of `workdir` once
+/// symbolic links are resolved are refused, since `needs` comes from the plugin.
+pub(crate) fn bundled_library(workdir: &Path, name: &str) -> Option<PathBuf> {
+        if workdir.as_os_str().is_empty() || Path::new(name).is_absolute() {
+                return None;
+        }
+        let bundled = crate::workspace::find(workdir, Path::new(name));
+        if !bundled.is_file() {
+                return None;
+        }
+        match (std::fs::canonicalize(&bundled), std::fs::canonicalize(workdir)) {
+                (Ok(file), Ok(root)) if file.starts_with(&root) => Some(bundled),
+                _ => {
+                        crate::diagnostics::warning!("Ignoring {}, which isn't inside the package.", bundled.display());
+                        None
+                }
+        }
+}
+
+/// ## BundledLibraries
+/// The libraries a plugin bundles (The entries of its `needs` found inside its package),
+/// loaded by their absolute path before its object file on Windows and freed when
+/// dropped. The object file then uses them instead of DLLs of the same name elsewhere,
+/// without adding directories to the DLL search path of the whole process where other
+/// plugins would find them. Elsewhere, the object file has to find them itself (Through
+/// its `rpath`), so this is empty.
+#[derive(Debug, Default)]
+pub(crate) struct BundledLibraries {
+        #[cfg(windows)]
+        libraries: Vec<libloading::Library>,
+}
+
+impl BundledLibraries {
+        /// Loads the libraries out of `needs` found inside `workdir`, in the order they're
+        /// listed (So libraries come after those they link to).
+        #[cfg(windows)]
+        pub(crate) fn for_package(workdir: &Path, needs: &[String]) -> Self {
+                let mut libraries = Vec::new();
+                for bundled in needs.iter().filter_map(|library| bundled_library(workdir, library)) {
+                        match load_library(&bundled) {
+                                Ok (library) => {
+                                        crate::diagnostics::trace!("Loaded the bundled library {}.", bundled.display());
+                                        libraries.push(library);
+                                },
+                                Err(e)       => crate::diagnostics::warning!("Couldn't load the bundled library {}: {}", bundled.display(), e)
+                        }
+                }
+                Self { libraries }
+        }
+
+        #[cfg(not(windows))]
+        pub(crate) fn for_package(_workdir: &Path, _needs: &[String]) -> Self {
+                Self::default()
+        }
+
+        /// Keeps the libraries loaded for good, for object files kept loaded.
+        #[cfg(windows)]
+        pub(crate) fn keep(mut self) {
+                self.libraries.drain(..).for_each(std::mem::forget);
+        }
+
+        #[cfg(not(windows))]
+        pub(crate) fn keep(self) {}
+}
+
+#[cfg(test)]
+mod tests {
+        use super::*;
+
+        #[test]
+        fn find_bundled_library() {
+                let workdir = std::env::temp_dir().join(format!("vplugin-find-library-{}", std::process::id()));
+                std::fs::create_dir_all(workdir.join("lib")).unwrap();
+                std::fs::write(workdir.join("lib").join("libbundled.so"), "").unwrap();
+                assert_eq!(find_library("lib/libbundled.so", &workdir), Some(Library::File(workdir.join("lib").join("libbundled.so"))));
+                assert_eq!(find_library("libvplugin-does-not-exist.so.1", &workdir), None);
+                std::fs::remove_dir_all(&workdir).unwrap();
+        }
+
+        #[cfg(all(target_os = "linux", target_env = "gnu"))]
+        #[test]
+        fn find_system_library() {
+                match find_library("libc.so.6", Path::new("")) {
+                        Some(Library::File(path)) => assert!(path.is_file()),
+                        other                     => panic!("{:?}", other)
+                }
+        }
+
+        #[cfg(windows)]
+        #[test]
+        fn find_system_dll() {
+                assert!(matches!(find_library("KERNEL32.dll", Path::new("")), Some(Library::File(_))));
+                assert_eq!(find_library("api-ms-win-crt-runtime-l1-1-0.dll", Path::new("")), Some(Library::System));
+        }
+
+        #[cfg(windows)]
+        #[test]
+        fn extended_length_prefixes() {
+                let missing = Path::new(r"C:\vplugin\does-not-exist\ünïcødé");
+                assert_eq!(extended_length(missing), PathBuf::from(r"\\?\C:\vplugin\does-not-exist\ünïcødé"));
+                assert_eq!(extended_length(Path::new(r"\\server\share\plugin")), PathBuf::from(r"\\?\UNC\server\share\plugin"));
+                assert_eq!(extended_length(Path::new(r"\\?\C:\already")), PathBuf::from(r"\\?\C:\already"));
+                assert_eq!(extended_length(Path::new("C:/mixed/separators")), PathBuf::from(r"\\?\C:\mixed\separators"));
+        }
+
+        #[cfg(windows)]
+        #[test]
+        fn extended_length_long_paths() {
+                let mut dir = std::env::temp_dir().join("vplugin-插件");
+                while dir.as_os_str().len() < 300 {
+                        dir.push("a-rather-long-directory-name");
+                }
+                let dir = extended_length(&dir);
+                std::fs::create_dir_all(&dir).unwrap();
+                std::fs::write(dir.join("metadata.toml"), "").unwrap();
+                std::fs::remove_dir_all(extended_length(&std::env::temp_dir().join("vplugin-插件"))).unwrap();
+        }
+}
@@ -17,25 +17,59 @@
 
 #![allow(dead_code)]
 
-extern crate libloading;
 extern crate log;
 
-use std::env::{self};
-use std::ffi::OsStr;
-use std::fs::{
-        self,
-        File
-};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+#[cfg(feature = "archive")]
+use std::io::Cursor;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
 use serde_derive::Deserialize;
-use libloading::{
-        Library,
-        Symbol
-};
-use zip::ZipArchive;
+use std::ffi::{c_int, c_void};
+use std::mem;
+use std::ops::ControlFlow;
+use std::time::Duration;
+use crate::api::{self, ApiTable, HostApiVersion};
+use crate::backend::{self, PluginBackend};
+use crate::i18n::Translations;
+use crate::sdk;
+use crate::fault::{Faults, Stage};
+use crate::isolation::{self, Export};
 use crate::VHook;
 use crate::error::VPluginError;
-use std::io::ErrorKind::{*, self};
+use crate::diagnostics::{self, Deprecation};
+use vplugin_core::{HookManifest, PluginMetadata, SettingsSchema};
+use vplugin_core::manifest::HOOK_MANIFEST_FILE;
+use crate::workspace;
+use crate::platform::{self, BundledLibraries};
+use crate::progress::{self, LoadPhase, LoadProgress, Reporter};
+use crate::builtin;
+use crate::state::PluginState;
+use crate::guard::{self, HookGuard, PinPolicy};
+use crate::hooks::Hooks;
+use crate::options::{CleanupPolicy, Requirement, SymbolVisibility, UnloadPolicy};
+use crate::unload::{self, LeakReason, LeakedLibrary, Leaks};
+use crate::version::{self, Constraints};
+use crate::license::{EntitlementCheck, LicensePolicy};
+use crate::permissions::PermissionPolicy;
+use crate::identity;
+use crate::lifecycle::Observers;
+use crate::intercept::{Call, CallKind, Interceptors};
+#[cfg(feature = "signing")]
+use crate::signing::{self, TrustedKeys};
+use uuid::Uuid;
+use crate::memory::{self, Allocator};
+use crate::objects::{self, ObjectRegistry};
+use crate::stop;
+use crate::panics::{self, PanicReport, Panics};
+use crate::threads::{self, ThreadRegistry};
+use crate::state_store::{self, PluginStateStore, StateTable};
+use crate::interfaces::{self, InterfaceRegistry, InterfaceTable};
+use semver::Version;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::io::ErrorKind::*;
 
 /* Personally I believe it looks much better like this */
 type LaterInitialized<T> = Option<T>;
@@ -53,7 +87,15 @@ macro_rules! init_now {
 /// This is purely for deserialization.
 #[derive(Deserialize)]
 struct Data {
-        metadata: Metadata
+        metadata: Metadata,
+        #[serde(default)]
+        dependencies: BTreeMap<String, String>,
+        #[serde(default)]
+        permissions: BTreeMap<String, String>,
+        #[serde(default)]
+        settings: SettingsSchema,
+        #[serde(default)]
+        config: toml::Table
 }
 
 #[derive(Deserialize)]
@@ -61,22 +103,139 @@ struct Metadata {
         description: Option<String>,
         version    : String,
         name       : String,
-        objfile    : String
+        objfile    : String,
+        requires_host: Option<String>,
+        api_version: Option<String>,
+        license    : Option<String>,
+        publisher  : Option<String>,
+        product_id : Option<String>,
+        #[serde(default)]
+        authors    : Vec<String>,
+        homepage   : Option<String>,
+        #[serde(default)]
+        tags       : Vec<String>,
+        min_host_version: Option<String>,
+        entry_signature: Option<String>,
+        #[serde(default)]
+        needs      : Vec<String>,
+        /* Any other key, kept for the application to use. */
+        #[serde(flatten)]
+        extra      : toml::Table
 }
-/// A struct that represents metadata about
-/// a single plugin, like its version and name.
-/// 
-/// This struct should only be returned by `PluginMetadata::load()`.
-/// Otherwise, undefined values will be returned, resulting in undefined
-/// behavior.
-#[derive(Debug)]
-#[repr(C)]
-pub struct PluginMetadata {
-        pub description: Option<String>,
-        pub version    : String,
-        pub name       : String,
-        pub filename   : PathBuf,
-        pub objfile    : String
+/// Options that affect how a plugin is loaded, usually provided
+/// by the `PluginManager` loading it.
+#[derive(Clone)]
+pub(crate) struct LoadOptions<'a> {
+        /// The directory the plugin will be extracted in.
+        pub(crate) workspace       : &'a Path,
+        /// Team identifiers allowed to sign the plugin's object file (macOS only).
+        /// An empty list disables signature verification.
+        pub(crate) allowed_team_ids: &'a [String],
+        /// Whether `.vpl` archives are accepted, or only directories and object files.
+        pub(crate) allow_archives  : bool,
+        /// Whether the plugin's directory is removed once it's unloaded.
+        pub(crate) cleanup         : CleanupPolicy,
+        /// Faults injected while loading and using the plugin (`testing` feature only).
+        pub(crate) faults          : Faults,
+        /// The interceptors wrapping the calls into the plugin.
+        pub(crate) interceptors    : Interceptors,
+        /// The observers notified of the lifecycle of the plugin.
+        pub(crate) observers       : Observers,
+        /// Where the plugin reports its diagnostics.
+        pub(crate) diagnostics     : diagnostics::Sink,
+        /// The version of the host's API negotiated with the plugin, if any.
+        pub(crate) host_api        : Option<HostApiVersion>,
+        /// The API tables the host registered, one per version.
+        pub(crate) api_tables      : &'a [ApiTable],
+        /// Symbols the host deprecated, with their replacement.
+        pub(crate) deprecated      : &'a [(String, Option<String>)],
+        /// The version requirements between the host and the plugin.
+        pub(crate) versions        : &'a Constraints,
+        /// The licenses the host accepts the plugin under, if it restricts them.
+        pub(crate) license_policy  : Option<&'a LicensePolicy>,
+        /// The capabilities the plugin may declare, if the host restricts them.
+        pub(crate) permission_policy: Option<&'a PermissionPolicy>,
+        /// Whether the user is entitled to the plugin, if the host checks it.
+        pub(crate) entitlement     : Option<&'a EntitlementCheck>,
+        /// Whether the symbols of the plugin are global, local or in their own namespace.
+        pub(crate) symbols         : SymbolVisibility,
+        /// Whether the library of the plugin is unloaded when it's dropped.
+        pub(crate) unload          : UnloadPolicy,
+        /// Where the libraries kept loaded are recorded.
+        pub(crate) leaks           : Leaks,
+        /// The objects the host hands to the plugin.
+        pub(crate) objects         : Arc<ObjectRegistry>,
+        /// Where the plugin keeps its state across reloads.
+        pub(crate) state_store     : Arc<PluginStateStore>,
+        /// The interfaces the plugins of the manager provide to each other.
+        pub(crate) interfaces      : Arc<InterfaceRegistry>,
+        /// How long unloading the plugin waits for the threads it started.
+        pub(crate) thread_timeout  : Duration,
+        /// Whether directories and object files are copied into the workspace before being
+        /// loaded, so the library isn't shared with other instances loaded from the same path.
+        pub(crate) separate_instance: bool,
+        /// The object file of the plugin if it's loaded from memory instead of being extracted:
+        /// its path inside the package, and its contents.
+        pub(crate) object          : Option<(&'a str, &'a [u8])>,
+        /// Where the progress of loading the plugin is reported, if anywhere.
+        pub(crate) progress        : Option<&'a Reporter<'a>>,
+        /// The keys the package has to be signed with, if any.
+        #[cfg(feature = "signing")]
+        pub(crate) trusted_keys    : &'a TrustedKeys,
+}
+
+/* What plugins loaded from memory show as their package. */
+#[cfg(feature = "archive")]
+pub(crate) const MEMORY_PACKAGE: &str = "<memory>";
+
+/* No version requirements at all. */
+static NO_CONSTRAINTS: Constraints = Constraints { host: None, accepts: Vec::new() };
+/* No signature required. */
+#[cfg(feature = "signing")]
+static NO_KEYS: TrustedKeys = TrustedKeys::new();
+
+/* Fails to compile when used with an `F` that isn't pointer sized, see `Plugin::symbol`. */
+struct PointerSized<F>(std::marker::PhantomData<F>);
+
+impl<F> PointerSized<F> {
+        const CHECK: () = assert!(
+                mem::size_of::<F>() == mem::size_of::<*mut c_void>(),
+                "Symbols can only be returned as pointer sized types"
+        );
+}
+
+impl<'a> LoadOptions<'a> {
+        pub(crate) fn new(workspace: &'a Path) -> Self {
+                Self {
+                        workspace,
+                        allowed_team_ids: &[],
+                        allow_archives  : true,
+                        cleanup         : CleanupPolicy::default(),
+                        faults          : Faults::default(),
+                        interceptors    : Interceptors::default(),
+                        observers       : Observers::default(),
+                        diagnostics     : diagnostics::Sink::default(),
+                        host_api        : None,
+                        api_tables      : &[],
+                        deprecated      : &[],
+                        versions        : &NO_CONSTRAINTS,
+                        license_policy  : None,
+                        permission_policy: None,
+                        entitlement     : None,
+                        symbols         : SymbolVisibility::default(),
+                        unload          : UnloadPolicy::default(),
+                        leaks           : Leaks::default(),
+                        objects         : Arc::default(),
+                        state_store     : Arc::default(),
+                        interfaces      : Arc::default(),
+                        thread_timeout  : threads::DEFAULT_THREAD_TIMEOUT,
+                        separate_instance: false,
+                        object          : None,
+                        progress        : None,
+                        #[cfg(feature = "signing")]
+                        trusted_keys    : &NO_KEYS,
+                }
+        }
 }
 
 /// The plugin type. This is used to identify a single plugin
@@ -88,114 +247,315 @@ pub struct Plugin {
         // Metadata about the plugin, will be None if the plugin
         // has not loaded its metadata yet.
         pub metadata       : PluginMetadata,
+        /* Unique to this load of the plugin, see `instance_id()`. */
+        pub(crate) instance_id: Uuid,
+        /* The hash of the package the plugin was loaded from, if it was loaded from one. */
+        pub(crate) content_hash: Option<String>,
         pub(crate) filename: PathBuf,
-        pub(crate) is_valid: bool,
-        pub(crate) started : bool,
-        pub(crate) raw     : LaterInitialized<Library>,
-
+        /* The directory the plugin was extracted into, unique to this plugin. */
+        pub(crate) workdir : PathBuf,
+        /* Whether `workdir` was created by VPlugin and has to be removed with the plugin. */
+        pub(crate) owns_workdir: bool,
+        pub(crate) cleanup : CleanupPolicy,
+        /* Set when loading, starting or terminating the plugin failed. */
+        pub(crate) failed  : bool,
+        pub(crate) state   : PluginState,
+        /* Shared with the hook guards of the plugin, which keep it loaded. */
+        pub(crate) raw     : LaterInitialized<Arc<dyn PluginBackend>>,
+        /* Prepended to every symbol looked up, only used by builtin plugins. */
+        pub(crate) symbol_prefix: String,
+        /* Number of HookGuards alive, the plugin can't be unloaded while it's not 0. */
+        pub(crate) pins    : Arc<AtomicUsize>,
+        pub(crate) pin_policy: PinPolicy,
+        /* Whether the application can run without the plugin, see `PluginManager::begin_all`. */
+        pub(crate) requirement: Requirement,
+        /* When the plugin was last started by a manager (See `PluginManager::shutdown`), 0 if never. */
+        pub(crate) start_order: u64,
+        /* The configuration the caller last started the plugin with (Nothing merged into it), kept for snapshots. */
+        pub(crate) config  : Option<String>,
+        pub(crate) unload  : UnloadPolicy,
+        /* The threads that were running before the library was loaded. */
+        pub(crate) threads : unload::Threads,
+        /* Set once the library was kept loaded instead of being unloaded. */
+        pub(crate) leaked  : bool,
+        pub(crate) leaks   : Leaks,
+        /* Counts what the plugin allocates through the host. */
+        pub(crate) memory  : Arc<Allocator>,
+        /* The objects the host hands to the plugin, shared by the plugins of a manager. */
+        pub(crate) objects : Arc<ObjectRegistry>,
+        /* The threads the plugin registered, and how long to wait for them when terminating it. */
+        pub(crate) workers : Arc<ThreadRegistry>,
+        /* The panics the plugin reported, see `vplugin_core::panic`. */
+        pub(crate) panics  : Arc<Panics>,
+        /* The namespace of the plugin in the state store of its manager. */
+        pub(crate) store   : Arc<StateTable>,
+        /* The interface registry of its manager, as handed to the plugin. See `interfaces`. */
+        pub(crate) interfaces: Arc<InterfaceTable>,
+        /* Set once a call into the plugin panicked or crashed, see `isolation`. */
+        pub(crate) crashed : AtomicBool,
+        /* The addresses of the symbols looked up so far, by name. See `bind_symbols`. */
+        pub(crate) symbols : RwLock<HashMap<String, usize>>,
+        /* The libraries the plugin bundles, loaded before its object file (Windows only). */
+        pub(crate) bundled : BundledLibraries,
+        /* Reported along with the panic of the entry point of the plugin, if it panicked. */
+        pub(crate) backtrace: Option<String>,
+        pub(crate) thread_timeout: Duration,
+        /* The sink of the manager that loaded the plugin. */
+        pub(crate) diagnostics: diagnostics::Sink,
+        pub(crate) faults  : Faults,
+        /* The interceptors of the manager that loaded the plugin, wrapping the calls into it. */
+        pub(crate) interceptors: Interceptors,
+        /* The observers of the manager that loaded the plugin. */
+        pub(crate) observers: Observers,
+        /* The version of the host's API negotiated when loading the plugin. */
+        pub(crate) api_version: Option<HostApiVersion>,
+        /* Whether the API table handed to the plugin is an adapter for an older version. */
+        pub(crate) api_adapter: bool,
+        /* The compiler that built the plugin, reported by Rust ABI plugins only. */
+        pub(crate) toolchain: Option<String>,
+        /* The hooks declared in the `hooks.toml` of the package, if any. */
+        pub(crate) hook_manifest: HookManifest,
 }
 
-impl PluginMetadata {
-        /// Reads a metadata.toml file or returns an error. This is useful
-        /// for libraries that wish to make use of VPlugin's internals.
-        pub fn read_from_str<T: for<'a> serde::Deserialize<'a>>(string: &str) -> Result<T, VPluginError> {
-                let data: T = match toml::from_str(string) {
-                        Ok (t) => t,
-                        Err(e) => {
-                                log::error!("Couldn't read metadata file: {}", e.to_string());
-                                return Err(VPluginError::ParametersError)
-                        }
-                };
+/* Reads the `metadata.toml` file of a plugin. */
+fn read_metadata(plugin: &Plugin) -> Result<PluginMetadata, VPluginError> {
+        let mut plugin_metadata = PluginMetadata {
+             filename   : plugin.filename.clone(),
+             ..Default::default()
+        };
 
-                Ok(data)
+        let metadata_path = workspace::find(&plugin.workdir, Path::new("metadata.toml"));
+        let f = match File::open(&metadata_path) {
+                Ok(val) => val,
+                Err(e) => {
+                        match e.kind() {
+                                PermissionDenied => return Err(VPluginError::PermissionDenied),
+                                Unsupported      => return Err(VPluginError::InternalError { err: "Unsupported file".into() }),
+                                NotFound         => return Err(VPluginError::NoSuchFile),
+                                Interrupted      => return Err(VPluginError::InvalidPlugin),
+                                UnexpectedEof    => return Err(VPluginError::InvalidPlugin),
+                                OutOfMemory      => return Err(VPluginError::InternalError { err: "Host is out of memory".into() }),
+                                Other            => return Err(VPluginError::InternalError { err: "Unknown error.".into() }),
+                                _                => return Err(VPluginError::from_io(&e))
+                        }
+                }
+        };
 
-        }
-        
-        fn load(plugin: &Plugin) -> Result<Self, VPluginError> {
-                let mut plugin_metadata = Self {
-                     description: None,
-                     version    : String::new(),
-                     name       : String::new(),
-                     filename   : plugin.filename.clone(),
-                     objfile    : String::new(),
-                };
+        let contents = match std::io::read_to_string(f) {
+                Ok(contents) => contents,
+                Err(e)        => {
+                        diagnostics::error!("Error reading metadata string: {}.", e);
+                        return Err(VPluginError::ParametersError);
+                }
+        };
+        let buffer = String::from(contents.as_str());
 
-                let f = match File::open("metadata.toml") {
-                        Ok(val) => val,
-                        Err(e) => {
-                                match e.kind() {
-                                        PermissionDenied => return Err(VPluginError::PermissionDenied),
-                                        Unsupported      => return Err(VPluginError::InternalError { err: "Unsupported file".into() }),
-                                        NotFound         => return Err(VPluginError::NoSuchFile),
-                                        Interrupted      => return Err(VPluginError::InvalidPlugin),
-                                        UnexpectedEof    => return Err(VPluginError::InvalidPlugin),
-                                        OutOfMemory      => return Err(VPluginError::InternalError { err: "Host is out of memory".into() }),
-                                        Other            => return Err(VPluginError::InternalError { err: "Unknown error.".into() }),
-                                        _ => panic!()
-                                }
-                        }
-                };
+        let data_raw: Data = match toml::from_str(&buffer) {
+                Ok(ok) => ok,
+                Err(_) => {
+                        return Err(VPluginError::ParametersError)
+                }
+        };
 
-                let contents = match std::io::read_to_string(f) {
-                        Ok(contents) => contents,
-                        Err(e)        => {
-                                log::error!("Error reading metadata string: {}.", e.to_string());
-                                return Err(VPluginError::ParametersError);
-                        }
-                };
-                let buffer = String::from(contents.as_str());
+        if data_raw.metadata.name.is_empty()
+        || data_raw.metadata.name.contains(' ') {
+                /* Without a name, it's impossible to identify the plugin for future errors. */
+                diagnostics::error!(
+                        "Plugin {} has an empty name in its metadata or its name contains an invalid character.",
+                        plugin.filename.display()
+                );
+                return Err(VPluginError::InvalidPlugin);
+        }
 
-                let data_raw: Data = match toml::from_str(&buffer) {
-                        Ok(ok) => ok,
-                        Err(_) => {
-                                return Err(VPluginError::ParametersError)
-                        }
-                };
+        if data_raw.metadata.version.is_empty()
+        || data_raw.metadata.version.contains(' ') {
+                diagnostics::error!(
+                        "
+                        Detected either empty or invalid version string in metadata.toml (Plugin
+                        '{}'
+                        ", data_raw.metadata.name
+                );
+        }
 
-                if data_raw.metadata.name.is_empty()
-                || data_raw.metadata.name.contains(' ') {
-                        /*
-                         * Here we panic as without a name, it's impossible to identify the plugin
-                         * for future errors.
-                         */
-                        panic!(
-                                "
-                                Attempted to use a plugin that has an empty name in its metadata or contains an
-                                invalid character in the field.
-                                "
-                        )
+        if let Some(api_version) = &data_raw.metadata.api_version {
+                if api_version.parse::<HostApiVersion>().is_err() {
+                        diagnostics::error!(
+                                "Plugin '{}' declares an invalid API version ('{}'), expected `major.minor`.",
+                                data_raw.metadata.name,
+                                api_version
+                        );
+                        return Err(VPluginError::InvalidPlugin);
                 }
+        }
 
-                if data_raw.metadata.version.is_empty()
-                || data_raw.metadata.version.contains(' ') {
-                        log::error!(
-                                "
-                                Detected either empty or invalid version string in metadata.toml (Plugin
-                                '{}'
-                                ", data_raw.metadata.name
+        /* They're looked for inside the package, which they can't leave. */
+        if let Some(library) = data_raw.metadata.needs.iter().find(|library| Path::new(library).components().any(|c| c == Component::ParentDir)) {
+                diagnostics::error!(
+                        "Plugin '{}' needs a library outside of its package ('{}').",
+                        data_raw.metadata.name,
+                        library
+                );
+                return Err(VPluginError::InvalidPlugin);
+        }
+
+        for (dependency, requirement) in &data_raw.dependencies {
+                if version::parse_req(requirement).is_err() {
+                        diagnostics::error!(
+                                "Plugin '{}' depends on '{}' with an invalid version requirement ('{}').",
+                                data_raw.metadata.name,
+                                dependency,
+                                requirement
                         );
+                        return Err(VPluginError::InvalidPlugin);
                 }
+        }
 
-                plugin_metadata.filename = "metadata.toml".to_owned().into();
-                plugin_metadata.version  = data_raw.metadata.version;
-                plugin_metadata.name     = data_raw.metadata.name;
-                plugin_metadata.objfile  = data_raw.metadata.objfile;
+        if let Err(e) = data_raw.settings.validate() {
+                diagnostics::error!("Plugin '{}' declares invalid settings: {}", data_raw.metadata.name, e);
+                return Err(VPluginError::InvalidPlugin);
+        }
+
+        plugin_metadata.filename = metadata_path;
+        plugin_metadata.description = data_raw.metadata.description;
+        plugin_metadata.version  = data_raw.metadata.version;
+        plugin_metadata.name     = data_raw.metadata.name;
+        plugin_metadata.objfile  = data_raw.metadata.objfile;
+        /* `min_host_version` is a shorthand for a `>=` requirement. */
+        plugin_metadata.requires_host = match (data_raw.metadata.requires_host, data_raw.metadata.min_host_version) {
+                (Some(requirement), Some(minimum)) => Some(format!(">={}, {}", minimum, requirement)),
+                (None, Some(minimum))              => Some(format!(">={}", minimum)),
+                (requirement, None)                => requirement
+        };
+        plugin_metadata.api_version = data_raw.metadata.api_version;
+        plugin_metadata.license  = data_raw.metadata.license;
+        plugin_metadata.publisher = data_raw.metadata.publisher;
+        plugin_metadata.product_id = data_raw.metadata.product_id;
+        plugin_metadata.authors  = data_raw.metadata.authors;
+        plugin_metadata.homepage = data_raw.metadata.homepage;
+        plugin_metadata.tags     = data_raw.metadata.tags;
+        plugin_metadata.entry_signature = data_raw.metadata.entry_signature;
+        plugin_metadata.needs    = data_raw.metadata.needs;
+        plugin_metadata.dependencies = data_raw.dependencies;
+        plugin_metadata.permissions = data_raw.permissions;
+        plugin_metadata.settings = data_raw.settings;
+        plugin_metadata.config = data_raw.config;
+        plugin_metadata.extra    = data_raw.metadata.extra;
+        /* The keys of `[metadata.extra]` join the others, those of `[metadata]` take precedence. */
+        match plugin_metadata.extra.remove("extra") {
+                Some(toml::Value::Table(table)) => for (key, value) in table {
+                        plugin_metadata.extra.entry(key).or_insert(value);
+                },
+                Some(other) => { plugin_metadata.extra.insert("extra".into(), other); },
+                None        => ()
+        }
 
-                Ok(plugin_metadata)
+        Ok(plugin_metadata)
+}
+
+/* Reads the `hooks.toml` file of the plugin extracted in `workdir`, if it has one. */
+fn read_hook_manifest(workdir: &Path) -> Result<HookManifest, VPluginError> {
+        let path = workspace::find(workdir, Path::new(HOOK_MANIFEST_FILE));
+        let contents = match std::fs::read_to_string(&path) {
+                Ok (contents)                  => contents,
+                Err(e) if e.kind() == NotFound => return Ok(HookManifest::default()),
+                Err(e)                         => {
+                        diagnostics::error!("Couldn't read {}: {}", path.display(), e);
+                        return Err(VPluginError::from_io(&e));
+                }
+        };
+        match HookManifest::parse(&contents) {
+                Ok (manifest) => Ok(manifest),
+                Err(e)        => {
+                        diagnostics::error!("{}: {}", HOOK_MANIFEST_FILE, e);
+                        Err(e)
+                }
         }
 }
 
 impl Plugin {
-        fn load_archive(filename: PathBuf) -> Result<Self, VPluginError> {
-                log::trace!("Loading plugin: {}.", &filename.display());
+        /* A plugin that hasn't loaded anything yet. */
+        fn unloaded(filename: PathBuf, workdir: PathBuf, owns_workdir: bool, options: &LoadOptions) -> Self {
+                let mut plugin = Self::blank(PluginMetadata {
+                        version : "0.0.0".into(),
+                        name    : "NULL".into(),
+                        filename: "-".into(),
+                        objfile : "-".into(),
+                        ..Default::default()
+                });
+                plugin.filename       = filename;
+                plugin.workdir        = workdir;
+                plugin.owns_workdir   = owns_workdir;
+                plugin.cleanup        = options.cleanup;
+                plugin.unload         = options.unload;
+                plugin.leaks          = options.leaks.clone();
+                plugin.objects        = options.objects.clone();
+                plugin.thread_timeout = options.thread_timeout;
+                plugin.diagnostics    = options.diagnostics.clone();
+                plugin.faults         = options.faults.clone();
+                plugin.interceptors   = options.interceptors.clone();
+                plugin.observers      = options.observers.clone();
+                plugin
+        }
+
+        /*
+         * A plugin described by `metadata` that hasn't loaded anything, with the default of
+         * every other field. Every constructor starts from it.
+         */
+        fn blank(metadata: PluginMetadata) -> Self {
+                Self {
+                        metadata,
+                        raw     : initialize_later!(),
+                        filename: PathBuf::new(),
+                        workdir : PathBuf::new(),
+                        owns_workdir: false,
+                        cleanup : CleanupPolicy::default(),
+                        failed  : false,
+                        state   : PluginState::Invalid,
+                        symbol_prefix: String::new(),
+                        pins    : Arc::default(),
+                        pin_policy: PinPolicy::default(),
+                        requirement: Requirement::default(),
+                        start_order: 0,
+                        instance_id: Uuid::new_v4(),
+                        content_hash: None,
+                        config  : None,
+                        unload  : UnloadPolicy::default(),
+                        threads : None,
+                        leaked  : false,
+                        leaks   : Leaks::default(),
+                        memory  : Arc::default(),
+                        objects : Arc::default(),
+                        workers : Arc::default(),
+                        panics  : Arc::default(),
+                        store   : Arc::default(),
+                        interfaces: Arc::default(),
+                        bundled : BundledLibraries::default(),
+                        crashed : AtomicBool::new(false),
+                        symbols : RwLock::default(),
+                        backtrace: None,
+                        thread_timeout: threads::DEFAULT_THREAD_TIMEOUT,
+                        diagnostics: diagnostics::Sink::default(),
+                        faults  : Faults::default(),
+                        interceptors: Interceptors::default(),
+                        observers: Observers::default(),
+                        api_version: None,
+                        api_adapter: false,
+                        toolchain: None,
+                        hook_manifest: HookManifest::default(),
+                }
+        }
+
+        #[cfg(feature = "archive")]
+        fn load_archive(filename: PathBuf, options: &LoadOptions) -> Result<Self, VPluginError> {
+                diagnostics::trace!("Loading plugin: {}.", &filename.display());
                 let fname = std::path::Path::new(&filename);
-                let file = match fs::File::open(fname) {
+                let file = match File::open(fname) {
                         Ok(val) => val,
                         Err(e) => {
-                                log::error!(
+                                diagnostics::error!(
                                         "Couldn't load {}: {} (error {})",
                                         filename.display(),
-                                        e.to_string(),
+                                        e,
                                         e.raw_os_error().unwrap_or(0)
                                 );
                                 match e.kind() {
@@ -206,102 +566,595 @@ impl Plugin {
                                         UnexpectedEof    => return Err(VPluginError::InvalidPlugin),
                                         OutOfMemory      => return Err(VPluginError::InternalError { err: "Host is out of memory".into() }),
                                         Other            => return Err(VPluginError::InternalError { err: "Unknown error.".into() }),
-                                        _ => panic!()
+                                        _                => return Err(VPluginError::from_io(&e))
                                 }
                         }
                 };
                 
-                match std::fs::create_dir(env::temp_dir().join("vplugin")) {
-                        Err(e) => match e.kind() {
-                                ErrorKind::AlreadyExists => (),
-                                _ => log::info!("Couldn't create VPlugin directory: {}", e.to_string()),
+                /* Directories left behind by previously unloaded plugins (Windows only). */
+                workspace::flush_pending();
+                let workdir = workspace::unique_dir(options.workspace)?;
+
+                /* Uncompressing the archive. */
+                diagnostics::trace!("Uncompressing plugin {} into {}", filename.display(), workdir.display());
+                let extracted = options.faults
+                        .check(Stage::Extract)
+                        .and_then(|_| Self::extract(file, &workdir, options));
+                if let Err(e) = extracted {
+                        if options.cleanup.removes(true) {
+                                workspace::remove_dir(&workdir);
                         }
-                        Ok(_) => env::set_current_dir(env::temp_dir().join("vplugin")).unwrap()
+                        return Err(e);
                 }
 
-                /* Uncompressing the archive. */
-                log::trace!("Uncompressing plugin {}", filename.display());
-                let archive = match zip::ZipArchive::new(file) {
-                        Ok (v) => v,
-                        Err(e) => {
-                                log::error!("Archive error: {}. Not extracting plugin.", e.to_string());
-                                return Err(VPluginError::InvalidPlugin)
+                let mut plugin = Self::unloaded(filename, workdir, true, options);
+                plugin.load_library(options)?;
+                Ok(plugin)
+        }
+
+        /*
+         * A `.vpl` archive held in memory. Its object file stays in memory if the platform can
+         * load it from there, the other files are extracted into a directory private to the user.
+         */
+        #[cfg(feature = "archive")]
+        fn load_memory(data: &[u8], options: &LoadOptions) -> Result<Self, VPluginError> {
+                diagnostics::trace!("Loading plugin from memory ({} bytes).", data.len());
+                #[cfg(feature = "signing")]
+                signing::verify_bytes(data, options.trusted_keys)?;
+
+                workspace::flush_pending();
+                let workdir = workspace::private_dir(options.workspace)?;
+                let object  = Self::memory_object(data);
+
+                diagnostics::trace!("Uncompressing plugin into {}", workdir.display());
+                let extracted = options.faults.check(Stage::Extract).and_then(|_| match &object {
+                        Some((objfile, _)) => crate::archive::extract_except(Cursor::new(data), &workdir, objfile, options.progress),
+                        None               => crate::archive::extract(Cursor::new(data), &workdir, options.progress)
+                });
+                if let Err(e) = extracted {
+                        if options.cleanup.removes(true) {
+                                workspace::remove_dir(&workdir);
                         }
+                        return Err(e);
+                }
+
+                let options = LoadOptions {
+                        object: object.as_ref().map(|(objfile, contents)| (objfile.as_str(), contents.as_slice())),
+                        ..options.clone()
                 };
-                Self::extract_archive_files(archive);
-
-                let mut plugin = Self {
-                        metadata: PluginMetadata {
-                                description: None,
-                                version: "0.0.0".into(),
-                                name: "NULL".into(),
-                                filename: "-".into(),
-                                objfile: "-".into()
+                let mut plugin = Self::unloaded(PathBuf::from(MEMORY_PACKAGE), workdir, true, &options);
+                plugin.load_library(&options)?;
+                Ok(plugin)
+        }
+
+        /*
+         * Reads the object file out of the archive `data` (Its path in the archive and its contents),
+         * if it can be loaded from memory on this platform.
+         */
+        #[cfg(all(feature = "archive", target_os = "linux"))]
+        fn memory_object(data: &[u8]) -> Option<(String, Vec<u8>)> {
+                let (_, metadata) = crate::archive::read_entry(Cursor::new(data), "metadata.toml")?;
+                let metadata: toml::Table = toml::from_str(std::str::from_utf8(&metadata).ok()?).ok()?;
+                let objfile = metadata.get("metadata")?.get("objfile")?.as_str()?;
+                Self::objfile_candidates(objfile)
+                        .iter()
+                        .find_map(|candidate| crate::archive::read_entry(Cursor::new(data), candidate))
+        }
+
+        #[cfg(all(feature = "archive", not(target_os = "linux")))]
+        fn memory_object(_data: &[u8]) -> Option<(String, Vec<u8>)> {
+                None
+        }
+
+        /* Extracts the archive, checking its signature first if the host requires one. */
+        #[cfg(all(feature = "archive", feature = "signing"))]
+        fn extract(file: File, workdir: &Path, options: &LoadOptions) -> Result<(), VPluginError> {
+                signing::extract(file, workdir, options.trusted_keys, options.progress)
+        }
+
+        #[cfg(all(feature = "archive", not(feature = "signing")))]
+        fn extract(file: File, workdir: &Path, options: &LoadOptions) -> Result<(), VPluginError> {
+                crate::archive::extract(file, workdir, options.progress)
+        }
+
+        #[cfg(not(feature = "archive"))]
+        fn load_archive(filename: PathBuf, _options: &LoadOptions) -> Result<Self, VPluginError> {
+                diagnostics::error!(
+                        "Can't load {}: VPlugin was built without the `archive` feature, only \
+                        directories and object files can be loaded.",
+                        filename.display()
+                );
+                Err(VPluginError::InvalidPlugin)
+        }
+
+        /*
+         * Reads the metadata of the plugin extracted in `workdir`, resolving its object
+         * file, without loading anything.
+         */
+        pub(crate) fn read_package(workdir: &Path) -> Result<PluginMetadata, VPluginError> {
+                let plugin = Self::unloaded(workdir.to_path_buf(), workdir.to_path_buf(), false, &LoadOptions::new(workdir));
+                let mut metadata = read_metadata(&plugin)?;
+                metadata.objfile = Self::resolve_objfile(workdir, &metadata.objfile);
+                Ok(metadata)
+        }
+
+        /*
+         * A plugin that was already extracted, loaded in place unless it's a separate instance
+         * or the system locks loaded libraries (See `platform`).
+         */
+        fn load_directory(directory: PathBuf, options: &LoadOptions) -> Result<Self, VPluginError> {
+                diagnostics::trace!("Loading plugin directory: {}.", directory.display());
+                let mut plugin = match options.separate_instance || platform::LOCKS_LOADED_LIBRARIES {
+                        true  => {
+                                let workdir = Self::instance_dir(&directory, options)?;
+                                Self::unloaded(directory, workdir, true, options)
                         },
-                        raw     : initialize_later!(),
-                        filename: filename.into(),
-                        is_valid: false,
-                        started : false,
+                        false => Self::unloaded(directory.clone(), directory, false, options)
                 };
+                /* The files actually loaded, in case they were copied. */
+                #[cfg(feature = "signing")]
+                if let Err(e) = signing::verify_directory(&plugin.workdir, options.trusted_keys) {
+                        plugin.failed = true;
+                        return Err(e);
+                }
+                plugin.load_library(options)?;
+                Ok(plugin)
+        }
 
-                #[allow(deprecated)]
-                if let Err(e) = plugin.load_metadata() {
+        /*
+         * Copies the package at `package` into a new directory of the workspace, so its library
+         * is loaded again instead of being shared with the other instances of the plugin.
+         */
+        fn instance_dir(package: &Path, options: &LoadOptions) -> Result<PathBuf, VPluginError> {
+                workspace::flush_pending();
+                let workdir = workspace::unique_dir(options.workspace)?;
+                diagnostics::trace!("Copying {} into {} for a separate instance.", package.display(), workdir.display());
+                if let Err(e) = workspace::copy_package(package, &workdir, options.progress) {
+                        workspace::remove_dir(&workdir);
                         return Err(e);
                 }
+                Ok(workdir)
+        }
+
+        /*
+         * A bare object file without any metadata. The name of the plugin is the
+         * name of the file, and its version is always 0.0.0.
+         */
+        fn load_object(filename: PathBuf, options: &LoadOptions) -> Result<Self, VPluginError> {
+                diagnostics::trace!("Loading object file: {}.", filename.display());
+                #[cfg(feature = "signing")]
+                signing::verify_object(&filename, options.trusted_keys)?;
+                let mut plugin = match options.separate_instance || platform::LOCKS_LOADED_LIBRARIES {
+                        true  => {
+                                let workdir = Self::instance_dir(&filename, options)?;
+                                Self::unloaded(filename.clone(), workdir, true, options)
+                        },
+                        false => {
+                                let directory = filename.parent().map(Path::to_path_buf).unwrap_or_default();
+                                Self::unloaded(filename.clone(), directory, false, options)
+                        }
+                };
+                plugin.metadata = Self::object_metadata(filename);
+
+                let _scope   = options.diagnostics.scope(Some(&plugin.metadata.name));
+                let metadata = plugin.metadata.clone();
+                Self::check_requirements(&metadata, options)?;
+                plugin.raw   = init_now!(plugin.open_library(&metadata, options)?);
+                plugin.state = PluginState::Loaded;
                 Ok(plugin)
         }
 
-        fn extract_archive_files(mut archive: ZipArchive<File>) {
-                for i in 0..archive.len() {
-                        let mut file = archive.by_index(i).unwrap();
-                        let outpath = match file.enclosed_name() {
-                            Some(path) => path.to_owned(),
-                            None => continue,
-                        };
+        /*
+         * Checks the version requirements between the host and the plugin (Including the version
+         * of the host's API it was built against), the license of
+         * the plugin, the capabilities it declares and whether the user is entitled to it,
+         * before loading it.
+         */
+        fn check_requirements(metadata: &PluginMetadata, options: &LoadOptions) -> Result<(), VPluginError> {
+                if let Err(e) = options.versions.resolve(metadata) {
+                        diagnostics::error!("Refusing plugin: {}.", e);
+                        return Err(e);
+                }
+                if let Some(host) = options.host_api {
+                        api::check_declared(metadata, host)?;
+                }
+                if let Some(policy) = options.license_policy {
+                        policy.check(metadata)?;
+                }
+                if let Some(policy) = options.permission_policy {
+                        policy.check(metadata)?;
+                }
+                match options.entitlement {
+                        Some(entitlement) => entitlement.check(metadata),
+                        None              => Ok(())
+                }
+        }
 
-                        if (*file.name()).ends_with('/') {
-                                fs::create_dir_all(&outpath).unwrap();
-                        } else {
-                                if let Some(p) = outpath.parent() {
-                                        if !p.exists() {
-                                            fs::create_dir_all(p).unwrap();
-                                        }
-                                }
-                                
-                                let mut outfile = fs::File::create(&outpath).unwrap();
-                                std::io::copy(&mut file, &mut outfile).unwrap();
+        /* The metadata of a bare object file, made up from its name. */
+        fn object_metadata(filename: PathBuf) -> PluginMetadata {
+                let objfile = filename.file_name().map(|f| f.to_string_lossy().into_owned()).unwrap_or_default();
+                let name    = filename.file_stem().map(|f| f.to_string_lossy().into_owned()).unwrap_or_default();
+                PluginMetadata {
+                        version    : "0.0.0".into(),
+                        name       : name.strip_prefix(std::env::consts::DLL_PREFIX).unwrap_or(&name).to_owned(),
+                        filename,
+                        objfile,
+                        ..Default::default()
+                }
+        }
+
+        /*
+         * Reads the metadata of the plugin at `filename` without loading it. Archives are
+         * extracted inside `workspace`, and removed right after.
+         */
+        pub(crate) fn inspect(filename: &Path, workspace: &Path) -> Result<PluginMetadata, VPluginError> {
+                Self::inspect_with(filename, workspace, |metadata, _| Ok(metadata))
+        }
+
+        /*
+         * Like `inspect`, but also hands `f` the directory the package is in, while it's still
+         * there (Archives are only extracted during the call).
+         */
+        pub(crate) fn inspect_with<T, F>(filename: &Path, workspace: &Path, f: F) -> Result<T, VPluginError>
+        where
+                F: FnOnce(PluginMetadata, &Path) -> Result<T, VPluginError>
+        {
+                if filename.is_dir() {
+                        return f(Self::read_package(filename)?, filename);
+                }
+                if Self::is_object_file(filename) {
+                        if !filename.is_file() {
+                                diagnostics::error!("Couldn't find {}.", filename.display());
+                                return Err(VPluginError::NoSuchFile);
+                        }
+                        let directory = filename.parent().unwrap_or(Path::new(""));
+                        return f(Self::object_metadata(filename.to_path_buf()), directory);
+                }
+                Self::inspect_archive(filename, workspace, f)
+        }
+
+        #[cfg(feature = "archive")]
+        fn inspect_archive<T, F>(filename: &Path, workspace: &Path, f: F) -> Result<T, VPluginError>
+        where
+                F: FnOnce(PluginMetadata, &Path) -> Result<T, VPluginError>
+        {
+                let file = match File::open(filename) {
+                        Ok (file) => file,
+                        Err(e)    => {
+                                diagnostics::error!("Couldn't open {}: {}", filename.display(), e);
+                                return Err(VPluginError::from_io(&e));
                         }
+                };
+                let workdir = workspace::unique_dir(workspace)?;
+                let result = crate::archive::extract(file, &workdir, None)
+                        .and_then(|_| Self::read_package(&workdir))
+                        .and_then(|metadata| f(PluginMetadata { filename: filename.to_path_buf(), ..metadata }, &workdir));
+                workspace::remove_dir(&workdir);
+                result
+        }
+
+        #[cfg(not(feature = "archive"))]
+        fn inspect_archive<T, F>(filename: &Path, _workspace: &Path, _f: F) -> Result<T, VPluginError>
+        where
+                F: FnOnce(PluginMetadata, &Path) -> Result<T, VPluginError>
+        {
+                diagnostics::error!(
+                        "Can't read {}: VPlugin was built without the `archive` feature.",
+                        filename.display()
+                );
+                Err(VPluginError::InvalidPlugin)
+        }
+
+        /* Whether `filename` looks like a shared library rather than an archive. */
+        pub(crate) fn is_object_file(filename: &Path) -> bool {
+                match filename.extension().and_then(|e| e.to_str()) {
+                        Some(extension) => ["so", "dll", "dylib"].contains(&extension),
+                        None            => false
                 }
         }
 
         /// Loads a plugin into memory and returns it.
         /// After 0.2.0, metadata is also loaded in this call so avoid calling it
         /// again (For your convenience, it has been marked as deprecated).
-        pub fn load(filename: PathBuf) -> Result<Plugin, VPluginError> {
-                let mut plugin = match Self::load_archive(filename) {
-                        Err(e) => {
-                                log::error!("Couldn't load archive, stopping here.");
-                                return Err(e);
+        /// 
+        /// `filename` can be anything that can be used as a path (`&str`, `String`,
+        /// `&Path`, `PathBuf`, ...), including paths that aren't valid UTF-8.
+        pub fn load(filename: impl AsRef<Path>) -> Result<Plugin, VPluginError> {
+                Self::load_in(filename.as_ref().to_path_buf(), &LoadOptions::new(&workspace::root()?))
+        }
+
+        /// Loads a plugin like [`load`](crate::plugin::Plugin::load), calling `progress` as it
+        /// goes so the application can show how far along it is, or cancel loading by returning
+        /// [`ControlFlow::Break`]. See [`progress`](crate::progress).
+        ///
+        /// ## Example
+        /// ```rust,no_run
+        /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+        /// use std::ops::ControlFlow;
+        ///
+        /// let plugin = vplugin::Plugin::load_with_progress("assets-pack.vpl", |progress| {
+        ///     println!("{:?}: {} of {:?} files", progress.phase, progress.files, progress.total_files);
+        ///     ControlFlow::Continue(())
+        /// })?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn load_with_progress(
+                filename: impl AsRef<Path>,
+                progress: impl FnMut(&LoadProgress) -> ControlFlow<()>
+        ) -> Result<Plugin, VPluginError> {
+                let workspace = workspace::root()?;
+                progress::with_reporter(progress, |reporter| {
+                        let options = LoadOptions { progress: Some(reporter), ..LoadOptions::new(&workspace) };
+                        Self::load_in(filename.as_ref().to_path_buf(), &options)
+                })
+        }
+
+        /// Loads a plugin from a `.vpl` archive held in memory, like one embedded in the
+        /// application with `include_bytes!` or just downloaded, without writing it to a file
+        /// first.
+        ///
+        /// On Linux, the object file of the plugin is loaded straight from memory and never
+        /// written to the disk. The other files of the package (Its metadata, translations,
+        /// bundled libraries, ...) and, on other systems, the object file too, are still
+        /// extracted, but into a directory only the user running the application can read.
+        ///
+        /// ## Example
+        /// Not compiled as a test, as it embeds a package from the sources of the application:
+        /// ```rust,ignore
+        /// static SPELLCHECK: &[u8] = include_bytes!("../plugins/spellcheck.vpl");
+        /// let plugin = vplugin::Plugin::load_from_bytes(SPELLCHECK)?;
+        /// ```
+        #[cfg(feature = "archive")]
+        pub fn load_from_bytes(data: &[u8]) -> Result<Plugin, VPluginError> {
+                Self::load_bytes_in(data, &LoadOptions::new(&workspace::root()?))
+        }
+
+        /// Loads a plugin that was statically linked into the application and registered
+        /// with [`register_builtin`](crate::builtin::register_builtin). No archive is
+        /// extracted and no library is loaded, the plugin's symbols are looked up in the
+        /// application itself.
+        /// 
+        /// This is the only way to use plugins on platforms that forbid loading code at
+        /// runtime, like iOS.
+        pub fn load_builtin(name: &str) -> Result<Plugin, VPluginError> {
+                let builtin = match builtin::find(name) {
+                        Some(b) => b,
+                        None    => {
+                                diagnostics::error!("No builtin plugin named '{}' has been registered.", name);
+                                return Err(VPluginError::InvalidPlugin);
                         }
-                        Ok (p) => p
                 };
-                
-                /* Until I rewrite the function a little, we shouldn't care about the warning. */
-                #[allow(deprecated)]
-                match plugin.load_metadata() {
+
+                let library = match backend::this_program() {
+                        Ok (l) => l,
                         Err(e) => {
-                                log::error!("Couldn't load metadata, stopping here.");
-                                return Err(e);
+                                diagnostics::error!("Couldn't access the symbols of the application: {}", e);
+                                return Err(VPluginError::FailedToInitialize);
+                        }
+                };
+
+                let mut plugin = Self::blank(PluginMetadata {
+                        description: builtin.description,
+                        version    : builtin.version,
+                        name       : builtin.name,
+                        ..Default::default()
+                });
+                plugin.raw           = init_now!(Arc::from(library));
+                plugin.state         = PluginState::Loaded;
+                plugin.symbol_prefix = builtin.symbol_prefix;
+                Ok(plugin)
+        }
+
+        /// Creates a loaded plugin described by `metadata`, whose symbols are looked up in
+        /// `backend` instead of a shared library. Nothing is extracted or loaded from the
+        /// disk. This is mostly useful for tests, see [`PluginBackend`](crate::backend::PluginBackend).
+        pub fn with_backend(metadata: PluginMetadata, backend: impl PluginBackend + 'static) -> Plugin {
+                let mut plugin = Self::blank(metadata);
+                plugin.raw   = init_now!(Arc::new(backend));
+                plugin.state = PluginState::Loaded;
+                plugin
+        }
+
+        /* Reports every symbol out of `deprecated` that the plugin exports. */
+        fn report_deprecated(&self, deprecated: &[(String, Option<String>)]) {
+                for (symbol, replacement) in deprecated {
+                        if unsafe { self.symbol::<*mut c_void>(symbol) }.is_err() {
+                                continue;
                         }
-                        Ok(_) => {
-                                fs::create_dir_all(
-                                        env::temp_dir()
-                                        .join("vplugin")
-                                        .join(&plugin.metadata.name)
-                                ).expect("Cannot create plugin directory!");
+                        let deprecation = Deprecation { symbol, replacement: replacement.as_deref() };
+                        match replacement {
+                                Some(replacement) => diagnostics::deprecated(
+                                        deprecation,
+                                        format_args!("Plugin exports '{}', which is deprecated. Use '{}' instead.", symbol, replacement)
+                                ),
+                                None => diagnostics::deprecated(
+                                        deprecation,
+                                        format_args!("Plugin exports '{}', which is deprecated.", symbol)
+                                )
                         }
                 }
-                Ok(plugin)
+        }
+
+        /* Reports the diagnostics of the current thread to this plugin's sink, attributed to it. */
+        fn scope(&self) -> diagnostics::Scope {
+                self.diagnostics.scope(Some(&self.metadata.name))
+        }
+
+        /* Returns the nul-terminated name a symbol is exported as. */
+        pub(crate) fn symbol_name(&self, name: &str) -> Vec<u8> {
+                format!("{}{}\0", self.symbol_prefix, name).into_bytes()
+        }
+
+        /*
+         * Looks up the symbol `name` in the plugin's backend and returns it as an `F`,
+         * which has to be pointer sized (A function pointer, usually), or the code asking
+         * for it doesn't compile. Symbols found are cached until the library is unloaded.
+         */
+        pub(crate) unsafe fn symbol<F: Copy>(&self, name: &str) -> Result<F, String> {
+                let () = PointerSized::<F>::CHECK;
+                let backend = match &self.raw {
+                        Some(backend) => backend,
+                        None          => return Err("The plugin is not loaded".into())
+                };
+                let cached = self.symbols.read().unwrap_or_else(|e| e.into_inner()).get(name).copied();
+                let address = match cached {
+                        Some(address) => address as *mut c_void,
+                        None          => {
+                                let address = backend.symbol(&self.symbol_name(name))?;
+                                self.symbols
+                                        .write()
+                                        .unwrap_or_else(|e| e.into_inner())
+                                        .insert(name.to_owned(), address as usize);
+                                address
+                        }
+                };
+                Ok(mem::transmute_copy::<*mut c_void, F>(&address))
+        }
+
+        /// Looks up the symbols `names` of the plugin right away, so later lookups of them
+        /// (By [`get_custom_hook`](crate::plugin::Plugin::get_custom_hook),
+        /// [`call_hook`](crate::plugin::Plugin::call_hook), ...) are served from a cache
+        /// instead of asking the dynamic loader again. Every symbol is cached the first time
+        /// it's found anyway, binding them beforehand keeps the lookups out of hot paths.
+        /// 
+        /// The plugin only has to be loaded. If any of the symbols is missing, the others
+        /// are still bound and `VPluginError::MissingSymbol` is returned.
+        /// 
+        /// ## Example
+        /// ```rust,no_run
+        /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let mut plugin = vplugin::Plugin::load("plugin.vpl")?;
+        /// # let dt = 0.016;
+        /// plugin.bind_symbols(&["on_frame", "on_input"])?;
+        /// loop {
+        ///     let on_frame = plugin.get_custom_hook::<f32, i32>("on_frame")?;
+        ///     unsafe { on_frame(dt) };
+        /// }
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn bind_symbols(&self, names: &[&str]) -> Result<(), VPluginError> {
+                let _scope = self.scope();
+                if self.raw.is_none() {
+                        diagnostics::error!("Plugin '{}' isn't loaded, can't bind its symbols.", self.metadata.name);
+                        return Err(VPluginError::InvalidPlugin);
+                }
+                let missing: Vec<&str> = names
+                        .iter()
+                        .copied()
+                        .filter(|name| unsafe { self.symbol::<*mut c_void>(name) }.is_err())
+                        .collect();
+                if missing.is_empty() {
+                        return Ok(());
+                }
+                diagnostics::error!(
+                        "Plugin '{}' doesn't export: {}.",
+                        self.metadata.name,
+                        missing.join(", ")
+                );
+                Err(VPluginError::MissingSymbol)
+        }
+
+        /// Loads a plugin, extracting it into a new directory inside the workspace
+        /// given by `options`.
+        pub(crate) fn load_in(filename: PathBuf, options: &LoadOptions) -> Result<Plugin, VPluginError> {
+                /* Builtin plugins are the only option on iOS, they don't need a dynamic loader. */
+                let _scope = options.diagnostics.scope(None);
+                #[cfg(not(target_os = "ios"))]
+                crate::probe::check_dynamic_loading()?;
+
+                let filename = platform::absolute(&filename);
+                let package  = filename.clone();
+                let loaded = if filename.is_dir() {
+                        Self::load_directory(filename, options)
+                } else if Self::is_object_file(&filename) {
+                        Self::load_object(filename, options)
+                } else if options.allow_archives {
+                        Self::load_archive(filename, options)
+                } else {
+                        diagnostics::error!(
+                                "Can't load {}: Loading archives is disabled, only directories and object files are allowed.",
+                                filename.display()
+                        );
+                        Err(VPluginError::InvalidPlugin)
+                };
+                Self::finish_loading(loaded, options, || identity::content_hash(&package))
+        }
+
+        /// Loads a plugin from the `.vpl` archive `data`, held in memory.
+        /// See [`load_from_bytes`](crate::plugin::Plugin::load_from_bytes).
+        #[cfg(feature = "archive")]
+        pub(crate) fn load_bytes_in(data: &[u8], options: &LoadOptions) -> Result<Plugin, VPluginError> {
+                let _scope = options.diagnostics.scope(None);
+                #[cfg(not(target_os = "ios"))]
+                crate::probe::check_dynamic_loading()?;
+
+                let loaded = match options.allow_archives {
+                        true  => Self::load_memory(data, options),
+                        false => {
+                                diagnostics::error!("Can't load a plugin from memory: Loading archives is disabled.");
+                                Err(VPluginError::InvalidPlugin)
+                        }
+                };
+                Self::finish_loading(loaded, options, || Ok(identity::bytes_hash(data)))
+        }
+
+        /* Checks the plugin once its library is loaded, and attaches what the host provides to it. */
+        fn finish_loading<H>(loaded: Result<Plugin, VPluginError>, options: &LoadOptions, hash: H) -> Result<Plugin, VPluginError>
+        where
+                H: FnOnce() -> std::io::Result<String>
+        {
+                let loaded = loaded.and_then(|mut plugin| {
+                        let _scope = plugin.scope();
+                        match sdk::check(&plugin).and_then(|_| sdk::check_toolchain(&plugin)) {
+                                Ok (toolchain) => plugin.toolchain = toolchain,
+                                Err(e)         => {
+                                        plugin.failed = true;
+                                        return Err(e);
+                                }
+                        }
+                        if let Some(host) = options.host_api {
+                                let negotiated = api::negotiate(&plugin, host).and_then(|version| match version {
+                                        Some(version) if !options.api_tables.is_empty() => {
+                                                let table = api::attach(&plugin, version, options.api_tables)?;
+                                                Ok((Some(version), table.adapter))
+                                        },
+                                        _ => Ok((version, false))
+                                });
+                                match negotiated {
+                                        Ok ((version, adapter)) => {
+                                                plugin.api_version = version;
+                                                plugin.api_adapter = adapter;
+                                        },
+                                        Err(e) => {
+                                                plugin.failed = true;
+                                                return Err(e);
+                                        }
+                                }
+                        }
+                        /* The state store of the plugin is keyed by it. */
+                        match hash() {
+                                Ok (hash) => plugin.content_hash = Some(hash),
+                                Err(e)    => diagnostics::warning!("Couldn't hash the package of the plugin: {}", e)
+                        }
+                        memory::attach(&plugin);
+                        objects::attach(&plugin);
+                        threads::attach(&plugin);
+                        panics::attach(&mut plugin);
+                        state_store::attach(&mut plugin, &options.state_store);
+                        interfaces::attach(&mut plugin, &options.interfaces);
+                        plugin.report_deprecated(options.deprecated);
+                        Ok(plugin)
+                });
+
+                match loaded {
+                        Err(e) => {
+                                diagnostics::error!("Couldn't load plugin, stopping here.");
+                                Err(e)
+                        }
+                        Ok (p) => Ok(p)
+                }
         }
 
         /// **Executes the plugin.**
@@ -311,10 +1164,8 @@ impl Plugin {
         /// There are a series of reasons you probably want to favor the normal
         /// [`PluginManager`](crate::plugin::PluginManager)'s implementation:
         /// * This function **ALWAYS** assumes your plugin's entry point is called `vplugin_init`. Any
-        /// other name will simply not work.
-        /// * If the plugin has already been started, no checks will be done. Meaning the same plugin will be started
-        /// twice.
-        /// * Last,
+        ///   other name will simply not work.
+        /// * Hosts usually configure their own entry point name, which this function doesn't know about.
         /// 
         /// In general, this function is intended mainly for test usage and not actual code.
         /// 
@@ -327,60 +1178,148 @@ impl Plugin {
         /// }
         /// ```
         pub fn begin(&mut self) -> Result<(), VPluginError> {
-                if !self.is_valid {
-                        log::error!(
-                                "Attempted to start plugin '{}', which is not marked as valid.",
-                                self.get_metadata().name
-                        );
-                        return Err(VPluginError::InvalidPlugin);
+                let _scope = self.scope();
+                self.check_transition(PluginState::Started)?;
+                if let Err(e) = self.faults.check(Stage::EntryPoint) {
+                        self.failed = true;
+                        return Err(e);
                 }
 
-                let plugin_entry: Symbol<unsafe extern "C" fn() -> i32>;
+                let plugin_entry: unsafe extern "C-unwind" fn() -> i32;
                 unsafe {
-                        plugin_entry = match self.raw
-                                        .as_ref()
-                                        .unwrap()
-                                        .get(b"vplugin_init\0")
+                        plugin_entry = match self.symbol("vplugin_init")
                                         {
                                                 Ok(fnc) => fnc,
                                                 Err(e)  => {
-                                                        log::error!(
+                                                        diagnostics::error!(
                                                                 "Couldn't initialize plugin: {}",
-                                                                e.to_string()
+                                                                e
                                                         );
+                                                        self.failed = true;
                                                         return Err(VPluginError::FailedToInitialize)
                                                 }
                                         };
 
-                        let ___result = plugin_entry();
+                        let ___result = match self.run_export(CallKind::EntryPoint, Export::Status(plugin_entry)) {
+                                Ok (v) => v,
+                                Err(e) => {
+                                        self.failed = true;
+                                        return Err(e);
+                                }
+                        };
                         if ___result != 0 {
+                                self.failed = true;
                                 return Err(VPluginError::FailedToInitialize);
                         }
                 }
                 
-                self.started = true;
+                self.state = PluginState::Started;
+                Ok(())
+        }
+
+        /// Returns the ID of this instance of the plugin, which is unique to every time a
+        /// plugin is loaded (Loading the same package twice, or again after it was unloaded,
+        /// gives it a new ID). Logs, events and messages can use it to refer to a specific
+        /// load of a specific plugin.
+        pub fn instance_id(&self) -> Uuid {
+                self.instance_id
+        }
+
+        /// Returns the SHA-256 hash of the package the plugin was loaded from, in lowercase
+        /// hexadecimal. It's the same every time the same package is loaded, and changes when
+        /// any of its files does. `None` for builtin plugins and plugins created with
+        /// [`with_backend`](crate::plugin::Plugin::with_backend).
+        pub fn content_hash(&self) -> Option<&str> {
+                self.content_hash.as_deref()
+        }
+
+        /// Returns the directory the files of the plugin are read from: the directory of its
+        /// own it was extracted (Or copied) into, inside the workspace of the manager, or the
+        /// directory it was loaded from in place. Every plugin loaded gets its own, so plugins
+        /// can be loaded from several threads at once. `None` for builtin plugins and plugins
+        /// created with [`with_backend`](crate::plugin::Plugin::with_backend).
+        pub fn directory(&self) -> Option<&Path> {
+                match self.workdir.as_os_str().is_empty() {
+                        true  => None,
+                        false => Some(&self.workdir)
+                }
+        }
+
+        /// Returns whether the application can run without the plugin, see
+        /// [`PluginManager::set_requirement`](crate::plugin_manager::PluginManager::set_requirement).
+        pub fn requirement(&self) -> Requirement {
+                self.requirement
+        }
+
+        /// Returns the current state of the plugin.
+        /// See [PluginState](crate::state::PluginState) for the possible states.
+        pub fn state(&self) -> PluginState {
+                match self.crashed.load(Ordering::Acquire) {
+                        true  => PluginState::Quarantined,
+                        false => self.state
+                }
+        }
+
+        /// Returns an error if the plugin isn't allowed to move to the `next` state.
+        pub(crate) fn check_transition(&self, next: PluginState) -> Result<(), VPluginError> {
+                let state = self.state();
+                if state.can_transition_to(next) && self.raw.is_some() {
+                        return Ok(());
+                }
+
+                diagnostics::error!(
+                        "Plugin '{}' can't go from {:?} to {:?}.",
+                        self.metadata.name,
+                        state,
+                        next
+                );
+                if state.is_usable() && self.raw.is_some() {
+                        Err(VPluginError::InvalidState { current: state, requested: next })
+                } else {
+                        Err(VPluginError::InvalidPlugin)
+                }
+        }
+
+        /// Moves the plugin to the `next` state, if allowed.
+        pub(crate) fn transition(&mut self, next: PluginState) -> Result<(), VPluginError> {
+                self.check_transition(next)?;
+                self.state = next;
                 Ok(())
         }
 
+        /* Hooks can only be used while the plugin is running. */
+        fn require_started(&self) -> Result<(), VPluginError> {
+                let state = self.state();
+                if state == PluginState::Started && self.raw.is_some() {
+                        return Ok(());
+                }
+                diagnostics::error!(
+                        "Plugin '{}' must be started to use its hooks, but it is {:?}.",
+                        self.metadata.name,
+                        state
+                );
+                if state.is_usable() {
+                        Err(VPluginError::InvalidState { current: state, requested: PluginState::Started })
+                } else {
+                        Err(VPluginError::InvalidPlugin)
+                }
+        }
+
         /// Returns a VHook (Generic function pointer) that can be used to exchange data between
         /// your application and the plugin.
         pub(super) fn load_vhook(&self, fn_name: &str) -> Result<VHook, VPluginError> {
-                if !self.started || !self.is_valid || self.raw.is_none() {
-                        log::error!("Attempted to load plugin function that isn't started or isn't valid");
-                        return Err(VPluginError::InvalidPlugin);
-                }
-                let hook: Symbol<VHook>;
+                let _scope = self.scope();
+                self.require_started()?;
+                self.faults.check(Stage::Hook(fn_name))?;
+                let hook: VHook;
                 unsafe {
-                        hook = match self.raw
-                                .as_ref()
-                                .unwrap_unchecked()
-                                .get(format!("{}\0", fn_name).as_bytes())
+                        hook = match self.symbol(fn_name)
                         {
                             Ok (v) => v,
                             Err(_) => return Err(VPluginError::MissingSymbol),
                         };
                 }
-                Ok(*hook)
+                Ok(hook)
         }
 
         pub(crate) fn get_hook(&self, fn_name: &str) -> Result<VHook, VPluginError> {
@@ -391,65 +1330,474 @@ impl Plugin {
         /// 'T' and 'P':
         /// - `T` is the return type of the function representing the hook,
         /// - `P` is the actual function declaration (Don't add `unsafe extern fn`, it's already specified).
+        /// 
         /// The function pointer returned can then be used to exchange data between the server and the plugin.
+        /// It dangles once the plugin is unloaded, use [`hook_guard`](crate::plugin::Plugin::hook_guard)
+        /// to keep the plugin loaded while it's in use.
         pub fn get_custom_hook<P, T>(
                 &self,
                 fn_name: impl AsRef<str>,
-        ) -> Result<unsafe extern fn(P) -> T, VPluginError> {
-                if !self.started || !self.is_valid || self.raw.is_none() {
-                        log::error!("Cannot load custom hook from non-started or invalid plugin.");
-                        return Err(VPluginError::InvalidPlugin);
-                }
-                let hook: Symbol<unsafe extern fn(P) -> T>;
+        ) -> Result<unsafe extern "C" fn(P) -> T, VPluginError> {
+                let _scope = self.scope();
+                self.require_started()?;
+                self.faults.check(Stage::Hook(fn_name.as_ref()))?;
+                let hook: unsafe extern "C" fn(P) -> T;
                 unsafe {
-                        hook = match self.raw
-                                .as_ref()
-                                .unwrap_unchecked()
-                                .get(format!("{}\0", fn_name.as_ref()).as_bytes())
+                        hook = match self.symbol(fn_name.as_ref())
                         {
                             Ok (v) => v,
                             Err(_) => return Err(VPluginError::MissingSymbol),
                         };
                 }
-                Ok(*hook)
+                Ok(hook)
+        }
+
+        /// Returns a hook like [`get_custom_hook`](crate::plugin::Plugin::get_custom_hook),
+        /// wrapped in a [`HookGuard`](crate::guard::HookGuard) that pins the plugin. While
+        /// the guard (Or a clone of it) is alive, the plugin can't be terminated, so the
+        /// hook can't be called after its code was unloaded.
+        pub fn hook_guard<P, T>(
+                &self,
+                fn_name: impl AsRef<str>,
+        ) -> Result<HookGuard<unsafe extern "C" fn(P) -> T>, VPluginError> {
+                let hook = self.get_custom_hook::<P, T>(fn_name)?;
+                Ok(HookGuard::new(hook, self))
+        }
+
+        /// Returns the symbol `name` of the plugin as `F`, usually an `unsafe extern "C" fn`
+        /// pointer. Unlike [`get_custom_hook`](crate::plugin::Plugin::get_custom_hook), any
+        /// function signature can be used. `F` has to be pointer sized, using any other type
+        /// is a compile error.
+        /// 
+        /// ## Safety
+        /// `F` must be the actual type of the symbol, and the value returned must not be used
+        /// once the plugin is terminated.
+        pub unsafe fn get_symbol<F: Copy>(&self, name: impl AsRef<str>) -> Result<F, VPluginError> {
+                let _scope = self.scope();
+                self.require_started()?;
+                self.faults.check(Stage::Hook(name.as_ref()))?;
+                match self.symbol::<F>(name.as_ref()) {
+                        Ok (v) => Ok(v),
+                        Err(_) => {
+                                diagnostics::error!(
+                                        "Plugin '{}' doesn't export '{}'.",
+                                        self.metadata.name,
+                                        name.as_ref()
+                                );
+                                Err(VPluginError::MissingSymbol)
+                        }
+                }
+        }
+
+        /// Returns the symbol `name` of the plugin as `F` like [`get_symbol`](crate::plugin::Plugin::get_symbol),
+        /// wrapped in a [`HookGuard`](crate::guard::HookGuard) that keeps the plugin from being
+        /// terminated, and its library loaded, while it's alive.
+        ///
+        /// ## Safety
+        /// `F` must be the actual type of the symbol.
+        ///
+        /// ## Example
+        /// ```rust,no_run
+        /// # struct Frame;
+        /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let mut plugin = vplugin::Plugin::load("plugin.vpl")?;
+        /// # let frame = std::ptr::null_mut::<Frame>();
+        /// let render = unsafe { plugin.hook::<unsafe extern "C" fn(*mut Frame, f32) -> i32>("render")? };
+        /// unsafe { (*render)(frame, 0.016) };
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub unsafe fn hook<F: Copy>(&self, name: impl AsRef<str>) -> Result<HookGuard<F>, VPluginError> {
+                let hook = self.get_symbol::<F>(name)?;
+                Ok(HookGuard::new(hook, self))
+        }
+
+        /// Looks up the hook `name` as `F` (See [`get_symbol`](crate::plugin::Plugin::get_symbol))
+        /// and calls `call` with it, wrapped by the interceptors of the manager that loaded
+        /// the plugin (See [`intercept`](crate::intercept)). Returns what `call` returned.
+        ///
+        /// ## Safety
+        /// `F` must be the actual type of the symbol, and `call` must not keep it around.
+        ///
+        /// ## Example
+        /// ```rust,no_run
+        /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let mut plugin = vplugin::Plugin::load("plugin.vpl")?;
+        /// let sum = unsafe {
+        ///     plugin.call_hook("add", |add: unsafe extern "C" fn(i32, i32) -> i32| add(1, 2))?
+        /// };
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub unsafe fn call_hook<F: Copy, R>(&self, name: impl AsRef<str>, call: impl FnOnce(F) -> R) -> Result<R, VPluginError> {
+                let hook = self.get_symbol::<F>(name.as_ref())?;
+                let _pin = self.pin();
+                let _scope = self.scope();
+                self.run_call(CallKind::Hook(name.as_ref()), || call(hook))
+        }
+
+        /*
+         * Calls into the plugin with `f`, wrapped by the interceptors of its manager and
+         * isolated from the application (See `isolation`).
+         */
+        pub(crate) fn run_call<R>(&self, kind: CallKind<'_>, f: impl FnOnce() -> R) -> Result<R, VPluginError> {
+                self.interceptors
+                        .run(&self.call(kind), || isolation::guarded(&self.metadata.name, &self.crashed, f))
+                        .and_then(|result| result)
+        }
+
+        /* Like `run_call`, for an export the crash guard calls directly (See `isolation`). */
+        pub(crate) fn run_export(&self, kind: CallKind<'_>, export: Export) -> Result<c_int, VPluginError> {
+                self.interceptors
+                        .run(&self.call(kind), || isolation::guarded_export(&self.metadata.name, &self.crashed, export))
+                        .and_then(|result| result)
+        }
+
+        /* Describes a call into the plugin to its interceptors. */
+        pub(crate) fn call<'a>(&'a self, kind: CallKind<'a>) -> Call<'a> {
+                Call { plugin: &self.metadata.name, instance: self.instance_id, kind }
+        }
+
+        /// Resolves the set of hooks `H`, usually declared with [`declare_hooks!`](crate::declare_hooks).
+        pub fn hooks<H: Hooks>(&self) -> Result<H, VPluginError> {
+                H::resolve(self)
+        }
+
+        /// Returns a guard pinning the plugin without holding any hook, see
+        /// [`HookGuard`](crate::guard::HookGuard).
+        pub fn pin(&self) -> HookGuard<()> {
+                HookGuard::new((), self)
+        }
+
+        /// Sets what [`terminate`](crate::plugin::Plugin::terminate) does if hook guards
+        /// of this plugin are still alive. See [`PinPolicy`](crate::guard::PinPolicy).
+        pub fn set_pin_policy(&mut self, policy: PinPolicy) {
+                self.pin_policy = policy;
+        }
+
+        /// Sets whether the library of the plugin is unloaded when the plugin is dropped.
+        /// See [`UnloadPolicy`](crate::options::UnloadPolicy).
+        pub fn set_unload_policy(&mut self, policy: UnloadPolicy) {
+                self.unload = policy;
+        }
+
+        /// Returns whether the library of the plugin will be unloaded when the plugin is dropped.
+        pub fn unload_policy(&self) -> UnloadPolicy {
+                self.unload
         }
 
         /// A function to load the plugin's metadata into
         /// the plugin. In order to access the plugin's metadata,
         /// use the [get_metadata](crate::plugin::Plugin::get_metadata) function.
-        /// See also: [PluginMetadata](crate::plugin::PluginMetadata)
+        /// See also: [PluginMetadata](crate::PluginMetadata)
         #[deprecated = "The plugin's metadata will be automatically loaded along with the plugin itself."]
         pub fn load_metadata(&mut self) -> Result<(), VPluginError> {
-                match PluginMetadata::load(self) {
-                        Ok (v) => {
-                                let plugin_dir_name = env::temp_dir()
-                                        .join("vplugin")
-                                        .join(&v.name);
-
-                                fs::create_dir_all(&plugin_dir_name).unwrap();
-                                fs::copy(&v.objfile, plugin_dir_name.join(&v.objfile)).unwrap();
+                let workdir = self.workdir.clone();
+                let options = LoadOptions {
+                        diagnostics: self.diagnostics.clone(),
+                        ..LoadOptions::new(&workdir)
+                };
+                self.load_library(&options)
+        }
 
-                                self.raw       = unsafe {
-                                        init_now!(Library::new(plugin_dir_name.join(&v.objfile)).unwrap())
+        /* Loads the metadata and then the object file it points to. */
+        fn load_library(&mut self, options: &LoadOptions) -> Result<(), VPluginError> {
+                match options.faults.check(Stage::Metadata).and_then(|_| read_metadata(self)) {
+                        Ok (mut v) => {
+                                let _scope = self.diagnostics.scope(Some(&v.name));
+                                v.objfile = match options.object {
+                                        Some((objfile, _)) => objfile.to_owned(),
+                                        None               => Self::resolve_objfile(&self.workdir, &v.objfile)
                                 };
-                                self.is_valid = true;
+                                let manifest = Self::check_requirements(&v, options)
+                                        .and_then(|_| read_hook_manifest(&self.workdir));
+                                let manifest = match manifest {
+                                        Ok (manifest) => manifest,
+                                        Err(e)        => {
+                                                self.failed = true;
+                                                return Err(e);
+                                        }
+                                };
+                                match self.open_library(&v, options) {
+                                        Ok (library) => {
+                                                self.symbols.get_mut().unwrap_or_else(|e| e.into_inner()).clear();
+                                                self.raw = init_now!(library);
+                                        },
+                                        Err(e)       => {
+                                                self.failed = true;
+                                                return Err(e);
+                                        }
+                                }
+                                if let Err(e) = self.check_hooks(&manifest) {
+                                        self.failed = true;
+                                        return Err(e);
+                                }
+                                self.hook_manifest = manifest;
+                                self.state    = PluginState::Loaded;
                                 self.metadata = v;
 
                                 Ok(())
                         },
                         Err(e) => {
-                                log::error!("Couldn't load metadata ({}): {}", self.filename.display(), e.to_string());
+                                diagnostics::error!("Couldn't load metadata ({}): {}", self.filename.display(), e);
+                                self.failed = true;
                                 Err(e)
                         }
                 }
         }
 
+        /* Checks that the plugin exports every hook declared in `manifest`. */
+        fn check_hooks(&self, manifest: &HookManifest) -> Result<(), VPluginError> {
+                let missing: Vec<&str> = manifest.hooks
+                        .keys()
+                        .filter(|hook| unsafe { self.symbol::<*mut c_void>(hook) }.is_err())
+                        .map(String::as_str)
+                        .collect();
+                if !missing.is_empty() {
+                        diagnostics::error!(
+                                "Plugin declares hooks in {} that it doesn't export: {}.",
+                                HOOK_MANIFEST_FILE,
+                                missing.join(", ")
+                        );
+                        return Err(VPluginError::MissingSymbol);
+                }
+                Ok(())
+        }
+
+        /* Opens the object file described by `metadata`. */
+        #[cfg(not(target_os = "ios"))]
+        fn open_library(&mut self, metadata: &PluginMetadata, options: &LoadOptions) -> Result<Arc<dyn PluginBackend>, VPluginError> {
+                options.faults.check(Stage::Open)?;
+                let objfile = workspace::find(&self.workdir, Path::new(&metadata.objfile));
+                if cfg!(not(target_os = "macos")) && metadata.objfile.ends_with(".dylib") {
+                        diagnostics::warning!(
+                                "Plugin '{}' uses a macOS library ({}), loading will most likely fail.",
+                                metadata.name,
+                                metadata.objfile
+                        );
+                }
+
+                #[cfg(target_os = "macos")]
+                if !options.allowed_team_ids.is_empty() {
+                        crate::macos::verify_team_identifier(&objfile, options.allowed_team_ids)?;
+                }
+                #[cfg(not(target_os = "macos"))]
+                let _ = options.allowed_team_ids;
+
+                let missing = backend::missing_libraries(&metadata.needs, &self.workdir);
+                if !missing.is_empty() {
+                        diagnostics::error!(
+                                "Plugin '{}' needs libraries that can't be found: {}.",
+                                metadata.name,
+                                missing.join(", ")
+                        );
+                        return Err(VPluginError::MissingLibraries { libraries: missing });
+                }
+
+                /* Plugins in their own namespace can't shadow anything. */
+                #[cfg(feature = "preflight")]
+                if options.symbols != SymbolVisibility::Isolated {
+                        Self::report_conflicts(&objfile);
+                }
+
+                if let Some(progress) = options.progress {
+                        progress.phase(LoadPhase::Loading, None, None)?;
+                }
+                self.threads = unload::threads();
+                let bundled = BundledLibraries::for_package(&self.workdir, &metadata.needs);
+                let opened = match options.object {
+                        #[cfg(target_os = "linux")]
+                        Some((objfile, data)) => backend::open_memory(objfile, data, options.symbols),
+                        _                     => backend::open(&objfile, options.symbols)
+                };
+                match opened {
+                        Ok (l) => {
+                                self.bundled = bundled;
+                                Ok(Arc::from(l))
+                        },
+                        Err(e) => {
+                                #[cfg(target_os = "macos")]
+                                if let Some(err) = crate::macos::diagnose_load_error(&objfile, &e) {
+                                        return Err(err);
+                                }
+                                diagnostics::error!("Couldn't load {}: {}", objfile.display(), e);
+                                #[cfg(feature = "preflight")]
+                                Self::report_unresolved(&objfile, &self.workdir, &metadata.needs);
+                                Err(VPluginError::InvalidPlugin)
+                        }
+                }
+        }
+
+        /* Warns about the symbols `objfile` exports that are already in the global scope. */
+        #[cfg(all(feature = "preflight", not(target_os = "ios")))]
+        fn report_conflicts(objfile: &Path) {
+                if let Ok(conflicts) = crate::preflight::conflicts(objfile) {
+                        if !conflicts.is_empty() {
+                                diagnostics::warning!(
+                                        "Plugin exports symbols the application or a global plugin already defines, one will shadow the other: {}.",
+                                        conflicts.join(", ")
+                                );
+                        }
+                }
+        }
+
+        /* Explains a failure to load `objfile` with the symbols it imports that can't be resolved. */
+        #[cfg(all(feature = "preflight", not(target_os = "ios")))]
+        fn report_unresolved(objfile: &Path, workdir: &Path, needs: &[String]) {
+                if let Ok(report) = crate::preflight::analyze(objfile, workdir, needs) {
+                        if !report.is_clean() {
+                                let names: Vec<&str> = report.unresolved.iter().map(|symbol| symbol.name.as_str()).collect();
+                                diagnostics::error!("Symbols that can't be resolved: {}.", names.join(", "));
+                        }
+                }
+        }
+
+        /* iOS can't load code at runtime, the plugin has to be builtin. */
+        #[cfg(target_os = "ios")]
+        fn open_library(&mut self, metadata: &PluginMetadata, options: &LoadOptions) -> Result<Arc<dyn PluginBackend>, VPluginError> {
+                options.faults.check(Stage::Open)?;
+                if builtin::find(&metadata.name).is_none() {
+                        diagnostics::error!(
+                                "Plugin '{}' can't be loaded at runtime on iOS and no builtin plugin with that name is registered.",
+                                metadata.name
+                        );
+                        return Err(VPluginError::InvalidPlugin);
+                }
+                let mut builtin = Self::load_builtin(&metadata.name)?;
+                self.symbol_prefix = std::mem::take(&mut builtin.symbol_prefix);
+                match builtin.raw.take() {
+                        Some(library) => Ok(library),
+                        None          => Err(VPluginError::InvalidPlugin)
+                }
+        }
+
+        /// Resolves the object file of a plugin. If `objfile` has no extension, the
+        /// native extension of the host (`.so`, `.dll` or `.dylib`) is appended,
+        /// falling back to the `lib` prefixed name and then to `objfile` itself.
+        /// This way one `metadata.toml` can be shared by multi-platform packages.
+        pub(crate) fn resolve_objfile(workdir: &Path, objfile: &str) -> String {
+                if Path::new(objfile).extension().is_some() {
+                        return objfile.to_owned();
+                }
+                let candidates = Self::objfile_candidates(objfile);
+                match candidates.into_iter().find(|c| workspace::find(workdir, Path::new(c)).is_file()) {
+                        Some(resolved) if resolved != objfile => {
+                                diagnostics::trace!("Resolved object file '{}' to '{}'", objfile, resolved);
+                                resolved
+                        },
+                        _ => objfile.to_owned()
+                }
+        }
+
+        /* The files `objfile` may refer to, in the order they're tried (See `resolve_objfile`). */
+        fn objfile_candidates(objfile: &str) -> Vec<String> {
+                if Path::new(objfile).extension().is_some() {
+                        return vec![objfile.to_owned()];
+                }
+                let (dir, name) = match objfile.rsplit_once('/') {
+                        Some((dir, name)) => (format!("{}/", dir), name),
+                        None              => (String::new(), objfile)
+                };
+                vec![
+                        format!("{}{}.{}", dir, name, std::env::consts::DLL_EXTENSION),
+                        format!("{}{}{}.{}", dir, std::env::consts::DLL_PREFIX, name, std::env::consts::DLL_EXTENSION),
+                        objfile.to_owned(),
+                ]
+        }
+
+        /// Loads the strings of the plugin translated in `locale` (`pt-BR`, `fr`, ...), from the
+        /// `assets/i18n` directory of its package. See [`Translations`](crate::i18n::Translations).
+        /// 
+        /// ## Example
+        /// ```rust,no_run
+        /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let mut plugin = vplugin::Plugin::load("plugin.vpl")?;
+        /// let strings = plugin.translations("fr")?;
+        /// let title   = strings.get_or_key("title");
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn translations(&self, locale: &str) -> Result<Translations, VPluginError> {
+                let _scope = self.scope();
+                /* Builtin plugins don't have a package. */
+                if self.workdir.as_os_str().is_empty() {
+                        return Err(VPluginError::NoSuchFile);
+                }
+                Translations::load(&self.workdir, locale)
+        }
+
+        /// Returns the locales the package of the plugin has translations for.
+        pub fn locales(&self) -> Vec<String> {
+                if self.workdir.as_os_str().is_empty() {
+                        return Vec::new();
+                }
+                Translations::available(&self.workdir)
+        }
+
         /// Returns a reference to the plugin metadata, if loaded.
         /// Otherwise, `None` is returned.
         pub fn get_metadata(&self) -> &PluginMetadata {
                 &self.metadata
         }
 
+        /// Returns the version of the plugin, parsed from its metadata. Missing minor
+        /// and patch numbers count as 0, so `1.4` is returned as `1.4.0`.
+        /// 
+        /// `VPluginError::InvalidVersion` is returned if the version isn't a valid
+        /// [semantic version](https://semver.org).
+        pub fn version(&self) -> Result<Version, VPluginError> {
+                let _scope = self.scope();
+                version::parse(&self.metadata.version).map_err(|e| {
+                        diagnostics::error!("Plugin '{}' has an invalid version: {}", self.metadata.name, e);
+                        e
+                })
+        }
+
+        /// Returns whether the version of the plugin is `version` or newer. `version`
+        /// can be partial (`"1.4"`). If either version can't be parsed, `false` is returned.
+        /// 
+        /// ## Example
+        /// ```rust,no_run
+        /// # let plugin = vplugin::Plugin::load("plugin.vpl").unwrap();
+        /// if plugin.version_at_least("1.4") {
+        ///     /* Use the features added in 1.4. */
+        /// }
+        /// ```
+        pub fn version_at_least(&self, version: &str) -> bool {
+                match (self.version(), version::parse(version)) {
+                        (Ok(current), Ok(required)) => current >= required,
+                        _ => false
+                }
+        }
+
+        /// Returns the version of the host's API negotiated with the plugin when it was
+        /// loaded, or `None` if the manager didn't declare one or the plugin doesn't take
+        /// part in the handshake. See [`HostApiVersion`](crate::api::HostApiVersion).
+        pub fn api_version(&self) -> Option<HostApiVersion> {
+                self.api_version
+        }
+
+        /// Returns whether the API table handed to the plugin is an adapter, registered with
+        /// [`PluginManager::register_api_adapter`](crate::plugin_manager::PluginManager::register_api_adapter)
+        /// for plugins built against an old version of the host's API.
+        pub fn uses_api_adapter(&self) -> bool {
+                self.api_adapter
+        }
+
+        /// Returns the version of the compiler that built the plugin (`rustc --version`),
+        /// if the plugin uses the Rust ABI (See [`export_plugin!`](vplugin_core::export_plugin)).
+        /// Such plugins are only loaded if it matches the compiler that built the host.
+        pub fn toolchain(&self) -> Option<&str> {
+                self.toolchain.as_deref()
+        }
+
+        /// Returns the hooks the plugin declares in the `hooks.toml` file of its package (Empty
+        /// if it has none). Every hook declared there was checked to be exported when the
+        /// plugin was loaded. See [`HookManifest`](vplugin_core::manifest::HookManifest).
+        pub fn hook_manifest(&self) -> &HookManifest {
+                &self.hook_manifest
+        }
+
         /// Unloads the plugin, if loaded and started,
         /// calling its destructor in the process and
         /// freeing up resources.
@@ -460,57 +1808,219 @@ impl Plugin {
         /// have a destructor function. In that case, you can try
         /// using [`Plugin::force_terminate`](crate::plugin::Plugin::force_terminate)
         /// to force the plugin to be removed, risking safety and undefined behavior.
+        ///
+        /// The observers of the manager that loaded the plugin are notified, see
+        /// [`lifecycle`](crate::lifecycle).
         pub fn terminate(&mut self) -> Result<(), VPluginError> {
-                if self.raw.is_none() {
-                        return Err(VPluginError::InvalidPlugin);
-                }
+                let result = self.run_destructor();
+                self.observers.terminated(self, result.as_ref().copied());
+                result
+        }
 
-                if !self.started {
-                        log::error!("Cannot terminate a plugin that wasn't started in the first place.");
-                        return Err(VPluginError::InvalidPlugin);
+        /* Terminates the plugin, without notifying the observers. */
+        fn run_destructor(&mut self) -> Result<(), VPluginError> {
+                let _scope = self.scope();
+                self.check_transition(PluginState::Terminated)?;
+                if let Err(pins) = guard::wait_unpinned(&self.pins, self.pin_policy) {
+                        diagnostics::error!(
+                                "Cannot terminate plugin '{}' while {} hook guard(s) are still alive.",
+                                self.metadata.name,
+                                pins
+                        );
+                        return Err(VPluginError::PluginInUse { pins });
+                }
+                if let Err(e) = self.faults.check(Stage::Destructor) {
+                        self.failed = true;
+                        return Err(e);
                 }
 
-                let destructor: Symbol<unsafe extern "C" fn() -> ()>;
+                let destructor: unsafe extern "C-unwind" fn();
                 unsafe {
-                        destructor = match self.raw
-                                .as_ref()
-                                .unwrap_unchecked()
-                                .get(b"vplugin_exit\0")
+                        destructor = match self.symbol("vplugin_exit")
                         {
                             Ok (v) => v,
                             Err(_) => {
-                                log::warn!(
-                                        target: "Destructor",
+                                diagnostics::warning!(
                                         "Plugin {} does not have a destructor. Force terminate if needed.",
                                         self.get_metadata().name
                                 );
+                                self.failed = true;
                                 return Err(VPluginError::InvalidPlugin)
                             },
                         };
 
-                        destructor();
+                        let result = self.run_export(CallKind::Destructor, Export::Unit(destructor));
+                        /* Whatever the plugin provided may be gone along with its state. */
+                        self.interfaces.withdraw();
+                        if let Err(e) = result {
+                                self.failed = true;
+                                return Err(e);
+                        }
                 }
-
-                self.started  = false;
+                self.state = PluginState::Terminated;
                 if cfg!(feature = "non_reusable_plugins") {
-                        self.is_valid = false;
-                        self.raw      = None;
+                        self.state    = PluginState::Invalid;
+                        self.release_library();
                         self.filename = PathBuf::new();
                 }
                 Ok(())
         }
 
+        /// Returns the last panic the plugin reported, if it forwards its panics to the host
+        /// (See [`vplugin_core::panic`]).
+        pub fn last_panic(&self) -> Option<PanicReport> {
+                self.panics.last()
+        }
+
+        /// Returns the backtrace of the plugin if it panicked the last time it was started,
+        /// captured in its panic hook and reported to the host (See [`PanicReport::backtrace`]).
+        /// Entry points returning an error without panicking have no backtrace, as the
+        /// failing code has already returned by then.
+        pub fn last_backtrace(&self) -> Option<&str> {
+                self.backtrace.as_deref()
+        }
+
+        /// Returns how many threads the plugin started are running, see [`threads`](crate::threads).
+        pub fn running_threads(&self) -> usize {
+                self.workers.running()
+        }
+
+        /// Sets how long unloading the plugin waits for the threads it started, before
+        /// giving up and keeping its library loaded. See [`threads`](crate::threads).
+        pub fn set_thread_timeout(&mut self, timeout: Duration) {
+                self.thread_timeout = timeout;
+        }
+
+        /// Asks the plugin to stop before terminating it, for plugins doing work in the
+        /// background (Threads, timers, ...) that must be wound down before the destructor runs.
+        ///
+        /// The plugin is asked through its `vplugin_stop` export, and has `timeout` to
+        /// acknowledge (See the specification). It's then terminated like with
+        /// [`terminate`](crate::plugin::Plugin::terminate). Plugins that don't export
+        /// `vplugin_stop` are terminated right away.
+        ///
+        /// ## Escalation
+        /// If the plugin doesn't acknowledge in time, it's terminated by force: its destructor
+        /// isn't called, since the plugin may still be running, and its library is kept loaded
+        /// for the same reason ([`LeakReason::Unresponsive`](crate::unload::LeakReason::Unresponsive)).
+        /// The plugin becomes invalid and `VPluginError::StopTimedOut` is returned.
+        pub fn request_stop(&mut self, timeout: Duration) -> Result<(), VPluginError> {
+                let _scope = self.scope();
+                self.check_transition(PluginState::Terminated)?;
+                let stop_plugin = match unsafe { self.symbol::<stop::Stop>(stop::STOP_SYMBOL) } {
+                        Ok (stop_plugin) => stop_plugin,
+                        Err(_)           => {
+                                diagnostics::trace!("Plugin doesn't export {}, terminating it right away.", stop::STOP_SYMBOL);
+                                return self.terminate();
+                        }
+                };
+
+                match stop::request(self, stop_plugin, timeout) {
+                        Ok (true)  => return self.terminate(),
+                        Ok (false) => (),
+                        Err(e)     => {
+                                self.failed = true;
+                                return Err(e);
+                        }
+                }
+                diagnostics::error!(
+                        "Plugin '{}' didn't stop within {:?}, terminating it by force.",
+                        self.metadata.name,
+                        timeout
+                );
+                self.failed = true;
+                self.state  = PluginState::Invalid;
+                self.interfaces.withdraw();
+                self.leak_library(LeakReason::Unresponsive);
+                Err(VPluginError::StopTimedOut { timeout_ms: timeout.as_millis() as u64 })
+        }
+
+        /* Unloads the library of the plugin, unless it has to be kept loaded. */
+        fn release_library(&mut self) {
+                self.interfaces.withdraw();
+                let reason = match self.pins.load(Ordering::Acquire) {
+                        _ if self.crashed.load(Ordering::Acquire) => Some(LeakReason::Crashed),
+                        0 if self.workers.wait(self.thread_timeout).is_err() => Some(LeakReason::ThreadsRunning),
+                        0 => unload::leak_reason(self.unload, &self.threads),
+                        _ => Some(LeakReason::Pinned)
+                };
+                match reason {
+                        Some(reason) => self.leak_library(reason),
+                        None         => {
+                                drop(self.raw.take());
+                                self.bundled = BundledLibraries::default();
+                        }
+                }
+                self.symbols.get_mut().unwrap_or_else(|e| e.into_inner()).clear();
+        }
+
+        /* Keeps the library of the plugin loaded for good, recording why. */
+        fn leak_library(&mut self, reason: LeakReason) {
+                let library = match self.raw.take() {
+                        Some(library) => library,
+                        None          => return
+                };
+                match reason {
+                        LeakReason::Pinned => diagnostics::warning!(
+                                "Plugin '{}' is dropped while hook guards are alive, its library will stay loaded until they're dropped.",
+                                self.metadata.name
+                        ),
+                        LeakReason::ThreadsDetected => diagnostics::trace!(
+                                "Threads were started since plugin '{}' was loaded, keeping its library loaded.",
+                                self.metadata.name
+                        ),
+                        LeakReason::Policy => diagnostics::trace!(
+                                "Keeping the library of plugin '{}' loaded.",
+                                self.metadata.name
+                        ),
+                        LeakReason::ThreadsRunning => diagnostics::warning!(
+                                "Threads of plugin '{}' are still running, its library will stay loaded.",
+                                self.metadata.name
+                        ),
+                        LeakReason::Unresponsive => diagnostics::warning!(
+                                "Plugin '{}' may still be running, its library will stay loaded.",
+                                self.metadata.name
+                        ),
+                        LeakReason::Crashed => diagnostics::warning!(
+                                "Plugin '{}' crashed, its library will stay loaded.",
+                                self.metadata.name
+                        )
+                }
+                match reason {
+                        /* The guards hold the library as well, it's unloaded once the last of them is dropped. */
+                        LeakReason::Pinned => drop(library),
+                        _                  => std::mem::forget(library)
+                }
+                /* The library may still free what it allocated through the host, or use its objects. */
+                std::mem::forget(Arc::clone(&self.memory));
+                std::mem::forget(Arc::clone(&self.objects));
+                std::mem::forget(Arc::clone(&self.workers));
+                std::mem::forget(Arc::clone(&self.panics));
+                std::mem::forget(Arc::clone(&self.store));
+                std::mem::forget(Arc::clone(&self.interfaces));
+                /* It may still use the libraries it bundles. */
+                std::mem::take(&mut self.bundled).keep();
+                self.leaked = true;
+                self.leaks.record(LeakedLibrary {
+                        plugin : self.metadata.name.clone(),
+                        version: self.metadata.version.clone(),
+                        objfile: self.metadata.objfile.clone(),
+                        reason,
+                });
+        }
+
         /// ###### *Returns whether the function specified is available on the plugin.*
         /// 
         /// **Deprecated**: This function has been replaced with [Plugin::is_symbol_present](crate::plugin::Plugin::is_symbol_present).
         #[deprecated = "Replaced by Plugin::is_symbol_present which is more accurate and safer."]
         pub fn is_function_available(&self, name: &str) -> bool {
+                let _scope = self.scope();
                 if self.raw.is_none() {
-                        log::warn!("Avoid using misinitialized plugins as properly loaded ones (Missing shared object file).");
+                        diagnostics::warning!("Avoid using misinitialized plugins as properly loaded ones (Missing shared object file).");
                         return false;
                 }
                 unsafe {
-                        self.raw.as_ref().unwrap().get::<unsafe extern "C" fn()>(name.as_bytes()).is_ok()
+                        self.symbol::<unsafe extern "C" fn()>(name).is_ok()
                 }
         }
 
@@ -536,38 +2046,212 @@ impl Plugin {
         /// }
         /// ```
         /// 
-        /// ## Panics
-        /// Panics if `fn_name` contains invalid encoding or `self` has not yet been properly initialized.
+        /// `false` is returned for plugins that aren't loaded anymore (Their library was
+        /// unloaded, after being terminated for example) or weren't loaded properly.
         pub fn is_symbol_present<T, S>(&self, fn_name: S) -> bool
         where
                 S: Sized + Into<String>
         {
+                let _scope = self.scope();
+                if self.raw.is_none() {
+                        diagnostics::warning!("Plugin '{}' is not loaded, none of its symbols are present.", self.metadata.name);
+                        return false;
+                }
                 unsafe {
-                        self.raw
-                                .as_ref()
-                                .unwrap()
-                                .get::<T>(fn_name.into().as_bytes())
-                                .is_ok()
+                        self.symbol::<*mut c_void>(&fn_name.into()).is_ok()
+                }
+        }
+
+        /// Returns the names of the symbols (Functions and variables) the object file of the
+        /// plugin exports, sorted. They're read from the object file itself (ELF, Mach-O or
+        /// PE), so hosts can find every optional hook a plugin provides, or show its
+        /// capabilities, instead of probing names one by one with
+        /// [`is_symbol_present`](crate::plugin::Plugin::is_symbol_present).
+        ///
+        /// `VPluginError::NoSuchFile` is returned for plugins whose object file isn't on the
+        /// disk: builtin plugins, plugins created with [`with_backend`](crate::plugin::Plugin::with_backend)
+        /// and plugins loaded from memory.
+        ///
+        /// ## Example
+        /// ```rust,no_run
+        /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let mut plugin = vplugin::Plugin::load("plugin.vpl")?;
+        /// let symbols = plugin.list_symbols()?;
+        /// let hooks: Vec<&String> = symbols.iter().filter(|name| name.starts_with("on_")).collect();
+        /// # Ok(())
+        /// # }
+        /// ```
+        #[cfg(feature = "preflight")]
+        pub fn list_symbols(&self) -> Result<Vec<String>, VPluginError> {
+                let _scope = self.scope();
+                match self.objfile_on_disk() {
+                        Some(objfile) => crate::preflight::exports(&objfile),
+                        None          => {
+                                diagnostics::error!("The object file of plugin '{}' isn't on the disk, its symbols can't be listed.", self.metadata.name);
+                                Err(VPluginError::NoSuchFile)
+                        }
+                }
+        }
+
+        /* The object file the plugin was loaded from, unless it isn't on the disk (See `list_symbols`). */
+        #[cfg(feature = "preflight")]
+        pub(crate) fn objfile_on_disk(&self) -> Option<PathBuf> {
+                if self.workdir.as_os_str().is_empty() || self.metadata.objfile.is_empty() {
+                        return None;
+                }
+                Some(workspace::find(&self.workdir, Path::new(&self.metadata.objfile))).filter(|objfile| objfile.is_file())
+        }
+}
+
+/// Prints a one-line summary of the plugin (`name v1.2.3 [started] — description`),
+/// useful for listing plugins. The alternate form (`{:#}`) prints every field on its
+/// own line instead.
+impl fmt::Display for Plugin {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let metadata = &self.metadata;
+                write!(f, "{} v{} [{}]", metadata.name, metadata.version, self.state)?;
+                if f.alternate() {
+                        writeln!(f)?;
+                        writeln!(f, "  Description: {}", metadata.description.as_deref().unwrap_or("-"))?;
+                        writeln!(f, "  Package    : {}", self.filename.display())?;
+                        writeln!(f, "  Directory  : {}", self.workdir.display())?;
+                        write!  (f, "  Object file: {}", metadata.objfile)
+                } else {
+                        match &metadata.description {
+                                Some(description) => write!(f, " — {}", description),
+                                None              => Ok(())
+                        }
                 }
         }
 }
 
 impl Drop for Plugin {
         fn drop(&mut self) {
-                let plugin_dir_name = env::temp_dir()
-                        .join("vplugin")
-                        .join(&self.metadata.name);
+                /*
+                 * The library has to be unloaded before its directory is removed,
+                 * as Windows refuses to delete a DLL that is still mapped.
+                 */
+                let _scope = self.scope();
+                self.release_library();
 
-                match std::fs::remove_dir_all(&plugin_dir_name) {
-                        Err(e) => {
-                                log::warn!(
-                                        "Couldn't remove directory '{}' corresponding to plugin '{}': {}",
-                                        plugin_dir_name.display(),
-                                        self.metadata.name,
-                                        e.to_string()
-                                )
-                        },
-                        Ok(_) => ()
+                if !self.owns_workdir || self.workdir.as_os_str().is_empty() {
+                        return;
                 }
+                /* A library kept loaded may still use the files next to it. */
+                if self.leaked || !self.cleanup.removes(self.failed) {
+                        diagnostics::trace!(
+                                "Keeping directory '{}' corresponding to plugin '{}'",
+                                self.workdir.display(),
+                                self.metadata.name
+                        );
+                        return;
+                }
+                diagnostics::trace!(
+                        "Removing directory '{}' corresponding to plugin '{}'",
+                        self.workdir.display(),
+                        self.metadata.name
+                );
+                workspace::remove_dir(&self.workdir);
+        }
+}
+
+#[cfg(test)]
+mod tests {
+        use std::sync::Mutex;
+        use crate::plugin_manager::PluginManager;
+        use super::*;
+
+        /* A backend answering from a fixed list of symbols, recording every lookup. */
+        #[derive(Debug, Default)]
+        struct ScriptedBackend {
+                symbols: Vec<(&'static str, usize)>,
+                lookups: Arc<Mutex<Vec<String>>>,
+        }
+
+        impl ScriptedBackend {
+                fn with(mut self, name: &'static str, address: *const ()) -> Self {
+                        self.symbols.push((name, address as usize));
+                        self
+                }
+        }
+
+        impl PluginBackend for ScriptedBackend {
+                fn symbol(&self, name: &[u8]) -> Result<*mut c_void, String> {
+                        let name = String::from_utf8_lossy(name.strip_suffix(b"\0").unwrap_or(name)).into_owned();
+                        self.lookups.lock().unwrap().push(name.clone());
+                        match self.symbols.iter().find(|(symbol, _)| *symbol == name) {
+                                Some((_, address)) => Ok(*address as *mut c_void),
+                                None               => Err(format!("Undefined symbol: {}", name))
+                        }
+                }
+        }
+
+        extern "C" fn init() -> i32 { 0 }
+        extern "C" fn exit() {}
+        extern "C" fn add_one(x: i32) -> i32 { x + 1 }
+
+        fn scripted() -> (ScriptedBackend, Arc<Mutex<Vec<String>>>) {
+                let backend = ScriptedBackend::default()
+                        .with("vplugin_init", init as *const ())
+                        .with("vplugin_exit", exit as *const ())
+                        .with("add_one", add_one as *const ());
+                let lookups = backend.lookups.clone();
+                (backend, lookups)
+        }
+
+        #[test]
+        fn custom_hook_needs_started_plugin() {
+                let (backend, lookups) = scripted();
+                let mut plugin = Plugin::with_backend(PluginMetadata::default(), backend);
+                assert!(matches!(plugin.get_custom_hook::<i32, i32>("add_one"), Err(VPluginError::InvalidState { .. })));
+                assert!(lookups.lock().unwrap().is_empty());
+
+                plugin.begin().unwrap();
+                let hook = plugin.get_custom_hook::<i32, i32>("add_one").unwrap();
+                assert_eq!(unsafe { hook(41) }, 42);
+                assert_eq!(plugin.get_custom_hook::<i32, i32>("missing"), Err(VPluginError::MissingSymbol));
+        }
+
+        #[test]
+        fn symbols_are_looked_up_once() {
+                let (backend, lookups) = scripted();
+                let mut plugin = Plugin::with_backend(PluginMetadata::default(), backend);
+                plugin.begin().unwrap();
+                plugin.get_custom_hook::<i32, i32>("add_one").unwrap();
+                plugin.get_custom_hook::<i32, i32>("add_one").unwrap();
+                assert_eq!(lookups.lock().unwrap().iter().filter(|name| *name == "add_one").count(), 1);
+        }
+
+        #[test]
+        fn prefixed_symbols() {
+                let (backend, lookups) = scripted();
+                let mut plugin = Plugin::with_backend(PluginMetadata::default(), backend);
+                plugin.symbol_prefix = "vplugin_".into();
+                assert!(plugin.is_symbol_present::<(), _>("init"));
+                assert!(!plugin.is_symbol_present::<(), _>("add_one"));
+                assert_eq!(*lookups.lock().unwrap(), ["vplugin_init", "vplugin_add_one"]);
+        }
+
+        #[test]
+        fn symbols_of_unloaded_plugins() {
+                let (backend, _) = scripted();
+                let mut plugin = Plugin::with_backend(PluginMetadata::default(), backend);
+                assert!(plugin.is_symbol_present::<(), _>("add_one"));
+                assert!(!plugin.is_symbol_present::<(), _>("missing"));
+
+                plugin.raw = None;
+                assert!(!plugin.is_symbol_present::<(), _>("add_one"));
+        }
+
+        #[test]
+        fn entry_point_of_the_manager() {
+                let backend = ScriptedBackend::default().with("start_plugin", init as *const ());
+                let lookups = backend.lookups.clone();
+                let mut manager = PluginManager::new();
+                manager.set_entry_point("start_plugin");
+                let handle = manager.insert(Plugin::with_backend(PluginMetadata::default(), backend));
+                manager.begin(handle).unwrap();
+                assert_eq!(manager.plugin(handle).unwrap().state(), PluginState::Started);
+                assert_eq!(lookups.lock().unwrap().first().map(String::as_str), Some("start_plugin"));
         }
 }
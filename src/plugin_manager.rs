@@ -15,71 +15,382 @@
 */
 
 extern crate libloading;
-use std::{ffi::{c_void, c_int, CString, OsStr}, env, fs, io::ErrorKind, path::PathBuf};
-use libloading::Symbol;
+use std::{ffi::CString, fs, path::{Path, PathBuf}, sync::Arc, time::{Duration, Instant}};
+use serde::Serialize;
+use crate::api::{self, ApiTable, HostApiVersion};
+use crate::compat::{self, CompatibilityMatrix, CompatibilityReport};
 use crate::error::VPluginError;
+use vplugin_core::PluginMetadata;
+use vplugin_core::manifest::{HookSignature, ENTRY_POINT_SIGNATURE};
+use crate::diagnostics::{self, Deprecation, DiagnosticsSink};
+use crate::workspace;
+use crate::settings;
+use crate::identity;
+use crate::license::{EntitlementCheck, LicensePolicy};
+use crate::permissions::{PermissionDecision, PermissionPolicy, PermissionRequest, Permissions, Prompt};
+use crate::state::PluginState;
+use crate::handle::{PluginHandle, Slots};
+use crate::hooks::Hooks;
+use crate::discovery::{self, DiscoveryReport};
+use crate::lifecycle::{LifecycleObserver, Observers};
+use crate::intercept::{CallInterceptor, CallKind, Interceptors};
+use crate::isolation::Export;
+#[cfg(feature = "signing")]
+use crate::signing::TrustedKeys;
+use crate::extension::{Extension, ExtensionChain, ExtensionPoint};
+use crate::guard::HookGuard;
+use crate::version::{self, Constraints};
+use crate::fault::{Faults, Stage};
+use crate::options::{CleanupPolicy, ManagerOptions, Requirement, StartPolicy, SymbolVisibility, UnloadPolicy};
+use crate::unload::{LeakedLibrary, Leaks};
+use crate::memory::MemoryUsage;
+use crate::objects::ObjectRegistry;
+use crate::threads;
+use crate::state_store::PluginStateStore;
+use crate::interfaces::InterfaceRegistry;
+use crate::progress::{self, LoadProgress, Reporter};
+use crate::telemetry::{Telemetry, TelemetrySink};
+use crate::snapshot::{self, ManagerSnapshot, PluginSnapshot};
+use uuid::Uuid;
+use crate::startup::{StartupFailure, StartupSummary};
+use crate::shutdown::{ShutdownFailure, ShutdownReport};
 
-use super::plugin::Plugin;
+use super::plugin::{LoadOptions, Plugin};
 
 /// ## PluginManager
 /// A `PluginManager` is responsible for managing all loaded plugins,
 /// like deploying them, attaching hooks, cleaning up the filesystem, etc.
 /// You should have it as a singleton instance in your application.
 /// 
+#[derive(Debug)]
 #[repr(C)]
 pub struct PluginManager {
-        entry: CString
+        entry    : CString,
+        /* Directory unique to this manager, plugins are extracted inside it. */
+        workspace: PathBuf,
+        team_ids : Vec<String>,
+        start    : StartPolicy,
+        allow_archives: bool,
+        cleanup  : CleanupPolicy,
+        host_api : Option<HostApiVersion>,
+        api_tables: Vec<ApiTable>,
+        /* The version of the host and the versions of plugins it accepts. */
+        versions : Constraints,
+        /* Symbols the host deprecated, with their replacement. */
+        deprecated: Vec<(String, Option<String>)>,
+        /* Where plugins keep their settings, see `set_data_dir`. */
+        data_dir : Option<PathBuf>,
+        /* The licenses plugins are accepted under, if restricted. */
+        license_policy: Option<LicensePolicy>,
+        /* The capabilities plugins may declare, if restricted. */
+        permission_policy: Option<PermissionPolicy>,
+        /* Decides whether the user is entitled to commercial plugins. */
+        entitlement: Option<EntitlementCheck>,
+        symbols  : SymbolVisibility,
+        unload   : UnloadPolicy,
+        /* The libraries the plugins of the manager kept loaded. */
+        leaks    : Leaks,
+        /* The objects handed to the plugins of the manager. */
+        objects  : Arc<ObjectRegistry>,
+        /* The state the plugins of the manager keep across reloads. */
+        state_store: Arc<PluginStateStore>,
+        /* The interfaces the plugins of the manager provide to each other. */
+        interfaces: Arc<InterfaceRegistry>,
+        thread_timeout: Duration,
+        /* Asks the user about the capabilities plugins request. */
+        prompt   : Option<Prompt>,
+        /* How many plugins the manager started, giving the order they were started in. */
+        starts   : u64,
+        plugins  : Slots<Plugin>,
+        /* Where the manager and its plugins report errors, warnings, ... */
+        diagnostics: diagnostics::Sink,
+        /* Where the records of plugins loading and starting go, if anywhere. */
+        telemetry: Telemetry,
+        faults   : Faults,
+        /* Wrap every call into the plugins of the manager. */
+        interceptors: Interceptors,
+        /* Notified as the plugins of the manager are loaded, started and terminated. */
+        observers: Observers,
+        /* The keys plugins have to be signed with, if any. */
+        #[cfg(feature = "signing")]
+        trusted_keys: TrustedKeys,
+        /* Hooks every plugin has to export to be started, see `require_hooks`. */
+        required_hooks: Vec<&'static str>,
+        /* The configuration of plugins set by the application, by name. See `configure`. */
+        config_overrides: std::collections::HashMap<String, toml::Table>,
 }
 
-/// ## VHook
-/// The `VHook` is a type to represent a generic function by VPlugin.
-/// There is only a generic parameter available, a standard `void*`
-/// which can then be translated into the actual struct (Expected to
-/// be provided by your application or library). The return value is always
-/// an integer, to indicate success or failure. To save data, you should make
-/// a field available to the struct you pass.
-/// ## Safety
-/// Generally, using void pointers is unsafe by miles, and you should avoid it.
-/// If you can, use Rust alternatives instead, such as generics or simply getting
-/// the functions yourself.
-/// In any case, you should only use VHook if you are ready to deal with type mismatches
-/// and a ton of other issues.
-pub type VHook = unsafe extern "C" fn(*mut c_void) -> c_int;
+pub use vplugin_core::VHook;
+
+/* The entry point plugins used before hosts could choose their own. */
+pub(crate) const LEGACY_ENTRY_POINT: &str = "vplugin_init";
+
+/* Receives the configuration given to `begin_plugin_with_config`. */
+const CONFIGURE_SYMBOL: &str = "vplugin_configure";
+
+/// Checks the signature of the entry point the package described by `metadata` declares
+/// (Its `entry_signature` field), and returns why it can't be called otherwise.
+pub(crate) fn verify_entry_signature(metadata: &PluginMetadata) -> Result<(), String> {
+        let expected = ENTRY_POINT_SIGNATURE.parse::<HookSignature>().map(|signature| signature.hash());
+        match (metadata.entry_signature.as_deref().map(|hash| u64::from_str_radix(hash, 16)), expected) {
+                (Some(Ok(hash)), Ok(expected)) if hash == expected => Ok(()),
+                (None, _) => Err("its package doesn't declare its signature".into()),
+                _         => Err(format!("its package declares another signature than `{}`", ENTRY_POINT_SIGNATURE))
+        }
+}
 
 impl PluginManager {
         /// Creates a new, empty PluginManager and returns it.
+        /// 
+        /// Each manager extracts its plugins into its own directory, so multiple
+        /// hosts (Or managers) can run at the same time without colliding.
+        /// 
+        /// ## Panics
+        /// Panics if there's no directory VPlugin can extract plugins into. See
+        /// [`PluginManager::try_new`](crate::plugin_manager::PluginManager::try_new)
+        /// for a version that returns an error instead.
         pub fn new() -> Self {
-                let dir = env::temp_dir().join("vplugin");
-                match fs::create_dir(dir) {
-                        Err(e) => {
-                                if let ErrorKind::AlreadyExists = e.kind() {
-                                        ()
-                                } else {
-                                        panic!("Unable to create VPlugin directory.")
-                                }
-                        },
-                        _ => ()
+                match Self::try_new() {
+                        Ok (manager) => manager,
+                        Err(e)       => panic!("Unable to create VPlugin directory: {}", e)
+                }
+        }
+
+        /// Creates a new, empty PluginManager and returns it, or returns an error if
+        /// no directory to extract plugins into can be created.
+        /// 
+        /// Plugins are normally extracted inside the temporary directory. If that one
+        /// isn't writable or is mounted `noexec`, the user's cache directory
+        /// (`$XDG_CACHE_HOME`, `%LOCALAPPDATA%` or `~/Library/Caches`) is used instead.
+        /// 
+        /// `VPluginError::DynamicLoadingUnavailable` is returned if the process can't
+        /// load shared libraries at all (For example a statically linked musl binary).
+        pub fn try_new() -> Result<Self, VPluginError> {
+                Self::with_options(ManagerOptions::default())
+        }
+
+        /// Creates a new, empty PluginManager extracting its plugins inside `workspace`
+        /// instead of the shared VPlugin directory. The manager still uses its own, randomly
+        /// named directory inside `workspace`, so tests running in parallel can share it.
+        /// 
+        /// ## Example
+        /// ```rust,no_run
+        /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+        /// let dir = std::env::temp_dir().join("my-test");
+        /// let manager = vplugin::PluginManager::new_in(&dir)?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn new_in(workspace: impl AsRef<Path>) -> Result<Self, VPluginError> {
+                Self::with_options(ManagerOptions {
+                        workspace: Some(workspace.as_ref().to_path_buf()),
+                        ..ManagerOptions::default()
+                })
+        }
+
+        /// Creates a new, empty PluginManager configured with `options`, or returns an
+        /// error if its directory can't be created, the entry point name is not valid or
+        /// one of the versions isn't.
+        /// 
+        /// See [`ManagerOptions`](crate::options::ManagerOptions).
+        pub fn with_options(options: ManagerOptions) -> Result<Self, VPluginError> {
+                #[cfg(not(target_os = "ios"))]
+                crate::probe::check_dynamic_loading()?;
+                let entry = match CString::new(options.entry_point) {
+                        Ok (entry) => entry,
+                        Err(e)     => {
+                                diagnostics::error!("Invalid entry point name: {}", e);
+                                return Err(VPluginError::ParametersError);
+                        }
+                };
+                let root = match options.workspace {
+                        Some(workspace) => workspace,
+                        None            => workspace::root()?
+                };
+                let mut versions = Constraints::default();
+                if let Some(host_version) = &options.host_version {
+                        versions.host = Some(version::parse(host_version)?);
+                }
+                for (name, requirement) in &options.accepts_plugins {
+                        let name = if name == "*" { None } else { Some(name.clone()) };
+                        versions.accepts.push((name, version::parse_req(requirement)?));
                 }
-                
-                Self {
-                        entry  : CString::new("vplugin_init").expect("CString::new error")
+                if let Some(policy) = &options.license_policy {
+                        policy.validate()?;
                 }
+                let workspace = workspace::unique_dir(&root)?;
+
+                Ok(Self {
+                        entry,
+                        workspace,
+                        team_ids : options.allowed_team_ids,
+                        start    : options.start,
+                        allow_archives: options.allow_archives,
+                        cleanup  : options.cleanup,
+                        host_api : options.host_api,
+                        api_tables: Vec::new(),
+                        versions,
+                        deprecated: Vec::new(),
+                        data_dir : options.data_dir,
+                        license_policy: options.license_policy,
+                        permission_policy: options.permission_policy,
+                        entitlement: None,
+                        symbols  : options.symbols,
+                        unload   : options.unload,
+                        leaks    : Leaks::default(),
+                        objects  : Arc::default(),
+                        state_store: Arc::default(),
+                        interfaces: Arc::default(),
+                        thread_timeout: threads::DEFAULT_THREAD_TIMEOUT,
+                        prompt   : None,
+                        starts   : 0,
+                        plugins  : Slots::default(),
+                        diagnostics: diagnostics::Sink::default(),
+                        telemetry: Telemetry::default(),
+                        faults   : Faults::default(),
+                        interceptors: Interceptors::default(),
+                        observers: Observers::default(),
+                        #[cfg(feature = "signing")]
+                        trusted_keys: TrustedKeys::default(),
+                        required_hooks: Vec::new(),
+                        config_overrides: std::collections::HashMap::new(),
+                })
         }
 
         /// Loads a plugin through PluginManager. This function calls Plugin::load(filename)
         /// under the hood, so you can also use it.
         /// 
         /// ## Parameters
-        /// * `filename` A path to the plugin to load.
+        /// * `filename` A path to the plugin to load. Anything that can be used as a path
+        ///   is accepted (`&str`, `String`, `&Path`, `PathBuf`, ...).
         /// 
-        /// ## Panics
-        /// May panic if `filename` is not a valid string.
-        pub fn load_plugin(&mut self, filename: PathBuf) -> Result<Plugin, VPluginError> {
-                Plugin::load(filename)
+        /// If the manager was created with [`StartPolicy::OnLoad`](crate::options::StartPolicy::OnLoad),
+        /// the plugin is started as well.
+        pub fn load_plugin(&mut self, filename: impl AsRef<Path>) -> Result<Plugin, VPluginError> {
+                self.load_as(filename.as_ref(), false, None)
+        }
+
+        /// Loads a plugin from a `.vpl` archive held in memory, like one embedded in the
+        /// application with `include_bytes!`, without writing the archive to a file first.
+        /// See [`Plugin::load_from_bytes`](crate::plugin::Plugin::load_from_bytes).
+        ///
+        /// If the manager was created with [`StartPolicy::OnLoad`](crate::options::StartPolicy::OnLoad),
+        /// the plugin is started as well.
+        #[cfg(feature = "archive")]
+        pub fn load_plugin_from_bytes(&mut self, data: &[u8]) -> Result<Plugin, VPluginError> {
+                let started = Instant::now();
+                let loaded  = Plugin::load_bytes_in(data, &self.load_options());
+                self.telemetry.loaded(Path::new(crate::plugin::MEMORY_PACKAGE), loaded.as_ref().map(|plugin| &plugin.metadata), started);
+                let mut plugin = loaded?;
+                self.observers.loaded(&plugin);
+                if self.start == StartPolicy::OnLoad {
+                        self.begin_plugin(&mut plugin)?;
+                }
+                Ok(plugin)
+        }
+
+        /// Loads every `.vpl` package inside `directory`, and inside its subdirectories if
+        /// `recursive` is set, in the order of their paths. The plugins aren't kept by the
+        /// manager, they're all returned in a [`DiscoveryReport`](crate::discovery::DiscoveryReport)
+        /// along with the packages that failed to load, which don't stop the others from
+        /// being loaded.
+        ///
+        /// An error is only returned if `directory` itself can't be read. If the manager was
+        /// created with [`StartPolicy::OnLoad`](crate::options::StartPolicy::OnLoad), the
+        /// plugins are started as well.
+        ///
+        /// ## Example
+        /// ```rust,no_run
+        /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let mut manager = vplugin::PluginManager::new();
+        /// let report = manager.load_from_directory("/usr/share/my-app/plugins", false)?;
+        /// println!("{}", report);
+        /// for plugin in report.into_plugins() {
+        ///     manager.insert(plugin);
+        /// }
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn load_from_directory(&mut self, directory: impl AsRef<Path>, recursive: bool) -> Result<DiscoveryReport, VPluginError> {
+                let found = discovery::find_packages(directory.as_ref(), recursive)?;
+                let mut report = DiscoveryReport::default();
+                for package in found.packages {
+                        let loaded = self.load_plugin(&package);
+                        report.plugins.push((package, loaded));
+                }
+                report.plugins.extend(found.unreadable.into_iter().map(|(dir, e)| (dir, Err(e))));
+                Ok(report)
+        }
+
+        /*
+         * Loads the plugin at `filename`, copying it first if it's a separate instance, and
+         * reporting the progress to `progress` if given.
+         */
+        fn load_as(&mut self, filename: &Path, separate_instance: bool, progress: Option<&Reporter<'_>>) -> Result<Plugin, VPluginError> {
+                let mut plugin = self.load_unstarted(filename, separate_instance, progress)?;
+                if self.start == StartPolicy::OnLoad {
+                        self.begin_plugin(&mut plugin)?;
+                }
+                Ok(plugin)
+        }
+
+        /* Loads the plugin at `filename` like `load_as`, without starting it whatever the start policy. */
+        fn load_unstarted(&mut self, filename: &Path, separate_instance: bool, progress: Option<&Reporter<'_>>) -> Result<Plugin, VPluginError> {
+                let started = Instant::now();
+                let options = LoadOptions { separate_instance, progress, ..self.load_options() };
+                let loaded  = Plugin::load_in(filename.to_path_buf(), &options);
+                self.telemetry.loaded(filename, loaded.as_ref().map(|plugin| &plugin.metadata), started);
+                let plugin = loaded?;
+                self.observers.loaded(&plugin);
+                Ok(plugin)
+        }
+
+        /* The options passed to every plugin loaded through this manager. */
+        fn load_options(&self) -> LoadOptions<'_> {
+                LoadOptions {
+                        workspace       : &self.workspace,
+                        allowed_team_ids: &self.team_ids,
+                        allow_archives  : self.allow_archives,
+                        cleanup         : self.cleanup,
+                        diagnostics     : self.diagnostics.clone(),
+                        faults          : self.faults.clone(),
+                        interceptors    : self.interceptors.clone(),
+                        observers       : self.observers.clone(),
+                        host_api        : self.host_api,
+                        api_tables      : &self.api_tables,
+                        versions        : &self.versions,
+                        deprecated      : &self.deprecated,
+                        license_policy  : self.license_policy.as_ref(),
+                        permission_policy: self.permission_policy.as_ref(),
+                        entitlement     : self.entitlement.as_ref(),
+                        symbols         : self.symbols,
+                        unload          : self.unload,
+                        leaks           : self.leaks.clone(),
+                        objects         : self.objects.clone(),
+                        state_store     : self.state_store.clone(),
+                        interfaces      : self.interfaces.clone(),
+                        thread_timeout  : self.thread_timeout,
+                        separate_instance: false,
+                        object          : None,
+                        progress        : None,
+                        #[cfg(feature = "signing")]
+                        trusted_keys    : &self.trusted_keys,
+                }
+        }
+
+        /// Loads a plugin that was statically linked into the application. See
+        /// [`Plugin::load_builtin`](crate::plugin::Plugin::load_builtin).
+        pub fn load_builtin(&mut self, name: &str) -> Result<Plugin, VPluginError> {
+                let _scope = self.diagnostics.scope(Some(name));
+                let mut plugin = Plugin::load_builtin(name)?;
+                plugin.diagnostics = self.diagnostics.clone();
+                plugin.observers = self.observers.clone();
+                self.observers.loaded(&plugin);
+                Ok(plugin)
         }
 
         /// **This function is no longer relevant, it's only kept for compatibility.**
-        #[deprecated(since = "v0.3.0", note = "This function is no longer relevant, it's only kept for compatibility.")]
+        #[deprecated(since = "0.3.0", note = "This function is no longer relevant, it's only kept for compatibility.")]
         pub fn register_plugin(&mut self, _plugin: &mut Plugin) -> Result<(), VPluginError> {
                 Ok(())
         }
@@ -92,6 +403,547 @@ impl PluginManager {
                 self.entry = CString::new(entry_point).expect("CString::new error")
         }
 
+        /// Restricts the plugins that can be loaded to those whose object file is signed
+        /// by one of the given team identifiers. The signature is verified with `codesign`
+        /// before the library is loaded.
+        /// 
+        /// This only has an effect on macOS, other platforms ignore it. Passing an empty
+        /// list disables the verification (The default).
+        pub fn set_allowed_team_ids<I, S>(&mut self, team_ids: I)
+        where
+                I: IntoIterator<Item = S>,
+                S: Into<String>
+        {
+                self.team_ids = team_ids.into_iter().map(Into::into).collect();
+        }
+
+        /// Sends the diagnostics (Errors, warnings, ...) of the manager and of the plugins
+        /// it loads from now on to `sink`, instead of the [`log`] crate. Plugins that were
+        /// loaded before keep reporting to the previous sink.
+        /// 
+        /// See [`DiagnosticsSink`](crate::DiagnosticsSink).
+        pub fn set_diagnostics_sink(&mut self, sink: impl DiagnosticsSink + 'static) {
+                self.diagnostics = diagnostics::Sink::new(sink);
+        }
+
+        /// Sends an anonymous [`TelemetryRecord`](crate::telemetry::TelemetryRecord) to `sink`
+        /// every time the manager loads or starts a plugin, from now on. Telemetry is off until
+        /// a sink is installed, see [`telemetry`](crate::telemetry).
+        pub fn set_telemetry_sink(&mut self, sink: impl TelemetrySink + 'static) {
+                self.telemetry = Telemetry::new(sink);
+        }
+
+        /// Stops sending telemetry records, for example once the user opted out.
+        pub fn disable_telemetry(&mut self) {
+                self.telemetry = Telemetry::default();
+        }
+
+        /// Sets whether the directories of the plugins loaded from now on are removed from
+        /// the disk once they're unloaded. See [`CleanupPolicy`](crate::options::CleanupPolicy).
+        /// 
+        /// The policy in place when the manager is dropped decides what happens to the
+        /// manager's own directory.
+        pub fn set_cleanup_policy(&mut self, cleanup: CleanupPolicy) {
+                self.cleanup = cleanup;
+        }
+
+        /// Sets whether the symbols of the plugins loaded from now on are added to the global
+        /// scope of the process, or kept in a linker namespace of their own. See [`SymbolVisibility`](crate::options::SymbolVisibility).
+        pub fn set_symbol_visibility(&mut self, symbols: SymbolVisibility) {
+                self.symbols = symbols;
+        }
+
+        /// Sets whether the libraries of the plugins loaded from now on are unloaded once the
+        /// plugins are dropped. Every plugin can still change it with
+        /// [`Plugin::set_unload_policy`](crate::plugin::Plugin::set_unload_policy). See
+        /// [`UnloadPolicy`](crate::options::UnloadPolicy).
+        pub fn set_unload_policy(&mut self, policy: UnloadPolicy) {
+                self.unload = policy;
+        }
+
+        /// Sets how long unloading the plugins loaded from now on waits for the threads they
+        /// started, 5 seconds by default. Every plugin can still change it with
+        /// [`Plugin::set_thread_timeout`](crate::plugin::Plugin::set_thread_timeout). See
+        /// [`threads`](crate::threads).
+        pub fn set_thread_timeout(&mut self, timeout: Duration) {
+                self.thread_timeout = timeout;
+        }
+
+        /// Returns the memory `plugin` allocated through the host, see [`memory`](crate::memory).
+        /// Plugins that don't use the allocator of the host always report nothing.
+        pub fn memory_usage(&self, plugin: &Plugin) -> MemoryUsage {
+                plugin.memory.usage()
+        }
+
+        /// Limits the memory `plugin` can allocate through the host to `limit` bytes, or
+        /// removes the limit. Allocations going past it fail (Return `NULL`), and are
+        /// counted in [`MemoryUsage::refused`](crate::memory::MemoryUsage::refused).
+        pub fn set_memory_limit(&self, plugin: &Plugin, limit: Option<usize>) {
+                plugin.memory.set_limit(limit);
+        }
+
+        /// Returns the registry of the objects handed to the plugins of the manager as opaque
+        /// handles, see [`objects`](crate::objects).
+        pub fn objects(&self) -> &ObjectRegistry {
+                &self.objects
+        }
+
+        /// Returns the state the plugins of the manager keep across terminate and reload
+        /// cycles, see [`state_store`](crate::state_store).
+        pub fn state_store(&self) -> &PluginStateStore {
+                &self.state_store
+        }
+
+        /// Returns the interfaces the plugins of the manager provide to each other, see
+        /// [`interfaces`](crate::interfaces).
+        pub fn interfaces(&self) -> &InterfaceRegistry {
+                &self.interfaces
+        }
+
+        /// Returns the libraries of the plugins loaded by the manager that were kept loaded
+        /// instead of being unloaded, in the order they were dropped.
+        pub fn leaked_libraries(&self) -> Vec<LeakedLibrary> {
+                self.leaks.list()
+        }
+
+        /// Declares the version of the API the application exposes to plugins. Plugins loaded
+        /// from now on are asked which version they use, and refused if they don't support it.
+        /// Plugins declaring the version they were built against in their metadata are also
+        /// refused when started, with `VPluginError::IncompatibleVersion`, if `version` isn't
+        /// compatible with it. See [`HostApiVersion`](crate::api::HostApiVersion).
+        pub fn set_host_api_version(&mut self, version: HostApiVersion) {
+                self.host_api = Some(version);
+        }
+
+        /// Sets the version of the application. Plugins declaring the versions of the application
+        /// they work with (With the `requires_host` field of their metadata) are refused if
+        /// `version` isn't one of them, with `VPluginError::UnsatisfiedVersion`.
+        /// 
+        /// `VPluginError::InvalidVersion` is returned if `version` isn't a valid version.
+        pub fn set_host_version(&mut self, version: &str) -> Result<(), VPluginError> {
+                self.versions.host = Some(version::parse(version)?);
+                Ok(())
+        }
+
+        /// Only accepts the plugins whose version matches `requirement` (`">=1.2, <2"`). With a
+        /// `name`, the requirement only applies to the plugins with that name, otherwise to every
+        /// plugin. Plugins that don't match any requirement are refused with
+        /// `VPluginError::UnsatisfiedVersion`.
+        /// 
+        /// Bare object files have no version, so they're considered version `0.0.0`.
+        /// 
+        /// `VPluginError::InvalidVersion` is returned if `requirement` isn't a valid requirement.
+        pub fn accept_plugins(&mut self, name: Option<&str>, requirement: &str) -> Result<(), VPluginError> {
+                let requirement = version::parse_req(requirement)?;
+                self.versions.accepts.push((name.map(str::to_owned), requirement));
+                Ok(())
+        }
+
+        /// Registers `table`, the version `version` of the API the application exposes to plugins
+        /// (Usually a struct of `extern "C"` function pointers). More than one version can be
+        /// registered: every plugin is handed the newest table with the major version it
+        /// negotiated, so plugins built against an old version keep working after the API breaks.
+        /// 
+        /// The newest registered version is declared to plugins, like
+        /// [`set_host_api_version`](crate::plugin_manager::PluginManager::set_host_api_version).
+        ///
+        /// With the `crash_guard` feature, the functions of the table should start by suspending
+        /// the guard with [`Unguarded::enter`](crate::isolation::Unguarded::enter), so a fault in
+        /// them isn't recovered from by jumping over their frames.
+        /// 
+        /// ## Example
+        /// ```rust,no_run
+        /// # use std::ffi::c_char;
+        /// # use vplugin::HostApiVersion;
+        /// # extern "C" fn host_log(_level: i32, _message: *const c_char) {}
+        /// # let mut manager = vplugin::PluginManager::new();
+        /// #[repr(C)]
+        /// struct ApiV2 {
+        ///     log: extern "C" fn(level: i32, message: *const c_char),
+        /// }
+        ///
+        /// static API_V2: ApiV2 = ApiV2 { log: host_log };
+        /// manager.register_api(HostApiVersion::new(2, 0), &API_V2);
+        /// ```
+        pub fn register_api<T: Sync + 'static>(&mut self, version: HostApiVersion, table: &'static T) {
+                self.add_api_table(version, table, false);
+        }
+
+        /// Registers `table` as version `version` of the application's API, like
+        /// [`register_api`](crate::plugin_manager::PluginManager::register_api), for an old
+        /// version implemented on top of the current one. Plugins handed an adapter are
+        /// reported by [`Plugin::uses_api_adapter`](crate::plugin::Plugin::uses_api_adapter).
+        pub fn register_api_adapter<T: Sync + 'static>(&mut self, version: HostApiVersion, table: &'static T) {
+                self.add_api_table(version, table, true);
+        }
+
+        fn add_api_table<T: Sync + 'static>(&mut self, version: HostApiVersion, table: &'static T, adapter: bool) {
+                self.api_tables.retain(|existing| existing.version != version);
+                self.api_tables.push(ApiTable { version, table: (table as *const T).cast(), adapter });
+                self.host_api = self.host_api.max(Some(version));
+        }
+
+        /// Restricts the licenses plugins loaded from now on can be under. Plugins whose
+        /// `license` isn't accepted by `policy` are refused with
+        /// `VPluginError::LicensePolicyViolation`. See [`LicensePolicy`](crate::license::LicensePolicy).
+        /// 
+        /// `VPluginError::ParametersError` is returned, and the previous policy kept, if
+        /// `policy` contains an invalid license.
+        pub fn set_license_policy(&mut self, policy: LicensePolicy) -> Result<(), VPluginError> {
+                policy.validate()?;
+                self.license_policy = Some(policy);
+                Ok(())
+        }
+
+        /// Restricts the capabilities plugins loaded from now on can declare in the
+        /// `[permissions]` section of their metadata. Plugins asking for a capability
+        /// `policy` doesn't allow are refused with `VPluginError::CapabilitiesRefused`, and
+        /// requests for them at runtime ([`Permissions::request`](crate::permissions::Permissions::request))
+        /// are refused as well. See [`PermissionPolicy`](crate::permissions::PermissionPolicy).
+        pub fn set_permission_policy(&mut self, policy: PermissionPolicy) {
+                self.permission_policy = Some(policy);
+        }
+
+        /// Sets the check deciding whether the user is entitled to the plugins loaded from now
+        /// on, for applications selling plugins. It's called with the metadata of every plugin
+        /// (Usually its `publisher` and `product_id`) before its library is loaded, and returns
+        /// why the user isn't entitled to it, if they aren't: the plugin is then refused with
+        /// `VPluginError::NotEntitled`, carrying that reason.
+        ///
+        /// The check runs on the thread loading the plugin, and can block (To ask a license
+        /// server, for example).
+        ///
+        /// ## Example
+        /// ```rust,no_run
+        /// # mod my_app {
+        /// #     pub mod store {
+        /// #         pub fn owns(_product: &str) -> bool { true }
+        /// #     }
+        /// # }
+        /// # let mut manager = vplugin::PluginManager::new();
+        /// manager.set_entitlement_check(|metadata| match &metadata.product_id {
+        ///     Some(product) if !my_app::store::owns(product) => Err(format!("{} isn't purchased", metadata.name)),
+        ///     _                                             => Ok(())
+        /// });
+        /// ```
+        pub fn set_entitlement_check<F>(&mut self, check: F)
+        where
+                F: Fn(&PluginMetadata) -> Result<(), String> + Send + Sync + 'static
+        {
+                self.entitlement = Some(EntitlementCheck(Arc::new(check)));
+        }
+
+        /// Adds `interceptor` around every call into the plugins of the manager (Their entry
+        /// point, destructor and hooks), including the plugins already loaded.
+        /// See [`intercept`](crate::intercept).
+        pub fn add_interceptor(&mut self, interceptor: impl CallInterceptor + 'static) {
+                self.interceptors.add(Arc::new(interceptor));
+        }
+
+        /// Adds `observer`, notified as the plugins of the manager are loaded, started and
+        /// terminated, including the plugins already loaded. See [`lifecycle`](crate::lifecycle).
+        pub fn add_observer(&mut self, observer: impl LifecycleObserver + 'static) {
+                self.observers.add(Arc::new(observer));
+        }
+
+        /// Trusts plugins signed with the Ed25519 public key `key`. Once any key is trusted,
+        /// every plugin loaded by the manager has to be signed with one of them: the
+        /// signature of archives is checked before they are extracted, and unsigned or
+        /// modified packages (And bare object files) are refused with
+        /// `VPluginError::SignatureInvalid`. See [`signing`](crate::signing).
+        ///
+        /// `VPluginError::ParametersError` is returned if `key` isn't a valid public key.
+        ///
+        /// ## Example
+        /// Not compiled as a test, as `publisher.pub` (The raw 32 bytes of the key) ships with
+        /// the application:
+        /// ```rust,ignore
+        /// manager.add_trusted_key(*include_bytes!("publisher.pub"))?;
+        /// let plugin = manager.load("plugin.vpl")?;
+        /// ```
+        #[cfg(feature = "signing")]
+        pub fn add_trusted_key(&mut self, key: [u8; 32]) -> Result<(), VPluginError> {
+                self.trusted_keys.add(&key)
+        }
+
+        /// Marks `symbol` as deprecated: plugins loaded from now on that export it are reported
+        /// with a [`Deprecation`](crate::diagnostics::Deprecation) warning, naming the plugin and
+        /// `replacement` (If any), so plugin authors know what to migrate to.
+        pub fn deprecate_symbol(&mut self, symbol: &str, replacement: Option<&str>) {
+                self.deprecated.push((symbol.to_owned(), replacement.map(str::to_owned)));
+        }
+
+        /// Runs every compatibility check on the plugin at `filename` (An archive, a directory or
+        /// an object file) against this manager (Its SDK, API version, entry point, ...) and
+        /// returns the outcome of each. The plugin isn't loaded, so none of its code runs:
+        /// archives are only extracted while they're checked, and what the plugin exports is
+        /// read from its object file (Which needs the `preflight` feature, those checks are
+        /// skipped without it). See [`CompatibilityReport`](crate::compat::CompatibilityReport).
+        ///
+        /// An error is returned if the metadata of the plugin can't be read.
+        ///
+        /// ## Example
+        /// ```rust,no_run
+        /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let mut manager = vplugin::PluginManager::new();
+        /// let report = manager.compatibility_report("plugin.vpl")?;
+        /// if report.is_compatible() {
+        ///     let plugin = manager.load_plugin("plugin.vpl")?;
+        /// }
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn compatibility_report(&self, filename: impl AsRef<Path>) -> Result<CompatibilityReport, VPluginError> {
+                self.report(filename.as_ref(), &self.versions)
+        }
+
+        /* Runs every compatibility check on the plugin at `filename`, with the version requirements `versions`. */
+        fn report(&self, filename: &Path, versions: &Constraints) -> Result<CompatibilityReport, VPluginError> {
+                let _scope = self.diagnostics.scope(None);
+                let entry  = self.entry.to_string_lossy();
+                let host   = compat::Host {
+                        versions,
+                        licenses: self.license_policy.as_ref(),
+                        api     : self.host_api,
+                        entry   : &entry,
+                };
+                Plugin::inspect_with(filename, &self.workspace, |metadata, package| {
+                        Ok(CompatibilityReport::new(&metadata, package, &host))
+                })
+        }
+
+        /// Reads the metadata of the plugin at `filename` (An archive, a directory or an object
+        /// file) without loading it. Archives are only extracted while their metadata is read.
+        pub fn inspect(&self, filename: impl AsRef<Path>) -> Result<PluginMetadata, VPluginError> {
+                let _scope = self.diagnostics.scope(None);
+                Plugin::inspect(filename.as_ref(), &self.workspace)
+        }
+
+        /// Loads the plugin at `filename` in a separate process, so it can't take the application
+        /// down if it crashes. Its metadata is read by the application, the plugin itself is
+        /// loaded and started by the sandbox process. Only available with the `sandbox` feature,
+        /// see [`sandbox`](crate::sandbox).
+        #[cfg(feature = "sandbox")]
+        pub fn load_plugin_sandboxed(&mut self, filename: impl AsRef<Path>) -> Result<crate::sandbox::SandboxedPlugin, VPluginError> {
+                /* Before `inspect`, which extracts archives. */
+                #[cfg(feature = "signing")]
+                crate::signing::verify_package(filename.as_ref(), &self.trusted_keys)?;
+                let metadata = self.inspect(filename.as_ref())?;
+                let _scope   = self.diagnostics.scope(Some(&metadata.name));
+                crate::sandbox::SandboxedPlugin::spawn(
+                        metadata,
+                        &self.entry.to_string_lossy(),
+                        filename.as_ref(),
+                        self.diagnostics.clone()
+                )
+        }
+
+        /// Checks whether the plugins at `plugins` would work with version `host_version` of
+        /// the application, without loading them (Archives are only extracted to read their
+        /// metadata). Applications can use it while upgrading, to warn about the installed
+        /// plugins that will stop working with the new version.
+        /// 
+        /// Every check of [`compatibility_report`](crate::plugin_manager::PluginManager::compatibility_report)
+        /// is run, with the version requirements of `host_version` (The plugin's `requires_host`
+        /// range, and the versions the manager accepts, see
+        /// [`accept_plugins`](crate::plugin_manager::PluginManager::accept_plugins)). A report is
+        /// returned for every plugin, or the error that prevented reading it.
+        /// 
+        /// ## Example
+        /// ```rust,no_run
+        /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let mut manager = vplugin::PluginManager::new();
+        /// # let installed = ["plugin.vpl"];
+        /// for (path, report) in manager.check_compatibility("3.0", &installed)? {
+        ///     match report {
+        ///         Ok (report) if !report.is_compatible() => println!("{} won't work with 3.0:\n{}", path.display(), report),
+        ///         Ok (_)                                  => (),
+        ///         Err(e)                                  => println!("{} can't be read: {}", path.display(), e)
+        ///     }
+        /// }
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn check_compatibility<P: AsRef<Path>>(
+                &self,
+                host_version: &str,
+                plugins: &[P]
+        ) -> Result<CompatibilityMatrix, VPluginError> {
+                let versions = Constraints {
+                        host   : Some(version::parse(host_version)?),
+                        accepts: self.versions.accepts.clone()
+                };
+                Ok(plugins
+                        .iter()
+                        .map(|path| (path.as_ref().to_path_buf(), self.report(path.as_ref(), &versions)))
+                        .collect())
+        }
+
+        /// Sets the directory plugins keep their data in, like the values of their settings.
+        /// Every plugin gets its own directory inside it, named after the plugin (See
+        /// [`plugin_data_dir`](crate::plugin_manager::PluginManager::plugin_data_dir)).
+        /// 
+        /// There's no default: applications usually keep this next to their own data.
+        pub fn set_data_dir(&mut self, dir: impl AsRef<Path>) {
+                self.data_dir = Some(dir.as_ref().to_path_buf());
+        }
+
+        /// Returns the directory `plugin` keeps its data in. The directory is only created
+        /// once something is stored in it.
+        /// 
+        /// `VPluginError::ParametersError` is returned if the manager has no data directory
+        /// (See [`set_data_dir`](crate::plugin_manager::PluginManager::set_data_dir)).
+        pub fn plugin_data_dir(&self, plugin: &Plugin) -> Result<PathBuf, VPluginError> {
+                match &self.data_dir {
+                        Some(dir) => Ok(workspace::join(dir, Path::new(&plugin.metadata.name))),
+                        None      => {
+                                diagnostics::error!("No data directory was set, plugins can't store anything.");
+                                Err(VPluginError::ParametersError)
+                        }
+                }
+        }
+
+        /// Returns the current value of every setting `plugin` declares (See
+        /// [`SettingsSchema`](crate::SettingsSchema)): the values stored in its data directory,
+        /// and the defaults of the settings that were never changed.
+        pub fn settings(&self, plugin: &Plugin) -> Result<toml::Table, VPluginError> {
+                let _scope = self.diagnostics.scope(Some(&plugin.metadata.name));
+                settings::read(&plugin.metadata.settings, &self.plugin_data_dir(plugin)?)
+        }
+
+        /// Changes the settings of `plugin`, storing `changes` in its data directory so they're
+        /// kept across runs. If the plugin is running, the changes are then handed to it,
+        /// serialized as TOML, through its `vplugin_settings_changed` export:
+        /// ```c
+        /// void vplugin_settings_changed(const uint8_t *data, size_t len);
+        /// ```
+        /// The data is only valid during the call, and isn't nul-terminated.
+        /// 
+        /// Every value must fit the setting it's given to, otherwise nothing is changed and
+        /// `VPluginError::InvalidSetting` is returned.
+        /// 
+        /// ## Example
+        /// ```rust,no_run
+        /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let mut manager = vplugin::PluginManager::new();
+        /// # let plugin = manager.load_plugin("plugin.vpl")?;
+        /// let mut changes = toml::Table::new();
+        /// changes.insert("font_size".into(), 14.into());
+        /// manager.set_settings(&plugin, changes)?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn set_settings(&self, plugin: &Plugin, changes: toml::Table) -> Result<(), VPluginError> {
+                let _scope = self.diagnostics.scope(Some(&plugin.metadata.name));
+                settings::write(&plugin.metadata.settings, &self.plugin_data_dir(plugin)?, &changes)?;
+                settings::notify(plugin, &changes);
+                Ok(())
+        }
+
+        /// Changes a single setting of `plugin`. See
+        /// [`set_settings`](crate::plugin_manager::PluginManager::set_settings).
+        pub fn set_setting(&self, plugin: &Plugin, key: &str, value: impl Into<toml::Value>) -> Result<(), VPluginError> {
+                let mut changes = toml::Table::new();
+                changes.insert(key.into(), value.into());
+                self.set_settings(plugin, changes)
+        }
+
+        /// Sets the prompt asking the user whether a plugin may use a capability, when
+        /// it asks for one (See [`Permissions`](crate::permissions::Permissions)). The
+        /// prompt only has to show the request: decisions the user makes permanent are
+        /// remembered by VPlugin, in the data directory of the plugin.
+        /// 
+        /// ## Example
+        /// ```rust,no_run
+        /// # use vplugin::PermissionDecision;
+        /// # fn ask_user(_question: &str) -> bool { true }
+        /// # let mut manager = vplugin::PluginManager::new();
+        /// manager.set_permission_prompt(|request| {
+        ///     if ask_user(&format!("Allow {} to use {}?", request.plugin, request.capability)) {
+        ///         PermissionDecision::AllowAlways
+        ///     } else {
+        ///         PermissionDecision::DenyOnce
+        ///     }
+        /// });
+        /// ```
+        pub fn set_permission_prompt<F>(&mut self, prompt: F)
+        where
+                F: Fn(&PermissionRequest<'_>) -> PermissionDecision + Send + Sync + 'static
+        {
+                self.prompt = Some(Prompt(Arc::new(prompt)));
+        }
+
+        /// Returns the capabilities of `plugin`, asking the user through the prompt of the
+        /// manager. The data directory must be set, see
+        /// [`set_data_dir`](crate::plugin_manager::PluginManager::set_data_dir).
+        ///
+        /// Permanent decisions are remembered for the package `plugin` was loaded from (By its
+        /// [content hash](crate::plugin::Plugin::content_hash)), so another package with the same
+        /// name doesn't inherit them. Builtin plugins, which have none, are remembered by name.
+        /// `VPluginError::PermissionDenied` is returned for plugins loaded from a package that
+        /// couldn't be hashed.
+        pub fn permissions(&self, plugin: &Plugin) -> Result<Permissions, VPluginError> {
+                let _scope   = self.diagnostics.scope(Some(&plugin.metadata.name));
+                let identity = match identity::of(plugin) {
+                        Some(identity) => identity,
+                        None           => {
+                                diagnostics::error!("The package of the plugin wasn't hashed, its permissions can't be told apart from another package's.");
+                                return Err(VPluginError::PermissionDenied);
+                        }
+                };
+                Ok(Permissions::new(
+                        &plugin.metadata,
+                        &identity,
+                        self.permission_policy.clone(),
+                        self.plugin_data_dir(plugin)?,
+                        self.prompt.clone(),
+                        self.diagnostics.clone()
+                ))
+        }
+
+        /// Looks for the symbols the object file of the plugin at `filename` imports that can't
+        /// be resolved, without loading it. See [`SymbolReport`](crate::preflight::SymbolReport).
+        /// 
+        /// ## Example
+        /// ```rust,no_run
+        /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let mut manager = vplugin::PluginManager::new();
+        /// let report = manager.symbol_report("plugin.vpl")?;
+        /// if !report.is_clean() {
+        ///     eprint!("{}", report);
+        /// }
+        /// # Ok(())
+        /// # }
+        /// ```
+        #[cfg(feature = "preflight")]
+        pub fn symbol_report(&self, filename: impl AsRef<Path>) -> Result<crate::preflight::SymbolReport, VPluginError> {
+                Plugin::inspect_with(filename.as_ref(), &self.workspace, |metadata, workdir| {
+                        let _scope  = self.diagnostics.scope(Some(&metadata.name));
+                        let objfile = workspace::find(workdir, Path::new(&metadata.objfile));
+                        crate::preflight::analyze(&objfile, workdir, &metadata.needs).map(|report| crate::preflight::SymbolReport {
+                                objfile: metadata.objfile.into(),
+                                ..report
+                        })
+                })
+        }
+
+        /// Returns the names of the symbols (Functions and variables) the object file of the
+        /// plugin at `filename` exports, sorted, without loading it.
+        #[cfg(feature = "preflight")]
+        pub fn exports(&self, filename: impl AsRef<Path>) -> Result<Vec<String>, VPluginError> {
+                Plugin::inspect_with(filename.as_ref(), &self.workspace, |metadata, workdir| {
+                        let _scope = self.diagnostics.scope(Some(&metadata.name));
+                        crate::preflight::exports(&workspace::find(workdir, Path::new(&metadata.objfile)))
+                })
+        }
+
+        /// Injects the faults of `injector` into the plugins loaded from now on, and into the
+        /// entry points called by the manager. See [`FaultInjector`](crate::testing::FaultInjector).
+        #[cfg(feature = "testing")]
+        pub fn set_fault_injector(&mut self, injector: std::sync::Arc<crate::testing::FaultInjector>) {
+                self.faults = Faults(Some(injector));
+        }
+
         /// Returns a hook from the plugin specified.
         /// See [VHook](crate::plugin_manager::VHook) for more information.
         pub fn get_hook(&mut self, plugin: &Plugin, hook: &str) -> Result<VHook, VPluginError> {
@@ -102,12 +954,13 @@ impl PluginManager {
         /// 'T' and 'P':
         /// - `T` is the return type of the function representing the hook,
         /// - `P` is the actual function declaration (Don't add `unsafe extern fn`, it's already specified).
+        /// 
         /// The function pointer returned can then be used to exchange data between the server and the plugin.
         pub fn get_custom_hook<P, T>(
                 &self,
                 plugin: &Plugin,
                 hook: impl AsRef<str>,
-        ) -> Result<unsafe extern fn(P) -> T, VPluginError> {
+        ) -> Result<unsafe extern "C" fn(P) -> T, VPluginError> {
                 plugin.get_custom_hook(hook)
         }
         
@@ -115,64 +968,1115 @@ impl PluginManager {
         /// 
         /// This function is used to execute the entry point of the plugin,
         /// effectively starting the plugin like a normal executable.
+        ///
+        /// The plugins it depends on (See the `[dependencies]` section of its metadata) are
+        /// started first, out of those owned by the manager. `VPluginError::MissingDependency`
+        /// is returned if one of them isn't loaded, and `VPluginError::CyclicDependency` if
+        /// they depend on each other. Dependencies started stay started, even if the plugin
+        /// fails to start afterwards.
         pub fn begin_plugin(&mut self, plugin: &mut Plugin) -> Result<(), VPluginError> {
-                if !plugin.is_valid {
-                        log::error!(
-                                "Attempted to start plugin '{}', which is not marked as valid.",
-                                plugin.get_metadata().name
-                        );
-                        return Err(VPluginError::InvalidPlugin);
+                self.start(plugin, None)
+        }
+
+        /// Executes the entry point of the plugin like
+        /// [`begin_plugin`](crate::plugin_manager::PluginManager::begin_plugin), after checking
+        /// that it can be called the way VPlugin calls it (`fn() -> i32`), instead of risking
+        /// undefined behavior:
+        /// - The package must declare the signature of its entry point, through the hash
+        ///   `vplugin-package` writes in the `entry_signature` field of its metadata (See
+        ///   [`HookSignature::hash`](vplugin_core::manifest::HookSignature::hash)), and it
+        ///   must be `fn() -> i32`.
+        /// - With the `preflight` feature, the entry point must be a function in the object
+        ///   file of the plugin, not a variable. Plugins whose object file isn't on the disk
+        ///   (Loaded from memory, ...) skip this check.
+        ///
+        /// `VPluginError::SignatureMismatch` is returned otherwise, and the plugin isn't started.
+        ///
+        /// ## Example
+        /// ```rust,no_run
+        /// # use vplugin::VPluginError;
+        /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let mut manager = vplugin::PluginManager::new();
+        /// let mut plugin = manager.load_plugin("plugin.vpl")?;
+        /// match manager.begin_plugin_checked(&mut plugin) {
+        ///     Err(VPluginError::SignatureMismatch { reason, .. }) => eprintln!("Broken plugin: {}", reason),
+        ///     other => other?
+        /// }
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn begin_plugin_checked(&mut self, plugin: &mut Plugin) -> Result<(), VPluginError> {
+                {
+                        let _scope = self.diagnostics.scope(Some(&plugin.metadata.name));
+                        self.check_entry_point(plugin)?;
                 }
+                self.begin_plugin(plugin)
+        }
 
-                if plugin.started {
-                        log::error!(
-                                "Plugin '{}' has already been initialized.",
-                                plugin.get_metadata().name
-                        );
-                        return Err(VPluginError::FailedToInitialize);
+        /// Executes the entry point of the plugin like
+        /// [`begin_plugin`](crate::plugin_manager::PluginManager::begin_plugin), but first
+        /// hands it `config` serialized as TOML, through its `vplugin_configure` export:
+        /// ```c
+        /// int vplugin_configure(const uint8_t *data, size_t len);
+        /// ```
+        /// This way plugins get their settings from the application, instead of reading its
+        /// configuration files themselves. The data is only valid during the call, and isn't
+        /// nul-terminated.
+        /// 
+        /// `config` overrides the defaults from the `[config]` section of the plugin's
+        /// `metadata.toml` and the configuration set with
+        /// [`set_config`](crate::plugin_manager::PluginManager::set_config), which the plugin
+        /// gets even when started with [`begin_plugin`](crate::plugin_manager::PluginManager::begin_plugin).
+        /// Tables are merged key by key, other values replace each other.
+        /// 
+        /// If `vplugin_configure` returns anything but 0, the plugin isn't started and
+        /// `VPluginError::FailedToInitialize` is returned. Plugins that don't export it are
+        /// started anyway, with a warning. `config` must serialize to a table (A struct or
+        /// a map), otherwise `VPluginError::ParametersError` is returned.
+        /// 
+        /// ## Example
+        /// ```rust,no_run
+        /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let mut manager = vplugin::PluginManager::new();
+        /// # let mut plugin = manager.load_plugin("plugin.vpl")?;
+        /// #[derive(serde_derive::Serialize)]
+        /// struct Settings { theme: String, autosave: bool }
+        ///
+        /// manager.begin_plugin_with_config(&mut plugin, &Settings { theme: "dark".into(), autosave: true })?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn begin_plugin_with_config<C>(&mut self, plugin: &mut Plugin, config: &C) -> Result<(), VPluginError>
+        where
+                C: Serialize + ?Sized
+        {
+                let config = match toml::to_string(config) {
+                        Ok (config) => config,
+                        Err(e)      => {
+                                let _scope = self.diagnostics.scope(Some(&plugin.metadata.name));
+                                diagnostics::error!("Couldn't serialize the configuration of the plugin: {}", e);
+                                return Err(VPluginError::ParametersError);
+                        }
+                };
+                self.start(plugin, Some(&config))
+        }
+
+        /// Sets the configuration handed to the plugin named `plugin` whenever it's started
+        /// from now on, overriding the defaults from the `[config]` section of its
+        /// `metadata.toml` (See [`begin_plugin_with_config`](crate::plugin_manager::PluginManager::begin_plugin_with_config)).
+        /// Setting it again replaces the previous configuration. `config` must serialize
+        /// to a table (A struct or a map), otherwise `VPluginError::ParametersError` is returned.
+        /// 
+        /// ## Example
+        /// ```rust,no_run
+        /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let mut manager = vplugin::PluginManager::new();
+        /// # let plugin = manager.load("plugin.vpl")?;
+        /// manager.set_config("ExamplePlugin", &toml::toml! { theme = "dark" })?;
+        /// manager.begin(plugin)?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn set_config<C>(&mut self, plugin: &str, config: &C) -> Result<(), VPluginError>
+        where
+                C: Serialize + ?Sized
+        {
+                let config = match toml::Table::try_from(config) {
+                        Ok (config) => config,
+                        Err(e)      => {
+                                let _scope = self.diagnostics.scope(Some(plugin));
+                                diagnostics::error!("Couldn't serialize the configuration of the plugin: {}", e);
+                                return Err(VPluginError::ParametersError);
+                        }
+                };
+                self.config_overrides.insert(plugin.to_owned(), config);
+                Ok(())
+        }
+
+        /* Starts `plugin`, handing it `config` first if there is one. */
+        fn start(&mut self, plugin: &mut Plugin, config: Option<&str>) -> Result<(), VPluginError> {
+                let _scope = self.diagnostics.scope(Some(&plugin.metadata.name));
+                plugin.check_transition(PluginState::Started)?;
+                /* The host may have changed its version since the plugin was loaded. */
+                if let Some(host) = self.host_api {
+                        api::check_declared(&plugin.metadata, host)?;
+                }
+                let supplied = config;
+                let config   = self.effective_config(plugin, config)?;
+                let config   = config.as_deref();
+                if !plugin.metadata.dependencies.is_empty() {
+                        let metadata = plugin.metadata.clone();
+                        self.start_dependencies(&metadata, &mut vec![metadata.name.clone()])?;
+                }
+                self.check_required_hooks(plugin)?;
+                /* Only panics reported by the entry point are attached to its error. */
+                plugin.panics.take();
+                plugin.backtrace = None;
+                let started = Instant::now();
+                let result  = self.run_entry_point(plugin, config).map_err(|e| match plugin.panics.take() {
+                        Some(report) => {
+                                plugin.backtrace = report.backtrace;
+                                VPluginError::PluginPanicked {
+                                        message : report.message,
+                                        location: report.location.unwrap_or_default(),
+                                }
+                        },
+                        None => e
+                });
+                if result.is_ok() {
+                        /* Only what the caller supplied: defaults and overrides are merged again next time. */
+                        plugin.config = supplied.map(str::to_owned);
+                        self.starts += 1;
+                        plugin.start_order = self.starts;
+                }
+                self.telemetry.started(&plugin.metadata, result.as_ref().copied(), plugin.backtrace.as_deref(), started);
+                self.observers.started(plugin, result.as_ref().copied());
+                result
+        }
+
+        /*
+         * The configuration `plugin` is started with: its defaults from `metadata.toml`, then
+         * what was set with `set_config`, then `config`. Plugins without any are left alone.
+         */
+        fn effective_config(&self, plugin: &Plugin, config: Option<&str>) -> Result<Option<String>, VPluginError> {
+                let overrides = self.config_overrides.get(&plugin.metadata.name);
+                if plugin.metadata.config.is_empty() && overrides.is_none() {
+                        return Ok(config.map(str::to_owned));
+                }
+                let mut merged = plugin.metadata.config.clone();
+                if let Some(overrides) = overrides {
+                        Self::merge_config(&mut merged, overrides.clone());
                 }
+                if let Some(config) = config {
+                        match toml::from_str(config) {
+                                Ok (config) => Self::merge_config(&mut merged, config),
+                                Err(e)      => {
+                                        diagnostics::error!("Couldn't read the configuration of the plugin: {}", e);
+                                        return Err(VPluginError::ParametersError);
+                                }
+                        }
+                }
+                match toml::to_string(&merged) {
+                        Ok (merged) => Ok(Some(merged)),
+                        Err(e)      => {
+                                diagnostics::error!("Couldn't serialize the configuration of the plugin: {}", e);
+                                Err(VPluginError::ParametersError)
+                        }
+                }
+        }
 
-                let plugin_entry: Symbol<unsafe extern "C" fn() -> i32>;
+        /* Refuses `plugin` if it doesn't export every hook required with `require_hooks`. */
+        fn check_required_hooks(&self, plugin: &Plugin) -> Result<(), VPluginError> {
+                let missing: Vec<&str> = self.required_hooks
+                        .iter()
+                        .copied()
+                        /* Only looked up, never called. */
+                        .filter(|hook| unsafe { plugin.symbol::<*mut std::ffi::c_void>(hook) }.is_err())
+                        .collect();
+                if missing.is_empty() {
+                        return Ok(());
+                }
+                diagnostics::error!(
+                        "Plugin '{}' doesn't export the required hooks: {}. Not starting it.",
+                        plugin.metadata.name,
+                        missing.join(", ")
+                );
+                Err(VPluginError::MissingSymbol)
+        }
+
+        /*
+         * Starts the plugins `metadata` depends on that aren't started yet, their own dependencies
+         * first. `chain` holds the plugins waiting for their dependencies, to detect cycles.
+         */
+        fn start_dependencies(&mut self, metadata: &PluginMetadata, chain: &mut Vec<String>) -> Result<(), VPluginError> {
+                for (name, requirement) in &metadata.dependencies {
+                        if let Some(position) = chain.iter().position(|plugin| plugin == name) {
+                                let mut cycle = chain[position..].to_vec();
+                                cycle.push(name.clone());
+                                diagnostics::error!("Cyclic dependency between plugins: {}", cycle.join(" -> "));
+                                return Err(VPluginError::CyclicDependency { cycle });
+                        }
+                        let handle = match self.find_dependency(name, requirement) {
+                                Some(handle) => handle,
+                                None         => {
+                                        diagnostics::error!(
+                                                "Plugin '{}' depends on '{}' ({}), but no matching version of it is loaded.",
+                                                metadata.name,
+                                                name,
+                                                requirement
+                                        );
+                                        return Err(VPluginError::MissingDependency {
+                                                plugin     : metadata.name.clone(),
+                                                dependency : name.clone(),
+                                                requirement: requirement.clone()
+                                        });
+                                }
+                        };
+                        let dependency = self.handle(handle)?;
+                        if dependency.state == PluginState::Started {
+                                continue;
+                        }
+
+                        let dependency = dependency.metadata.clone();
+                        chain.push(name.clone());
+                        self.start_dependencies(&dependency, chain)?;
+                        chain.pop();
+                        diagnostics::trace!("Starting '{}', which '{}' depends on.", name, metadata.name);
+                        self.begin(handle)?;
+                }
+                Ok(())
+        }
+
+        /*
+         * Returns the plugin named `name` matching `requirement` a plugin depends on: the one
+         * already started if any, the highest version otherwise.
+         */
+        fn find_dependency(&self, name: &str, requirement: &str) -> Option<PluginHandle> {
+                let requirement = version::parse_req(requirement).ok()?;
+                self.plugins
+                        .iter()
+                        .filter(|(_, plugin)| plugin.metadata.name == name)
+                        .filter_map(|(handle, plugin)| {
+                                let version = version::parse(&plugin.metadata.version).ok()?;
+                                requirement.matches(&version).then_some((handle, plugin.state == PluginState::Started, version))
+                        })
+                        .max_by(|(_, a_started, a), (_, b_started, b)| (a_started, a).cmp(&(b_started, b)))
+                        .map(|(handle, _, _)| handle)
+        }
+
+        /* Sorts `handles` so the dependencies of every plugin come before it (Cycles are left as they are). */
+        fn dependency_order(&self, handles: Vec<PluginHandle>) -> Vec<PluginHandle> {
+                fn visit(manager: &PluginManager, handle: PluginHandle, pending: &[PluginHandle], sorted: &mut Vec<PluginHandle>) {
+                        if sorted.contains(&handle) {
+                                return;
+                        }
+                        sorted.push(handle);
+                        let position = sorted.len() - 1;
+                        if let Some(plugin) = manager.plugins.get(handle) {
+                                for (name, requirement) in &plugin.metadata.dependencies {
+                                        match manager.find_dependency(name, requirement) {
+                                                Some(dependency) if pending.contains(&dependency) => visit(manager, dependency, pending, sorted),
+                                                _                                                 => ()
+                                        }
+                                }
+                        }
+                        /* Moved after its dependencies, which were just added. */
+                        let handle = sorted.remove(position);
+                        sorted.push(handle);
+                }
+
+                let mut sorted = Vec::with_capacity(handles.len());
+                for &handle in &handles {
+                        visit(self, handle, &handles, &mut sorted);
+                }
+                sorted
+        }
+
+        /* Runs the entry point of `plugin`, which can be started. */
+        fn run_entry_point(&mut self, plugin: &mut Plugin, config: Option<&str>) -> Result<(), VPluginError> {
+                if let Err(e) = self.faults.check(Stage::EntryPoint) {
+                        plugin.failed = true;
+                        return Err(e);
+                }
+                if let Some(config) = config {
+                        if let Err(e) = Self::configure(plugin, config) {
+                                plugin.failed = true;
+                                return Err(e);
+                        }
+                }
+
+                let plugin_entry: unsafe extern "C-unwind" fn() -> i32;
                 unsafe {
-                        plugin_entry = match plugin.raw
-                                        .as_ref()
-                                        .unwrap()
-                                        .get(self.entry.as_bytes())
+                        plugin_entry = match self.entry_point(plugin)
                                         {
                                                 Ok(fnc) => fnc,
                                                 Err(e)  => {
-                                                        log::error!(
+                                                        diagnostics::error!(
                                                                 "Couldn't initialize plugin: {}",
-                                                                e.to_string()
+                                                                e
                                                         );
+                                                        plugin.failed = true;
                                                         return Err(VPluginError::FailedToInitialize)
                                                 }
                                         };
 
-                        let ___result = plugin_entry();
+                        let ___result = match plugin.run_export(CallKind::EntryPoint, Export::Status(plugin_entry)) {
+                                Ok (v) => v,
+                                Err(e) => {
+                                        plugin.failed = true;
+                                        return Err(e);
+                                }
+                        };
                         if ___result != 0 {
-                                log::error!("Couldn't start plugin: Entry point '{}' did not return success", self.entry.as_c_str().to_string_lossy());
+                                diagnostics::error!(
+                                        "Couldn't start plugin: Entry point '{}' did not return success",
+                                        self.entry.as_c_str().to_string_lossy()
+                                );
+                                plugin.failed = true;
                                 return Err(VPluginError::FailedToInitialize);
                         }
                 }
 
-                plugin.started = true;
+                plugin.transition(PluginState::Started)
+        }
+}
+
+impl PluginManager {
+        /* Merges `overrides` into `base`, table by table. */
+        fn merge_config(base: &mut toml::Table, overrides: toml::Table) {
+                for (key, value) in overrides {
+                        match (base.get_mut(&key), value) {
+                                (Some(toml::Value::Table(base)), toml::Value::Table(value)) => Self::merge_config(base, value),
+                                (_, value)                                                  => { base.insert(key, value); }
+                        }
+                }
+        }
+
+        /* Hands `config` to the `vplugin_configure` export of `plugin`. */
+        fn configure(plugin: &Plugin, config: &str) -> Result<(), VPluginError> {
+                let configure = match unsafe { plugin.symbol::<unsafe extern "C-unwind" fn(*const u8, usize) -> i32>(CONFIGURE_SYMBOL) } {
+                        Ok (configure) => configure,
+                        Err(_)         => {
+                                diagnostics::warning!(
+                                        "Plugin doesn't export {}, starting it without its configuration.",
+                                        CONFIGURE_SYMBOL
+                                );
+                                return Ok(());
+                        }
+                };
+                match plugin.run_export(CallKind::Configure, Export::Configure(configure, config.as_ptr(), config.len()))? {
+                        0      => Ok(()),
+                        status => {
+                                diagnostics::error!("Plugin refused its configuration ({} returned {}).", CONFIGURE_SYMBOL, status);
+                                Err(VPluginError::FailedToInitialize)
+                        }
+                }
+        }
+
+        /*
+         * Checks that the entry point of `plugin` is what VPlugin calls it as, see `begin_plugin_checked`.
+         */
+        fn check_entry_point(&self, plugin: &Plugin) -> Result<(), VPluginError> {
+                let entry = self.entry.to_string_lossy().into_owned();
+                let mismatch = |reason: String| {
+                        diagnostics::error!("Entry point '{}' of plugin '{}' can't be called: {}.", entry, plugin.metadata.name, reason);
+                        VPluginError::SignatureMismatch { symbol: entry.clone(), reason }
+                };
+
+                verify_entry_signature(&plugin.metadata).map_err(mismatch)?;
+
+                #[cfg(feature = "preflight")]
+                match plugin.objfile_on_disk() {
+                        Some(objfile) => {
+                                if crate::preflight::is_function(&objfile, &entry)? == Some(false) {
+                                        return Err(mismatch("it's not a function".into()));
+                                }
+                        },
+                        None => diagnostics::trace!("The object file of the plugin isn't on the disk, not checking that its entry point is a function.")
+                }
+                Ok(())
+        }
+
+        /*
+         * Looks up the entry point of `plugin`. Plugins that only export the legacy `vplugin_init`
+         * while the host configured another entry point aren't started, but are reported so their
+         * authors know what to export instead.
+         */
+        unsafe fn entry_point(&self, plugin: &Plugin) -> Result<unsafe extern "C-unwind" fn() -> i32, String> {
+                let entry = self.entry.to_string_lossy();
+                let error = match plugin.symbol(&entry) {
+                        Ok (entry) => return Ok(entry),
+                        Err(e)     => e
+                };
+                if entry != LEGACY_ENTRY_POINT && plugin.symbol::<*mut std::ffi::c_void>(LEGACY_ENTRY_POINT).is_ok() {
+                        diagnostics::deprecated(
+                                Deprecation { symbol: LEGACY_ENTRY_POINT, replacement: Some(&entry) },
+                                format_args!(
+                                        "Plugin only exports the legacy entry point '{}', it has to export '{}' instead.",
+                                        LEGACY_ENTRY_POINT,
+                                        entry
+                                )
+                        );
+                }
+                Err(error)
+        }
+}
+
+/// ## Owned plugins
+/// Instead of passing `&mut Plugin` around, the manager can own its plugins and hand
+/// out [`PluginHandle`](crate::handle::PluginHandle)s, which can be copied freely and
+/// stored by any part of the application.
+impl PluginManager {
+        /// Loads a plugin (See [`load_plugin`](crate::plugin_manager::PluginManager::load_plugin)),
+        /// keeps it inside the manager and returns a handle to it.
+        pub fn load(&mut self, filename: impl AsRef<Path>) -> Result<PluginHandle, VPluginError> {
+                let plugin = self.load_plugin(filename)?;
+                Ok(self.insert(plugin))
+        }
+
+        /// Loads a plugin like [`load`](crate::plugin_manager::PluginManager::load), calling
+        /// `progress` as it goes so the application can show a progress bar, or cancel loading
+        /// by returning [`ControlFlow::Break`](std::ops::ControlFlow::Break). See
+        /// [`progress`](crate::progress).
+        ///
+        /// ## Example
+        /// ```rust,no_run
+        /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let mut manager = vplugin::PluginManager::new();
+        /// use std::ops::ControlFlow;
+        ///
+        /// let handle = manager.load_with_progress("assets-pack.vpl", |progress| {
+        ///     if let Some(total) = progress.total_bytes {
+        ///         println!("{:?}: {}%", progress.phase, progress.bytes * 100 / total.max(1));
+        ///     }
+        ///     ControlFlow::Continue(())
+        /// })?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn load_with_progress(
+                &mut self,
+                filename: impl AsRef<Path>,
+                progress: impl FnMut(&LoadProgress) -> std::ops::ControlFlow<()>
+        ) -> Result<PluginHandle, VPluginError> {
+                let plugin = progress::with_reporter(progress, |reporter| self.load_as(filename.as_ref(), false, Some(reporter)))?;
+                Ok(self.insert(plugin))
+        }
+
+        /// Loads a plugin from a `.vpl` archive held in memory (See
+        /// [`load_plugin_from_bytes`](crate::plugin_manager::PluginManager::load_plugin_from_bytes)),
+        /// keeps it inside the manager and returns a handle to it.
+        #[cfg(feature = "archive")]
+        pub fn load_from_bytes(&mut self, data: &[u8]) -> Result<PluginHandle, VPluginError> {
+                let plugin = self.load_plugin_from_bytes(data)?;
+                Ok(self.insert(plugin))
+        }
+
+        /// Loads every plugin out of `filenames`, keeps them inside the manager and returns
+        /// their handles, in the same order. The plugins are only added to the manager if all
+        /// of them load: otherwise those loaded so far are dropped (And terminated first if the
+        /// manager starts plugins on load), which unloads their libraries and removes the
+        /// directories they were extracted into, and the error of the plugin that failed is
+        /// returned.
+        ///
+        /// This is meant for plugins that only work together, like the parts of a single feature.
+        ///
+        /// ## Example
+        /// ```rust,no_run
+        /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+        /// let mut manager = vplugin::PluginManager::new();
+        /// let handles = manager.load_transaction(["sync-core.vpl", "sync-ui.vpl", "sync-cloud.vpl"])?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn load_transaction<I, P>(&mut self, filenames: I) -> Result<Vec<PluginHandle>, VPluginError>
+        where
+                I: IntoIterator<Item = P>,
+                P: AsRef<Path>
+        {
+                let mut loaded = Vec::new();
+                for filename in filenames {
+                        let error = match self.load_plugin(filename.as_ref()) {
+                                Ok (plugin) => {
+                                        loaded.push(plugin);
+                                        continue;
+                                },
+                                Err(e)      => e
+                        };
+                        let _scope = self.diagnostics.scope(None);
+                        diagnostics::error!(
+                                "Couldn't load {} ({}), rolling back the {} plugin(s) loaded with it.",
+                                filename.as_ref().display(),
+                                error,
+                                loaded.len()
+                        );
+                        while let Some(mut plugin) = loaded.pop() {
+                                if plugin.state == PluginState::Started {
+                                        let _ = plugin.terminate();
+                                }
+                        }
+                        return Err(error);
+                }
+                Ok(loaded.into_iter().map(|plugin| self.insert(plugin)).collect())
+        }
+
+        /// Loads another instance of the plugin at `filename`, independent from the instances
+        /// already loaded, keeps it inside the manager and returns a handle to it. Instances
+        /// are told apart by their [`instance_id`](crate::plugin::Plugin::instance_id), see
+        /// [`instance`](crate::plugin_manager::PluginManager::instance).
+        ///
+        /// Archives are extracted again every time they're loaded, so [`load`](crate::plugin_manager::PluginManager::load)
+        /// already gives them their own library. Directories and object files are loaded in
+        /// place, where the dynamic linker would hand back the library of the first instance
+        /// (Sharing its global state): this copies them into the workspace of the manager first.
+        /// On Windows they're always copied, so this is the same as `load`.
+        ///
+        /// ## Example
+        /// ```rust,no_run
+        /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let mut manager = vplugin::PluginManager::new();
+        /// let reverb = manager.load_instance("effects/reverb")?;
+        /// let second = manager.load_instance("effects/reverb")?;
+        /// let id     = manager.plugin(second).unwrap().instance_id();
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn load_instance(&mut self, filename: impl AsRef<Path>) -> Result<PluginHandle, VPluginError> {
+                let plugin = self.load_as(filename.as_ref(), true, None)?;
+                Ok(self.insert(plugin))
+        }
+
+        /// Returns the handle of the plugin whose [`instance_id`](crate::plugin::Plugin::instance_id)
+        /// is `id`, if the manager owns it.
+        pub fn instance(&self, id: Uuid) -> Option<PluginHandle> {
+                self.plugins
+                        .iter()
+                        .find(|(_, plugin)| plugin.instance_id == id)
+                        .map(|(handle, _)| handle)
+        }
+
+        /// Returns the handles of every instance of the plugin named `name` owned by the manager.
+        pub fn instances<'a>(&'a self, name: &'a str) -> impl Iterator<Item = PluginHandle> + 'a {
+                self.plugins
+                        .iter()
+                        .filter(move |(_, plugin)| plugin.metadata.name == name)
+                        .map(|(handle, _)| handle)
+        }
+
+        /// Loads a plugin the application can't run without, see
+        /// [`set_requirement`](crate::plugin_manager::PluginManager::set_requirement).
+        pub fn load_required(&mut self, filename: impl AsRef<Path>) -> Result<PluginHandle, VPluginError> {
+                let handle = self.load(filename)?;
+                self.set_requirement(handle, Requirement::Required)?;
+                Ok(handle)
+        }
+
+        /// Moves an already loaded plugin into the manager and returns a handle to it.
+        pub fn insert(&mut self, mut plugin: Plugin) -> PluginHandle {
+                plugin.interceptors = self.interceptors.clone();
+                plugin.observers = self.observers.clone();
+                self.plugins.insert(plugin)
+        }
+
+        /// Removes the plugin from the manager and returns it, giving its ownership
+        /// back to the caller. The handle (And every copy of it) becomes invalid.
+        pub fn remove(&mut self, handle: PluginHandle) -> Option<Plugin> {
+                self.plugins.remove(handle)
+        }
+
+        /// Returns the plugin referred to by `handle`, if it's still owned by the manager.
+        pub fn plugin(&self, handle: PluginHandle) -> Option<&Plugin> {
+                self.plugins.get(handle)
+        }
+
+        /// Returns the plugin referred to by `handle` mutably, if it's still owned by the manager.
+        pub fn plugin_mut(&mut self, handle: PluginHandle) -> Option<&mut Plugin> {
+                self.plugins.get_mut(handle)
+        }
+
+        /// Executes the entry point of the plugin referred to by `handle`.
+        /// See [`begin_plugin`](crate::plugin_manager::PluginManager::begin_plugin).
+        pub fn begin(&mut self, handle: PluginHandle) -> Result<(), VPluginError> {
+                let mut plugin = self.take(handle)?;
+                let result = self.begin_plugin(&mut plugin);
+                self.put_back(handle, plugin);
+                result
+        }
+
+        /// Executes the entry point of the plugin referred to by `handle`, handing it `config`
+        /// first. See [`begin_plugin_with_config`](crate::plugin_manager::PluginManager::begin_plugin_with_config).
+        pub fn begin_with_config<C>(&mut self, handle: PluginHandle, config: &C) -> Result<(), VPluginError>
+        where
+                C: Serialize + ?Sized
+        {
+                let mut plugin = self.take(handle)?;
+                let result = self.begin_plugin_with_config(&mut plugin, config);
+                self.put_back(handle, plugin);
+                result
+        }
+
+        /// Sets whether the application can run without the plugin referred to by `handle`,
+        /// which decides what [`begin_all`](crate::plugin_manager::PluginManager::begin_all)
+        /// does if it fails to start. Plugins are optional by default.
+        pub fn set_requirement(&mut self, handle: PluginHandle, requirement: Requirement) -> Result<(), VPluginError> {
+                self.handle_mut(handle)?.requirement = requirement;
+                Ok(())
+        }
+
+        /// Starts every plugin owned by the manager that isn't started yet, in the order
+        /// they were added (Except that plugins are started after their dependencies).
+        ///
+        /// Optional plugins that fail to start are reported and listed in the returned
+        /// [`StartupSummary`](crate::startup::StartupSummary), so the application can run
+        /// without them. If a required plugin fails, the plugins started so far are
+        /// terminated again and `VPluginError::RequiredPluginFailed` is returned.
+        ///
+        /// ## Example
+        /// ```rust,no_run
+        /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+        /// let mut manager = vplugin::PluginManager::new();
+        /// manager.load_required("storage.vpl")?;
+        /// manager.load("spellcheck.vpl")?;
+        ///
+        /// let summary = manager.begin_all()?;
+        /// for failure in &summary.failed {
+        ///     println!("{} is unavailable: {}", failure.plugin, failure.error);
+        /// }
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn begin_all(&mut self) -> Result<StartupSummary, VPluginError> {
+                let mut summary = StartupSummary::default();
+                let pending: Vec<PluginHandle> = self.plugins
+                        .iter()
+                        .filter(|(_, plugin)| plugin.state != PluginState::Started)
+                        .map(|(handle, _)| handle)
+                        .collect();
+
+                for handle in self.dependency_order(pending) {
+                        /* Started meanwhile, as the dependency of another plugin. */
+                        if self.handle(handle)?.state == PluginState::Started {
+                                summary.started.push(handle);
+                                continue;
+                        }
+                        let error = match self.begin(handle) {
+                                Ok (()) => {
+                                        summary.started.push(handle);
+                                        continue;
+                                },
+                                Err(e)  => e
+                        };
+                        let plugin = self.handle(handle)?;
+                        let name   = plugin.metadata.name.clone();
+                        let _scope = self.diagnostics.scope(Some(&name));
+                        if plugin.requirement == Requirement::Optional {
+                                diagnostics::warning!("Optional plugin failed to start ({}), continuing without it.", error);
+                                summary.failed.push(StartupFailure { handle, plugin: name, error });
+                                continue;
+                        }
+
+                        diagnostics::error!("Required plugin failed to start ({}), terminating the plugins started.", error);
+                        for started in summary.started.into_iter().rev() {
+                                let _ = self.terminate(started);
+                        }
+                        return Err(VPluginError::RequiredPluginFailed { plugin: name, error: Box::new(error) });
+                }
+                Ok(summary)
+        }
+
+        /// Terminates the plugin referred to by `handle`. The plugin stays owned by the
+        /// manager and can be started again. See [`Plugin::terminate`](crate::plugin::Plugin::terminate).
+        pub fn terminate(&mut self, handle: PluginHandle) -> Result<(), VPluginError> {
+                self.handle_mut(handle)?.terminate()
+        }
+
+        /// Asks the plugin referred to by `handle` to stop, then terminates it. Plugins that
+        /// don't acknowledge within `timeout` are terminated by force.
+        /// See [`Plugin::request_stop`](crate::plugin::Plugin::request_stop).
+        pub fn request_stop(&mut self, handle: PluginHandle, timeout: Duration) -> Result<(), VPluginError> {
+                self.handle_mut(handle)?.request_stop(timeout)
+        }
+
+        /// Terminates every started plugin owned by the manager, in the reverse order they
+        /// were started (So plugins are terminated before the plugins they depend on), then
+        /// unloads all of them and removes the directory they were extracted into. Plugins
+        /// failing to terminate don't stop the others, their errors are collected in the
+        /// returned [`ShutdownReport`](crate::ShutdownReport).
+        ///
+        /// The manager is empty afterwards, and can still load plugins. Dropping the manager
+        /// does the same, without terminating the plugins or reporting anything.
+        ///
+        /// ## Example
+        /// ```rust,no_run
+        /// # let mut manager = vplugin::PluginManager::new();
+        /// let report = manager.shutdown();
+        /// if !report.is_clean() {
+        ///     eprintln!("{} plugin(s) didn't terminate cleanly", report.failed.len());
+        /// }
+        /// ```
+        pub fn shutdown(&mut self) -> ShutdownReport {
+                let _scope = self.diagnostics.scope(None);
+                let mut started: Vec<(PluginHandle, u64)> = self.plugins
+                        .iter()
+                        .filter(|(_, plugin)| plugin.state == PluginState::Started)
+                        .map(|(handle, plugin)| (handle, plugin.start_order))
+                        .collect();
+                /* Plugins started before being added to the manager (Order 0) go first, in the order they were added. */
+                started.sort_by_key(|&(_, order)| match order {
+                        0     => std::cmp::Reverse(u64::MAX),
+                        order => std::cmp::Reverse(order)
+                });
+
+                let mut report = ShutdownReport::default();
+                for (handle, _) in started {
+                        let plugin = match self.plugins.get_mut(handle) {
+                                Some(plugin) => plugin,
+                                None         => continue
+                        };
+                        let name = plugin.metadata.name.clone();
+                        match plugin.terminate() {
+                                Ok (()) => report.terminated.push(name),
+                                Err(e)  => {
+                                        diagnostics::warning!("Plugin '{}' failed to terminate ({}), unloading it anyway.", name, e);
+                                        report.failed.push(ShutdownFailure { plugin: name, error: e });
+                                }
+                        }
+                }
+                self.release_plugins();
+                report
+        }
+
+        /// Returns a hook from the plugin referred to by `handle`.
+        /// See [VHook](crate::plugin_manager::VHook) for more information.
+        pub fn hook(&self, handle: PluginHandle, hook: &str) -> Result<VHook, VPluginError> {
+                self.handle(handle)?.get_hook(hook)
+        }
+
+        /// Returns a hook from the plugin referred to by `handle`.
+        /// See [`get_custom_hook`](crate::plugin_manager::PluginManager::get_custom_hook).
+        pub fn custom_hook<P, T>(
+                &self,
+                handle: PluginHandle,
+                hook: impl AsRef<str>,
+        ) -> Result<unsafe extern "C" fn(P) -> T, VPluginError> {
+                self.handle(handle)?.get_custom_hook(hook)
+        }
+
+        /// Calls the hook `name` of the plugin referred to by `handle`, wrapped by the
+        /// interceptors of the manager. See [`Plugin::call_hook`](crate::plugin::Plugin::call_hook).
+        ///
+        /// ## Safety
+        /// `F` must be the actual type of the symbol, and `call` must not keep it around.
+        pub unsafe fn call_hook<F: Copy, R>(
+                &self,
+                handle: PluginHandle,
+                name: impl AsRef<str>,
+                call: impl FnOnce(F) -> R,
+        ) -> Result<R, VPluginError> {
+                self.handle(handle)?.call_hook(name, call)
+        }
+
+        /// Resolves the set of hooks `H` of the plugin referred to by `handle`.
+        /// See [`declare_hooks!`](crate::declare_hooks).
+        pub fn hooks<H: Hooks>(&self, handle: PluginHandle) -> Result<H, VPluginError> {
+                self.handle(handle)?.hooks()
+        }
+
+        /// Returns the implementations of the extension point `point` by the started plugins
+        /// of the manager, in the order they were added. Plugins that don't export the symbol
+        /// of the extension point are left out of the chain.
+        /// See [`extension`](crate::extension).
+        pub fn extensions<F: Copy>(&self, point: &ExtensionPoint<F>) -> ExtensionChain<F> {
+                let links = self.plugins
+                        .iter()
+                        .filter(|(_, plugin)| plugin.state == PluginState::Started)
+                        .filter_map(|(handle, plugin)| {
+                                /* Safe as long as the extension point was declared with the right type. */
+                                let hook = unsafe { plugin.symbol::<F>(point.name()) }.ok()?;
+                                Some(Extension {
+                                        plugin      : handle,
+                                        name        : plugin.metadata.name.clone(),
+                                        instance    : plugin.instance_id,
+                                        hook        : HookGuard::new(hook, plugin),
+                                        interceptors: plugin.interceptors.clone()
+                                })
+                        })
+                        .collect();
+                ExtensionChain::new(point.name(), links)
+        }
+
+        /// Requires every plugin started from now on to export the hooks `H` (Usually declared
+        /// with [`declare_hooks!`](crate::declare_hooks)). They are looked up before the entry
+        /// point of the plugin runs, and a plugin missing any of them isn't started:
+        /// `VPluginError::MissingSymbol` is returned, and the missing hooks are reported.
+        /// The hooks can then be resolved with [`hooks`](crate::plugin_manager::PluginManager::hooks).
+        ///
+        /// ## Example
+        /// ```rust,no_run
+        /// # struct Context;
+        /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let mut manager = vplugin::PluginManager::new();
+        /// # let plugin = manager.load("plugin.vpl")?;
+        /// vplugin::declare_hooks! {
+        ///     pub struct EditorHooks {
+        ///         fn render(ctx: *mut Context) -> i32;
+        ///     }
+        /// }
+        ///
+        /// manager.require_hooks::<EditorHooks>();
+        /// manager.begin(plugin)?;
+        /// let hooks: EditorHooks = manager.hooks(plugin)?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn require_hooks<H: Hooks>(&mut self) {
+                for hook in H::names() {
+                        if !self.required_hooks.contains(hook) {
+                                self.required_hooks.push(hook);
+                        }
+                }
+        }
+
+        /// Starts the plugin referred to by `handle` and resolves its hooks `H` right away,
+        /// so a plugin missing any of them is rejected on start. In that case the plugin
+        /// is terminated again and `VPluginError::MissingSymbol` is returned.
+        pub fn begin_with_hooks<H: Hooks>(&mut self, handle: PluginHandle) -> Result<H, VPluginError> {
+                self.begin(handle)?;
+                match self.hooks(handle) {
+                        Ok (hooks) => Ok(hooks),
+                        Err(e)     => {
+                                let _ = self.terminate(handle);
+                                Err(e)
+                        }
+                }
+        }
+
+        /// Returns a serializable snapshot of the plugins owned by the manager, see
+        /// [`ManagerSnapshot`](crate::snapshot::ManagerSnapshot).
+        ///
+        /// This calls into the plugins: those that are started and export `vplugin_save_state`
+        /// are asked for their state, on the calling thread.
+        pub fn snapshot(&self) -> ManagerSnapshot {
+                ManagerSnapshot {
+                        entry_point: self.entry.to_string_lossy().into_owned(),
+                        plugins    : self.plugins
+                                .iter()
+                                .map(|(_, plugin)| {
+                                        let _scope = self.diagnostics.scope(Some(&plugin.metadata.name));
+                                        PluginSnapshot {
+                                                name   : plugin.metadata.name.clone(),
+                                                version: plugin.metadata.version.clone(),
+                                                package: plugin.filename.clone(),
+                                                state  : plugin.state(),
+                                                requirement: plugin.requirement,
+                                                api_version: plugin.api_version,
+                                                config : plugin.config.clone(),
+                                                saved_state: snapshot::save_state(plugin),
+                                        }
+                                })
+                                .collect(),
+                        leaked     : self.leaks.list(),
+                }
+        }
+
+        /// Loads the plugins of `snapshot` again (From their packages, which must still exist),
+        /// and starts those that were started when it was taken, with the configuration they
+        /// were started with and the state they saved. They're started with the entry point of
+        /// the snapshot, the one of the manager stays as is for the plugins loaded afterwards.
+        /// The handles of the plugins restored are returned. Plugins are always handed their
+        /// state before being started, even if the manager starts plugins when loading them
+        /// ([`StartPolicy::OnLoad`](crate::options::StartPolicy::OnLoad)).
+        ///
+        /// Optional plugins that can't be restored are reported and skipped. If a required one
+        /// can't, `VPluginError::RequiredPluginFailed` is returned, and the plugins restored so
+        /// far stay owned by the manager. `VPluginError::ParametersError` is returned if the
+        /// entry point of the snapshot contains a nul byte, before anything is restored.
+        ///
+        /// ## Example
+        /// ```rust,no_run
+        /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let mut manager = vplugin::PluginManager::new();
+        /// # let session = std::env::temp_dir().join("session.toml");
+        /// /* When exiting. */
+        /// std::fs::write(&session, toml::to_string(&manager.snapshot())?)?;
+        ///
+        /// /* After restarting. */
+        /// let snapshot = toml::from_str(&std::fs::read_to_string(&session)?)?;
+        /// manager.restore(&snapshot)?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn restore(&mut self, snapshot: &ManagerSnapshot) -> Result<Vec<PluginHandle>, VPluginError> {
+                let entry = match CString::new(snapshot.entry_point.as_str()) {
+                        Ok (entry) => entry,
+                        Err(_)     => {
+                                diagnostics::error!("The entry point of the snapshot ('{}') contains a nul byte.", snapshot.entry_point.escape_debug());
+                                return Err(VPluginError::ParametersError);
+                        }
+                };
+                /* Only the plugins of the snapshot are started with it, not those loaded afterwards. */
+                let entry    = std::mem::replace(&mut self.entry, entry);
+                let restored = self.restore_plugins(snapshot);
+                self.entry   = entry;
+                restored
+        }
+
+        /* Restores the plugins of `snapshot`, see `restore`. */
+        fn restore_plugins(&mut self, snapshot: &ManagerSnapshot) -> Result<Vec<PluginHandle>, VPluginError> {
+                let mut restored = Vec::new();
+                for saved in &snapshot.plugins {
+                        let error = match self.restore_plugin(saved) {
+                                Ok (handle) => {
+                                        restored.push(handle);
+                                        continue;
+                                },
+                                Err(e)      => e
+                        };
+                        let _scope = self.diagnostics.scope(Some(&saved.name));
+                        if saved.requirement == Requirement::Optional {
+                                diagnostics::warning!("Couldn't restore optional plugin ({}), continuing without it.", error);
+                                continue;
+                        }
+                        diagnostics::error!("Couldn't restore required plugin ({}).", error);
+                        return Err(VPluginError::RequiredPluginFailed { plugin: saved.name.clone(), error: Box::new(error) });
+                }
+                Ok(restored)
+        }
+
+        /* Loads and starts again the plugin described by `saved`. */
+        fn restore_plugin(&mut self, saved: &PluginSnapshot) -> Result<PluginHandle, VPluginError> {
+                let mut plugin = match saved.package.as_os_str().is_empty() {
+                        true  => self.load_builtin(&saved.name)?,
+                        false => self.load_unstarted(&saved.package, false, None)?
+                };
+                let _scope = self.diagnostics.scope(Some(&plugin.metadata.name));
+                if plugin.metadata.version != saved.version {
+                        diagnostics::warning!(
+                                "Plugin was version {} when the snapshot was taken, it's now {}.",
+                                saved.version,
+                                plugin.metadata.version
+                        );
+                }
+                if plugin.api_version != saved.api_version {
+                        diagnostics::warning!(
+                                "Plugin used version {} of the API when the snapshot was taken, it now uses {}.",
+                                saved.api_version.map(|v| v.to_string()).unwrap_or_else(|| "none".into()),
+                                plugin.api_version.map(|v| v.to_string()).unwrap_or_else(|| "none".into())
+                        );
+                }
+                plugin.requirement = saved.requirement;
+
+                if saved.state == PluginState::Started && plugin.state != PluginState::Started {
+                        if let Some(state) = &saved.saved_state {
+                                snapshot::restore_state(&plugin, state);
+                        }
+                        self.start(&mut plugin, saved.config.as_deref())?;
+                }
+                Ok(self.insert(plugin))
+        }
+
+        /// Returns the plugin named `name` with the highest version out of those owned by
+        /// the manager. Plugins whose version can't be parsed are ignored.
+        pub fn newest(&self, name: &str) -> Option<PluginHandle> {
+                self.plugins
+                        .iter()
+                        .filter(|(_, plugin)| plugin.metadata.name == name)
+                        .filter_map(|(handle, plugin)| Some((handle, version::parse(&plugin.metadata.version).ok()?)))
+                        .max_by(|(_, a), (_, b)| a.cmp(b))
+                        .map(|(handle, _)| handle)
+        }
+
+        /// Returns an iterator over the handles of all plugins owned by the manager.
+        pub fn handles(&self) -> impl Iterator<Item = PluginHandle> + '_ {
+                self.plugins.iter().map(|(handle, _)| handle)
+        }
+
+        /// Returns an iterator over all plugins owned by the manager, with their handles,
+        /// in the order they were added.
+        pub fn iter_plugins(&self) -> impl Iterator<Item = (PluginHandle, &Plugin)> + '_ {
+                self.plugins.iter()
+        }
+
+        /// Returns how many plugins the manager owns.
+        pub fn count(&self) -> usize {
+                self.plugins.iter().count()
+        }
+
+        /// Returns the plugin named `name` owned by the manager. If several of them are
+        /// (Different versions, or instances), the first one added is returned; use
+        /// [`newest`](crate::plugin_manager::PluginManager::newest) or
+        /// [`instances`](crate::plugin_manager::PluginManager::instances) to choose.
+        pub fn get_plugin(&self, name: &str) -> Option<&Plugin> {
+                self.plugins
+                        .iter()
+                        .find(|(_, plugin)| plugin.metadata.name == name)
+                        .map(|(_, plugin)| plugin)
+        }
+
+        /// Terminates every plugin named `name` owned by the manager (If started) and
+        /// unloads it, invalidating its handles. If a plugin fails to terminate, it stays
+        /// in the manager and the error is returned.
+        pub fn unload_plugin(&mut self, name: &str) -> Result<(), VPluginError> {
+                let _scope = self.diagnostics.scope(Some(name));
+                let handles: Vec<PluginHandle> = self.instances(name).collect();
+                if handles.is_empty() {
+                        diagnostics::error!("No plugin named '{}' is loaded.", name);
+                        return Err(VPluginError::InvalidHandle);
+                }
+                for handle in handles {
+                        if self.handle(handle)?.state == PluginState::Started {
+                                self.terminate(handle)?;
+                        }
+                        self.remove(handle);
+                }
                 Ok(())
         }
+
+        fn handle(&self, handle: PluginHandle) -> Result<&Plugin, VPluginError> {
+                let _scope = self.diagnostics.scope(None);
+                match self.plugins.get(handle) {
+                        Some(plugin) => Ok(plugin),
+                        None         => {
+                                diagnostics::error!("Plugin handle {} is not valid.", handle);
+                                Err(VPluginError::InvalidHandle)
+                        }
+                }
+        }
+
+        fn handle_mut(&mut self, handle: PluginHandle) -> Result<&mut Plugin, VPluginError> {
+                let _scope = self.diagnostics.scope(None);
+                match self.plugins.get_mut(handle) {
+                        Some(plugin) => Ok(plugin),
+                        None         => {
+                                diagnostics::error!("Plugin handle {} is not valid.", handle);
+                                Err(VPluginError::InvalidHandle)
+                        }
+                }
+        }
+
+        /*
+         * Temporarily takes a plugin out of its slot, so it can be passed to functions
+         * borrowing the manager as well. put_back() must be called afterwards.
+         */
+        fn take(&mut self, handle: PluginHandle) -> Result<Plugin, VPluginError> {
+                self.handle(handle)?;
+                Ok(self.plugins.take(handle).expect("Handle was just checked"))
+        }
+
+        fn put_back(&mut self, handle: PluginHandle, plugin: Plugin) {
+                self.plugins.restore(handle, plugin);
+        }
+
+        /* Unloads every plugin, then removes the workspace according to the cleanup policy. */
+        fn release_plugins(&mut self) {
+                /* Unload the plugins first, their directories can't be removed otherwise. */
+                self.plugins.clear();
+                workspace::flush_pending();
+                match self.cleanup {
+                        /* The directories of the libraries kept loaded are kept as well. */
+                        CleanupPolicy::Always if self.leaks.is_empty() => workspace::remove_dir(&self.workspace),
+                        /* Only empty (And removed) if none of the plugins was kept. */
+                        CleanupPolicy::Always | CleanupPolicy::OnSuccess => { let _ = fs::remove_dir(&self.workspace); },
+                        CleanupPolicy::Never     => return
+                }
+
+                /* Only succeeds if no other host is using the directory anymore. */
+                if let Some(root) = self.workspace.parent() {
+                        let _ = fs::remove_dir(root);
+                }
+        }
+}
+
+impl Default for PluginManager {
+        /// Same as [`PluginManager::new`](crate::plugin_manager::PluginManager::new).
+        fn default() -> Self {
+                Self::new()
+        }
 }
 
 impl Drop for PluginManager {
         fn drop(&mut self) {
-            let vplugin_dir = env::temp_dir().join("vplugin");
-            match std::fs::remove_dir_all(&vplugin_dir) {
-                Ok(()) => log::trace!("Removed directory: {}", vplugin_dir.display()),
-                Err(e) => {
-                        log::warn!(
-                                "Couldn't remove {}: {} . No cleanup will be performed.",
-                                vplugin_dir.display(),
-                                e.to_string(),
-                        )
-                }
-            }
+            let _scope = self.diagnostics.scope(None);
+            self.release_plugins();
         }
 }
\ No newline at end of file
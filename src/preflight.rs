@@ -0,0 +1,390 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Analysis of the undefined symbols of object files, before they're loaded. Only
+//! available with the `preflight` feature.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use object::{BinaryFormat, Object, ObjectSection, ObjectSymbol, SectionKind};
+use object::read::elf::{Dyn, ElfFile, FileHeader};
+use serde_derive::{Deserialize, Serialize};
+use crate::backend::{self, PluginBackend};
+use crate::diagnostics;
+use crate::error::VPluginError;
+use crate::platform;
+use vplugin_core::sdk::{SdkInfo, SDK_INFO_SYMBOL};
+
+/// ## SymbolReport
+/// The symbols an object file imports that can't be resolved, returned by
+/// [`PluginManager::symbol_report`](crate::plugin_manager::PluginManager::symbol_report).
+///
+/// Every symbol is looked up in the application, then in the library it's imported from
+/// (If the object file says), the libraries in the `needs` field of the plugin and the
+/// libraries the object file links to. On Linux, missing functions are usually only
+/// noticed when they're first called, so this finds build mistakes (A missing `-l`
+/// flag, a library built against another version, ...) before they crash the application.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SymbolReport {
+        /// The object file analyzed, relative to its package.
+        pub objfile   : PathBuf,
+        /// How many symbols the object file imports.
+        pub imported  : usize,
+        /// The symbols that can't be resolved.
+        pub unresolved: Vec<UnresolvedSymbol>,
+        /// The symbols the object file exports that the application, or a plugin loaded
+        /// with [`SymbolVisibility::Global`](crate::options::SymbolVisibility::Global),
+        /// already defines (Unix only). One of them shadows the other, depending on the
+        /// order they're loaded in.
+        pub conflicts : Vec<String>,
+}
+
+/// A symbol of a [`SymbolReport`] that can't be resolved.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnresolvedSymbol {
+        /// The name of the symbol.
+        pub name   : String,
+        /// The library the object file expects the symbol from, if it says.
+        pub library: Option<String>,
+}
+
+impl SymbolReport {
+        /// Returns whether every imported symbol can be resolved, and no exported one
+        /// conflicts with another.
+        pub fn is_clean(&self) -> bool {
+                self.unresolved.is_empty() && self.conflicts.is_empty()
+        }
+}
+
+impl fmt::Display for SymbolReport {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                writeln!(
+                        f,
+                        "{}: {} of {} imported symbols can't be resolved",
+                        self.objfile.display(),
+                        self.unresolved.len(),
+                        self.imported
+                )?;
+                for symbol in &self.unresolved {
+                        match &symbol.library {
+                                Some(library) => writeln!(f, "  {} (From {})", symbol.name, library)?,
+                                None          => writeln!(f, "  {}", symbol.name)?
+                        }
+                }
+                if !self.conflicts.is_empty() {
+                        writeln!(f, "{} exported symbols are already defined:", self.conflicts.len())?;
+                        for symbol in &self.conflicts {
+                                writeln!(f, "  {}", symbol)?;
+                        }
+                }
+                Ok(())
+        }
+}
+
+/// Analyzes the object file `objfile` of a plugin extracted in `workdir`, which needs the
+/// libraries `needs`.
+pub(crate) fn analyze(objfile: &Path, workdir: &Path, needs: &[String]) -> Result<SymbolReport, VPluginError> {
+        let data = read(objfile)?;
+        let invalid = |e| invalid(objfile, e);
+        let file = object::File::parse(&*data).map_err(invalid)?;
+        let imports = imports(&file).map_err(invalid)?;
+        let linked = match &file {
+                object::File::Elf32(elf) => needed(elf).map_err(invalid)?,
+                object::File::Elf64(elf) => needed(elf).map_err(invalid)?,
+                _                        => Vec::new()
+        };
+
+        let mut resolver = Resolver::new(workdir);
+        let unresolved = imports
+                .iter()
+                .filter(|(name, library)| {
+                        let libraries = library.iter().chain(needs).chain(&linked);
+                        !resolver.resolve(name, libraries)
+                })
+                .map(|(name, library)| UnresolvedSymbol { name: name.clone(), library: library.clone() })
+                .collect();
+        Ok(SymbolReport {
+                objfile   : objfile.to_path_buf(),
+                imported  : imports.len(),
+                unresolved,
+                conflicts : conflicts_in(&file, &resolver).map_err(invalid)?
+        })
+}
+
+/// Returns the symbols `objfile` exports that are already in the global scope of the
+/// process, so would conflict with those of the application or of global plugins.
+#[cfg(not(target_os = "ios"))]
+pub(crate) fn conflicts(objfile: &Path) -> Result<Vec<String>, VPluginError> {
+        let data = read(objfile)?;
+        let file = object::File::parse(&*data).map_err(|e| invalid(objfile, e))?;
+        conflicts_in(&file, &Resolver::new(Path::new(""))).map_err(|e| invalid(objfile, e))
+}
+
+/// Returns the names of the symbols `objfile` exports (Functions and variables), sorted.
+pub(crate) fn exports(objfile: &Path) -> Result<Vec<String>, VPluginError> {
+        let data = read(objfile)?;
+        let file = object::File::parse(&*data).map_err(|e| invalid(objfile, e))?;
+        let mut exports: Vec<String> = file
+                .exports()
+                .map_err(|e| invalid(objfile, e))?
+                .into_iter()
+                .map(|export| symbol_name(&file, export.name()))
+                .collect();
+        exports.sort();
+        exports.dedup();
+        Ok(exports)
+}
+
+/// Returns whether the symbol `name` that `objfile` exports is a function, judging from
+/// the section it's in (Code rather than data), or `None` if `objfile` doesn't export it.
+pub(crate) fn is_function(objfile: &Path, name: &str) -> Result<Option<bool>, VPluginError> {
+        let data = read(objfile)?;
+        let file = object::File::parse(&*data).map_err(|e| invalid(objfile, e))?;
+        let exports = file.exports().map_err(|e| invalid(objfile, e))?;
+        let address = match exports.iter().find(|export| symbol_name(&file, export.name()) == name) {
+                Some(export) => export.address(),
+                None         => return Ok(None)
+        };
+        Ok(Some(file.sections().any(|section| {
+                let range = section.address()..section.address() + section.size();
+                section.kind() == SectionKind::Text && range.contains(&address)
+        })))
+}
+
+/// Reads the [`SdkInfo`] `objfile` exports (See `export_plugin!`) without loading it, or
+/// `None` if it doesn't export one.
+pub(crate) fn sdk_info(objfile: &Path) -> Result<Option<SdkInfo>, VPluginError> {
+        let data = read(objfile)?;
+        let file = object::File::parse(&*data).map_err(|e| invalid(objfile, e))?;
+        let exports = file.exports().map_err(|e| invalid(objfile, e))?;
+        let address = match exports.iter().find(|export| symbol_name(&file, export.name()) == SDK_INFO_SYMBOL) {
+                Some(export) => export.address(),
+                None         => return Ok(None)
+        };
+        let size = std::mem::size_of::<SdkInfo>() as u64;
+        Ok(file
+                .sections()
+                .find_map(|section| section.data_range(address, size).ok().flatten())
+                .and_then(|bytes| SdkInfo::from_bytes(bytes, file.is_little_endian())))
+}
+
+/// Returns the architecture `objfile` was built for if it isn't the host's, `None` if it
+/// is (Or the host's architecture isn't known).
+pub(crate) fn foreign_architecture(objfile: &Path) -> Result<Option<String>, VPluginError> {
+        use object::Architecture;
+
+        let data = read(objfile)?;
+        let file = object::File::parse(&*data).map_err(|e| invalid(objfile, e))?;
+        let host = match std::env::consts::ARCH {
+                "x86"         => Architecture::I386,
+                "x86_64"      => Architecture::X86_64,
+                "arm"         => Architecture::Arm,
+                "aarch64"     => Architecture::Aarch64,
+                "riscv64"     => Architecture::Riscv64,
+                "powerpc64"   => Architecture::PowerPc64,
+                "s390x"       => Architecture::S390x,
+                "loongarch64" => Architecture::LoongArch64,
+                _             => return Ok(None)
+        };
+        match file.architecture() {
+                architecture if architecture == host => Ok(None),
+                architecture                          => Ok(Some(format!("{:?}", architecture)))
+        }
+}
+
+fn read(objfile: &Path) -> Result<Vec<u8>, VPluginError> {
+        match fs::read(objfile) {
+                Ok (data) => Ok(data),
+                Err(e)    => {
+                        diagnostics::error!("Couldn't read {}: {}", objfile.display(), e);
+                        Err(VPluginError::from_io(&e))
+                }
+        }
+}
+
+fn invalid(objfile: &Path, e: object::Error) -> VPluginError {
+        diagnostics::error!("Couldn't analyze {}: {}", objfile.display(), e);
+        VPluginError::InvalidPlugin
+}
+
+/*
+ * The symbols `file` exports that are already defined in the global scope. VPlugin's own
+ * symbols are exported by every plugin, and reserved names (Starting with an underscore)
+ * by every library, so those are skipped. Windows has no global scope.
+ */
+fn conflicts_in(file: &object::File<'_>, resolver: &Resolver<'_>) -> object::Result<Vec<String>> {
+        if cfg!(windows) {
+                return Ok(Vec::new());
+        }
+        Ok(file
+                .exports()?
+                .into_iter()
+                .map(|export| symbol_name(file, export.name()))
+                .filter(|name| !name.starts_with('_') && !name.starts_with("vplugin_"))
+                .filter(|name| resolver.in_program(name))
+                .collect())
+}
+
+/* The name `dlsym` knows `name` of `file` by. */
+fn symbol_name(file: &object::File<'_>, name: &[u8]) -> String {
+        let name = String::from_utf8_lossy(name);
+        /* Mach-O prefixes every C symbol with an underscore, `dlsym` doesn't. */
+        match file.format() {
+                BinaryFormat::MachO => name.strip_prefix('_').unwrap_or(&name).to_owned(),
+                _                   => name.into_owned()
+        }
+}
+
+/* The symbols `file` imports, with the library they're imported from if known. */
+fn imports(file: &object::File<'_>) -> object::Result<Vec<(String, Option<String>)>> {
+        /* Weak symbols are allowed to stay undefined. */
+        let weak: Vec<&str> = file
+                .dynamic_symbols()
+                .filter(|symbol| symbol.is_undefined() && symbol.is_weak())
+                .filter_map(|symbol| symbol.name().ok())
+                .collect();
+        Ok(file
+                .imports()?
+                .into_iter()
+                .filter_map(|import| {
+                        if weak.iter().any(|weak| weak.as_bytes() == import.name()) {
+                                return None;
+                        }
+                        let name = symbol_name(file, import.name());
+                        let library = match import.library() {
+                                []      => None,
+                                library => Some(String::from_utf8_lossy(library).into_owned())
+                        };
+                        Some((name, library))
+                })
+                .collect())
+}
+
+/* The libraries an ELF file links to (Its `DT_NEEDED` entries). */
+fn needed<Elf: FileHeader<Endian = object::Endianness>>(file: &ElfFile<'_, Elf>) -> object::Result<Vec<String>> {
+        let endian   = file.endian();
+        let sections = file.elf_section_table();
+        let mut needed = Vec::new();
+        if let Some((dynamic, link)) = sections.dynamic(endian, file.data())? {
+                let strings = sections.strings(endian, file.data(), link)?;
+                for entry in dynamic {
+                        if entry.tag32(endian) == Some(object::elf::DT_NEEDED) {
+                                needed.push(String::from_utf8_lossy(entry.string(endian, strings)?).into_owned());
+                        }
+                }
+        }
+        Ok(needed)
+}
+
+/*
+ * Looks symbols up, reading every library at most once. Libraries are parsed, never loaded:
+ * they come from the plugin (Its `needs` and the libraries it links to), and loading one
+ * runs its initializers.
+ */
+struct Resolver<'a> {
+        workdir  : &'a Path,
+        program  : Option<Box<dyn PluginBackend>>,
+        libraries: HashMap<String, Exports>,
+}
+
+/* What a library exports. */
+enum Exports {
+        Symbols(HashSet<String>),
+        /* A library of the system that can't be read (See `platform::Library::System`), assumed to export everything. */
+        Everything,
+        /* The library can't be found or read. */
+        Nothing,
+}
+
+impl<'a> Resolver<'a> {
+        fn new(workdir: &'a Path) -> Self {
+                Self { workdir, program: backend::this_program().ok(), libraries: HashMap::new() }
+        }
+
+        /* Whether `name` is in the global scope: the application, and global libraries. */
+        fn in_program(&self, name: &str) -> bool {
+                match &self.program {
+                        Some(program) => program.symbol(&nul_terminated(name)).is_ok(),
+                        None          => false
+                }
+        }
+
+        /* Whether `name` is exported by the application or one of `libraries`. */
+        fn resolve<'l>(&mut self, name: &str, libraries: impl Iterator<Item = &'l String>) -> bool {
+                if self.in_program(name) {
+                        return true;
+                }
+                for library in libraries {
+                        let workdir = self.workdir;
+                        let exports = self.libraries
+                                .entry(library.clone())
+                                .or_insert_with(|| Self::read_exports(library, workdir));
+                        match exports {
+                                Exports::Symbols(symbols) if symbols.contains(name) => return true,
+                                Exports::Everything                                 => return true,
+                                _                                                   => ()
+                        }
+                }
+                false
+        }
+
+        /* Finds `library` (Bundled in `workdir` first, then the system's) and reads the symbols it exports. */
+        fn read_exports(library: &str, workdir: &Path) -> Exports {
+                let path = match platform::find_library(library, workdir) {
+                        Some(platform::Library::File(path)) => path,
+                        Some(platform::Library::System)     => return Exports::Everything,
+                        None                                => return Exports::Nothing
+                };
+                let data = match fs::read(&path) {
+                        Ok (data) => data,
+                        Err(e)    => {
+                                diagnostics::trace!("Couldn't read {}: {}", path.display(), e);
+                                return Exports::Nothing;
+                        }
+                };
+                let symbols = object::File::parse(&*data).and_then(|file| library_exports(&file));
+                match symbols {
+                        Ok (symbols) => Exports::Symbols(symbols),
+                        Err(e)       => {
+                                diagnostics::trace!("Couldn't read the symbols of {}: {}", path.display(), e);
+                                Exports::Nothing
+                        }
+                }
+        }
+}
+
+/*
+ * The symbols a library exports. On ELF, the dynamic symbols it defines: `exports()` leaves
+ * out some of those the dynamic linker resolves to, like the IFUNCs of glibc.
+ */
+fn library_exports(file: &object::File<'_>) -> object::Result<HashSet<String>> {
+        if file.format() != BinaryFormat::Elf {
+                return Ok(file.exports()?.iter().map(|export| symbol_name(file, export.name())).collect());
+        }
+        Ok(file
+                .dynamic_symbols()
+                .filter(|symbol| !symbol.is_undefined() && symbol.is_global())
+                .filter_map(|symbol| symbol.name().ok().map(str::to_owned))
+                .collect())
+}
+
+fn nul_terminated(name: &str) -> Vec<u8> {
+        let mut symbol = name.as_bytes().to_vec();
+        symbol.push(0);
+        symbol
+}
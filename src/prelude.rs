@@ -0,0 +1,29 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! The types almost every host needs, importable at once:
+//! ```
+//! use vplugin::prelude::*;
+//! ```
+
+pub use crate::plugin_manager::{PluginManager, VHook};
+pub use crate::plugin::Plugin;
+pub use crate::handle::PluginHandle;
+pub use crate::guard::HookGuard;
+pub use crate::hooks::Hooks;
+pub use crate::source::PluginSource;
+pub use crate::shareable::Shareable;
+pub use vplugin_core::{export_plugin, PluginMetadata, PluginState, Result, VPluginError};
@@ -0,0 +1,84 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Detection of environments where plugins can't be loaded at all, so
+//! that a single clear error can be returned instead of an obscure
+//! `dlopen` failure for every plugin.
+
+use std::sync::OnceLock;
+use crate::error::VPluginError;
+use crate::diagnostics;
+
+static DYNAMIC_LOADING: OnceLock<Result<(), String>> = OnceLock::new();
+
+/// Returns whether the host process is able to load shared libraries at runtime.
+/// 
+/// This is checked once and the result is reused afterwards. Builtin plugins
+/// (See [`register_builtin`](crate::builtin::register_builtin)) don't need dynamic
+/// loading and can still be used if this returns `false`.
+pub fn dynamic_loading_available() -> bool {
+        check_dynamic_loading().is_ok()
+}
+
+/// Returns `VPluginError::DynamicLoadingUnavailable` if shared libraries can't
+/// be loaded in this process.
+pub(crate) fn check_dynamic_loading() -> Result<(), VPluginError> {
+        let result = DYNAMIC_LOADING.get_or_init(|| {
+                let result = probe();
+                if let Err(reason) = &result {
+                        diagnostics::error!("Plugins can't be loaded in this process: {}", reason);
+                }
+                result
+        });
+
+        match result {
+                Ok(())      => Ok(()),
+                Err(reason) => Err(VPluginError::DynamicLoadingUnavailable { reason: reason.clone() })
+        }
+}
+
+fn probe() -> Result<(), String> {
+        if cfg!(all(target_env = "musl", target_feature = "crt-static")) {
+                return Err(
+                        "the host is a statically linked musl binary, which cannot load shared libraries. \
+                        Build it with `-C target-feature=-crt-static` or for a `-gnu` target instead".into()
+                );
+        }
+        if cfg!(target_os = "ios") {
+                return Err(
+                        "iOS doesn't allow loading code at runtime. Link the plugins into the application \
+                        and register them with `vplugin::register_builtin` instead".into()
+                );
+        }
+        if cfg!(all(target_env = "gnu", target_feature = "crt-static")) {
+                diagnostics::warning!(
+                        "The host is statically linked against glibc. Loading plugins may work, but \
+                        only if the exact same glibc version is installed on the system."
+                );
+        }
+
+        /* Opening the running program itself fails if the dynamic loader is missing or stubbed out. */
+        #[cfg(unix)]
+        if let Err(e) = unsafe { libloading::os::unix::Library::open(None::<&str>, libloading::os::unix::RTLD_LAZY) } {
+                return Err(format!(
+                        "the dynamic loader isn't available ({}). This usually means the host is statically \
+                        linked or runs in a sandbox that forbids loading libraries",
+                        e
+                ));
+        }
+
+        Ok(())
+}
@@ -0,0 +1,174 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Progress reports while loading plugins.
+//!
+//! Loading a plugin with large assets can take a while, most of it spent extracting
+//! its archive. [`Plugin::load_with_progress`](crate::plugin::Plugin::load_with_progress)
+//! and [`PluginManager::load_with_progress`](crate::plugin_manager::PluginManager::load_with_progress)
+//! call a callback as the plugin is loaded, with a [`LoadProgress`] telling how far along
+//! it is. The callback can cancel loading by returning [`ControlFlow::Break`], in which
+//! case `VPluginError::Cancelled` is returned and what was extracted is removed (Following
+//! the [`CleanupPolicy`](crate::options::CleanupPolicy) of the manager, like any failure).
+//!
+//! Loading can only be cancelled until the library of the plugin is opened, in the
+//! [`LoadPhase::Loading`] report.
+
+use std::cell::{Cell, RefCell};
+use std::ops::ControlFlow;
+use crate::error::VPluginError;
+use crate::diagnostics;
+
+/* Bytes between two reports while the same file is being written. */
+#[cfg(feature = "archive")]
+const REPORT_INTERVAL: u64 = 1 << 20;
+
+/// The phases of loading a plugin, in the order they happen. Only the phases the
+/// package goes through are reported: directories skip `Extracting`, and are only
+/// `Copying` if they're loaded from a copy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadPhase {
+        /// The archive of the plugin is being extracted.
+        Extracting,
+        /// The package is being copied into the workspace (See
+        /// [`PluginManager::load_instance`](crate::plugin_manager::PluginManager::load_instance)).
+        Copying,
+        /// The library of the plugin is about to be opened, there's no way back after this.
+        Loading,
+}
+
+/// ## LoadProgress
+/// How far along loading a plugin is, see the [module documentation](crate::progress).
+/// The counts are those of the current phase, and the totals are `None` if they
+/// aren't known in advance (Or mean nothing for the phase, like for `Loading`).
+///
+/// ## Example
+/// ```rust,no_run
+/// # struct ProgressBar;
+/// # impl ProgressBar { fn set_fraction(&self, _fraction: f64) {} }
+/// # struct Button;
+/// # impl Button { fn clicked(&self) -> bool { false } }
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let mut manager = vplugin::PluginManager::new();
+/// # let progress_bar = ProgressBar;
+/// # let cancel_button = Button;
+/// use std::ops::ControlFlow;
+///
+/// let plugin = manager.load_with_progress("assets-pack.vpl", |progress| {
+///     if let Some(total) = progress.total_bytes {
+///         progress_bar.set_fraction(progress.bytes as f64 / total.max(1) as f64);
+///     }
+///     match cancel_button.clicked() {
+///         true  => ControlFlow::Break(()),
+///         false => ControlFlow::Continue(())
+///     }
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadProgress {
+        /// What's being done.
+        pub phase      : LoadPhase,
+        /// The bytes written so far.
+        pub bytes      : u64,
+        /// The bytes to write in total.
+        pub total_bytes: Option<u64>,
+        /// The files written so far.
+        pub files      : usize,
+        /// The files to write in total.
+        pub total_files: Option<usize>,
+}
+
+/// Calls `f` with a [`Reporter`] reporting to `callback`.
+pub(crate) fn with_reporter<R>(
+        callback: impl FnMut(&LoadProgress) -> ControlFlow<()>,
+        f       : impl FnOnce(&Reporter<'_>) -> R
+) -> R {
+        let callback = RefCell::new(callback);
+        let report   = |progress: &LoadProgress| (callback.borrow_mut())(progress);
+        f(&Reporter::new(&report))
+}
+
+/// Reports the progress of loading a single plugin to the callback of the application.
+pub(crate) struct Reporter<'a> {
+        callback : &'a dyn Fn(&LoadProgress) -> ControlFlow<()>,
+        progress : Cell<LoadProgress>,
+        /* The bytes written when progress was last reported. */
+        reported : Cell<u64>,
+        cancelled: Cell<bool>,
+}
+
+impl<'a> Reporter<'a> {
+        fn new(callback: &'a dyn Fn(&LoadProgress) -> ControlFlow<()>) -> Self {
+                Self {
+                        callback,
+                        progress : Cell::new(LoadProgress {
+                                phase      : LoadPhase::Extracting,
+                                bytes      : 0,
+                                total_bytes: None,
+                                files      : 0,
+                                total_files: None,
+                        }),
+                        reported : Cell::new(0),
+                        cancelled: Cell::new(false),
+                }
+        }
+
+        /// Starts `phase`, which writes `total_files` files and `total_bytes` bytes if known.
+        pub(crate) fn phase(&self, phase: LoadPhase, total_files: Option<usize>, total_bytes: Option<u64>) -> Result<(), VPluginError> {
+                self.progress.set(LoadProgress { phase, bytes: 0, total_bytes, files: 0, total_files });
+                self.report()
+        }
+
+        /// Records that `bytes` more bytes were written, reporting it every now and then.
+        #[cfg(feature = "archive")]
+        pub(crate) fn bytes(&self, bytes: u64) -> Result<(), VPluginError> {
+                let mut progress = self.progress.get();
+                progress.bytes += bytes;
+                self.progress.set(progress);
+                match progress.bytes - self.reported.get() >= REPORT_INTERVAL {
+                        true  => self.report(),
+                        false => Ok(())
+                }
+        }
+
+        /// Records that another file was written, along with `bytes` bytes that weren't
+        /// recorded yet, reporting it.
+        pub(crate) fn file(&self, bytes: u64) -> Result<(), VPluginError> {
+                let mut progress = self.progress.get();
+                progress.files += 1;
+                progress.bytes += bytes;
+                self.progress.set(progress);
+                self.report()
+        }
+
+        /// Whether the callback cancelled loading.
+        pub(crate) fn cancelled(&self) -> bool {
+                self.cancelled.get()
+        }
+
+        fn report(&self) -> Result<(), VPluginError> {
+                let progress = self.progress.get();
+                self.reported.set(progress.bytes);
+                if let ControlFlow::Break(()) = (self.callback)(&progress) {
+                        diagnostics::trace!("Loading the plugin was cancelled while {:?}.", progress.phase);
+                        self.cancelled.set(true);
+                        return Err(VPluginError::Cancelled);
+                }
+                Ok(())
+        }
+}
@@ -0,0 +1,417 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Sandboxed plugins, running in a child process so a plugin that crashes (Or misbehaves)
+//! can't take the host down with it. Only available with the `sandbox` feature.
+//!
+//! The sandbox process is the application itself, started again with an environment
+//! variable telling it to serve a plugin instead of running normally. Applications using
+//! sandboxed plugins must therefore call [`serve_if_child`] first thing in `main`:
+//! ```rust,no_run
+//! fn main() -> Result<(), vplugin::VPluginError> {
+//!     vplugin::sandbox::serve_if_child();
+//!
+//!     let mut manager = vplugin::PluginManager::new();
+//!     let mut plugin  = manager.load_plugin_sandboxed("plugin.vpl")?;
+//!     plugin.begin()?;
+//!     let reply = plugin.call("on_message", b"hello")?;
+//!     println!("{}", String::from_utf8_lossy(&reply));
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Hooks are called across processes, so they can't take arbitrary arguments: a sandboxed
+//! hook receives bytes and writes bytes back (See the plugin format specification, section
+//! "Sandboxed hooks"). How they're encoded is up to the application and its plugins.
+//!
+//! The plugin is loaded by the sandbox process with the default options, so what the host
+//! configured on its manager (API tables, objects, interceptors, ...) isn't available to it.
+
+use std::env;
+use std::ffi::c_void;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+use std::process::{self, Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use vplugin_core::PluginMetadata;
+use crate::diagnostics;
+use crate::error::VPluginError;
+use crate::ipc::{self, Request, Response};
+use crate::state::PluginState;
+
+/* Set in the environment of sandbox processes. */
+const SANDBOX_VAR: &str = "VPLUGIN_SANDBOX";
+
+/// How long a sandboxed plugin has to answer a request by default, see
+/// [`SandboxedPlugin::set_timeout`].
+pub const DEFAULT_SANDBOX_TIMEOUT: Duration = Duration::from_secs(30);
+
+/* How long dropping a sandboxed plugin waits for its process to exit before killing it. */
+const EXIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/* The signature of sandboxed hooks, and of the function they write their output with. */
+type OutputWriter  = unsafe extern "C" fn(*mut c_void, *const u8, usize);
+type SandboxedHook = unsafe extern "C" fn(*const u8, usize, OutputWriter, *mut c_void) -> i32;
+
+/// ## SandboxedPlugin
+/// A plugin running in its own process, returned by
+/// [`PluginManager::load_plugin_sandboxed`](crate::plugin_manager::PluginManager::load_plugin_sandboxed).
+///
+/// If the process crashes, the request that was running fails with
+/// `VPluginError::SandboxCrashed` and the plugin becomes invalid; load it again to restart it.
+/// A process that doesn't answer within the timeout is killed. Dropping the plugin stops
+/// its process, without calling its destructor.
+pub struct SandboxedPlugin {
+        metadata : PluginMetadata,
+        state    : PluginState,
+        child    : Child,
+        /* Closed once the process crashed or was killed. */
+        requests : Option<BufWriter<ChildStdin>>,
+        responses: Receiver<io::Result<Option<Response>>>,
+        timeout  : Duration,
+        diagnostics: diagnostics::Sink,
+}
+
+impl SandboxedPlugin {
+        /* Starts a sandbox process and loads the plugin at `path` in it. */
+        pub(crate) fn spawn(
+                metadata: PluginMetadata,
+                entry: &str,
+                path: &Path,
+                diagnostics: diagnostics::Sink,
+        ) -> Result<Self, VPluginError> {
+                let name   = metadata.name.clone();
+                let failed = |reason: String| {
+                        diagnostics::error!("Couldn't start the sandbox of '{}': {}", name, reason);
+                        VPluginError::SandboxFailed { plugin: name.clone(), reason }
+                };
+                /* Otherwise every sandbox would start another one. */
+                if env::var_os(SANDBOX_VAR).is_some() {
+                        return Err(failed("this is a sandbox process, vplugin::sandbox::serve_if_child() wasn't called".into()));
+                }
+
+                let program = env::current_exe().map_err(|e| failed(e.to_string()))?;
+                let mut child = Command::new(program)
+                        .env(SANDBOX_VAR, "1")
+                        .stdin(Stdio::piped())
+                        .stdout(Stdio::piped())
+                        .stderr(Stdio::inherit())
+                        .spawn()
+                        .map_err(|e| failed(e.to_string()))?;
+
+                let requests = child.stdin.take().map(BufWriter::new);
+                let stdout   = child.stdout.take();
+                let (sender, responses) = mpsc::channel();
+                if let Some(stdout) = stdout {
+                        thread::spawn(move || {
+                                let mut stdout = BufReader::new(stdout);
+                                loop {
+                                        let response = Response::read_from(&mut stdout);
+                                        let done     = !matches!(response, Ok(Some(_)));
+                                        if sender.send(response).is_err() || done {
+                                                break;
+                                        }
+                                }
+                        });
+                }
+
+                let mut plugin = Self {
+                        metadata,
+                        state    : PluginState::Invalid,
+                        child,
+                        requests,
+                        responses,
+                        timeout  : DEFAULT_SANDBOX_TIMEOUT,
+                        diagnostics,
+                };
+                match plugin.receive()? {
+                        Response::Ready(handshake) if handshake == ipc::HANDSHAKE => (),
+                        _ => {
+                                plugin.kill();
+                                return Err(failed("the sandbox process didn't answer the handshake".into()));
+                        }
+                }
+                plugin.request(Request::Load { entry: entry.to_owned(), path: path.to_string_lossy().into_owned() })?;
+                plugin.state = PluginState::Loaded;
+                Ok(plugin)
+        }
+
+        /// Returns the metadata of the plugin.
+        pub fn get_metadata(&self) -> &PluginMetadata {
+                &self.metadata
+        }
+
+        /// Returns the state of the plugin. Plugins whose process crashed or was killed
+        /// are [`PluginState::Invalid`].
+        pub fn state(&self) -> PluginState {
+                self.state
+        }
+
+        /// Sets how long the plugin has to answer a request (Including the hooks it runs)
+        /// before its process is killed. Defaults to [`DEFAULT_SANDBOX_TIMEOUT`].
+        pub fn set_timeout(&mut self, timeout: Duration) {
+                self.timeout = timeout;
+        }
+
+        /// Returns whether the process of the plugin is still running.
+        pub fn is_alive(&mut self) -> bool {
+                matches!(self.child.try_wait(), Ok(None))
+        }
+
+        /// Starts the plugin, running its entry point in its process.
+        pub fn begin(&mut self) -> Result<(), VPluginError> {
+                self.transition(PluginState::Started)?;
+                self.request(Request::Begin)?;
+                self.state = PluginState::Started;
+                Ok(())
+        }
+
+        /// Calls the sandboxed hook `hook` of the plugin, which must be started, with
+        /// `input`. Returns what the hook wrote back.
+        pub fn call(&mut self, hook: &str, input: &[u8]) -> Result<Vec<u8>, VPluginError> {
+                if self.state != PluginState::Started {
+                        let _scope = self.scope();
+                        diagnostics::error!("Plugin '{}' must be started to call its hooks.", self.metadata.name);
+                        return Err(VPluginError::InvalidState { current: self.state, requested: PluginState::Started });
+                }
+                self.request(Request::Call { hook: hook.to_owned(), input: input.to_vec() })
+        }
+
+        /// Terminates the plugin, running its destructor in its process. The process keeps
+        /// running, so the plugin can be started again.
+        pub fn terminate(&mut self) -> Result<(), VPluginError> {
+                self.transition(PluginState::Terminated)?;
+                self.request(Request::Terminate)?;
+                self.state = PluginState::Terminated;
+                Ok(())
+        }
+
+        fn scope(&self) -> diagnostics::Scope {
+                self.diagnostics.scope(Some(&self.metadata.name))
+        }
+
+        fn transition(&self, next: PluginState) -> Result<(), VPluginError> {
+                if self.state.can_transition_to(next) {
+                        return Ok(());
+                }
+                let _scope = self.scope();
+                diagnostics::error!("Plugin '{}' can't go from {:?} to {:?}.", self.metadata.name, self.state, next);
+                match self.state.is_usable() {
+                        true  => Err(VPluginError::InvalidState { current: self.state, requested: next }),
+                        false => Err(VPluginError::InvalidPlugin)
+                }
+        }
+
+        /* Sends `request` and waits for its response. */
+        fn request(&mut self, request: Request) -> Result<Vec<u8>, VPluginError> {
+                let sent = match &mut self.requests {
+                        Some(requests) => request.write_to(requests),
+                        None           => return Err(self.crashed())
+                };
+                if sent.is_err() {
+                        return Err(self.crashed());
+                }
+                match self.receive()? {
+                        Response::Ok(output)  => Ok(output),
+                        Response::Err(reason) => {
+                                let _scope = self.scope();
+                                diagnostics::error!("Sandboxed plugin '{}' failed: {}", self.metadata.name, reason);
+                                Err(VPluginError::SandboxFailed { plugin: self.metadata.name.clone(), reason })
+                        },
+                        Response::Ready(_)    => Err(self.protocol_error("unexpected handshake".into()))
+                }
+        }
+
+        fn receive(&mut self) -> Result<Response, VPluginError> {
+                match self.responses.recv_timeout(self.timeout) {
+                        Ok (Ok(Some(response)))             => Ok(response),
+                        Ok (Ok(None))                       => Err(self.crashed()),
+                        Err(RecvTimeoutError::Disconnected) => Err(self.crashed()),
+                        Ok (Err(e))                         => Err(self.protocol_error(e.to_string())),
+                        Err(RecvTimeoutError::Timeout)      => {
+                                self.kill();
+                                let _scope = self.scope();
+                                diagnostics::error!(
+                                        "Sandboxed plugin '{}' didn't answer within {:?}, its process was killed.",
+                                        self.metadata.name,
+                                        self.timeout
+                                );
+                                Err(VPluginError::SandboxFailed {
+                                        plugin: self.metadata.name.clone(),
+                                        reason: format!("no answer within {:?}", self.timeout)
+                                })
+                        }
+                }
+        }
+
+        /* The process exited (Or closed its pipes), reports how. */
+        fn crashed(&mut self) -> VPluginError {
+                self.requests = None;
+                self.state    = PluginState::Invalid;
+                let status = match self.child.wait() {
+                        Ok (status) => status.to_string(),
+                        Err(e)      => e.to_string()
+                };
+                let _scope = self.scope();
+                diagnostics::error!("The process of sandboxed plugin '{}' exited ({}).", self.metadata.name, status);
+                VPluginError::SandboxCrashed { plugin: self.metadata.name.clone(), status }
+        }
+
+        fn protocol_error(&mut self, reason: String) -> VPluginError {
+                self.kill();
+                let _scope = self.scope();
+                diagnostics::error!("Invalid message from sandboxed plugin '{}': {}", self.metadata.name, reason);
+                VPluginError::SandboxFailed { plugin: self.metadata.name.clone(), reason }
+        }
+
+        fn kill(&mut self) {
+                self.requests = None;
+                self.state    = PluginState::Invalid;
+                let _ = self.child.kill();
+                let _ = self.child.wait();
+        }
+}
+
+impl fmt::Debug for SandboxedPlugin {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct("SandboxedPlugin")
+                        .field("name", &self.metadata.name)
+                        .field("state", &self.state)
+                        .field("process", &self.child.id())
+                        .finish()
+        }
+}
+
+impl Drop for SandboxedPlugin {
+        fn drop(&mut self) {
+                /* Closing its input makes the process exit on its own. */
+                self.requests = None;
+                let started = Instant::now();
+                while started.elapsed() < EXIT_TIMEOUT {
+                        match self.child.try_wait() {
+                                Ok (None) => thread::sleep(Duration::from_millis(10)),
+                                _         => return
+                        }
+                }
+                self.kill();
+        }
+}
+
+/// Serves a sandboxed plugin if the application was started as a sandbox process (See
+/// the [module documentation](crate::sandbox)), and exits once the host is done with
+/// it. Does nothing otherwise.
+///
+/// Call it first thing in `main`, before the application does anything else.
+pub fn serve_if_child() {
+        if env::var_os(SANDBOX_VAR).is_none() {
+                return;
+        }
+        let status = match serve() {
+                Ok (()) => 0,
+                Err(e)  => {
+                        diagnostics::error!("Sandbox process failed: {}", e);
+                        1
+                }
+        };
+        process::exit(status);
+}
+
+/* Answers the requests of the host until it closes the pipe. */
+fn serve() -> io::Result<()> {
+        let mut responses = BufWriter::new(protocol_output()?);
+        let mut requests  = BufReader::new(io::stdin().lock());
+        Response::Ready(ipc::HANDSHAKE.to_vec()).write_to(&mut responses)?;
+
+        let mut manager = crate::PluginManager::try_new().map_err(io::Error::other)?;
+        let mut plugin  = None;
+        while let Some(request) = Request::read_from(&mut requests)? {
+                let result = match (request, plugin) {
+                        (Request::Load { entry, path }, None) => {
+                                manager.set_entry_point(&entry);
+                                manager.load(path).map(|handle| {
+                                        plugin = Some(handle);
+                                        Vec::new()
+                                }).map_err(|e| e.to_string())
+                        },
+                        (Request::Load { .. }, Some(_))       => Err("A plugin is already loaded".into()),
+                        (_, None)                             => Err("No plugin is loaded".into()),
+                        (Request::Begin, Some(handle))        => manager.begin(handle).map(|()| Vec::new()).map_err(|e| e.to_string()),
+                        (Request::Terminate, Some(handle))    => manager.terminate(handle).map(|()| Vec::new()).map_err(|e| e.to_string()),
+                        (Request::Call { hook, input }, Some(handle)) => call(&manager, handle, &hook, &input)
+                };
+                let response = match result {
+                        Ok (output) => Response::Ok(output),
+                        Err(reason) => Response::Err(reason)
+                };
+                response.write_to(&mut responses)?;
+        }
+        Ok(())
+}
+
+/* Runs the sandboxed hook `hook` of the plugin with `input`. */
+fn call(manager: &crate::PluginManager, handle: crate::PluginHandle, hook: &str, input: &[u8]) -> Result<Vec<u8>, String> {
+        let mut output: Vec<u8> = Vec::new();
+        let context = &mut output as *mut Vec<u8> as *mut c_void;
+        let status  = unsafe {
+                manager.call_hook(handle, hook, |hook: SandboxedHook| hook(input.as_ptr(), input.len(), write_output, context))
+        };
+        match status {
+                Ok (0)      => Ok(output),
+                Ok (status) => Err(format!("Hook '{}' returned {}", hook, status)),
+                Err(e)      => Err(e.to_string())
+        }
+}
+
+/* Handed to sandboxed hooks, appends `data` to the `Vec<u8>` behind `context`. */
+unsafe extern "C" fn write_output(context: *mut c_void, data: *const u8, len: usize) {
+        if context.is_null() || data.is_null() {
+                return;
+        }
+        let output = &mut *(context as *mut Vec<u8>);
+        output.extend_from_slice(std::slice::from_raw_parts(data, len));
+}
+
+/*
+ * Where responses are written. On Unix, the standard output is moved to another file
+ * descriptor and replaced by the standard error, so what the plugin prints can't corrupt
+ * the messages. On Windows, plugins must not write to the standard output.
+ */
+fn protocol_output() -> io::Result<File> {
+        #[cfg(unix)]
+        {
+                use std::os::fd::AsFd;
+                use std::ffi::c_int;
+
+                extern "C" {
+                        fn dup2(old: c_int, new: c_int) -> c_int;
+                }
+
+                let output = io::stdout().as_fd().try_clone_to_owned()?;
+                if unsafe { dup2(2, 1) } < 0 {
+                        return Err(io::Error::last_os_error());
+                }
+                Ok(File::from(output))
+        }
+        #[cfg(windows)]
+        {
+                use std::os::windows::io::AsHandle;
+                Ok(File::from(io::stdout().as_handle().try_clone_to_owned()?))
+        }
+}
@@ -0,0 +1,134 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Checks that plugins were built against a version of the SDK compatible with the
+//! host's. See [`export_plugin!`](vplugin_core::export_plugin).
+
+use std::ffi::{c_char, CStr};
+use semver::{Version, VersionReq};
+use vplugin_core::sdk::{self, LAYOUT_HASH_SYMBOL, RUSTC_VERSION, RUSTC_VERSION_SYMBOL, SDK_VERSION, SDK_VERSION_SYMBOL};
+use crate::diagnostics;
+use crate::error::VPluginError;
+use crate::plugin::Plugin;
+
+/*
+ * The plugin SDKs each host SDK can load. The first entry matching the host's SDK is
+ * used; a host missing from the table only loads plugins built with the same minor
+ * version of the SDK.
+ */
+const COMPATIBILITY: &[(&str, &str)] = &[
+        /* Host SDK,           Plugin SDKs */
+        (">=0.3.0, <0.5.0", ">=0.3.0, <0.5.0"),
+];
+
+type SdkVersion = unsafe extern "C" fn() -> *const c_char;
+type LayoutHash = unsafe extern "C" fn() -> u64;
+
+/// Returns whether a host built with the SDK `host` can load plugins built with `plugin`.
+fn compatible(host: &Version, plugin: &Version) -> bool {
+        let entry = COMPATIBILITY.iter().find(|(h, _)| {
+                VersionReq::parse(h).map(|req| req.matches(host)).unwrap_or(false)
+        });
+        match entry {
+                Some((_, p)) => VersionReq::parse(p).map(|req| req.matches(plugin)).unwrap_or(false),
+                None         => host.major == plugin.major && host.minor == plugin.minor
+        }
+}
+
+/* Reads the string returned by the export `name` of `plugin`, if it has one. */
+fn reported_string(plugin: &Plugin, name: &str) -> Option<String> {
+        let query = unsafe { plugin.symbol::<SdkVersion>(name) }.ok()?;
+        let reported = unsafe { query() };
+        if reported.is_null() {
+                return Some(String::new());
+        }
+        Some(unsafe { CStr::from_ptr(reported) }.to_string_lossy().into_owned())
+}
+
+/// The SDK version `plugin` reports, `None` for plugins that don't use `export_plugin!`
+/// (Like plugins written in C).
+pub(crate) fn reported_sdk(plugin: &Plugin) -> Option<String> {
+        reported_string(plugin, SDK_VERSION_SYMBOL)
+}
+
+/// Returns an error if plugins built with the SDK `plugin_sdk` can't be loaded.
+pub(crate) fn verify_sdk(plugin_sdk: &str) -> Result<(), VPluginError> {
+        let compatible = match (Version::parse(SDK_VERSION), Version::parse(plugin_sdk)) {
+                (Ok(host), Ok(version)) => compatible(&host, &version),
+                _                       => false
+        };
+        if !compatible {
+                return Err(VPluginError::SdkVersionMismatch {
+                        plugin_sdk: plugin_sdk.into(),
+                        host_sdk  : SDK_VERSION.into()
+                });
+        }
+        Ok(())
+}
+
+/// Checks the SDK `plugin` was built against, if it reports it.
+pub(crate) fn check(plugin: &Plugin) -> Result<(), VPluginError> {
+        let plugin_sdk = match reported_sdk(plugin) {
+                Some(version) => version,
+                None          => return Ok(())
+        };
+        if let Err(e) = verify_sdk(&plugin_sdk) {
+                diagnostics::error!("{}.", e);
+                return Err(e);
+        }
+        diagnostics::trace!("Plugin was built with VPlugin {}.", plugin_sdk);
+        Ok(())
+}
+
+/// The compiler version and layout hash reported by `plugin`, `None` if it doesn't use
+/// the Rust ABI (See `export_plugin!(rust_abi)`).
+pub(crate) fn reported_toolchain(plugin: &Plugin) -> Option<(String, u64)> {
+        let layout = unsafe { plugin.symbol::<LayoutHash>(LAYOUT_HASH_SYMBOL) }.ok()?;
+        let rustc  = reported_string(plugin, RUSTC_VERSION_SYMBOL)?;
+        Some((rustc, unsafe { layout() }))
+}
+
+/// Returns an error if a Rust ABI plugin built with the compiler `rustc`, whose types have
+/// the layout hash `layout`, can't be used by the host.
+pub(crate) fn verify_toolchain(rustc: &str, layout: u64) -> Result<(), VPluginError> {
+        /* An unknown compiler on either side can't be compared, the layouts still are. */
+        if !rustc.is_empty() && !RUSTC_VERSION.is_empty() && rustc != RUSTC_VERSION {
+                return Err(VPluginError::ToolchainMismatch { plugin: rustc.into(), host: RUSTC_VERSION.into() });
+        }
+        let host_layout = sdk::layout_hash();
+        if layout != host_layout {
+                return Err(VPluginError::ToolchainMismatch {
+                        plugin: format!("{} (layout {:016x})", rustc, layout),
+                        host  : format!("{} (layout {:016x})", RUSTC_VERSION, host_layout)
+                });
+        }
+        Ok(())
+}
+
+/// Checks the toolchain of Rust ABI plugins, and returns the compiler version they report.
+/// Other plugins aren't checked and `None` is returned.
+pub(crate) fn check_toolchain(plugin: &Plugin) -> Result<Option<String>, VPluginError> {
+        let (rustc, layout) = match reported_toolchain(plugin) {
+                Some(toolchain) => toolchain,
+                None            => return Ok(None)
+        };
+        if let Err(e) = verify_toolchain(&rustc, layout) {
+                diagnostics::error!("Refusing Rust ABI plugin: {}.", e);
+                return Err(e);
+        }
+        diagnostics::trace!("Plugin uses the Rust ABI and was built with '{}'.", rustc);
+        Ok(Some(rustc))
+}
@@ -0,0 +1,76 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Storage of the values of the settings plugins declare (See
+//! [`SettingsSchema`](crate::SettingsSchema)), inside the data directory of
+//! every plugin, and delivery of the changes to the plugins.
+
+use std::path::Path;
+use vplugin_core::SettingsSchema;
+use crate::error::VPluginError;
+use crate::diagnostics;
+use crate::plugin::Plugin;
+use crate::store;
+use crate::state::PluginState;
+
+/// The file the values of the settings are kept in, inside the data directory of the plugin.
+pub(crate) const SETTINGS_FILE: &str = "settings.toml";
+
+/* Receives the settings that changed, while the plugin is running. */
+const SETTINGS_CHANGED_SYMBOL: &str = "vplugin_settings_changed";
+
+/// Returns the stored values of the settings in `schema`, read from `dir`, with the
+/// default of every setting that wasn't changed. Values that no longer fit the schema
+/// (After the plugin was updated, for example) are ignored.
+pub(crate) fn read(schema: &SettingsSchema, dir: &Path) -> Result<toml::Table, VPluginError> {
+        let mut values = schema.defaults();
+        for (key, value) in store::read(&dir.join(SETTINGS_FILE))? {
+                match schema.check(&key, &value) {
+                        Ok (_) => { values.insert(key, value); },
+                        Err(e) => diagnostics::warning!("Ignoring the stored value of a setting: {}", e)
+                }
+        }
+        Ok(values)
+}
+
+/// Stores `changes` in `dir`, on top of the values already there. Every value must fit
+/// `schema`, otherwise nothing is stored.
+pub(crate) fn write(schema: &SettingsSchema, dir: &Path, changes: &toml::Table) -> Result<(), VPluginError> {
+        for (key, value) in changes {
+                if let Err(e) = schema.check(key, value) {
+                        diagnostics::error!("{}", e);
+                        return Err(e);
+                }
+        }
+        let path = dir.join(SETTINGS_FILE);
+        let mut values = store::read(&path)?;
+        values.extend(changes.iter().map(|(key, value)| (key.clone(), value.clone())));
+        store::write(&path, &values)
+}
+
+/// Hands `changes`, serialized as TOML, to the `vplugin_settings_changed` export of
+/// `plugin`. Plugins that aren't running or don't export it aren't told anything.
+pub(crate) fn notify(plugin: &Plugin, changes: &toml::Table) {
+        if plugin.state() != PluginState::Started {
+                return;
+        }
+        let changed = match unsafe { plugin.symbol::<unsafe extern "C" fn(*const u8, usize)>(SETTINGS_CHANGED_SYMBOL) } {
+                Ok (changed) => changed,
+                Err(_)       => return
+        };
+        let changes = changes.to_string();
+        unsafe { changed(changes.as_ptr(), changes.len()) };
+}
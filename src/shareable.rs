@@ -22,6 +22,9 @@
 /// As the plugin may internally create new threads, it's important to ensure runtime safety
 /// by using these traits.
 /// 
+/// With the `derive` feature, `#[derive(Shareable)]` implements it for plain old data
+/// (`#[repr(C)]` structs) and serde types, see [`vplugin_derive::Shareable`].
+/// 
 /// # Example
 /// ```
 /// use vplugin::Shareable;
@@ -53,5 +56,221 @@ where
     /// Sends `self` as a pointer (`ptr`) to the plugin given.
     /// This function is marked `unsafe` because pointer dereferencing
     /// and sizes are
+    /// 
+    /// # Safety
+    /// `ptr` must point to a valid, properly aligned instance of `Self`
+    /// that stays alive for as long as the plugin may use it.
     unsafe fn send_ptr(ptr: *mut Self, plugin: &crate::Plugin);
-}
\ No newline at end of file
+}
+/// # PlainData
+/// Types any bit pattern is a valid value of, without padding: integers, floats and arrays
+/// of them. `#[derive(Shareable)]` sends plain old data as its bytes, so every field has to
+/// implement it (Generic fields included, which can be bound by it).
+///
+/// # Safety
+/// Every bit pattern of the size of the type must be a valid value, and every byte of a
+/// value must be initialized.
+pub unsafe trait PlainData: Copy + Send + Sync + 'static {}
+
+macro_rules! plain_data {
+    ($($ty:ty),*) => { $( unsafe impl PlainData for $ty {} )* };
+}
+plain_data!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+unsafe impl<T: PlainData, const N: usize> PlainData for [T; N] {}
+
+/* What `#[derive(Shareable)]` expands to. Not part of the public API. */
+#[doc(hidden)]
+pub mod __private {
+    use std::ffi::c_int;
+    use serde::{de::DeserializeOwned, Serialize};
+    use crate::diagnostics;
+    use crate::error::VPluginError;
+    use crate::plugin::Plugin;
+
+    type Receiver = unsafe extern "C" fn(*const u8, usize) -> c_int;
+
+    /// Sends the bytes of `value`, plain old data, to the hook `hook`.
+    ///
+    /// # Safety
+    /// Every byte of `T` must be initialized: it has no padding, and only [`PlainData`](super::PlainData) fields.
+    pub unsafe fn send_pod<T>(value: &T, plugin: &Plugin, hook: &str) {
+        let bytes = unsafe {
+            std::slice::from_raw_parts((value as *const T).cast::<u8>(), std::mem::size_of::<T>())
+        };
+        send_bytes(bytes, plugin, hook)
+    }
+
+    /// Sends `value`, serialized, to the hook `hook`.
+    pub fn send_serialized<T: Serialize>(value: &T, plugin: &Plugin, hook: &str) {
+        match toml::to_string(value) {
+            Ok (serialized) => send_bytes(serialized.as_bytes(), plugin, hook),
+            Err(e)          => diagnostics::error!("Couldn't serialize the data sent to {}: {}", hook, e)
+        }
+    }
+
+    fn send_bytes(bytes: &[u8], plugin: &Plugin, hook: &str) {
+        let _pin = plugin.pin();
+        let receiver = match unsafe { plugin.get_symbol::<Receiver>(hook) } {
+            Ok (receiver) => receiver,
+            Err(e)        => {
+                diagnostics::error!("Couldn't send data to {}: {}", hook, e);
+                return;
+            }
+        };
+        let status = unsafe { receiver(bytes.as_ptr(), bytes.len()) };
+        if status != 0 {
+            diagnostics::warning!("{} refused the data sent to it ({}).", hook, status);
+        }
+    }
+
+    /// Reads the plain old data sent with [`send_pod`].
+    ///
+    /// # Safety
+    /// `data` must point to `len` readable bytes, and any bit pattern must be a valid `T`
+    /// (It only has [`PlainData`](super::PlainData) fields).
+    pub unsafe fn receive_pod<T>(data: *const u8, len: usize) -> Result<T, VPluginError> {
+        if data.is_null() || len != std::mem::size_of::<T>() {
+            return Err(VPluginError::ParametersError);
+        }
+        Ok(std::ptr::read_unaligned(data.cast::<T>()))
+    }
+
+    /// Deserializes the value sent with [`send_serialized`].
+    ///
+    /// # Safety
+    /// `data` must point to `len` readable bytes.
+    pub unsafe fn receive_serialized<T: DeserializeOwned>(data: *const u8, len: usize) -> Result<T, VPluginError> {
+        if data.is_null() {
+            return Err(VPluginError::ParametersError);
+        }
+        let bytes = std::slice::from_raw_parts(data, len);
+        let serialized = std::str::from_utf8(bytes).map_err(|_| VPluginError::ParametersError)?;
+        toml::from_str(serialized).map_err(|e| VPluginError::InternalError { err: e.to_string() })
+    }
+}
+
+/// The symbol [`SerdeShareable`] delivers values through:
+/// ```c
+/// int vplugin_receive(const char* name, const uint8_t* data, size_t len);
+/// ```
+#[cfg(feature = "serde_shareable")]
+pub const RECEIVE_SYMBOL: &str = "vplugin_receive";
+
+/// # SerdeShareable
+/// Shares any serde type with plugins, without implementing [`Shareable`] by hand. The value
+/// is serialized with `bincode` and delivered, along with a name telling plugins what it is,
+/// through the single [`vplugin_receive`](RECEIVE_SYMBOL) symbol of the plugin, which returns
+/// `0` if it accepted the value. Plugins decode it with [`SerdeShareable::decode`].
+///
+/// Only available with the `serde_shareable` feature.
+///
+/// # Example
+/// ```rust,no_run
+/// # let plugin = vplugin::Plugin::load("plugin.vpl").unwrap();
+/// use vplugin::{SerdeShareable, Shareable};
+///
+/// #[derive(serde_derive::Serialize, serde_derive::Deserialize)]
+/// struct Theme {
+///     name: String,
+///     colors: Vec<u32>
+/// }
+///
+/// let mut theme = SerdeShareable::new("theme", Theme { name: "dark".into(), colors: vec![] });
+/// theme.send(&plugin);
+/// ```
+#[cfg(feature = "serde_shareable")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SerdeShareable<T> {
+    name : String,
+    value: T,
+}
+
+#[cfg(feature = "serde_shareable")]
+impl<T> SerdeShareable<T>
+where
+    T: serde::Serialize + serde::de::DeserializeOwned + Send + Sync
+{
+    /// Wraps `value`, sent to plugins under `name`.
+    pub fn new(name: impl Into<String>, value: T) -> Self {
+        Self { name: name.into(), value }
+    }
+
+    /// The name the value is sent under.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the value.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// Sends the value to `plugin`, returning `VPluginError::MissingSymbol` if it doesn't
+    /// export [`vplugin_receive`](RECEIVE_SYMBOL), or `VPluginError::ParametersError` if
+    /// it refused the value.
+    pub fn try_send(&self, plugin: &crate::Plugin) -> Result<(), crate::VPluginError> {
+        use std::ffi::{c_char, c_int, CString};
+        use crate::diagnostics;
+        use crate::error::VPluginError;
+
+        type Receiver = unsafe extern "C" fn(*const c_char, *const u8, usize) -> c_int;
+
+        let data = bincode::serialize(&self.value).map_err(|e| {
+            diagnostics::error!("Couldn't serialize {}: {}", self.name, e);
+            VPluginError::InternalError { err: e.to_string() }
+        })?;
+        let name = CString::new(self.name.as_str()).map_err(|_| VPluginError::ParametersError)?;
+
+        let _pin = plugin.pin();
+        let receiver = unsafe { plugin.get_symbol::<Receiver>(RECEIVE_SYMBOL)? };
+        let status = unsafe { receiver(name.as_ptr(), data.as_ptr(), data.len()) };
+        if status != 0 {
+            diagnostics::warning!("The plugin refused {} ({}).", self.name, status);
+            return Err(VPluginError::ParametersError);
+        }
+        Ok(())
+    }
+
+    /// Decodes a value received by [`vplugin_receive`](RECEIVE_SYMBOL), for plugins.
+    ///
+    /// # Safety
+    /// `data` must point to `len` readable bytes.
+    pub unsafe fn decode(data: *const u8, len: usize) -> Result<T, crate::VPluginError> {
+        if data.is_null() {
+            return Err(crate::VPluginError::ParametersError);
+        }
+        bincode::deserialize(std::slice::from_raw_parts(data, len)).map_err(|_| crate::VPluginError::ParametersError)
+    }
+}
+
+#[cfg(feature = "serde_shareable")]
+impl<T> std::ops::Deref for SerdeShareable<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+#[cfg(feature = "serde_shareable")]
+impl<T> std::ops::DerefMut for SerdeShareable<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+#[cfg(feature = "serde_shareable")]
+impl<T> Shareable for SerdeShareable<T>
+where
+    T: serde::Serialize + serde::de::DeserializeOwned + Send + Sync
+{
+    fn send(&mut self, plugin: &crate::Plugin) {
+        if let Err(e) = self.try_send(plugin) {
+            crate::diagnostics::error!("Couldn't send {} to the plugin: {}", self.name, e);
+        }
+    }
+
+    unsafe fn send_ptr(ptr: *mut Self, plugin: &crate::Plugin) {
+        (*ptr).send(plugin)
+    }
+}
@@ -0,0 +1,55 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+use crate::error::VPluginError;
+
+/// ## ShutdownReport
+/// What [`PluginManager::shutdown`](crate::plugin_manager::PluginManager::shutdown) did.
+/// Plugins failing to terminate don't stop the others from being terminated, their errors
+/// are all collected here instead.
+///
+/// ## Example
+/// ```rust,no_run
+/// # let mut manager = vplugin::PluginManager::new();
+/// let report = manager.shutdown();
+/// for failure in &report.failed {
+///     log::warn!("Plugin '{}' didn't terminate cleanly: {}", failure.plugin, failure.error);
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+        /// The names of the plugins that were terminated, in the order they were.
+        pub terminated: Vec<String>,
+        /// The plugins that failed to terminate. Their libraries are unloaded anyway, unless
+        /// the unload policy of the manager keeps them loaded.
+        pub failed    : Vec<ShutdownFailure>,
+}
+
+impl ShutdownReport {
+        /// Returns whether every plugin terminated cleanly.
+        pub fn is_clean(&self) -> bool {
+                self.failed.is_empty()
+        }
+}
+
+/// A plugin that failed to terminate, inside a [`ShutdownReport`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShutdownFailure {
+        /// The name of the plugin.
+        pub plugin: String,
+        /// Why it failed to terminate.
+        pub error : VPluginError,
+}
@@ -0,0 +1,244 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Signatures of plugin packages, so applications only load plugins published by
+//! someone they trust. Only available with the `signing` feature.
+//!
+//! Publishers sign a package with their Ed25519 key ([`sign_directory`]), which adds a
+//! [`SIGNATURE_FILE`] to it, then pack it as usual. Applications register the public keys
+//! they trust with [`PluginManager::add_trusted_key`](crate::plugin_manager::PluginManager::add_trusted_key);
+//! from then on, every package loaded has to be signed by one of them, and the signature
+//! of archives is checked before anything is extracted. Packages that aren't signed, or
+//! whose files changed since, fail with `VPluginError::SignatureInvalid`. Bare object files
+//! can't carry a signature, so they're refused.
+//!
+//! The signature covers every file of the package except the signature itself. The files
+//! are hashed with SHA-256 in the order of their paths: every file adds its path relative
+//! to the package (With `/` as separator), a nul byte, its length as 8 little-endian bytes
+//! and its contents. The signature file holds the 64 bytes of the Ed25519 signature of
+//! that hash.
+
+use std::fs;
+use std::io;
+#[cfg(feature = "archive")]
+use std::fs::File;
+#[cfg(feature = "archive")]
+use std::io::{Cursor, Read, Seek};
+use std::path::Path;
+#[cfg(feature = "archive")]
+use crate::progress::Reporter;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use sha2::{Digest, Sha256};
+use crate::diagnostics;
+use crate::error::VPluginError;
+use crate::identity;
+
+/// The file holding the signature, at the root of the package.
+pub const SIGNATURE_FILE: &str = "package.sig";
+
+/// The public keys plugins have to be signed with, registered on a manager.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TrustedKeys(Vec<VerifyingKey>);
+
+impl TrustedKeys {
+        pub(crate) const fn new() -> Self {
+                Self(Vec::new())
+        }
+
+        pub(crate) fn add(&mut self, key: &[u8; 32]) -> Result<(), VPluginError> {
+                match VerifyingKey::from_bytes(key) {
+                        Ok (key) => {
+                                self.0.push(key);
+                                Ok(())
+                        },
+                        Err(e) => {
+                                diagnostics::error!("Invalid public key: {}", e);
+                                Err(VPluginError::ParametersError)
+                        }
+                }
+        }
+
+        pub(crate) fn is_empty(&self) -> bool {
+                self.0.is_empty()
+        }
+
+        /* Checks `signature` (The contents of the signature file) against the hash of a package. */
+        fn verify(&self, digest: &[u8], signature: Option<Vec<u8>>) -> Result<(), VPluginError> {
+                let signature = match signature {
+                        Some(signature) => signature,
+                        None            => return Err(invalid(format!("The package isn't signed (No {} file)", SIGNATURE_FILE)))
+                };
+                let signature = match <[u8; 64]>::try_from(signature.as_slice()) {
+                        Ok (bytes) => Signature::from_bytes(&bytes),
+                        Err(_)     => return Err(invalid(format!("{} isn't a valid signature", SIGNATURE_FILE)))
+                };
+                match self.0.iter().any(|key| key.verify_strict(digest, &signature).is_ok()) {
+                        true  => Ok(()),
+                        false => Err(invalid("The package was modified, or isn't signed by a trusted key".into()))
+                }
+        }
+}
+
+/// Signs the package in `directory` with the Ed25519 key `secret_key`, writing the
+/// signature to the [`SIGNATURE_FILE`] inside it. The directory can be packed into an
+/// archive afterwards, as long as its files don't change.
+pub fn sign_directory(directory: &Path, secret_key: &[u8; 32]) -> Result<(), VPluginError> {
+        let digest = match directory_digest(directory) {
+                Ok (digest) => digest.0,
+                Err(e)      => {
+                        diagnostics::error!("Couldn't read {}: {}", directory.display(), e);
+                        return Err(VPluginError::from_io(&e));
+                }
+        };
+        let signature = SigningKey::from_bytes(secret_key).sign(&digest);
+        if let Err(e) = fs::write(directory.join(SIGNATURE_FILE), signature.to_bytes()) {
+                diagnostics::error!("Couldn't write the signature of {}: {}", directory.display(), e);
+                return Err(VPluginError::from_io(&e));
+        }
+        Ok(())
+}
+
+/// Returns the public key matching the Ed25519 key `secret_key`, the one applications
+/// register to trust the plugins signed with it.
+pub fn public_key(secret_key: &[u8; 32]) -> [u8; 32] {
+        SigningKey::from_bytes(secret_key).verifying_key().to_bytes()
+}
+
+/// Checks the signature of the package in `directory`, if the host trusts any key.
+pub(crate) fn verify_directory(directory: &Path, keys: &TrustedKeys) -> Result<(), VPluginError> {
+        if keys.is_empty() {
+                return Ok(());
+        }
+        match directory_digest(directory) {
+                Ok ((digest, signature)) => keys.verify(&digest, signature),
+                Err(e)                   => Err(invalid(format!("Couldn't read the package: {}", e)))
+        }
+}
+
+/// Refuses bare object files if the host trusts any key, as they can't be signed.
+pub(crate) fn verify_object(filename: &Path, keys: &TrustedKeys) -> Result<(), VPluginError> {
+        if keys.is_empty() {
+                return Ok(());
+        }
+        Err(invalid(format!("{} is a bare object file, which can't be signed", filename.display())))
+}
+
+/// Checks the signature of the package at `path` (A directory, an archive or an object
+/// file) without loading or extracting it, if the host trusts any key.
+#[cfg(feature = "sandbox")]
+pub(crate) fn verify_package(path: &Path, keys: &TrustedKeys) -> Result<(), VPluginError> {
+        if keys.is_empty() || path.is_dir() {
+                return verify_directory(path, keys);
+        }
+        if crate::plugin::Plugin::is_object_file(path) {
+                return verify_object(path, keys);
+        }
+        verify_archive(path, keys)
+}
+
+#[cfg(all(feature = "sandbox", feature = "archive"))]
+fn verify_archive(path: &Path, keys: &TrustedKeys) -> Result<(), VPluginError> {
+        let file = File::open(path).map_err(|e| invalid(format!("Couldn't read {}: {}", path.display(), e)))?;
+        let (digest, signature) = archive_digest(io::BufReader::new(file))?;
+        keys.verify(&digest, signature)
+}
+
+#[cfg(all(feature = "sandbox", not(feature = "archive")))]
+fn verify_archive(path: &Path, _keys: &TrustedKeys) -> Result<(), VPluginError> {
+        Err(invalid(format!("Can't check {}: VPlugin was built without the `archive` feature", path.display())))
+}
+
+/// Checks the signature of the archive `data`, held in memory, if the host trusts any key.
+#[cfg(feature = "archive")]
+pub(crate) fn verify_bytes(data: &[u8], keys: &TrustedKeys) -> Result<(), VPluginError> {
+        if keys.is_empty() {
+                return Ok(());
+        }
+        let (digest, signature) = archive_digest(Cursor::new(data))?;
+        keys.verify(&digest, signature)
+}
+
+/// Extracts the archive `file` into `destination`, after checking its signature if the
+/// host trusts any key. The archive is read once, so it can't change in between.
+#[cfg(feature = "archive")]
+pub(crate) fn extract(mut file: File, destination: &Path, keys: &TrustedKeys, progress: Option<&Reporter<'_>>) -> Result<(), VPluginError> {
+        if keys.is_empty() {
+                return crate::archive::extract(file, destination, progress);
+        }
+        let mut data = Vec::new();
+        if let Err(e) = file.read_to_end(&mut data) {
+                diagnostics::error!("Couldn't read the archive: {}", e);
+                return Err(VPluginError::from_io(&e));
+        }
+        let mut archive = Cursor::new(data);
+        let (digest, signature) = archive_digest(&mut archive)?;
+        keys.verify(&digest, signature)?;
+        archive.rewind().map_err(|e| VPluginError::from_io(&e))?;
+        crate::archive::extract(archive, destination, progress)
+}
+
+/* Hashes the files of an archive (See the module documentation), returning the signature file apart. */
+#[cfg(feature = "archive")]
+fn archive_digest<R: Read + Seek>(file: R) -> Result<(Vec<u8>, Option<Vec<u8>>), VPluginError> {
+        let mut files = Vec::new();
+        crate::archive::for_each_entry(file, |entry| {
+                /* Not extracted either. */
+                if entry.is_dir {
+                        return Ok(());
+                }
+                let mut contents = Vec::new();
+                entry.contents.read_to_end(&mut contents).map_err(|e| invalid(format!("Invalid archive: {}", e)))?;
+                files.push((entry.name(), contents));
+                Ok(())
+        })?;
+        Ok(digest(files))
+}
+
+/* Hashes the files of a directory (See the module documentation), returning the signature file apart. */
+fn directory_digest(directory: &Path) -> io::Result<(Vec<u8>, Option<Vec<u8>>)> {
+        let mut files = Vec::new();
+        identity::list_files(directory, directory, &mut files)?;
+        let files = files
+                .into_iter()
+                .map(|relative| {
+                        let path = relative.split('/').fold(directory.to_path_buf(), |path, part| path.join(part));
+                        fs::read(path).map(|contents| (relative, contents))
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+        Ok(digest(files))
+}
+
+fn digest(mut files: Vec<(String, Vec<u8>)>) -> (Vec<u8>, Option<Vec<u8>>) {
+        files.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut hasher    = Sha256::new();
+        let mut signature = None;
+        for (path, contents) in files {
+                if path == SIGNATURE_FILE {
+                        signature = Some(contents);
+                        continue;
+                }
+                hasher.update(path.as_bytes());
+                hasher.update([0]);
+                hasher.update((contents.len() as u64).to_le_bytes());
+                hasher.update(&contents);
+        }
+        (hasher.finalize().to_vec(), signature)
+}
+
+fn invalid(reason: String) -> VPluginError {
+        diagnostics::error!("Refusing plugin: {}.", reason);
+        VPluginError::SignatureInvalid { reason }
+}
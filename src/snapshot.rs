@@ -0,0 +1,131 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+use std::ffi::c_void;
+use std::path::PathBuf;
+use serde_derive::{Deserialize, Serialize};
+use crate::api::HostApiVersion;
+use crate::diagnostics;
+use crate::options::Requirement;
+use crate::plugin::Plugin;
+use crate::state::PluginState;
+use crate::unload::LeakedLibrary;
+
+/* Hands the plugin a callback to write its state with, when a snapshot is taken. */
+const SAVE_STATE_SYMBOL: &str = "vplugin_save_state";
+
+/* Receives the state saved in a snapshot, before the plugin is started again. */
+const RESTORE_STATE_SYMBOL: &str = "vplugin_restore_state";
+
+/* Appends a part of the state of the plugin to its context, a `Vec<u8>`. */
+type StateWriter = unsafe extern "C" fn(*mut c_void, *const u8, usize);
+
+/// ## ManagerSnapshot
+/// A serializable description of the plugins owned by a
+/// [`PluginManager`](crate::plugin_manager::PluginManager), returned by
+/// [`PluginManager::snapshot`](crate::plugin_manager::PluginManager::snapshot).
+/// 
+/// Applications can persist it (With any serde format) to remember which plugins,
+/// and which versions of them, were active in a session, and bring them back with
+/// [`PluginManager::restore`](crate::plugin_manager::PluginManager::restore) after the
+/// application restarts (Or crashed).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManagerSnapshot {
+        /// The entry point name configured on the manager.
+        pub entry_point: String,
+        /// Every plugin owned by the manager, in no particular order.
+        pub plugins    : Vec<PluginSnapshot>,
+        /// The libraries of the manager's plugins that were kept loaded, see
+        /// [`PluginManager::leaked_libraries`](crate::plugin_manager::PluginManager::leaked_libraries).
+        #[serde(default)]
+        pub leaked     : Vec<LeakedLibrary>,
+}
+
+/// A single plugin inside a [`ManagerSnapshot`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PluginSnapshot {
+        /// The name of the plugin.
+        pub name   : String,
+        /// The version of the plugin.
+        pub version: String,
+        /// The package the plugin was loaded from. Empty for builtin plugins.
+        pub package: PathBuf,
+        /// The state the plugin was in. Plugins that were started are started again when
+        /// the snapshot is restored.
+        pub state  : PluginState,
+        /// Whether the application can run without the plugin, see
+        /// [`Requirement`](crate::options::Requirement).
+        #[serde(default)]
+        pub requirement: Requirement,
+        /// The version of the host's API negotiated with the plugin, if any.
+        #[serde(default)]
+        pub api_version: Option<HostApiVersion>,
+        /// The configuration the application last started the plugin with, as TOML, if any. See
+        /// [`begin_plugin_with_config`](crate::plugin_manager::PluginManager::begin_plugin_with_config).
+        /// The defaults of the plugin and the configuration set with
+        /// [`set_config`](crate::plugin_manager::PluginManager::set_config) aren't part of it, they're
+        /// merged again when the snapshot is restored.
+        #[serde(default)]
+        pub config : Option<String>,
+        /// The state the plugin saved through `vplugin_save_state`, if it was running and
+        /// exports it. It's handed back to the plugin through `vplugin_restore_state`.
+        #[serde(default)]
+        pub saved_state: Option<Vec<u8>>,
+}
+
+/// Returns the state of `plugin`, if it's running and exports `vplugin_save_state`.
+pub(crate) fn save_state(plugin: &Plugin) -> Option<Vec<u8>> {
+        if plugin.state() != PluginState::Started {
+                return None;
+        }
+        let save = unsafe { plugin.symbol::<unsafe extern "C" fn(StateWriter, *mut c_void) -> i32>(SAVE_STATE_SYMBOL) }.ok()?;
+        let mut state = Vec::new();
+        match unsafe { save(write_state, &mut state as *mut Vec<u8> as *mut c_void) } {
+                0      => Some(state),
+                status => {
+                        diagnostics::warning!("Plugin couldn't save its state ({} returned {}).", SAVE_STATE_SYMBOL, status);
+                        None
+                }
+        }
+}
+
+unsafe extern "C" fn write_state(context: *mut c_void, data: *const u8, len: usize) {
+        let state = match (context as *mut Vec<u8>).as_mut() {
+                Some(state) if !data.is_null() => state,
+                _                              => return
+        };
+        state.extend_from_slice(std::slice::from_raw_parts(data, len));
+}
+
+/// Hands `state` to the `vplugin_restore_state` export of `plugin`, which isn't started yet.
+/// Plugins that don't export it or refuse the state are started without it.
+pub(crate) fn restore_state(plugin: &Plugin, state: &[u8]) {
+        let restore = match unsafe { plugin.symbol::<unsafe extern "C" fn(*const u8, usize) -> i32>(RESTORE_STATE_SYMBOL) } {
+                Ok (restore) => restore,
+                Err(_)       => {
+                        diagnostics::warning!("Plugin doesn't export {}, starting it without its saved state.", RESTORE_STATE_SYMBOL);
+                        return;
+                }
+        };
+        match unsafe { restore(state.as_ptr(), state.len()) } {
+                0      => (),
+                status => diagnostics::warning!(
+                        "Plugin refused its saved state ({} returned {}), starting it without it.",
+                        RESTORE_STATE_SYMBOL,
+                        status
+                )
+        }
+}
@@ -0,0 +1,84 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+use crate::error::VPluginError;
+use crate::plugin::Plugin;
+use crate::state::PluginState;
+use vplugin_core::PluginMetadata;
+
+/// ## PluginSource
+/// The interface shared by real plugins ([`Plugin`](crate::plugin::Plugin)) and test
+/// doubles like [`MockPlugin`](crate::testing::MockPlugin) (With the `testing` feature).
+///
+/// Application code written against `PluginSource` instead of `Plugin` can be unit-tested
+/// without building and packaging actual plugins:
+/// ```rust
+/// use vplugin::PluginSource;
+///
+/// fn frame(plugin: &impl PluginSource) -> i32 {
+///     unsafe { plugin.call_hook::<f32, i32>("on_frame", 0.016) }.unwrap_or(-1)
+/// }
+/// ```
+pub trait PluginSource {
+        /// The metadata of the plugin.
+        fn metadata(&self) -> &PluginMetadata;
+
+        /// The current state of the plugin.
+        fn state(&self) -> PluginState;
+
+        /// Starts the plugin by calling its entry point.
+        fn begin(&mut self) -> Result<(), VPluginError>;
+
+        /// Terminates the plugin by calling its destructor.
+        fn terminate(&mut self) -> Result<(), VPluginError>;
+
+        /// Returns whether the plugin has a hook named `name`.
+        fn has_hook(&self, name: &str) -> bool;
+
+        /// Calls the hook `name`, taking a `P` and returning a `T`. The plugin must
+        /// be started.
+        ///
+        /// ## Safety
+        /// For real plugins, the hook must have the signature `extern "C" fn(P) -> T`.
+        unsafe fn call_hook<P: 'static, T: 'static>(&self, name: &str, arg: P) -> Result<T, VPluginError>;
+}
+
+impl PluginSource for Plugin {
+        fn metadata(&self) -> &PluginMetadata {
+                &self.metadata
+        }
+
+        fn state(&self) -> PluginState {
+                Plugin::state(self)
+        }
+
+        fn begin(&mut self) -> Result<(), VPluginError> {
+                Plugin::begin(self)
+        }
+
+        fn terminate(&mut self) -> Result<(), VPluginError> {
+                Plugin::terminate(self)
+        }
+
+        fn has_hook(&self, name: &str) -> bool {
+                self.raw.is_some() && self.is_symbol_present::<unsafe extern "C" fn(), _>(name)
+        }
+
+        unsafe fn call_hook<P: 'static, T: 'static>(&self, name: &str, arg: P) -> Result<T, VPluginError> {
+                let hook = self.get_custom_hook::<P, T>(name)?;
+                Ok(hook(arg))
+        }
+}
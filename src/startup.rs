@@ -0,0 +1,68 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+use crate::error::VPluginError;
+use crate::handle::PluginHandle;
+
+/// ## StartupSummary
+/// What [`PluginManager::begin_all`](crate::plugin_manager::PluginManager::begin_all) did,
+/// when none of the required plugins failed. If optional plugins did, the application is
+/// running in a degraded mode, and can tell the user which plugins are missing.
+///
+/// ## Example
+/// ```rust,no_run
+/// # mod my_app {
+/// #     pub fn notify(_message: String) {}
+/// # }
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let mut manager = vplugin::PluginManager::new();
+/// let summary = manager.begin_all()?;
+/// if summary.is_degraded() {
+///     my_app::notify(format!("Some features are unavailable: {}", summary.missing().collect::<Vec<_>>().join(", ")));
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StartupSummary {
+        /// The plugins that were started.
+        pub started: Vec<PluginHandle>,
+        /// The optional plugins that failed to start, the application runs without them.
+        pub failed : Vec<StartupFailure>,
+}
+
+impl StartupSummary {
+        /// Returns whether optional plugins failed to start.
+        pub fn is_degraded(&self) -> bool {
+                !self.failed.is_empty()
+        }
+
+        /// Returns the names of the plugins that failed to start.
+        pub fn missing(&self) -> impl Iterator<Item = &str> {
+                self.failed.iter().map(|failure| failure.plugin.as_str())
+        }
+}
+
+/// An optional plugin that failed to start, inside a [`StartupSummary`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartupFailure {
+        /// The handle of the plugin, which is still owned by the manager.
+        pub handle: PluginHandle,
+        /// The name of the plugin.
+        pub plugin: String,
+        /// Why it failed to start.
+        pub error : VPluginError,
+}
@@ -0,0 +1,231 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Key/value state plugins keep across terminate and reload cycles.
+//!
+//! Every manager owns a [`PluginStateStore`], holding a separate namespace of bytes keys
+//! and values for every package. The state of a plugin outlives its library: a plugin
+//! that is terminated and loaded again finds what it saved before. Namespaces are keyed
+//! by the content hash of the package (See [`PluginStateStore::namespace`]) rather than by
+//! the name of the plugin, so another plugin calling itself the same can't read or change
+//! the state. A new version of the package thus starts with an empty state, unless the
+//! host moves the state over. Plugins reach their namespace through their
+//! `vplugin_set_store` export:
+//! ```c
+//! typedef struct vplugin_store {
+//!     int (*set)   (const struct vplugin_store *self, const uint8_t *key, size_t key_len,
+//!                   const uint8_t *value, size_t value_len);
+//!     int (*get)   (const struct vplugin_store *self, const uint8_t *key, size_t key_len,
+//!                   void (*write)(void *context, const uint8_t *data, size_t len), void *context);
+//!     int (*remove)(const struct vplugin_store *self, const uint8_t *key, size_t key_len);
+//! } vplugin_store;
+//!
+//! void vplugin_set_store(const vplugin_store *store);
+//! ```
+//! `get` calls `write` with `context` and the value, which is only valid during the call.
+//! The functions return [`STORE_OK`], [`STORE_NOT_FOUND`] (`get` and `remove` only) or
+//! [`STORE_INVALID`]. The host reads and changes the same state with
+//! [`PluginManager::state_store`](crate::plugin_manager::PluginManager::state_store).
+//!
+//! The state lives in memory only, it's gone once the manager is dropped. Plugins that need
+//! their state to survive the application restarting save it in snapshots instead (See
+//! [`snapshot`](crate::snapshot)).
+
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{c_int, c_void};
+use std::sync::{Arc, Mutex, MutexGuard};
+use crate::diagnostics;
+use crate::identity;
+use crate::plugin::Plugin;
+
+/// The symbol plugins export to receive their state store.
+pub const SET_STORE_SYMBOL: &str = "vplugin_set_store";
+
+/// Returned when the operation succeeded.
+pub const STORE_OK: c_int = 0;
+/// Returned when there is no value for the key.
+pub const STORE_NOT_FOUND: c_int = 1;
+/// Returned when the arguments are invalid (A `NULL` pointer with a non-zero length, ...).
+pub const STORE_INVALID: c_int = -1;
+
+type ValueWriter = unsafe extern "C" fn(*mut c_void, *const u8, usize);
+
+/* The keys and values of a single plugin. */
+type Namespace = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/// ## PluginStateStore
+/// The state the plugins of a manager saved, one namespace per package. See the
+/// [module documentation](crate::state_store).
+///
+/// ## Example
+/// ```rust,no_run
+/// # use vplugin::PluginStateStore;
+/// # let manager = vplugin::PluginManager::new();
+/// # let plugin = vplugin::Plugin::load("plugin.vpl").unwrap();
+/// let namespace = PluginStateStore::namespace(&plugin).unwrap();
+/// manager.state_store().set(&namespace, b"count", &42u32.to_le_bytes());
+/// assert_eq!(manager.state_store().get(&namespace, b"count"), Some(42u32.to_le_bytes().to_vec()));
+/// ```
+#[derive(Debug, Default)]
+pub struct PluginStateStore {
+        plugins: Mutex<HashMap<String, Namespace>>,
+}
+
+impl PluginStateStore {
+        /// Returns the namespace of `plugin`: the [content hash](crate::plugin::Plugin::content_hash)
+        /// of its package, or `builtin:<name>` for builtin plugins. `None` if its package wasn't
+        /// hashed, in which case the plugin isn't handed the store.
+        pub fn namespace(plugin: &Plugin) -> Option<String> {
+                identity::of(plugin)
+        }
+
+        /// Returns the value saved for `key` in `namespace`, if any.
+        pub fn get(&self, namespace: &str, key: &[u8]) -> Option<Vec<u8>> {
+                self.lock().get(namespace)?.get(key).cloned()
+        }
+
+        /// Saves `value` for `key` in `namespace`, replacing the previous value.
+        pub fn set(&self, namespace: &str, key: &[u8], value: &[u8]) {
+                self.lock()
+                        .entry(namespace.to_owned())
+                        .or_default()
+                        .insert(key.to_vec(), value.to_vec());
+        }
+
+        /// Removes `key` from `namespace`, returning its value if there was one.
+        pub fn remove(&self, namespace: &str, key: &[u8]) -> Option<Vec<u8>> {
+                let mut namespaces = self.lock();
+                let keys = namespaces.get_mut(namespace)?;
+                let value = keys.remove(key);
+                if keys.is_empty() {
+                        namespaces.remove(namespace);
+                }
+                value
+        }
+
+        /// Returns the keys in `namespace`, in (Bytewise) order.
+        pub fn keys(&self, namespace: &str) -> Vec<Vec<u8>> {
+                match self.lock().get(namespace) {
+                        Some(keys) => keys.keys().cloned().collect(),
+                        None       => Vec::new()
+                }
+        }
+
+        /// Forgets the whole state in `namespace`.
+        pub fn clear(&self, namespace: &str) {
+                self.lock().remove(namespace);
+        }
+
+        fn lock(&self) -> MutexGuard<'_, HashMap<String, Namespace>> {
+                self.plugins.lock().unwrap_or_else(|e| e.into_inner())
+        }
+}
+
+/// ## StateTable
+/// The namespace of a single plugin in a [`PluginStateStore`], handed to it as a
+/// `const vplugin_store *`. See the [module documentation](crate::state_store).
+#[repr(C)]
+#[derive(Debug)]
+pub struct StateTable {
+        /// Saves a value for a key.
+        pub set   : unsafe extern "C" fn(*const StateTable, *const u8, usize, *const u8, usize) -> c_int,
+        /// Writes the value saved for a key.
+        pub get   : unsafe extern "C" fn(*const StateTable, *const u8, usize, ValueWriter, *mut c_void) -> c_int,
+        /// Removes a key.
+        pub remove: unsafe extern "C" fn(*const StateTable, *const u8, usize) -> c_int,
+        store     : Arc<PluginStateStore>,
+        namespace : String,
+}
+
+impl Default for StateTable {
+        fn default() -> Self {
+                Self::new(Arc::default(), "")
+        }
+}
+
+impl StateTable {
+        fn new(store: Arc<PluginStateStore>, namespace: &str) -> Self {
+                Self {
+                        set      : store_set,
+                        get      : store_get,
+                        remove   : store_remove,
+                        store,
+                        namespace: namespace.to_owned(),
+                }
+        }
+}
+
+/* The bytes at `data`, or `None` if the pointer is NULL while `len` isn't 0. */
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+        match (data.is_null(), len) {
+                (true, 0) => Some(&[]),
+                (true, _) => None,
+                _         => Some(std::slice::from_raw_parts(data, len))
+        }
+}
+
+unsafe extern "C" fn store_set(table: *const StateTable, key: *const u8, key_len: usize, value: *const u8, value_len: usize) -> c_int {
+        match (table.as_ref(), bytes(key, key_len), bytes(value, value_len)) {
+                (Some(table), Some(key), Some(value)) => {
+                        table.store.set(&table.namespace, key, value);
+                        STORE_OK
+                },
+                _ => STORE_INVALID
+        }
+}
+
+unsafe extern "C" fn store_get(table: *const StateTable, key: *const u8, key_len: usize, write: ValueWriter, context: *mut c_void) -> c_int {
+        let (table, key) = match (table.as_ref(), bytes(key, key_len)) {
+                (Some(table), Some(key)) => (table, key),
+                _                        => return STORE_INVALID
+        };
+        /* Copied out, so the plugin can use the store from `write`. */
+        match table.store.get(&table.namespace, key) {
+                Some(value) => {
+                        write(context, value.as_ptr(), value.len());
+                        STORE_OK
+                },
+                None => STORE_NOT_FOUND
+        }
+}
+
+unsafe extern "C" fn store_remove(table: *const StateTable, key: *const u8, key_len: usize) -> c_int {
+        match (table.as_ref(), bytes(key, key_len)) {
+                (Some(table), Some(key)) => match table.store.remove(&table.namespace, key) {
+                        Some(_) => STORE_OK,
+                        None    => STORE_NOT_FOUND
+                },
+                _ => STORE_INVALID
+        }
+}
+
+/// Hands `plugin` its namespace in `store`, if it exports `vplugin_set_store`.
+pub(crate) fn attach(plugin: &mut Plugin, store: &Arc<PluginStateStore>) {
+        let set_store = match unsafe { plugin.symbol::<unsafe extern "C" fn(*const StateTable)>(SET_STORE_SYMBOL) } {
+                Ok (set_store) => set_store,
+                Err(_)         => {
+                        diagnostics::trace!("Plugin doesn't export {}, it can't keep state across reloads.", SET_STORE_SYMBOL);
+                        return;
+                }
+        };
+        match PluginStateStore::namespace(plugin) {
+                Some(namespace) => {
+                        plugin.store = Arc::new(StateTable::new(store.clone(), &namespace));
+                        unsafe { set_store(&*plugin.store) }
+                },
+                None => diagnostics::warning!("The package of the plugin wasn't hashed, it can't keep state across reloads.")
+        }
+}
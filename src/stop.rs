@@ -0,0 +1,77 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Cooperative stop requests, see [`Plugin::request_stop`](crate::plugin::Plugin::request_stop).
+//!
+//! Plugins doing work in the background export:
+//! ```c
+//! void vplugin_stop(void (*acknowledge)(void *context), void *context);
+//! ```
+//! which asks them to wind that work down. Once they did (From any thread, possibly after
+//! `vplugin_stop` returned) they call `acknowledge(context)`, exactly once.
+
+use std::ffi::c_void;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+use crate::error::VPluginError;
+use crate::intercept::CallKind;
+use crate::isolation::{Export, Unguarded};
+use crate::plugin::Plugin;
+
+/// The symbol plugins export to be asked to stop.
+pub(crate) const STOP_SYMBOL: &str = "vplugin_stop";
+
+pub(crate) type Stop = unsafe extern "C-unwind" fn(Acknowledge, *mut c_void);
+
+/// What plugins call once they stopped, with the context they were given.
+pub(crate) type Acknowledge = unsafe extern "C" fn(*mut c_void);
+
+/* Set once the plugin acknowledged the request. */
+#[derive(Default)]
+struct Signal {
+        stopped     : Mutex<bool>,
+        acknowledged: Condvar,
+}
+
+unsafe extern "C" fn acknowledge(context: *mut c_void) {
+        let _unguarded = Unguarded::enter();
+        let signal = &*(context as *const Signal);
+        *signal.stopped.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        signal.acknowledged.notify_all();
+}
+
+/// Asks `plugin` to stop through its `vplugin_stop` export `stop`, and waits up to `timeout`
+/// for it to acknowledge. Returns whether it did, or the error if the call failed (See
+/// [`Plugin::run_export`](crate::plugin::Plugin::run_export)).
+pub(crate) fn request(plugin: &Plugin, stop: Stop, timeout: Duration) -> Result<bool, VPluginError> {
+        let signal = Arc::new(Signal::default());
+        let context = Arc::into_raw(Arc::clone(&signal)) as *mut c_void;
+        /* A plugin whose call failed may still acknowledge later, the signal is leaked then. */
+        plugin.run_export(CallKind::Stop, Export::Stop(stop, acknowledge, context))?;
+
+        let stopped = signal.stopped.lock().unwrap_or_else(|e| e.into_inner());
+        let (stopped, _) = signal.acknowledged
+                .wait_timeout_while(stopped, timeout, |stopped| !*stopped)
+                .unwrap_or_else(|e| e.into_inner());
+        let acknowledged = *stopped;
+        drop(stopped);
+
+        /* A plugin that didn't acknowledge may still do it later, the signal has to outlive it. */
+        if acknowledged {
+                drop(unsafe { Arc::from_raw(context as *const Signal) });
+        }
+        Ok(acknowledged)
+}
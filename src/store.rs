@@ -0,0 +1,61 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! The TOML files VPlugin keeps inside the data directory of plugins (The values
+//! of their settings, the permissions they were granted, ...).
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use crate::error::VPluginError;
+use crate::diagnostics;
+
+/// Reads the table stored at `path`. A file that doesn't exist yet is an empty table,
+/// and so is a corrupted one, with a warning.
+pub(crate) fn read(path: &Path) -> Result<toml::Table, VPluginError> {
+        let contents = match fs::read_to_string(path) {
+                Ok (contents) => contents,
+                Err(e) if e.kind() == ErrorKind::NotFound => return Ok(toml::Table::new()),
+                Err(e) => {
+                        diagnostics::error!("Couldn't read {}: {}", path.display(), e);
+                        return Err(VPluginError::from_io(&e));
+                }
+        };
+        match contents.parse::<toml::Table>() {
+                Ok (table) => Ok(table),
+                Err(e)     => {
+                        diagnostics::warning!("Ignoring the corrupted file {}: {}", path.display(), e);
+                        Ok(toml::Table::new())
+                }
+        }
+}
+
+/// Stores `table` at `path`, creating its directory if needed.
+pub(crate) fn write(path: &Path, table: &toml::Table) -> Result<(), VPluginError> {
+        let temporary = path.with_extension("toml.tmp");
+        /* Written aside first, so a crash never leaves a truncated file behind. */
+        let written = match path.parent() {
+                Some(dir) => fs::create_dir_all(dir),
+                None      => Ok(())
+        }
+        .and_then(|_| fs::write(&temporary, table.to_string()))
+        .and_then(|_| fs::rename(&temporary, path));
+        if let Err(e) = written {
+                diagnostics::error!("Couldn't write {}: {}", path.display(), e);
+                return Err(VPluginError::from_io(&e));
+        }
+        Ok(())
+}
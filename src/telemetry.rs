@@ -0,0 +1,221 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+
+//! Anonymous records of how plugins behave in the field, for application vendors.
+//!
+//! Nothing is recorded unless the application installs a [`TelemetrySink`] with
+//! [`PluginManager::set_telemetry_sink`](crate::plugin_manager::PluginManager::set_telemetry_sink).
+//! VPlugin never sends the records anywhere itself, what happens to them (And whether the
+//! user agreed to it) is up to the application.
+
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use serde_derive::{Deserialize, Serialize};
+use crate::error::VPluginError;
+use vplugin_core::PluginMetadata;
+
+/// What a [`TelemetryRecord`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TelemetryEvent {
+        /// A plugin was loaded (Extracted, checked and its library opened).
+        Load,
+        /// The entry point of a plugin was run.
+        Start,
+}
+
+/// ## TelemetryRecord
+/// A single record handed to a [`TelemetrySink`]. Records are anonymous: they never contain
+/// paths, the configuration of plugins or the messages of errors, which may all reveal
+/// something about the user.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TelemetryRecord {
+        /// What happened.
+        pub event   : TelemetryEvent,
+        /// The name of the plugin. When a plugin fails to load before its metadata is read,
+        /// the name of its package file (Without the extension) is used instead.
+        pub plugin  : String,
+        /// The version of the plugin, unless it failed to load before its metadata was read.
+        pub version : Option<String>,
+        /// How long it took.
+        pub duration: Duration,
+        /// The kind of error that occurred (The name of the [`VPluginError`] variant, like
+        /// `"MissingLibraries"`), or `None` if it succeeded.
+        pub error   : Option<String>,
+        /// The backtrace of the plugin if it panicked (See
+        /// [`Plugin::last_backtrace`](crate::plugin::Plugin::last_backtrace)). It contains the
+        /// names of functions and the paths of the source files on the machine the plugin was
+        /// built on, but nothing about the user.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub backtrace: Option<String>,
+}
+
+impl TelemetryRecord {
+        /// Returns whether the operation succeeded.
+        pub fn succeeded(&self) -> bool {
+                self.error.is_none()
+        }
+}
+
+/// ## TelemetrySink
+/// Receives the [`TelemetryRecord`]s of a [`PluginManager`](crate::plugin_manager::PluginManager),
+/// once installed with [`set_telemetry_sink`](crate::plugin_manager::PluginManager::set_telemetry_sink).
+///
+/// ## Example
+/// ```rust,no_run
+/// # mod my_app {
+/// #     pub mod analytics {
+/// #         pub fn send(_event: &str, _plugin: &str, _error: &str) {}
+/// #     }
+/// #     pub struct Settings { pub share_usage_data: bool }
+/// #     pub fn settings() -> Settings { Settings { share_usage_data: true } }
+/// # }
+/// use vplugin::{PluginManager, TelemetryRecord, TelemetrySink};
+///
+/// struct Failures;
+///
+/// impl TelemetrySink for Failures {
+///     fn record(&self, record: &TelemetryRecord) {
+///         if let Some(error) = &record.error {
+///             my_app::analytics::send("plugin_failure", &record.plugin, error);
+///         }
+///     }
+/// }
+///
+/// let mut manager = PluginManager::new();
+/// if my_app::settings().share_usage_data {
+///     manager.set_telemetry_sink(Failures);
+/// }
+/// ```
+pub trait TelemetrySink: Send + Sync {
+        /// Called for every record, on the thread using the manager. This shouldn't
+        /// block: send the record to a background thread if it has to go over the network.
+        fn record(&self, record: &TelemetryRecord);
+}
+
+/// The telemetry sink of a manager, if the application installed one.
+#[derive(Clone, Default)]
+pub(crate) struct Telemetry(Option<Arc<dyn TelemetrySink>>);
+
+impl Telemetry {
+        pub(crate) fn new(sink: impl TelemetrySink + 'static) -> Self {
+                Self(Some(Arc::new(sink)))
+        }
+
+        /// Records the loading of the package `filename`, which started at `started`.
+        pub(crate) fn loaded(&self, filename: &Path, metadata: Result<&PluginMetadata, &VPluginError>, started: Instant) {
+                match metadata {
+                        Ok (metadata) => self.record(TelemetryEvent::Load, metadata.name.clone(), Some(metadata), None, None, started),
+                        Err(e)        => {
+                                let package = filename
+                                        .file_stem()
+                                        .map(|stem| stem.to_string_lossy().into_owned())
+                                        .unwrap_or_default();
+                                self.record(TelemetryEvent::Load, package, None, Some(e), None, started)
+                        }
+                }
+        }
+
+        /// Records running the entry point of the plugin described by `metadata`, which started at
+        /// `started`, with the backtrace captured if it failed.
+        pub(crate) fn started(
+                &self,
+                metadata : &PluginMetadata,
+                result   : Result<(), &VPluginError>,
+                backtrace: Option<&str>,
+                started  : Instant
+        ) {
+                self.record(TelemetryEvent::Start, metadata.name.clone(), Some(metadata), result.err(), backtrace, started);
+        }
+
+        fn record(
+                &self,
+                event    : TelemetryEvent,
+                plugin   : String,
+                metadata : Option<&PluginMetadata>,
+                error    : Option<&VPluginError>,
+                backtrace: Option<&str>,
+                started  : Instant
+        ) {
+                if let Some(sink) = &self.0 {
+                        sink.record(&TelemetryRecord {
+                                event,
+                                plugin,
+                                version : metadata.map(|metadata| metadata.version.clone()),
+                                duration: started.elapsed(),
+                                error   : error.map(|e| kind(e).into()),
+                                backtrace: backtrace.map(str::to_owned),
+                        });
+                }
+        }
+}
+
+impl fmt::Debug for Telemetry {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self.0 {
+                        Some(_) => f.write_str("Telemetry(enabled)"),
+                        None    => f.write_str("Telemetry(disabled)")
+                }
+        }
+}
+
+/* The name of the variant of `e`, without what it carries (Which may contain paths). */
+fn kind(e: &VPluginError) -> &'static str {
+        match e {
+                VPluginError::ParametersError                  => "ParametersError",
+                VPluginError::InvalidPlugin                    => "InvalidPlugin",
+                VPluginError::NoSuchFile                       => "NoSuchFile",
+                VPluginError::PermissionDenied                 => "PermissionDenied",
+                VPluginError::MissingSymbol                    => "MissingSymbol",
+                VPluginError::FailedToInitialize               => "FailedToInitialize",
+                VPluginError::CodeSignatureRejected { .. }     => "CodeSignatureRejected",
+                VPluginError::DynamicLoadingUnavailable { .. } => "DynamicLoadingUnavailable",
+                VPluginError::ConflictingEntries { .. }        => "ConflictingEntries",
+                VPluginError::InvalidState { .. }              => "InvalidState",
+                VPluginError::PluginInUse { .. }               => "PluginInUse",
+                VPluginError::InvalidHandle                    => "InvalidHandle",
+                VPluginError::StaleHandle { .. }               => "StaleHandle",
+                VPluginError::HandleBorrowed { .. }            => "HandleBorrowed",
+                VPluginError::StopTimedOut { .. }              => "StopTimedOut",
+                VPluginError::PluginPanicked { .. }            => "PluginPanicked",
+                VPluginError::PluginCrashed { .. }             => "PluginCrashed",
+                VPluginError::RequiredPluginFailed { .. }      => "RequiredPluginFailed",
+                VPluginError::MissingDependency { .. }         => "MissingDependency",
+                VPluginError::CyclicDependency { .. }          => "CyclicDependency",
+                VPluginError::InvalidVersion { .. }            => "InvalidVersion",
+                VPluginError::UnsatisfiedVersion { .. }        => "UnsatisfiedVersion",
+                VPluginError::IncompatibleApi { .. }           => "IncompatibleApi",
+                VPluginError::SdkVersionMismatch { .. }        => "SdkVersionMismatch",
+                VPluginError::ToolchainMismatch { .. }         => "ToolchainMismatch",
+                VPluginError::MissingLibraries { .. }          => "MissingLibraries",
+                VPluginError::LicensePolicyViolation { .. }    => "LicensePolicyViolation",
+                VPluginError::NotEntitled { .. }               => "NotEntitled",
+                VPluginError::CallRefused { .. }               => "CallRefused",
+                VPluginError::SandboxCrashed { .. }            => "SandboxCrashed",
+                VPluginError::SandboxFailed { .. }             => "SandboxFailed",
+                VPluginError::SignatureInvalid { .. }          => "SignatureInvalid",
+                VPluginError::InvalidSetting { .. }            => "InvalidSetting",
+                VPluginError::InvalidHookManifest { .. }       => "InvalidHookManifest",
+                VPluginError::SignatureMismatch { .. }         => "SignatureMismatch",
+                VPluginError::CapabilitiesRefused { .. }       => "CapabilitiesRefused",
+                VPluginError::IncompatibleVersion { .. }       => "IncompatibleVersion",
+                VPluginError::Cancelled                        => "Cancelled",
+                VPluginError::InternalError { .. }             => "InternalError",
+        }
+}
@@ -0,0 +1,638 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Test doubles for applications using VPlugin. Only available with the
+//! `testing` feature, which is meant to be enabled in `[dev-dependencies]`.
+
+use std::any::Any;
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::ffi::c_void;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use crate::backend::PluginBackend;
+use crate::diagnostics;
+use crate::error::VPluginError;
+use crate::fault::Stage;
+use crate::source::PluginSource;
+use crate::state::PluginState;
+use vplugin_core::PluginMetadata;
+
+type MockHook = Box<dyn Fn(Box<dyn Any>) -> Result<Box<dyn Any>, VPluginError>>;
+
+/// ## MockPlugin
+/// A plugin whose entry point, destructor and hooks are Rust closures registered by the
+/// test. It implements [`PluginSource`](crate::source::PluginSource) and follows the
+/// same lifecycle as a real plugin: hooks can only be called once it's started, it can't
+/// be started twice, etc.
+///
+/// ## Example
+/// ```rust
+/// use vplugin::PluginSource;
+/// use vplugin::testing::MockPlugin;
+///
+/// let mut plugin = MockPlugin::new("example", "1.0.0")
+///     .with_hook("add_one", |x: i32| x + 1);
+///
+/// plugin.begin().unwrap();
+/// assert_eq!(unsafe { plugin.call_hook::<i32, i32>("add_one", 41) }, Ok(42));
+/// assert_eq!(plugin.calls("add_one"), 1);
+/// ```
+pub struct MockPlugin {
+        metadata   : PluginMetadata,
+        state      : PluginState,
+        entry_point: Box<dyn Fn() -> i32>,
+        destructor : Option<Box<dyn Fn()>>,
+        hooks      : HashMap<String, (MockHook, Cell<usize>)>,
+}
+
+impl MockPlugin {
+        /// Creates a plugin named `name` with the given `version`, already loaded. Its
+        /// entry point succeeds and its destructor does nothing.
+        pub fn new(name: &str, version: &str) -> Self {
+                Self {
+                        metadata   : PluginMetadata {
+                                version    : version.into(),
+                                name       : name.into(),
+                                ..Default::default()
+                        },
+                        state      : PluginState::Loaded,
+                        entry_point: Box::new(|| 0),
+                        destructor : Some(Box::new(|| ())),
+                        hooks      : HashMap::new(),
+                }
+        }
+
+        /// Replaces the metadata of the plugin, for code that reads the
+        /// description or the extra keys.
+        pub fn with_metadata(mut self, metadata: PluginMetadata) -> Self {
+                self.metadata = metadata;
+                self
+        }
+
+        /// Sets the entry point. Like a real one, it returns 0 on success.
+        pub fn with_entry_point(mut self, entry_point: impl Fn() -> i32 + 'static) -> Self {
+                self.entry_point = Box::new(entry_point);
+                self
+        }
+
+        /// Sets the destructor. `None` makes the plugin behave like one without
+        /// a `vplugin_exit` function.
+        pub fn with_destructor(mut self, destructor: Option<Box<dyn Fn()>>) -> Self {
+                self.destructor = destructor;
+                self
+        }
+
+        /// Adds the hook `name`, taking a `P` and returning a `T`. Calling it with
+        /// other types returns `VPluginError::ParametersError`.
+        pub fn with_hook<P: 'static, T: 'static>(mut self, name: &str, hook: impl Fn(P) -> T + 'static) -> Self {
+                let hook: MockHook = Box::new(move |arg| match arg.downcast::<P>() {
+                        Ok (arg) => Ok(Box::new(hook(*arg))),
+                        Err(_)   => Err(VPluginError::ParametersError)
+                });
+                self.hooks.insert(name.to_owned(), (hook, Cell::new(0)));
+                self
+        }
+
+        /// Returns how many times the hook `name` was called.
+        pub fn calls(&self, name: &str) -> usize {
+                self.hooks.get(name).map_or(0, |(_, calls)| calls.get())
+        }
+
+        fn transition(&mut self, next: PluginState) -> Result<(), VPluginError> {
+                if self.state.can_transition_to(next) {
+                        return Ok(());
+                }
+                diagnostics::error!(
+                        "Plugin '{}' can't go from {:?} to {:?}.",
+                        self.metadata.name,
+                        self.state,
+                        next
+                );
+                Err(VPluginError::InvalidState { current: self.state, requested: next })
+        }
+}
+
+impl PluginSource for MockPlugin {
+        fn metadata(&self) -> &PluginMetadata {
+                &self.metadata
+        }
+
+        fn state(&self) -> PluginState {
+                self.state
+        }
+
+        fn begin(&mut self) -> Result<(), VPluginError> {
+                self.transition(PluginState::Started)?;
+                if (self.entry_point)() != 0 {
+                        return Err(VPluginError::FailedToInitialize);
+                }
+                self.state = PluginState::Started;
+                Ok(())
+        }
+
+        fn terminate(&mut self) -> Result<(), VPluginError> {
+                self.transition(PluginState::Terminated)?;
+                match &self.destructor {
+                        Some(destructor) => destructor(),
+                        None             => return Err(VPluginError::InvalidPlugin)
+                }
+                self.state = PluginState::Terminated;
+                Ok(())
+        }
+
+        fn has_hook(&self, name: &str) -> bool {
+                self.hooks.contains_key(name)
+        }
+
+        unsafe fn call_hook<P: 'static, T: 'static>(&self, name: &str, arg: P) -> Result<T, VPluginError> {
+                if self.state != PluginState::Started {
+                        return Err(VPluginError::InvalidState { current: self.state, requested: PluginState::Started });
+                }
+                let (hook, calls) = match self.hooks.get(name) {
+                        Some(hook) => hook,
+                        None       => return Err(VPluginError::MissingSymbol)
+                };
+                calls.set(calls.get() + 1);
+                match hook(Box::new(arg))?.downcast::<T>() {
+                        Ok (result) => Ok(*result),
+                        Err(_)      => Err(VPluginError::ParametersError)
+                }
+        }
+}
+
+/// ## FakeBackend
+/// A [`PluginBackend`](crate::backend::PluginBackend) whose symbols are registered by the
+/// test, so the manager's logic (Entry points, hooks, the lifecycle of plugins) can be
+/// exercised without loading any library.
+///
+/// ## Example
+/// ```rust
+/// use vplugin::{Plugin, PluginManager, PluginMetadata};
+/// use vplugin::testing::FakeBackend;
+///
+/// extern "C" fn init() -> i32 { 0 }
+/// extern "C" fn add_one(x: i32) -> i32 { x + 1 }
+///
+/// let backend = FakeBackend::new()
+///     .with_symbol("vplugin_init", init as *const ())
+///     .with_symbol("add_one", add_one as *const ());
+/// let plugin  = Plugin::with_backend(PluginMetadata::default(), backend);
+///
+/// let mut manager = PluginManager::new();
+/// let handle = manager.insert(plugin);
+/// manager.begin(handle).unwrap();
+/// let add_one = manager.custom_hook::<i32, i32>(handle, "add_one").unwrap();
+/// assert_eq!(unsafe { add_one(41) }, 42);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FakeBackend {
+        symbols: HashMap<Vec<u8>, usize>,
+}
+
+impl FakeBackend {
+        /// Creates a backend without any symbol.
+        pub fn new() -> Self {
+                Self::default()
+        }
+
+        /// Adds the symbol `name` at `address`, usually a function cast to a
+        /// pointer (`my_function as *const ()`).
+        pub fn with_symbol(mut self, name: &str, address: *const ()) -> Self {
+                self.symbols.insert(name.as_bytes().to_vec(), address as usize);
+                self
+        }
+}
+
+impl PluginBackend for FakeBackend {
+        fn symbol(&self, name: &[u8]) -> Result<*mut c_void, String> {
+                let name = name.strip_suffix(b"\0").unwrap_or(name);
+                match self.symbols.get(name) {
+                        Some(address) => Ok(*address as *mut c_void),
+                        None          => Err(format!("Undefined symbol: {}", String::from_utf8_lossy(name)))
+                }
+        }
+}
+
+/// The language of the source of a [`Fixture`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+        /// Compiled with `rustc` (Or `$RUSTC`) as a `cdylib`.
+        Rust,
+        /// Compiled with `cc` (Or `$CC`) as a shared library.
+        C,
+}
+
+/// ## Fixture
+/// A plugin built from source while the test runs, created with [`fixture`]. The source
+/// is compiled into a shared library and packaged together with a generated
+/// `metadata.toml`, so tests don't depend on prebuilt plugins.
+///
+/// ## Example
+/// ```rust
+/// use vplugin::testing::fixture;
+///
+/// let dir = std::env::temp_dir().join("my-test");
+/// let vpl = fixture("counter")
+///     .rust(r#"
+///         #[no_mangle] pub extern "C" fn vplugin_init() -> i32 { 0 }
+///         #[no_mangle] pub extern "C" fn vplugin_exit() {}
+///     "#)
+///     .build(&dir)
+///     .unwrap();
+///
+/// let mut manager = vplugin::PluginManager::new();
+/// let plugin = manager.load(vpl).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Fixture {
+        name       : String,
+        version    : String,
+        description: Option<String>,
+        api_version: Option<String>,
+        entry_signature: Option<String>,
+        dependencies: BTreeMap<String, String>,
+        permissions: BTreeMap<String, String>,
+        needs      : Vec<String>,
+        config     : toml::Table,
+        language   : Language,
+        source     : String,
+        #[cfg(feature = "signing")]
+        signing_key: Option<[u8; 32]>,
+}
+
+/// Starts describing a fixture plugin named `name`. Its source has to be
+/// given with [`Fixture::rust`] or [`Fixture::c`].
+pub fn fixture(name: &str) -> Fixture {
+        Fixture {
+                name       : name.to_owned(),
+                version    : "0.1.0".into(),
+                description: None,
+                api_version: None,
+                entry_signature: None,
+                dependencies: BTreeMap::new(),
+                permissions: BTreeMap::new(),
+                needs      : Vec::new(),
+                config     : toml::Table::new(),
+                language   : Language::Rust,
+                source     : String::new(),
+                #[cfg(feature = "signing")]
+                signing_key: None,
+        }
+}
+
+impl Fixture {
+        /// Uses `source`, written in Rust, as the source of the plugin. Functions have
+        /// to be `#[no_mangle] pub extern "C"` to be found.
+        pub fn rust(mut self, source: &str) -> Self {
+                self.language = Language::Rust;
+                self.source   = source.to_owned();
+                self
+        }
+
+        /// Uses `source`, written in C, as the source of the plugin.
+        pub fn c(mut self, source: &str) -> Self {
+                self.language = Language::C;
+                self.source   = source.to_owned();
+                self
+        }
+
+        /// Sets the version written in the metadata (`0.1.0` by default).
+        pub fn version(mut self, version: &str) -> Self {
+                self.version = version.to_owned();
+                self
+        }
+
+        /// Sets the description written in the metadata.
+        pub fn description(mut self, description: &str) -> Self {
+                self.description = Some(description.to_owned());
+                self
+        }
+
+        /// Sets the version of the host's API written in the metadata (`api_version`).
+        pub fn api_version(mut self, version: &str) -> Self {
+                self.api_version = Some(version.to_owned());
+                self
+        }
+
+        /// Declares `signature` (Like `fn() -> i32`) as the signature of the entry point of the
+        /// plugin, written in the metadata as its hash (`entry_signature`) like `vplugin-package`
+        /// does. See [`PluginManager::begin_plugin_checked`](crate::plugin_manager::PluginManager::begin_plugin_checked).
+        pub fn entry_signature(mut self, signature: &str) -> Self {
+                self.entry_signature = Some(signature.to_owned());
+                self
+        }
+
+        /// Adds `name` to the dependencies of the plugin, with the version requirement `requirement`.
+        pub fn dependency(mut self, name: &str, requirement: &str) -> Self {
+                self.dependencies.insert(name.to_owned(), requirement.to_owned());
+                self
+        }
+
+        /// Adds `capability` to the permissions of the plugin, needed for `reason`.
+        pub fn permission(mut self, capability: &str, reason: &str) -> Self {
+                self.permissions.insert(capability.to_owned(), reason.to_owned());
+                self
+        }
+
+        /// Adds `library` to the native libraries the plugin needs (`needs`).
+        pub fn needs(mut self, library: &str) -> Self {
+                self.needs.push(library.to_owned());
+                self
+        }
+
+        /// Sets `key` to `value` in the `[config]` section of the metadata, the default
+        /// configuration of the plugin.
+        pub fn config(mut self, key: &str, value: impl Into<toml::Value>) -> Self {
+                self.config.insert(key.to_owned(), value.into());
+                self
+        }
+
+        /// Signs the package with the Ed25519 key `secret_key` once it's built.
+        /// See [`signing`](crate::signing).
+        #[cfg(feature = "signing")]
+        pub fn signed_with(mut self, secret_key: [u8; 32]) -> Self {
+                self.signing_key = Some(secret_key);
+                self
+        }
+
+        /// Builds the plugin as an unpacked directory (`<dir>/<name>`) and returns its path.
+        /// The directory can be loaded like an archive.
+        pub fn build_directory(&self, dir: &Path) -> Result<PathBuf, VPluginError> {
+                let package = dir.join(&self.name);
+                if let Err(e) = fs::create_dir_all(&package) {
+                        diagnostics::error!("Couldn't create {}: {}", package.display(), e);
+                        return Err(VPluginError::from_io(&e));
+                }
+
+                let objfile = format!("plugin.{}", std::env::consts::DLL_EXTENSION);
+                self.compile(&package, &package.join(&objfile))?;
+
+                let mut metadata = toml::Table::new();
+                metadata.insert("name".into(), self.name.clone().into());
+                metadata.insert("version".into(), self.version.clone().into());
+                metadata.insert("objfile".into(), objfile.into());
+                if let Some(description) = &self.description {
+                        metadata.insert("description".into(), description.clone().into());
+                }
+                if let Some(api_version) = &self.api_version {
+                        metadata.insert("api_version".into(), api_version.clone().into());
+                }
+                if !self.needs.is_empty() {
+                        metadata.insert("needs".into(), self.needs.clone().into());
+                }
+                if let Some(signature) = &self.entry_signature {
+                        match signature.parse::<vplugin_core::HookSignature>() {
+                                Ok (signature) => { metadata.insert("entry_signature".into(), format!("{:016x}", signature.hash()).into()); },
+                                Err(e)         => {
+                                        diagnostics::error!("Invalid entry point signature for fixture '{}': {}", self.name, e);
+                                        return Err(VPluginError::ParametersError);
+                                }
+                        }
+                }
+                let mut document = toml::Table::new();
+                document.insert("metadata".into(), metadata.into());
+                if !self.dependencies.is_empty() {
+                        let dependencies: toml::Table = self.dependencies
+                                .iter()
+                                .map(|(name, requirement)| (name.clone(), requirement.clone().into()))
+                                .collect();
+                        document.insert("dependencies".into(), dependencies.into());
+                }
+                if !self.permissions.is_empty() {
+                        let permissions: toml::Table = self.permissions
+                                .iter()
+                                .map(|(capability, reason)| (capability.clone(), reason.clone().into()))
+                                .collect();
+                        document.insert("permissions".into(), permissions.into());
+                }
+                if !self.config.is_empty() {
+                        document.insert("config".into(), self.config.clone().into());
+                }
+
+                if let Err(e) = fs::write(package.join("metadata.toml"), document.to_string()) {
+                        diagnostics::error!("Couldn't write the metadata of fixture '{}': {}", self.name, e);
+                        return Err(VPluginError::from_io(&e));
+                }
+                #[cfg(feature = "signing")]
+                if let Some(secret_key) = &self.signing_key {
+                        crate::signing::sign_directory(&package, secret_key)?;
+                }
+                Ok(package)
+        }
+
+        /// Builds the plugin and packages it as `<dir>/<name>.vpl`, returning its path.
+        #[cfg(feature = "archive")]
+        pub fn build(&self, dir: &Path) -> Result<PathBuf, VPluginError> {
+                let package = self.build_directory(dir)?;
+                let archive = dir.join(format!("{}.vpl", self.name));
+                crate::archive::pack(&package, &archive)?;
+                Ok(archive)
+        }
+
+        /* Compiles the source into the shared library `output`. */
+        fn compile(&self, package: &Path, output: &Path) -> Result<(), VPluginError> {
+                let (source, mut command) = match self.language {
+                        Language::Rust => {
+                                let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+                                let mut command = Command::new(rustc);
+                                command.args(["--crate-type", "cdylib", "--edition", "2021", "--crate-name"]);
+                                command.arg(self.name.replace('-', "_"));
+                                (package.join("plugin.rs"), command)
+                        },
+                        Language::C => {
+                                let cc = env::var_os("CC").unwrap_or_else(|| "cc".into());
+                                let mut command = Command::new(cc);
+                                command.args(["-shared", "-fPIC"]);
+                                (package.join("plugin.c"), command)
+                        }
+                };
+                if let Err(e) = fs::write(&source, &self.source) {
+                        diagnostics::error!("Couldn't write {}: {}", source.display(), e);
+                        return Err(VPluginError::from_io(&e));
+                }
+
+                let output = command.arg("-o").arg(output).arg(&source).output();
+                match output {
+                        Ok(out) if out.status.success() => Ok(()),
+                        Ok(out) => {
+                                let stderr = String::from_utf8_lossy(&out.stderr).into_owned();
+                                diagnostics::error!("Couldn't compile fixture '{}':\n{}", self.name, stderr);
+                                Err(VPluginError::InternalError { err: stderr })
+                        },
+                        Err(e) => {
+                                diagnostics::error!("Couldn't run the compiler for fixture '{}': {}", self.name, e);
+                                Err(VPluginError::InternalError { err: e.to_string() })
+                        }
+                }
+        }
+}
+
+/// Feeds `data` to the loader as if it was the contents of a `.vpl` file, and returns the
+/// metadata found inside. This is meant to be used by fuzzers (`cargo fuzz`, whose
+/// `libfuzzer-sys` VPlugin doesn't depend on, so the example isn't compiled as a test):
+/// ```rust,ignore
+/// libfuzzer_sys::fuzz_target!(|data: &[u8]| {
+///     let _ = vplugin::testing::fuzz_load_bytes(data);
+/// });
+/// ```
+///
+/// Whatever `data` contains, an error is returned instead of panicking. Everything up to
+/// loading the object file is done (Extracting the archive, reading and validating the
+/// metadata), but the object file itself is never loaded, as that would run its code.
+#[cfg(feature = "archive")]
+pub fn fuzz_load_bytes(data: &[u8]) -> Result<PluginMetadata, VPluginError> {
+        let workdir = crate::workspace::unique_dir(&crate::workspace::root()?)?;
+        let result  = crate::archive::extract(std::io::Cursor::new(data), &workdir, None)
+                .and_then(|_| crate::plugin::Plugin::read_package(&workdir));
+        crate::workspace::remove_dir(&workdir);
+        result
+}
+
+/// A stage of loading or using a plugin, where a [`FaultInjector`] can inject a fault.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FaultStage {
+        /// Extracting the `.vpl` archive.
+        Extract,
+        /// Reading `metadata.toml`.
+        Metadata,
+        /// Loading the object file.
+        Open,
+        /// Calling the entry point.
+        EntryPoint,
+        /// Looking up the hook with this name. The hook can't be intercepted once its
+        /// function pointer was returned, so the fault happens when it's requested.
+        Hook(String),
+        /// Calling the destructor.
+        Destructor,
+}
+
+/// What happens when a [`FaultInjector`] triggers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fault {
+        /// The stage fails with this error, as if the plugin was broken or missing.
+        Fail(VPluginError),
+        /// The stage is delayed, as if the plugin was slow, and then continues normally.
+        Delay(Duration),
+}
+
+#[derive(Debug)]
+struct Rule {
+        stage    : FaultStage,
+        fault    : Fault,
+        /* How many more times the rule triggers, `None` for always. */
+        remaining: Option<usize>,
+}
+
+/// ## FaultInjector
+/// Makes specific stages of loading and using plugins fail or slow down on purpose, so
+/// hosts can test how they behave when a plugin is missing, slow or crashing. Install it
+/// with [`PluginManager::set_fault_injector`](crate::plugin_manager::PluginManager::set_fault_injector);
+/// it applies to every plugin loaded by the manager afterwards.
+///
+/// ## Example
+/// ```rust
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use vplugin::VPluginError;
+/// use vplugin::testing::{Fault, FaultInjector, FaultStage};
+///
+/// let faults = Arc::new(FaultInjector::new());
+/// faults.inject(FaultStage::EntryPoint, Fault::Fail(VPluginError::FailedToInitialize));
+/// faults.inject_times(FaultStage::Open, Fault::Delay(Duration::from_secs(2)), 1);
+///
+/// let mut manager = vplugin::PluginManager::new();
+/// manager.set_fault_injector(faults.clone());
+/// ```
+#[derive(Debug, Default)]
+pub struct FaultInjector {
+        rules: Mutex<Vec<Rule>>,
+}
+
+impl FaultInjector {
+        /// Creates an injector without any fault.
+        pub fn new() -> Self {
+                Self::default()
+        }
+
+        /// Injects `fault` every time `stage` is reached.
+        pub fn inject(&self, stage: FaultStage, fault: Fault) {
+                self.push(Rule { stage, fault, remaining: None });
+        }
+
+        /// Injects `fault` the next `times` times `stage` is reached only.
+        pub fn inject_times(&self, stage: FaultStage, fault: Fault, times: usize) {
+                self.push(Rule { stage, fault, remaining: Some(times) });
+        }
+
+        /// Removes every fault.
+        pub fn clear(&self) {
+                self.rules.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        }
+
+        fn push(&self, rule: Rule) {
+                self.rules.lock().unwrap_or_else(|e| e.into_inner()).push(rule);
+        }
+
+        /* Applies the faults injected at `stage`, in the order they were added. */
+        pub(crate) fn trigger(&self, stage: Stage<'_>) -> Result<(), VPluginError> {
+                let faults: Vec<Fault> = {
+                        let mut rules = self.rules.lock().unwrap_or_else(|e| e.into_inner());
+                        let faults = rules
+                                .iter_mut()
+                                .filter(|rule| rule.stage.matches(stage) && rule.remaining != Some(0))
+                                .map(|rule| {
+                                        if let Some(remaining) = &mut rule.remaining {
+                                                *remaining -= 1;
+                                        }
+                                        rule.fault.clone()
+                                })
+                                .collect();
+                        rules.retain(|rule| rule.remaining != Some(0));
+                        faults
+                };
+
+                for fault in faults {
+                        match fault {
+                                Fault::Delay(delay) => thread::sleep(delay),
+                                Fault::Fail(e)      => {
+                                        diagnostics::error!("Injected fault at {:?}: {}", stage, e);
+                                        return Err(e);
+                                }
+                        }
+                }
+                Ok(())
+        }
+}
+
+impl FaultStage {
+        fn matches(&self, stage: Stage<'_>) -> bool {
+                match (self, stage) {
+                        (Self::Extract,    Stage::Extract)    => true,
+                        (Self::Metadata,   Stage::Metadata)   => true,
+                        (Self::Open,       Stage::Open)       => true,
+                        (Self::EntryPoint, Stage::EntryPoint) => true,
+                        (Self::Hook(a),    Stage::Hook(b))    => a == b,
+                        (Self::Destructor, Stage::Destructor) => true,
+                        _ => false
+                }
+        }
+}
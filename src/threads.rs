@@ -0,0 +1,169 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Threads started by plugins, so their library isn't unloaded under them.
+//!
+//! Every plugin loaded from the disk gets its own [`ThreadRegistry`], handed to it after
+//! the object registry (See [`objects`](crate::objects)) through its `vplugin_set_threads`
+//! export:
+//! ```c
+//! typedef struct vplugin_threads {
+//!     int (*spawn)(const struct vplugin_threads *self, void (*entry)(void *argument), void *argument);
+//! } vplugin_threads;
+//!
+//! void vplugin_set_threads(const vplugin_threads *threads);
+//! ```
+//! Plugins start their threads through `spawn` rather than on their own: VPlugin starts
+//! the thread, which calls `entry` with `argument`. Once `entry` returns, the thread only
+//! runs code of the host, so it's no longer counted as running, and VPlugin joins it.
+//! Before the library of the plugin is unloaded (After its destructor, usually telling
+//! the threads to exit), VPlugin waits for the threads still running. If they don't
+//! finish in time (See
+//! [`PluginManager::set_thread_timeout`](crate::plugin_manager::PluginManager::set_thread_timeout)),
+//! the library is kept loaded instead ([`LeakReason::ThreadsRunning`](crate::unload::LeakReason::ThreadsRunning)).
+
+use std::ffi::{c_int, c_void};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use crate::diagnostics;
+use crate::plugin::Plugin;
+use crate::isolation::Unguarded;
+
+/// The symbol plugins export to receive their thread registry.
+pub const SET_THREADS_SYMBOL: &str = "vplugin_set_threads";
+
+/// How long unloading a plugin waits for its threads by default.
+pub const DEFAULT_THREAD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The function a thread started by a plugin runs.
+pub type ThreadEntry = unsafe extern "C" fn(*mut c_void);
+
+/// ## ThreadRegistry
+/// The threads a single plugin started, handed to it as a `const vplugin_threads *`.
+/// See the [module documentation](crate::threads).
+#[repr(C)]
+#[derive(Debug)]
+pub struct ThreadRegistry {
+        /// Starts a thread calling `entry` with `argument`. Returns `0` on success, `-1` if
+        /// `entry` is null or the thread couldn't be started.
+        pub spawn: unsafe extern "C" fn(*const ThreadRegistry, Option<ThreadEntry>, *mut c_void) -> c_int,
+        running  : Mutex<usize>,
+        exited   : Condvar,
+        handles  : Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Default for ThreadRegistry {
+        fn default() -> Self {
+                Self {
+                        spawn  : spawn_thread,
+                        running: Mutex::new(0),
+                        exited : Condvar::new(),
+                        handles: Mutex::new(Vec::new()),
+                }
+        }
+}
+
+impl ThreadRegistry {
+        /// Returns how many threads of the plugin are still running its code.
+        pub fn running(&self) -> usize {
+                *self.lock()
+        }
+
+        /// Waits up to `timeout` for every thread of the plugin to finish, and joins them.
+        /// Returns how many are still running if they didn't.
+        pub(crate) fn wait(&self, timeout: Duration) -> Result<(), usize> {
+                let running = self.lock();
+                let (running, _) = self.exited
+                        .wait_timeout_while(running, timeout, |running| *running > 0)
+                        .unwrap_or_else(|e| e.into_inner());
+                if *running > 0 {
+                        return Err(*running);
+                }
+                drop(running);
+                /* What's left of the threads is host code, they're about to exit. */
+                for handle in std::mem::take(&mut *self.handles()) {
+                        let _ = handle.join();
+                }
+                Ok(())
+        }
+
+        fn lock(&self) -> MutexGuard<'_, usize> {
+                self.running.lock().unwrap_or_else(|e| e.into_inner())
+        }
+
+        fn handles(&self) -> MutexGuard<'_, Vec<JoinHandle<()>>> {
+                self.handles.lock().unwrap_or_else(|e| e.into_inner())
+        }
+
+        fn finished(&self) {
+                let mut running = self.lock();
+                *running = running.saturating_sub(1);
+                if *running == 0 {
+                        self.exited.notify_all();
+                }
+        }
+}
+
+/* What the thread needs, the registry outliving it as it's never freed while threads run. */
+struct Start {
+        registry: *const ThreadRegistry,
+        entry   : ThreadEntry,
+        argument: *mut c_void,
+}
+
+/* The argument belongs to the plugin, which hands it to the thread it asked for. */
+unsafe impl Send for Start {}
+
+impl Start {
+        fn run(self) {
+                unsafe {
+                        (self.entry)(self.argument);
+                        (*self.registry).finished();
+                }
+        }
+}
+
+unsafe extern "C" fn spawn_thread(registry: *const ThreadRegistry, entry: Option<ThreadEntry>, argument: *mut c_void) -> c_int {
+        let _unguarded = Unguarded::enter();
+        let (registry_ref, entry) = match (registry.as_ref(), entry) {
+                (Some(registry), Some(entry)) => (registry, entry),
+                _                             => return -1
+        };
+        let start = Start { registry, entry, argument };
+        *registry_ref.lock() += 1;
+        match thread::Builder::new().spawn(move || start.run()) {
+                Ok (handle) => {
+                        let mut handles = registry_ref.handles();
+                        handles.retain(|handle| !handle.is_finished());
+                        handles.push(handle);
+                        0
+                },
+                Err(e)      => {
+                        diagnostics::warning!("Couldn't start a thread for a plugin: {}", e);
+                        registry_ref.finished();
+                        -1
+                }
+        }
+}
+
+/// Hands `plugin` its thread registry, if it exports `vplugin_set_threads`.
+pub(crate) fn attach(plugin: &Plugin) {
+        match unsafe { plugin.symbol::<unsafe extern "C" fn(*const ThreadRegistry)>(SET_THREADS_SYMBOL) } {
+                Ok (set_threads) => unsafe { set_threads(&*plugin.workers) },
+                Err(_)           => diagnostics::trace!("Plugin doesn't export {}, its threads aren't tracked.", SET_THREADS_SYMBOL)
+        }
+}
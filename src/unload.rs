@@ -0,0 +1,118 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+
+//! What happens to the library of a plugin once it's dropped, see
+//! [`UnloadPolicy`](crate::options::UnloadPolicy).
+
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use serde_derive::{Deserialize, Serialize};
+use crate::options::UnloadPolicy;
+
+/// ## LeakedLibrary
+/// The library of a plugin that was kept loaded instead of being unloaded, listed by
+/// [`PluginManager::leaked_libraries`](crate::plugin_manager::PluginManager::leaked_libraries).
+/// The library, and the directory the plugin was extracted into, stay around until the
+/// process exits.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeakedLibrary {
+        /// The name of the plugin.
+        pub plugin : String,
+        /// The version of the plugin.
+        pub version: String,
+        /// The object file of the plugin, relative to its package.
+        pub objfile: String,
+        /// Why the library wasn't unloaded.
+        pub reason : LeakReason,
+}
+
+/// Why the library of a plugin was kept loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeakReason {
+        /// The plugin's [`UnloadPolicy`](crate::options::UnloadPolicy) is `Leak`.
+        Policy,
+        /// The policy is `LeakIfThreadsDetected`, and threads the plugin may have
+        /// started were still running.
+        ThreadsDetected,
+        /// Hook guards of the plugin were still alive, unloading would have left
+        /// their hooks dangling. The library is unloaded once the last of them is dropped.
+        Pinned,
+        /// Threads the plugin started (See [`threads`](crate::threads)) were
+        /// still running when its library was about to be unloaded.
+        ThreadsRunning,
+        /// The plugin didn't acknowledge a stop request in time (See
+        /// [`Plugin::request_stop`](crate::plugin::Plugin::request_stop)), and may still be running.
+        Unresponsive,
+        /// A call into the plugin panicked or crashed (See [`isolation`](crate::isolation)),
+        /// and its library may be in any state.
+        Crashed,
+}
+
+/// The libraries leaked by the plugins of a manager, shared with the plugins.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Leaks(Arc<Mutex<Vec<LeakedLibrary>>>);
+
+impl Leaks {
+        pub(crate) fn record(&self, leaked: LeakedLibrary) {
+                self.0.lock().unwrap_or_else(|e| e.into_inner()).push(leaked);
+        }
+
+        pub(crate) fn is_empty(&self) -> bool {
+                self.0.lock().unwrap_or_else(|e| e.into_inner()).is_empty()
+        }
+
+        pub(crate) fn list(&self) -> Vec<LeakedLibrary> {
+                self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+        }
+}
+
+/// The threads of the process, or `None` where they can't be listed.
+pub(crate) type Threads = Option<BTreeSet<u64>>;
+
+/// Lists the threads currently running in the process (Linux and Android only).
+pub(crate) fn threads() -> Threads {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+                let tasks = std::fs::read_dir("/proc/self/task").ok()?;
+                Some(tasks
+                        .filter_map(|task| task.ok()?.file_name().to_str()?.parse().ok())
+                        .collect())
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        None
+}
+
+/// Returns why the library of a plugin has to be kept loaded under `policy`, if it has.
+/// `before` are the threads that were running before the library was loaded.
+///
+/// Threads can't be attributed to a library, so any thread started since then counts,
+/// including those of the application. Where threads can't be listed at all, they're
+/// always assumed to be there.
+pub(crate) fn leak_reason(policy: UnloadPolicy, before: &Threads) -> Option<LeakReason> {
+        match policy {
+                UnloadPolicy::Unload                => None,
+                UnloadPolicy::Leak                  => Some(LeakReason::Policy),
+                UnloadPolicy::LeakIfThreadsDetected => {
+                        let started = match (before, threads()) {
+                                (Some(before), Some(now)) => now.difference(before).next().is_some(),
+                                _                         => true
+                        };
+                        started.then_some(LeakReason::ThreadsDetected)
+                }
+        }
+}
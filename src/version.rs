@@ -0,0 +1,104 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Parsing of the version strings found in plugin metadata, and the version
+//! requirements between hosts and plugins.
+
+use semver::{Version, VersionReq};
+use vplugin_core::PluginMetadata;
+use crate::error::VPluginError;
+
+/// Parses `version` as a semantic version. Plugins don't always use full versions,
+/// so a leading `v` is ignored and a missing minor or patch number counts as 0
+/// (`1.4` is `1.4.0`).
+pub(crate) fn parse(version: &str) -> Result<Version, VPluginError> {
+        let trimmed = version.trim();
+        let trimmed = trimmed.strip_prefix(['v', 'V']).unwrap_or(trimmed);
+
+        /* The pre-release / build metadata part is kept as is. */
+        let split = trimmed.find(['-', '+']).unwrap_or(trimmed.len());
+        let (core, rest) = trimmed.split_at(split);
+        let padding = match core.matches('.').count() {
+                0 => ".0.0",
+                1 => ".0",
+                _ => ""
+        };
+
+        match Version::parse(&format!("{}{}{}", core, padding, rest)) {
+                Ok (v) => Ok(v),
+                Err(_) => Err(VPluginError::InvalidVersion { version: version.to_owned() })
+        }
+}
+
+/// Parses `requirement` as a semantic version requirement (`">=1.2, <2"`).
+pub(crate) fn parse_req(requirement: &str) -> Result<VersionReq, VPluginError> {
+        match VersionReq::parse(requirement.trim()) {
+                Ok (req) => Ok(req),
+                Err(_)   => Err(VPluginError::InvalidVersion { version: requirement.to_owned() })
+        }
+}
+
+/// The version requirements between a host and its plugins: the version of the host,
+/// checked against the `requires_host` field of plugins, and the versions of plugins
+/// the host accepts.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Constraints {
+        pub(crate) host   : Option<Version>,
+        /* `None` applies to every plugin, `Some` to the plugins with that name. */
+        pub(crate) accepts: Vec<(Option<String>, VersionReq)>,
+}
+
+impl Constraints {
+        /// Checks every requirement between the host and the plugin described by `metadata`,
+        /// returning which one failed first.
+        pub(crate) fn resolve(&self, metadata: &PluginMetadata) -> Result<(), VPluginError> {
+                if let (Some(host), Some(requirement)) = (&self.host, &metadata.requires_host) {
+                        if !parse_req(requirement)?.matches(host) {
+                                return Err(VPluginError::UnsatisfiedVersion {
+                                        constraint : format!("Plugin '{}' (requires_host)", metadata.name),
+                                        requirement: requirement.clone(),
+                                        version    : host.to_string()
+                                });
+                        }
+                }
+
+                let mut accepts = self.accepts
+                        .iter()
+                        .filter(|(name, _)| match name {
+                                Some(name) => *name == metadata.name,
+                                None       => true
+                        })
+                        .peekable();
+                if accepts.peek().is_none() {
+                        return Ok(());
+                }
+                let version = parse(&metadata.version)?;
+                for (name, requirement) in accepts {
+                        if !requirement.matches(&version) {
+                                let constraint = match name {
+                                        Some(name) => format!("The host (accepts_plugins for '{}')", name),
+                                        None       => "The host (accepts_plugins)".to_owned()
+                                };
+                                return Err(VPluginError::UnsatisfiedVersion {
+                                        constraint,
+                                        requirement: requirement.to_string(),
+                                        version    : version.to_string()
+                                });
+                        }
+                }
+                Ok(())
+        }
+}
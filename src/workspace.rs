@@ -0,0 +1,423 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Management of the directories VPlugin extracts plugins into.
+//!
+//! Every `PluginManager` and every loaded `Plugin` gets its own directory,
+//! so multiple hosts (or multiple plugins inside the same host) never share
+//! a path. On Windows a loaded DLL cannot be deleted, so directories that
+//! can't be removed yet are remembered and removed later.
+//!
+//! The shared root is normally inside the temporary directory, but if that
+//! one can't be used (Read-only, mounted `noexec`, ...) the user's cache
+//! directory is used instead. On Android, libraries can only be loaded from
+//! directories owned by the application, so its code cache directory is
+//! preferred there.
+
+use std::env;
+use std::fs::{self, DirBuilder};
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+use std::process;
+use std::sync::{Mutex, OnceLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::error::VPluginError;
+use crate::diagnostics;
+use crate::platform;
+use crate::progress::{LoadPhase, Reporter};
+
+static INSTANCE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+static PENDING_REMOVAL : Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+static ROOT            : OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// Returns the root directory shared by all VPlugin instances.
+/// 
+/// The first usable location out of the temporary directory and the user's cache
+/// directory is picked the first time this is called, and reused afterwards.
+pub(crate) fn root() -> Result<PathBuf, VPluginError> {
+        let root = ROOT.get_or_init(|| {
+                for candidate in candidates() {
+                        match check_usable(&candidate) {
+                                Ok(()) => return Some(candidate),
+                                Err(reason) => diagnostics::warning!(
+                                        "Can't use {} to extract plugins: {}. Trying the next location.",
+                                        candidate.display(),
+                                        reason
+                                )
+                        }
+                }
+                diagnostics::error!("No usable directory to extract plugins into was found.");
+                None
+        });
+
+        match root {
+                Some(root) => Ok(root.clone()),
+                None => Err(VPluginError::InternalError { err: "No usable directory to extract plugins into".into() })
+        }
+}
+
+/* The locations the root directory can be in, in order of preference. */
+fn candidates() -> Vec<PathBuf> {
+        let mut candidates = Vec::new();
+        #[cfg(target_os = "android")]
+        if let Some(dir) = android_code_cache() {
+                candidates.push(dir.join("vplugin"));
+        }
+        candidates.push(env::temp_dir().join("vplugin"));
+        if let Some(cache) = cache_dir() {
+                candidates.push(cache.join("vplugin"));
+        }
+        candidates
+}
+
+/*
+ * The directory returned by `Context.getCodeCacheDir()`, derived from the package
+ * name (Which is what Android sets as the process name) so no JNI call is needed.
+ */
+#[cfg(target_os = "android")]
+fn android_code_cache() -> Option<PathBuf> {
+        let cmdline = fs::read("/proc/self/cmdline").ok()?;
+        let name    = cmdline.split(|b| *b == 0).next()?;
+        let package = String::from_utf8_lossy(name);
+        /* Services running in their own process are called `package:service`. */
+        let package = package.split(':').next()?;
+        if package.is_empty() || package.contains('/') {
+                return None;
+        }
+        Some(PathBuf::from("/data/data").join(package).join("code_cache"))
+}
+
+#[cfg(windows)]
+fn cache_dir() -> Option<PathBuf> {
+        env::var_os("LOCALAPPDATA").map(PathBuf::from)
+}
+
+#[cfg(target_os = "macos")]
+fn cache_dir() -> Option<PathBuf> {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library").join("Caches"))
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn cache_dir() -> Option<PathBuf> {
+        match env::var_os("XDG_CACHE_HOME") {
+                Some(cache) if !cache.is_empty() => Some(PathBuf::from(cache)),
+                _ => env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache"))
+        }
+}
+
+/* Checks that plugins can be extracted into `dir` and then loaded from there. */
+fn check_usable(dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+
+        let probe = dir.join(format!(".probe-{}", process::id()));
+        let written = fs::write(&probe, b"vplugin");
+        let _ = fs::remove_file(&probe);
+        if let Err(e) = written {
+                return Err(format!("directory is not writable ({})", e));
+        }
+
+        if is_noexec(dir) {
+                return Err("filesystem is mounted noexec".into());
+        }
+        Ok(())
+}
+
+/* Finds the mount point `dir` is on through /proc/mounts and checks its options. */
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn is_noexec(dir: &Path) -> bool {
+        let dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+        let mounts = match fs::read_to_string("/proc/mounts") {
+                Ok (mounts) => mounts,
+                Err(_)      => return false
+        };
+
+        mounts
+                .lines()
+                .filter_map(|line| {
+                        let mut fields = line.split_whitespace();
+                        let mount_point = fields.nth(1)?;
+                        let options     = fields.nth(1)?;
+                        Some((PathBuf::from(mount_point), options))
+                })
+                .filter(|(mount_point, _)| dir.starts_with(mount_point))
+                .max_by_key(|(mount_point, _)| mount_point.as_os_str().len())
+                .map(|(_, options)| options.split(',').any(|o| o == "noexec"))
+                .unwrap_or(false)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn is_noexec(_dir: &Path) -> bool {
+        false
+}
+
+/// Creates a new, uniquely named directory inside `parent` and returns its path.
+/// The name is derived from the process ID, a per-process counter and a random
+/// number, so it can't collide with another host running at the same time and
+/// can't be guessed in advance.
+/// 
+/// On Windows the returned path is an extended-length (`\\?\`) path, so that
+/// the files extracted inside it aren't limited to `MAX_PATH` characters.
+pub(crate) fn unique_dir(parent: &Path) -> Result<PathBuf, VPluginError> {
+        create_unique(parent, &DirBuilder::new())
+}
+
+/// Like [`unique_dir`], but only the user running the application can read the
+/// directory (On Unix, other systems keep the default permissions). It's created with
+/// these permissions, so it's never readable by others, even briefly.
+#[cfg(feature = "archive")]
+pub(crate) fn private_dir(parent: &Path) -> Result<PathBuf, VPluginError> {
+        let mut builder = DirBuilder::new();
+        #[cfg(unix)]
+        {
+                use std::os::unix::fs::DirBuilderExt;
+                builder.mode(0o700);
+        }
+        create_unique(parent, &builder)
+}
+
+/* Creates a new directory inside `parent` with `builder`, see `unique_dir`. */
+fn create_unique(parent: &Path, builder: &DirBuilder) -> Result<PathBuf, VPluginError> {
+        if let Err(e) = fs::create_dir_all(parent) {
+                diagnostics::error!("Couldn't create directory {}: {}", parent.display(), e);
+                return Err(VPluginError::from_io(&e));
+        }
+        let parent = platform::extended_length(parent);
+
+        loop {
+                let id  = INSTANCE_COUNTER.fetch_add(1, Ordering::Relaxed);
+                let dir = parent.join(format!("{}-{}-{:08x}", process::id(), id, random() as u32));
+                match builder.create(&dir) {
+                        Ok(()) => return Ok(dir),
+                        /* Left behind by a process that used the same PID, try the next one. */
+                        Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                        Err(e) => {
+                                diagnostics::error!("Couldn't create directory {}: {}", dir.display(), e);
+                                return Err(VPluginError::from_io(&e));
+                        }
+                }
+        }
+}
+
+/* A random number, good enough for directory names. */
+fn random() -> u64 {
+        use std::collections::hash_map::RandomState;
+        use std::hash::{BuildHasher, Hasher};
+
+        /* Every RandomState is seeded with new random keys. */
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_usize(INSTANCE_COUNTER.load(Ordering::Relaxed));
+        hasher.finish()
+}
+
+/// Joins a relative path coming from a plugin (An archive entry, the `objfile` field)
+/// onto `base`. Only normal components are kept and every one of them is pushed
+/// separately, so paths using `/` stay valid under an extended-length prefix on
+/// Windows, which doesn't treat `/` as a separator.
+pub(crate) fn join(base: &Path, relative: &Path) -> PathBuf {
+        let mut path = base.to_path_buf();
+        for component in relative.components() {
+                if let Component::Normal(c) = component {
+                        path.push(c);
+                }
+        }
+        path
+}
+
+/// Like [`join`], but if a component doesn't exist with the exact same case, an
+/// existing entry that only differs by case is used instead. This way well-known
+/// files like `metadata.toml` are found even if the archive calls them `Metadata.toml`,
+/// regardless of whether the filesystem is case-sensitive.
+pub(crate) fn find(base: &Path, relative: &Path) -> PathBuf {
+        let mut path = base.to_path_buf();
+        for component in relative.components() {
+                let name = match component {
+                        Component::Normal(c) => c,
+                        _ => continue
+                };
+                if path.join(name).exists() {
+                        path.push(name);
+                        continue;
+                }
+
+                let wanted = name.to_string_lossy().to_lowercase();
+                let found  = fs::read_dir(&path).ok().and_then(|entries| {
+                        entries
+                                .filter_map(Result::ok)
+                                .map(|entry| entry.file_name())
+                                .find(|entry| entry.to_string_lossy().to_lowercase() == wanted)
+                });
+                match found {
+                        Some(entry) => path.push(entry),
+                        None        => path.push(name)
+                }
+        }
+        path
+}
+
+/// Copies the package at `source` into the directory `destination`, which must exist:
+/// everything inside it if it's a directory, the file itself otherwise. The progress is
+/// reported to `progress`, if given.
+pub(crate) fn copy_package(source: &Path, destination: &Path, progress: Option<&Reporter<'_>>) -> Result<(), VPluginError> {
+        if let Some(progress) = progress {
+                let (files, bytes) = size(source);
+                progress.phase(LoadPhase::Copying, Some(files), Some(bytes))?;
+        }
+        let copied = match (source.is_dir(), source.file_name()) {
+                (false, Some(name)) => copy(source, &destination.join(name), progress),
+                _                   => copy(source, destination, progress)
+        };
+        if progress.is_some_and(Reporter::cancelled) {
+                return Err(VPluginError::Cancelled);
+        }
+        if let Err(e) = copied {
+                diagnostics::error!("Couldn't copy {} into {}: {}", source.display(), destination.display(), e);
+                return Err(VPluginError::from_io(&e));
+        }
+        Ok(())
+}
+
+fn copy(source: &Path, target: &Path, progress: Option<&Reporter<'_>>) -> std::io::Result<()> {
+        if !source.is_dir() {
+                let bytes = fs::copy(source, target)?;
+                return match progress.map(|progress| progress.file(bytes)) {
+                        Some(Err(_)) => Err(std::io::Error::other("Loading was cancelled")),
+                        _            => Ok(())
+                };
+        }
+        fs::create_dir_all(target)?;
+        for entry in fs::read_dir(source)? {
+                let entry = entry?;
+                copy(&entry.path(), &target.join(entry.file_name()), progress)?;
+        }
+        Ok(())
+}
+
+/* How many files there are at `path` (Counting those inside directories) and their total size. */
+fn size(path: &Path) -> (usize, u64) {
+        if !path.is_dir() {
+                return (1, fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0));
+        }
+        fs::read_dir(path)
+                .into_iter()
+                .flatten()
+                .filter_map(Result::ok)
+                .map(|entry| size(&entry.path()))
+                .fold((0, 0), |(files, bytes), (more_files, more_bytes)| (files + more_files, bytes + more_bytes))
+}
+
+/// Removes `dir` and everything inside it. If the directory is still locked
+/// (A DLL inside it is mapped by the process on Windows), removal is retried a
+/// few times and then deferred until [`flush_pending`] is called.
+pub(crate) fn remove_dir(dir: &Path) {
+        if !dir.exists() {
+                return;
+        }
+
+        for attempt in 0..REMOVAL_ATTEMPTS {
+                match fs::remove_dir_all(dir) {
+                        Ok(()) => {
+                                diagnostics::trace!("Removed directory: {}", dir.display());
+                                return;
+                        },
+                        Err(e) if e.kind() == ErrorKind::NotFound => return,
+                        Err(e) => {
+                                if attempt + 1 == REMOVAL_ATTEMPTS {
+                                        diagnostics::warning!(
+                                                "Couldn't remove {}: {}. Removal will be retried later.",
+                                                dir.display(),
+                                                e
+                                        );
+                                } else {
+                                        std::thread::sleep(REMOVAL_DELAY);
+                                }
+                        }
+                }
+        }
+
+        if let Ok(mut pending) = PENDING_REMOVAL.lock() {
+                pending.push(dir.to_path_buf());
+        }
+}
+
+/// Retries the removal of every directory that couldn't be removed earlier.
+pub(crate) fn flush_pending() {
+        let pending = match PENDING_REMOVAL.lock() {
+                Ok(mut pending) => std::mem::take(&mut *pending),
+                Err(_) => return,
+        };
+
+        for dir in pending {
+                match fs::remove_dir_all(&dir) {
+                        Ok(()) => diagnostics::trace!("Removed deferred directory: {}", dir.display()),
+                        Err(e) if e.kind() == ErrorKind::NotFound => (),
+                        Err(e) => {
+                                diagnostics::warning!("Couldn't remove {}: {}. No cleanup will be performed.", dir.display(), e);
+                        }
+                }
+        }
+}
+
+/* Windows may keep a file locked for a short while after FreeLibrary(). */
+#[cfg(windows)]
+const REMOVAL_ATTEMPTS: u32 = 5;
+#[cfg(not(windows))]
+const REMOVAL_ATTEMPTS: u32 = 1;
+const REMOVAL_DELAY: std::time::Duration = std::time::Duration::from_millis(50);
+
+#[cfg(test)]
+mod tests {
+        use super::*;
+
+        #[test]
+        fn join_keeps_unicode_components() {
+                let base = Path::new("base");
+                assert_eq!(join(base, Path::new("ünïcødé/插件.so")), base.join("ünïcødé").join("插件.so"));
+                assert_eq!(join(base, Path::new("../../Пользователь")), base.join("Пользователь"));
+        }
+
+        #[test]
+        fn unique_dir_with_unicode_and_long_parents() {
+                let mut parent = env::temp_dir().join(format!("vplugin-workspace-{}-データ", process::id()));
+                let top = parent.clone();
+                while parent.as_os_str().len() < 300 {
+                        parent.push("a-rather-long-directory-name");
+                }
+                let first  = unique_dir(&parent).unwrap();
+                let second = unique_dir(&parent).unwrap();
+                assert_ne!(first, second);
+
+                let file = join(&first, Path::new("ünïcødé/metadata.toml"));
+                fs::create_dir_all(file.parent().unwrap()).unwrap();
+                fs::write(&file, "").unwrap();
+                assert_eq!(find(&first, Path::new("ÜNÏCØDÉ/Metadata.toml")), file);
+
+                remove_dir(&first);
+                remove_dir(&second);
+                assert!(!first.exists() && !second.exists());
+                remove_dir(&platform::extended_length(&top));
+        }
+
+        #[cfg(all(unix, feature = "archive"))]
+        #[test]
+        fn private_dir_is_created_private() {
+                use std::os::unix::fs::PermissionsExt;
+
+                let parent = env::temp_dir().join(format!("vplugin-private-{}", process::id()));
+                let dir = private_dir(&parent).unwrap();
+                assert_eq!(fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+                remove_dir(&parent);
+        }
+}
@@ -0,0 +1,133 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+
+/* The C API (`vplugin::capi`), called the way a C application would. */
+
+use std::ffi::{c_char, CStr};
+use std::path::PathBuf;
+use std::ptr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use vplugin::capi::*;
+use vplugin::testing::fixture;
+use vplugin::{Call, CallInterceptor, PluginHandle};
+
+const SOURCE: &str = r#"
+        #[no_mangle] pub extern "C" fn vplugin_init() -> i32 { 0 }
+        #[no_mangle] pub extern "C" fn vplugin_exit() {}
+        #[no_mangle] pub extern "C" fn add_one(x: i32) -> i32 { x + 1 }
+"#;
+
+fn plugin(test: &str) -> Vec<u8> {
+        let dir = std::env::temp_dir().join(format!("vplugin-tests-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let vpl: PathBuf = fixture("capi").rust(SOURCE).build(&dir).unwrap();
+        let mut path = vpl.to_str().unwrap().as_bytes().to_vec();
+        path.push(0);
+        path
+}
+
+fn last_error() -> String {
+        let error = vplugin_last_error();
+        assert!(!error.is_null());
+        unsafe { CStr::from_ptr(error) }.to_string_lossy().into_owned()
+}
+
+#[test]
+fn plugin_lifecycle() {
+        let path = plugin("capi-lifecycle");
+        unsafe {
+                let manager = vplugin_manager_new();
+                assert!(!manager.is_null());
+                let mut handle = std::mem::MaybeUninit::<PluginHandle>::uninit();
+                assert_eq!(vplugin_load(manager, path.as_ptr() as *const c_char, handle.as_mut_ptr()), 0);
+                let handle = handle.assume_init();
+                assert_eq!(vplugin_begin(manager, handle), 0);
+
+                let hook = vplugin_get_hook(manager, handle, c"add_one".as_ptr());
+                assert!(!hook.is_null());
+                let add_one: extern "C" fn(i32) -> i32 = std::mem::transmute(hook);
+                assert_eq!(add_one(41), 42);
+                assert!(vplugin_get_hook(manager, handle, c"missing".as_ptr()).is_null());
+                assert!(!last_error().is_empty());
+
+                assert_eq!(vplugin_terminate(manager, handle), 0);
+                assert_eq!(vplugin_unload(manager, handle), 0);
+                /* The handle is gone along with the plugin. */
+                assert_eq!(vplugin_begin(manager, handle), -1);
+                assert_eq!(vplugin_unload(manager, handle), -1);
+                vplugin_manager_free(manager);
+        }
+}
+
+#[test]
+fn null_pointers() {
+        let path = plugin("capi-null");
+        unsafe {
+                let manager = vplugin_manager_new();
+                let mut handle = std::mem::MaybeUninit::<PluginHandle>::uninit();
+                assert_eq!(vplugin_load(ptr::null_mut(), path.as_ptr() as *const c_char, handle.as_mut_ptr()), -1);
+                assert_eq!(vplugin_load(manager, ptr::null(), handle.as_mut_ptr()), -1);
+                assert_eq!(vplugin_load(manager, path.as_ptr() as *const c_char, ptr::null_mut()), -1);
+                assert!(!last_error().is_empty());
+
+                assert_eq!(vplugin_load(manager, path.as_ptr() as *const c_char, handle.as_mut_ptr()), 0);
+                let handle = handle.assume_init();
+                assert_eq!(vplugin_begin(ptr::null_mut(), handle), -1);
+                assert_eq!(vplugin_terminate(ptr::null_mut(), handle), -1);
+                assert_eq!(vplugin_unload(ptr::null_mut(), handle), -1);
+                assert!(vplugin_get_hook(ptr::null(), handle, c"add_one".as_ptr()).is_null());
+                assert!(vplugin_get_hook(manager, handle, ptr::null()).is_null());
+
+                vplugin_manager_free(manager);
+                vplugin_manager_free(ptr::null_mut());
+        }
+}
+
+/* Panics in `before`, and counts how many of them are still alive. */
+struct Panicking(Arc<AtomicUsize>);
+
+impl CallInterceptor for Panicking {
+        fn before(&self, _: &Call<'_>) -> Result<(), String> {
+                panic!("the interceptor panicked")
+        }
+}
+
+impl Drop for Panicking {
+        fn drop(&mut self) {
+                self.0.fetch_sub(1, Ordering::SeqCst);
+        }
+}
+
+#[test]
+fn panics_become_errors() {
+        let path = plugin("capi-panic");
+        let alive = Arc::new(AtomicUsize::new(1));
+        unsafe {
+                let manager = vplugin_manager_new();
+                (*manager).add_interceptor(Panicking(Arc::clone(&alive)));
+                let mut handle = std::mem::MaybeUninit::<PluginHandle>::uninit();
+                assert_eq!(vplugin_load(manager, path.as_ptr() as *const c_char, handle.as_mut_ptr()), 0);
+
+                assert_eq!(vplugin_begin(manager, handle.assume_init()), -1);
+                assert!(last_error().contains("VPlugin panicked: the interceptor panicked"));
+
+                /* Freeing the manager drops what it owns. */
+                vplugin_manager_free(manager);
+        }
+        assert_eq!(alive.load(Ordering::SeqCst), 0);
+}
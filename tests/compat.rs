@@ -0,0 +1,123 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+
+/* Compatibility reports of fixture plugins, which are evaluated without loading the plugins. */
+
+use std::path::{Path, PathBuf};
+use vplugin::{CheckKind, CheckOutcome, CompatibilityReport, HostApiVersion, PluginManager};
+use vplugin::testing::fixture;
+
+/* Creates the file named by `VPLUGIN_TEST_MARKER` as soon as the library is loaded. */
+const SOURCE: &str = r#"
+        #[no_mangle] pub extern "C" fn vplugin_init() -> i32 { 0 }
+
+        #[cfg(target_os = "linux")]
+        #[used]
+        #[link_section = ".init_array"]
+        static ON_LOAD: extern "C" fn() = {
+                extern "C" fn on_load() {
+                        if let Some(marker) = std::env::var_os("VPLUGIN_TEST_MARKER") {
+                                let _ = std::fs::write(marker, "loaded");
+                        }
+                }
+                on_load
+        };
+"#;
+
+fn output_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vplugin-tests-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+}
+
+fn outcome(report: &CompatibilityReport, kind: CheckKind) -> &CheckOutcome {
+        &report.checks.iter().find(|check| check.kind == kind).unwrap().outcome
+}
+
+/* The report of the plugin at `vpl`, checking that it wasn't loaded to write it. */
+fn unloaded_report(manager: &PluginManager, vpl: &Path) -> CompatibilityReport {
+        let marker = std::env::temp_dir().join(format!("vplugin-tests-compat-loaded-{}", std::process::id()));
+        std::env::set_var("VPLUGIN_TEST_MARKER", &marker);
+        let report = manager.compatibility_report(vpl).unwrap();
+        assert!(!marker.exists(), "The plugin was loaded");
+        report
+}
+
+#[test]
+fn compatible_plugin() {
+        let dir = output_dir("compat-compatible");
+        let vpl = fixture("compatible")
+                .rust(SOURCE)
+                .api_version("1.0")
+                .entry_signature("fn() -> i32")
+                .build(&dir)
+                .unwrap();
+
+        let mut manager = PluginManager::new();
+        manager.set_host_api_version(HostApiVersion::new(1, 2));
+        let report = unloaded_report(&manager, &vpl);
+        assert!(report.is_compatible(), "{}", report);
+        assert!(matches!(outcome(&report, CheckKind::HostApi), CheckOutcome::Passed { .. }));
+        assert!(matches!(outcome(&report, CheckKind::Signatures), CheckOutcome::Passed { .. }));
+        #[cfg(feature = "preflight")]
+        assert!(matches!(outcome(&report, CheckKind::EntryPoint), CheckOutcome::Passed { .. }));
+}
+
+#[test]
+fn newer_api_version() {
+        let dir = output_dir("compat-api");
+        let vpl = fixture("newer-api").rust(SOURCE).api_version("2.0").build(&dir).unwrap();
+
+        let mut manager = PluginManager::new();
+        manager.set_host_api_version(HostApiVersion::new(1, 2));
+        let report = unloaded_report(&manager, &vpl);
+        assert!(!report.is_compatible());
+        match outcome(&report, CheckKind::HostApi) {
+                CheckOutcome::Failed { reason } => assert!(reason.contains("2.0"), "{}", reason),
+                other                           => panic!("{:?}", other)
+        }
+}
+
+#[test]
+fn mismatched_entry_signature() {
+        let dir = output_dir("compat-signature");
+        let vpl = fixture("bad-signature").rust(SOURCE).entry_signature("fn(x: i32) -> i32").build(&dir).unwrap();
+
+        let manager = PluginManager::new();
+        let report = unloaded_report(&manager, &vpl);
+        assert!(matches!(outcome(&report, CheckKind::Signatures), CheckOutcome::Failed { .. }));
+}
+
+#[cfg(feature = "preflight")]
+#[test]
+fn missing_entry_point() {
+        let dir = output_dir("compat-entry");
+        let vpl = fixture("no-entry").rust(SOURCE).build(&dir).unwrap();
+
+        let mut manager = PluginManager::new();
+        manager.set_entry_point("plugin_main");
+        let report = unloaded_report(&manager, &vpl);
+        /* Only the legacy `vplugin_init` is exported. */
+        match outcome(&report, CheckKind::EntryPoint) {
+                CheckOutcome::Failed { reason } => assert!(reason.contains("legacy"), "{}", reason),
+                other                           => panic!("{:?}", other)
+        }
+
+        let vpl = fixture("no-entry").rust(r#"#[no_mangle] pub extern "C" fn other() {}"#).build(&dir).unwrap();
+        let report = unloaded_report(&manager, &vpl);
+        assert!(matches!(outcome(&report, CheckKind::EntryPoint), CheckOutcome::Failed { .. }));
+}
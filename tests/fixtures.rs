@@ -0,0 +1,401 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+/* Plugins built from source by `vplugin::testing::fixture`, then loaded and started. */
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use vplugin::{Diagnostic, DiagnosticsSink, HostApiVersion, PluginManager, PluginState, PluginStateStore, VPluginError};
+use vplugin::testing::fixture;
+
+const SOURCE: &str = r#"
+        #[no_mangle] pub extern "C" fn vplugin_init() -> i32 { 0 }
+        #[no_mangle] pub extern "C" fn vplugin_exit() {}
+        #[no_mangle] pub extern "C" fn add_one(x: i32) -> i32 { x + 1 }
+"#;
+
+fn output_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vplugin-tests-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+}
+
+#[test]
+fn archive_fixture() {
+        let dir = output_dir("fixture-archive");
+        let vpl = fixture("adder")
+                .rust(SOURCE)
+                .version("1.2.3")
+                .description("Adds one")
+                .build(&dir)
+                .unwrap();
+        assert_eq!(vpl, dir.join("adder.vpl"));
+
+        let mut manager = PluginManager::new();
+        let handle = manager.load(&vpl).unwrap();
+        let plugin = manager.plugin(handle).unwrap();
+        assert_eq!(plugin.metadata.name, "adder");
+        assert_eq!(plugin.metadata.version, "1.2.3");
+        assert_eq!(plugin.metadata.description.as_deref(), Some("Adds one"));
+
+        manager.begin(handle).unwrap();
+        let add_one = manager.custom_hook::<i32, i32>(handle, "add_one").unwrap();
+        assert_eq!(unsafe { add_one(41) }, 42);
+        manager.terminate(handle).unwrap();
+}
+
+#[test]
+fn directory_fixture() {
+        let dir = output_dir("fixture-directory");
+        let package = fixture("unpacked").rust(SOURCE).config("answer", 42).build_directory(&dir).unwrap();
+        assert!(package.join("metadata.toml").is_file());
+
+        let mut manager = PluginManager::new();
+        let handle = manager.load(&package).unwrap();
+        assert_eq!(manager.plugin(handle).unwrap().metadata.config.get("answer"), Some(&toml::Value::Integer(42)));
+        manager.begin(handle).unwrap();
+        manager.terminate(handle).unwrap();
+}
+
+#[test]
+fn failing_entry_point() {
+        let dir = output_dir("fixture-failing");
+        let vpl = fixture("failing")
+                .rust(r#"#[no_mangle] pub extern "C" fn vplugin_init() -> i32 { 1 }"#)
+                .build(&dir)
+                .unwrap();
+
+        let mut manager = PluginManager::new();
+        let handle = manager.load(&vpl).unwrap();
+        assert_eq!(manager.begin(handle), Err(vplugin::VPluginError::FailedToInitialize));
+}
+
+#[test]
+fn invalid_source() {
+        let dir = output_dir("fixture-invalid");
+        assert!(fixture("invalid").rust("this isn't Rust").build(&dir).is_err());
+}
+
+/* Records the deprecated symbols reported to it. */
+#[derive(Clone, Default)]
+struct Deprecations(Arc<Mutex<Vec<String>>>);
+
+impl DiagnosticsSink for Deprecations {
+        fn report(&self, diagnostic: &Diagnostic<'_>) {
+                if let Some(deprecation) = &diagnostic.deprecation {
+                        self.0.lock().unwrap().push(deprecation.symbol.to_owned());
+                }
+        }
+}
+
+#[test]
+fn legacy_entry_point() {
+        let dir = output_dir("fixture-legacy");
+        let vpl = fixture("legacy").rust(SOURCE).build(&dir).unwrap();
+
+        let deprecations = Deprecations::default();
+        let mut manager = PluginManager::new();
+        manager.set_entry_point("app_entry");
+        manager.set_diagnostics_sink(deprecations.clone());
+        let handle = manager.load(&vpl).unwrap();
+        assert_eq!(manager.begin(handle), Err(vplugin::VPluginError::FailedToInitialize));
+        assert_eq!(*deprecations.0.lock().unwrap(), ["vplugin_init"]);
+}
+
+#[test]
+fn host_threads() {
+        let dir = output_dir("fixture-threads");
+        let vpl = fixture("threads")
+                .rust(r#"
+                        use std::ffi::c_void;
+
+                        #[repr(C)]
+                        pub struct Threads {
+                                spawn: unsafe extern "C" fn(*const Threads, unsafe extern "C" fn(*mut c_void), *mut c_void) -> i32,
+                        }
+
+                        static mut THREADS: *const Threads = std::ptr::null();
+
+                        unsafe extern "C" fn worker(_: *mut c_void) {
+                                std::thread::sleep(std::time::Duration::from_millis(200));
+                        }
+
+                        #[no_mangle] pub unsafe extern "C" fn vplugin_set_threads(threads: *const Threads) { THREADS = threads; }
+                        #[no_mangle] pub unsafe extern "C" fn vplugin_init() -> i32 {
+                                ((*THREADS).spawn)(THREADS, worker, std::ptr::null_mut())
+                        }
+                        #[no_mangle] pub extern "C" fn vplugin_exit() {}
+                "#)
+                .build(&dir)
+                .unwrap();
+
+        let mut manager = PluginManager::new();
+        let handle = manager.load(&vpl).unwrap();
+        manager.begin(handle).unwrap();
+        assert_eq!(manager.plugin(handle).unwrap().running_threads(), 1);
+        manager.terminate(handle).unwrap();
+
+        let plugin = manager.remove(handle).unwrap();
+        drop(plugin);
+        assert!(manager.leaked_libraries().is_empty());
+}
+
+#[test]
+fn incompatible_api_version() {
+        let dir = output_dir("fixture-api");
+        let vpl = fixture("newer-api").rust(SOURCE).api_version("2.0").build(&dir).unwrap();
+        let incompatible = VPluginError::IncompatibleVersion { expected: "1.2".into(), found: "2.0".into() };
+
+        /* Refused when loaded... */
+        let mut manager = PluginManager::new();
+        manager.set_host_api_version(HostApiVersion::new(1, 2));
+        assert_eq!(manager.load(&vpl).err(), Some(incompatible.clone()));
+
+        /* ...and when started, if the host changed its version since. */
+        let mut manager = PluginManager::new();
+        let handle = manager.load(&vpl).unwrap();
+        manager.set_host_api_version(HostApiVersion::new(1, 2));
+        assert_eq!(manager.begin(handle).err(), Some(incompatible));
+}
+
+#[test]
+fn state_belongs_to_the_package() {
+        const STATEFUL: &str = r#"
+                #[repr(C)]
+                pub struct Store {
+                        set: unsafe extern "C" fn(*const Store, *const u8, usize, *const u8, usize) -> i32,
+                }
+
+                static mut STORE: *const Store = std::ptr::null();
+
+                #[no_mangle] pub unsafe extern "C" fn vplugin_set_store(store: *const Store) { STORE = store; }
+                #[no_mangle] pub unsafe extern "C" fn vplugin_init() -> i32 {
+                        ((*STORE).set)(STORE, b"key".as_ptr(), 3, VALUE.as_ptr(), VALUE.len())
+                }
+                #[no_mangle] pub extern "C" fn vplugin_exit() {}
+        "#;
+        let dir = output_dir("fixture-state");
+        let original = fixture("stateful")
+                .rust(&format!("{}\nconst VALUE: &[u8] = b\"original\";", STATEFUL))
+                .build(&dir.join("original"))
+                .unwrap();
+        let impostor = fixture("stateful")
+                .rust(&format!("{}\nconst VALUE: &[u8] = b\"impostor\";", STATEFUL))
+                .build(&dir.join("impostor"))
+                .unwrap();
+
+        let mut manager = PluginManager::new();
+        let original = manager.load(&original).unwrap();
+        let impostor = manager.load(&impostor).unwrap();
+        manager.begin(original).unwrap();
+        manager.begin(impostor).unwrap();
+
+        let namespace = |handle| PluginStateStore::namespace(manager.plugin(handle).unwrap()).unwrap();
+        assert_ne!(namespace(original), namespace(impostor));
+        assert_eq!(manager.state_store().get(&namespace(original), b"key").as_deref(), Some(&b"original"[..]));
+        assert_eq!(manager.state_store().get(&namespace(impostor), b"key").as_deref(), Some(&b"impostor"[..]));
+        assert!(manager.state_store().get("stateful", b"key").is_none());
+}
+
+#[test]
+fn snapshots_keep_the_supplied_config() {
+        let dir = output_dir("fixture-config");
+        let vpl = fixture("configured")
+                .rust(r#"
+                        #[no_mangle] pub extern "C" fn vplugin_configure(_: *const u8, _: usize) -> i32 { 0 }
+                        #[no_mangle] pub extern "C" fn vplugin_init() -> i32 { 0 }
+                "#)
+                .config("answer", 42)
+                .build(&dir)
+                .unwrap();
+
+        let mut config = toml::Table::new();
+        config.insert("verbose".into(), true.into());
+        let mut manager = PluginManager::new();
+        let handle = manager.load(&vpl).unwrap();
+        manager.begin_with_config(handle, &config).unwrap();
+
+        /* The defaults aren't part of it, they would outlive an update of the plugin. */
+        let snapshot = manager.snapshot();
+        let saved: toml::Table = toml::from_str(snapshot.plugins[0].config.as_deref().unwrap()).unwrap();
+        assert_eq!(saved, config);
+}
+
+#[cfg(all(unix, feature = "crash_guard"))]
+#[test]
+fn crashing_entry_point() {
+        let dir = output_dir("fixture-crash");
+        let vpl = fixture("crashing")
+                .rust(r#"
+                        #[no_mangle] pub unsafe extern "C" fn vplugin_init() -> i32 {
+                                std::ptr::write_volatile(std::ptr::null_mut::<i32>(), 1);
+                                0
+                        }
+                "#)
+                .build(&dir)
+                .unwrap();
+
+        let mut manager = PluginManager::new();
+        let handle = manager.load(&vpl).unwrap();
+        assert_eq!(manager.begin(handle).err(), Some(VPluginError::PluginCrashed { signal: "SIGSEGV".into() }));
+        assert_eq!(manager.plugin(handle).unwrap().state(), vplugin::PluginState::Quarantined);
+}
+
+/* The guard is suspended while the plugin calls into the host, and resumed afterwards. */
+#[cfg(all(unix, feature = "crash_guard"))]
+#[test]
+fn crashing_after_calling_the_host() {
+        let dir = output_dir("fixture-crash-host");
+        let vpl = fixture("crashing-host")
+                .rust(r#"
+                        #[repr(C)]
+                        pub struct Allocator {
+                                alloc: unsafe extern "C" fn(*const Allocator, usize) -> *mut u8,
+                                realloc: unsafe extern "C" fn(*const Allocator, *mut u8, usize) -> *mut u8,
+                                free: unsafe extern "C" fn(*const Allocator, *mut u8),
+                        }
+                        static mut ALLOCATOR: *const Allocator = std::ptr::null();
+
+                        #[no_mangle] pub unsafe extern "C" fn vplugin_set_allocator(allocator: *const Allocator) {
+                                ALLOCATOR = allocator;
+                        }
+                        #[no_mangle] pub unsafe extern "C" fn vplugin_init() -> i32 {
+                                let block = ((*ALLOCATOR).alloc)(ALLOCATOR, 16);
+                                ((*ALLOCATOR).free)(ALLOCATOR, block);
+                                std::ptr::write_volatile(std::ptr::null_mut::<i32>(), 1);
+                                0
+                        }
+                "#)
+                .build(&dir)
+                .unwrap();
+
+        let mut manager = PluginManager::new();
+        let handle = manager.load(&vpl).unwrap();
+        assert_eq!(manager.begin(handle).err(), Some(VPluginError::PluginCrashed { signal: "SIGSEGV".into() }));
+        assert_eq!(manager.plugin(handle).unwrap().state(), PluginState::Quarantined);
+}
+
+#[test]
+fn restore_keeps_the_entry_point_of_the_manager() {
+        let dir = output_dir("fixture-restore-entry");
+        let custom = fixture("custom-entry")
+                .rust(r#"#[no_mangle] pub extern "C" fn custom_init() -> i32 { 0 }"#)
+                .build(&dir)
+                .unwrap();
+        let default = fixture("default-entry").rust(SOURCE).build(&dir).unwrap();
+
+        let mut saved = PluginManager::new();
+        saved.set_entry_point("custom_init");
+        let handle = saved.load(&custom).unwrap();
+        saved.begin(handle).unwrap();
+        let snapshot = saved.snapshot();
+
+        let mut manager = PluginManager::new();
+        let restored = manager.restore(&snapshot).unwrap();
+        assert_eq!(manager.plugin(restored[0]).unwrap().state(), PluginState::Started);
+        let handle = manager.load(&default).unwrap();
+        manager.begin(handle).unwrap();
+}
+
+#[test]
+fn configure_and_stop() {
+        let dir = output_dir("fixture-configure-stop");
+        let vpl = fixture("stopping")
+                .rust(r#"
+                        #[no_mangle] pub unsafe extern "C" fn vplugin_configure(config: *const u8, len: usize) -> i32 {
+                                let config = std::str::from_utf8(std::slice::from_raw_parts(config, len)).unwrap();
+                                if config.contains("answer = 42") { 0 } else { 1 }
+                        }
+                        #[no_mangle] pub extern "C" fn vplugin_init() -> i32 { 0 }
+                        #[no_mangle] pub extern "C" fn vplugin_exit() {}
+                        #[no_mangle] pub unsafe extern "C" fn vplugin_stop(acknowledge: extern "C" fn(*mut u8), context: *mut u8) {
+                                acknowledge(context);
+                        }
+                "#)
+                .config("answer", 42)
+                .build(&dir)
+                .unwrap();
+
+        let mut manager = PluginManager::new();
+        let handle = manager.load(&vpl).unwrap();
+        manager.begin_with_config(handle, &toml::Table::new()).unwrap();
+        manager.request_stop(handle, std::time::Duration::from_secs(5)).unwrap();
+}
+
+#[cfg(all(unix, feature = "crash_guard"))]
+#[test]
+fn crashing_stop() {
+        let dir = output_dir("fixture-crash-stop");
+        let vpl = fixture("crashing-stop")
+                .rust(r#"
+                        #[no_mangle] pub extern "C" fn vplugin_init() -> i32 { 0 }
+                        #[no_mangle] pub unsafe extern "C" fn vplugin_stop(_: extern "C" fn(*mut u8), _: *mut u8) {
+                                std::ptr::write_volatile(std::ptr::null_mut::<i32>(), 1);
+                        }
+                "#)
+                .build(&dir)
+                .unwrap();
+
+        let mut manager = PluginManager::new();
+        let handle = manager.load(&vpl).unwrap();
+        manager.begin(handle).unwrap();
+        assert_eq!(manager.request_stop(handle, std::time::Duration::from_secs(5)).err(), Some(VPluginError::PluginCrashed { signal: "SIGSEGV".into() }));
+        assert_eq!(manager.plugin(handle).unwrap().state(), PluginState::Quarantined);
+}
+
+#[test]
+fn memory_accounting() {
+        let dir = output_dir("fixture-memory");
+        let vpl = fixture("allocating")
+                .rust(r#"
+                        #[repr(C)]
+                        pub struct Allocator {
+                                alloc: unsafe extern "C" fn(*const Allocator, usize) -> *mut u8,
+                                realloc: unsafe extern "C" fn(*const Allocator, *mut u8, usize) -> *mut u8,
+                                free: unsafe extern "C" fn(*const Allocator, *mut u8),
+                        }
+                        static mut ALLOCATOR: *const Allocator = std::ptr::null();
+                        static mut BLOCK: *mut u8 = std::ptr::null_mut();
+
+                        #[no_mangle] pub unsafe extern "C" fn vplugin_set_allocator(allocator: *const Allocator) {
+                                ALLOCATOR = allocator;
+                        }
+                        #[no_mangle] pub unsafe extern "C" fn vplugin_init() -> i32 {
+                                BLOCK = ((*ALLOCATOR).alloc)(ALLOCATOR, 4096);
+                                if BLOCK.is_null() { 1 } else { 0 }
+                        }
+                        #[no_mangle] pub unsafe extern "C" fn vplugin_exit() {
+                                ((*ALLOCATOR).free)(ALLOCATOR, BLOCK);
+                        }
+                "#)
+                .build(&dir)
+                .unwrap();
+
+        let mut manager = PluginManager::new();
+        let handle = manager.load(&vpl).unwrap();
+        manager.begin(handle).unwrap();
+        let usage = manager.memory_usage(manager.plugin(handle).unwrap());
+        assert_eq!((usage.live, usage.peak, usage.allocations), (4096, 4096, 1));
+        manager.terminate(handle).unwrap();
+        assert_eq!(manager.memory_usage(manager.plugin(handle).unwrap()).live, 0);
+
+        /* The plugin fails to start once it can't allocate. */
+        if !cfg!(feature = "non_reusable_plugins") {
+                manager.set_memory_limit(manager.plugin(handle).unwrap(), Some(1024));
+                assert_eq!(manager.begin(handle), Err(VPluginError::FailedToInitialize));
+                assert_eq!(manager.memory_usage(manager.plugin(handle).unwrap()).refused, 1);
+        }
+}
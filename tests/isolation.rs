@@ -0,0 +1,97 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+
+/*
+ * Plugins panicking in their entry point or destructor, linked into the test like builtin
+ * plugins, which are quarantined instead of taking the test down.
+ */
+
+use std::ffi::c_void;
+use std::time::Duration;
+use vplugin::{Plugin, PluginManager, PluginMetadata, PluginState, VPluginError};
+use vplugin::testing::FakeBackend;
+
+extern "C-unwind" fn init() -> i32 { 0 }
+extern "C-unwind" fn panicking_init() -> i32 { panic!("the entry point panicked") }
+extern "C-unwind" fn panicking_exit() { panic!("the destructor panicked") }
+extern "C-unwind" fn panicking_configure(_: *const u8, _: usize) -> i32 { panic!("the configuration panicked") }
+extern "C-unwind" fn panicking_stop(_: extern "C" fn(*mut c_void), _: *mut c_void) { panic!("the stop request panicked") }
+
+fn plugin(backend: FakeBackend) -> Plugin {
+        let metadata = PluginMetadata { name: "panicking".into(), ..PluginMetadata::default() };
+        Plugin::with_backend(metadata, backend)
+}
+
+#[test]
+fn entry_point_panicking() {
+        let mut manager = PluginManager::new();
+        let handle = manager.insert(plugin(FakeBackend::new().with_symbol("vplugin_init", panicking_init as *const ())));
+
+        match manager.begin(handle) {
+                Err(VPluginError::PluginPanicked { message, .. }) => assert_eq!(message, "the entry point panicked"),
+                other                                             => panic!("{:?}", other)
+        }
+        assert_eq!(manager.plugin(handle).unwrap().state(), PluginState::Quarantined);
+        assert!(manager.terminate(handle).is_err());
+}
+
+#[test]
+fn destructor_panicking() {
+        let backend = FakeBackend::new()
+                .with_symbol("vplugin_init", init as *const ())
+                .with_symbol("vplugin_exit", panicking_exit as *const ());
+        let mut manager = PluginManager::new();
+        let handle = manager.insert(plugin(backend));
+        manager.begin(handle).unwrap();
+
+        match manager.terminate(handle) {
+                Err(VPluginError::PluginPanicked { message, .. }) => assert_eq!(message, "the destructor panicked"),
+                other                                             => panic!("{:?}", other)
+        }
+        assert_eq!(manager.plugin(handle).unwrap().state(), PluginState::Quarantined);
+}
+
+#[test]
+fn configure_panicking() {
+        let backend = FakeBackend::new()
+                .with_symbol("vplugin_init", init as *const ())
+                .with_symbol("vplugin_configure", panicking_configure as *const ());
+        let mut manager = PluginManager::new();
+        let handle = manager.insert(plugin(backend));
+
+        match manager.begin_with_config(handle, &toml::Table::new()) {
+                Err(VPluginError::PluginPanicked { message, .. }) => assert_eq!(message, "the configuration panicked"),
+                other                                             => panic!("{:?}", other)
+        }
+        assert_eq!(manager.plugin(handle).unwrap().state(), PluginState::Quarantined);
+}
+
+#[test]
+fn stop_panicking() {
+        let backend = FakeBackend::new()
+                .with_symbol("vplugin_init", init as *const ())
+                .with_symbol("vplugin_stop", panicking_stop as *const ());
+        let mut manager = PluginManager::new();
+        let handle = manager.insert(plugin(backend));
+        manager.begin(handle).unwrap();
+
+        match manager.request_stop(handle, Duration::from_secs(1)) {
+                Err(VPluginError::PluginPanicked { message, .. }) => assert_eq!(message, "the stop request panicked"),
+                other                                             => panic!("{:?}", other)
+        }
+        assert_eq!(manager.plugin(handle).unwrap().state(), PluginState::Quarantined);
+}
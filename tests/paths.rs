@@ -0,0 +1,114 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+/*
+ * Plugins whose names or paths aren't plain ASCII, or are longer than the 260
+ * characters Windows limits paths to unless they are extended-length paths.
+ */
+
+use std::path::PathBuf;
+use vplugin::PluginManager;
+use vplugin::testing::fixture;
+
+const SOURCE: &str = r#"
+        #[no_mangle] pub extern "C" fn vplugin_init() -> i32 { 0 }
+        #[no_mangle] pub extern "C" fn vplugin_exit() {}
+"#;
+
+fn output_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vplugin-tests-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+}
+
+#[test]
+fn unicode_plugin_name() {
+        let dir = output_dir("unicode");
+        let vpl = fixture("ünïcødé-插件").rust(SOURCE).build(&dir).unwrap();
+
+        let mut manager = PluginManager::new();
+        let handle = manager.load(&vpl).unwrap();
+        manager.begin(handle).unwrap();
+        assert_eq!(manager.plugin(handle).unwrap().metadata.name, "ünïcødé-插件");
+        assert!(manager.get_plugin("ünïcødé-插件").is_some());
+        manager.terminate(handle).unwrap();
+}
+
+#[test]
+fn unicode_directories() {
+        let dir = output_dir("unicode-dirs").join("Пользователь").join("データ");
+        let vpl = fixture("plugin").rust(SOURCE).build(&dir).unwrap();
+        let unpacked = fixture("unpacked").rust(SOURCE).build_directory(&dir).unwrap();
+
+        let mut manager = PluginManager::new_in(dir.join("工作区")).unwrap();
+        let archive   = manager.load(&vpl).unwrap();
+        let directory = manager.load(&unpacked).unwrap();
+        manager.begin(archive).unwrap();
+        manager.begin(directory).unwrap();
+        assert!(manager.plugin(archive).unwrap().directory().unwrap().starts_with(dir.join("工作区")));
+        manager.terminate(archive).unwrap();
+        manager.terminate(directory).unwrap();
+}
+
+#[test]
+fn paths_longer_than_max_path() {
+        let mut dir = output_dir("long-paths");
+        while dir.as_os_str().len() < 300 {
+                dir.push("a-rather-long-directory-name-for-a-plugin");
+        }
+        let vpl = fixture("long").rust(SOURCE).build(&dir).unwrap();
+
+        let mut manager = PluginManager::new_in(dir.join("workspace")).unwrap();
+        let handle = manager.load(&vpl).unwrap();
+        manager.begin(handle).unwrap();
+        assert!(manager.plugin(handle).unwrap().directory().unwrap().as_os_str().len() > 260);
+        manager.terminate(handle).unwrap();
+}
+
+#[test]
+fn needs_outside_the_package() {
+        let dir = output_dir("needs-outside");
+        let vpl = fixture("escaping").rust(SOURCE).needs("../../outside.dll").needs("../libc.so.6").build(&dir).unwrap();
+
+        let mut manager = PluginManager::new();
+        assert!(matches!(manager.load(&vpl), Err(vplugin::VPluginError::InvalidPlugin)));
+}
+
+#[cfg(windows)]
+#[test]
+fn bundled_dlls_are_loaded_by_path() {
+        #[link(name = "kernel32")]
+        extern "system" {
+                fn GetModuleHandleW(name: *const u16) -> *mut std::ffi::c_void;
+        }
+        let loaded = |name: &str| {
+                let name: Vec<u16> = name.encode_utf16().chain(Some(0)).collect();
+                !unsafe { GetModuleHandleW(name.as_ptr()) }.is_null()
+        };
+
+        let dir = output_dir("bundled-dlls");
+        let helper = fixture("helper").rust("#[no_mangle] pub extern \"C\" fn vplugin_helper() -> i32 { 1 }").build_directory(&dir).unwrap();
+        let package = fixture("bundling").rust(SOURCE).needs("lib/vplugin-bundled-helper.dll").build_directory(&dir).unwrap();
+        std::fs::create_dir_all(package.join("lib")).unwrap();
+        std::fs::copy(helper.join("plugin.dll"), package.join("lib").join("vplugin-bundled-helper.dll")).unwrap();
+
+        assert!(!loaded("vplugin-bundled-helper.dll"));
+        let mut manager = PluginManager::new();
+        let handle = manager.load(&package).unwrap();
+        assert!(loaded("vplugin-bundled-helper.dll"));
+        manager.begin(handle).unwrap();
+        manager.terminate(handle).unwrap();
+}
@@ -0,0 +1,135 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+
+/* Capabilities granted to fixture plugins through the permission prompt. */
+
+use std::ffi::CString;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use vplugin::{PermissionDecision, PermissionPolicy, PluginManager, VPluginError};
+use vplugin::permissions::request_capability;
+use vplugin::testing::fixture;
+
+fn output_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vplugin-tests-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+}
+
+#[test]
+fn grants_belong_to_the_package() {
+        let dir = output_dir("permissions-package");
+        let original = fixture("notes")
+                .rust(r#"#[no_mangle] pub extern "C" fn vplugin_init() -> i32 { 0 }"#)
+                .permission("network", "Syncs the notes")
+                .build(&dir.join("original"))
+                .unwrap();
+        let impostor = fixture("notes")
+                .rust(r#"#[no_mangle] pub extern "C" fn vplugin_init() -> i32 { 1 }"#)
+                .permission("network", "Syncs the notes")
+                .build(&dir.join("impostor"))
+                .unwrap();
+
+        let prompts = Arc::new(AtomicUsize::new(0));
+        let mut manager = PluginManager::new();
+        manager.set_data_dir(dir.join("data"));
+        manager.set_permission_prompt({
+                let prompts = prompts.clone();
+                move |_| {
+                        prompts.fetch_add(1, Ordering::SeqCst);
+                        PermissionDecision::AllowAlways
+                }
+        });
+
+        let plugin = manager.load_plugin(&original).unwrap();
+        assert!(manager.permissions(&plugin).unwrap().request("network").unwrap());
+        assert_eq!(prompts.load(Ordering::SeqCst), 1);
+        /* Remembered for the same package. */
+        let again = manager.load_plugin(&original).unwrap();
+        assert_eq!(manager.permissions(&again).unwrap().granted("network").unwrap(), Some(true));
+
+        /* Not for another package with the same name. */
+        let impostor = manager.load_plugin(&impostor).unwrap();
+        assert_eq!(manager.permissions(&impostor).unwrap().granted("network").unwrap(), None);
+        assert!(manager.permissions(&impostor).unwrap().grants().unwrap().is_empty());
+}
+
+#[test]
+fn panicking_prompt() {
+        let dir = output_dir("permissions-panic");
+        let vpl = fixture("panicky")
+                .rust(r#"#[no_mangle] pub extern "C" fn vplugin_init() -> i32 { 0 }"#)
+                .permission("network", "Downloads updates")
+                .permission("filesystem", "Saves them")
+                .build(&dir)
+                .unwrap();
+
+        let mut manager = PluginManager::new();
+        manager.set_data_dir(dir.join("data"));
+        manager.set_permission_prompt(|request| match request.capability {
+                "network" => panic!("The prompt failed"),
+                _         => PermissionDecision::AllowOnce
+        });
+        let plugin = manager.load_plugin(&vpl).unwrap();
+        let permissions = manager.permissions(&plugin).unwrap();
+
+        let network    = CString::new("network").unwrap();
+        let filesystem = CString::new("filesystem").unwrap();
+        assert_eq!(unsafe { request_capability(&permissions, network.as_ptr()) }, -1);
+        assert_eq!(unsafe { request_capability(&permissions, filesystem.as_ptr()) }, 1);
+        assert_eq!(permissions.granted("filesystem").unwrap(), Some(true));
+}
+
+#[test]
+fn refused_capabilities() {
+        let dir = output_dir("permissions-refused");
+        let vpl = fixture("greedy")
+                .rust(r#"#[no_mangle] pub extern "C" fn vplugin_init() -> i32 { 0 }"#)
+                .permission("network", "Downloads updates")
+                .permission("filesystem", "Saves them")
+                .build(&dir)
+                .unwrap();
+
+        let prompts = Arc::new(AtomicUsize::new(0));
+        let mut manager = PluginManager::new();
+        manager.set_data_dir(dir.join("data"));
+        manager.set_permission_prompt({
+                let prompts = prompts.clone();
+                move |_| {
+                        prompts.fetch_add(1, Ordering::SeqCst);
+                        PermissionDecision::AllowOnce
+                }
+        });
+        let plugin = manager.load_plugin(&vpl).unwrap();
+
+        /* Undeclared capabilities are refused without asking. */
+        let permissions = manager.permissions(&plugin).unwrap();
+        assert!(!permissions.request("camera").unwrap());
+        assert_eq!(prompts.load(Ordering::SeqCst), 0);
+
+        /* So are those the policy doesn't allow, at runtime and when loading. */
+        manager.set_permission_policy(PermissionPolicy { allow: Vec::new(), deny: vec!["network".into()] });
+        let permissions = manager.permissions(&plugin).unwrap();
+        assert!(!permissions.request("network").unwrap());
+        assert!(permissions.request("filesystem").unwrap());
+        assert_eq!(prompts.load(Ordering::SeqCst), 1);
+        match manager.load_plugin(&vpl) {
+                Err(VPluginError::CapabilitiesRefused { capabilities, .. }) => assert_eq!(capabilities, ["network"]),
+                other                                                      => panic!("Expected the plugin to be refused, got {:?}", other.map(|_| ()))
+        }
+}
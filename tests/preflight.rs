@@ -0,0 +1,65 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+
+/* Symbol reports of fixture plugins, which read the libraries they link to without loading them. */
+
+use std::path::PathBuf;
+use vplugin::PluginManager;
+use vplugin::testing::fixture;
+
+fn output_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vplugin-tests-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+#[test]
+fn unresolved_symbols() {
+        let dir = output_dir("preflight-unresolved");
+        let vpl = fixture("unresolved")
+                .rust(r#"
+                        #[link(name = "m")]
+                        extern "C" {
+                                fn cos(x: f64) -> f64;
+                                fn vplugin_tests_undefined() -> i32;
+                        }
+                        #[no_mangle] pub extern "C" fn vplugin_init() -> i32 {
+                                unsafe { cos(0.0) as i32 + vplugin_tests_undefined() }
+                        }
+                "#)
+                .build(&dir)
+                .unwrap();
+
+        let manager = PluginManager::new();
+        let report = manager.symbol_report(&vpl).unwrap();
+        let unresolved: Vec<&str> = report.unresolved.iter().map(|symbol| symbol.name.as_str()).collect();
+        assert_eq!(unresolved, ["vplugin_tests_undefined"]);
+}
+
+#[test]
+fn clean_report() {
+        let dir = output_dir("preflight-clean");
+        let vpl = fixture("clean")
+                .rust(r#"#[no_mangle] pub extern "C" fn vplugin_init() -> i32 { 0 }"#)
+                .build(&dir)
+                .unwrap();
+
+        let manager = PluginManager::new();
+        let report = manager.symbol_report(&vpl).unwrap();
+        assert!(report.is_clean(), "{}", report);
+}
@@ -0,0 +1,91 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+/*
+ * Loading, terminating and loading plugins again. On Windows a loaded DLL locks its
+ * file, so these are the paths where removing the directories plugins are extracted
+ * into is deferred until the library is unloaded.
+ */
+
+use std::path::PathBuf;
+use vplugin::PluginManager;
+use vplugin::testing::fixture;
+
+const SOURCE: &str = r#"
+        #[no_mangle] pub extern "C" fn vplugin_init() -> i32 { 0 }
+        #[no_mangle] pub extern "C" fn vplugin_exit() {}
+"#;
+
+fn output_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vplugin-tests-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+}
+
+#[test]
+fn managers_extract_into_separate_directories() {
+        let dir = output_dir("separate");
+        let vpl = fixture("separate").rust(SOURCE).build(&dir).unwrap();
+
+        let mut first  = PluginManager::new();
+        let mut second = PluginManager::new();
+        let a = first.load(&vpl).unwrap();
+        let b = second.load(&vpl).unwrap();
+        let a = first.plugin(a).unwrap().directory().unwrap().to_path_buf();
+        let b = second.plugin(b).unwrap().directory().unwrap().to_path_buf();
+        assert_ne!(a, b);
+        assert!(a.is_dir() && b.is_dir());
+
+        drop(first);
+        assert!(!a.exists());
+        assert!(b.is_dir());
+}
+
+#[test]
+fn terminate_and_load_again() {
+        let dir = output_dir("reload");
+        let vpl = fixture("reload").rust(SOURCE).build(&dir).unwrap();
+        let mut manager = PluginManager::new();
+
+        let handle = manager.load(&vpl).unwrap();
+        manager.begin(handle).unwrap();
+        manager.terminate(handle).unwrap();
+        let plugin  = manager.remove(handle).unwrap();
+        let workdir = plugin.directory().unwrap().to_path_buf();
+        drop(plugin);
+        assert!(!workdir.exists(), "{} wasn't removed once the plugin was unloaded", workdir.display());
+
+        let handle = manager.load(&vpl).unwrap();
+        manager.begin(handle).unwrap();
+        assert_ne!(manager.plugin(handle).unwrap().directory(), Some(workdir.as_path()));
+        manager.terminate(handle).unwrap();
+}
+
+#[test]
+fn package_can_be_replaced_while_loaded() {
+        let dir = output_dir("replace");
+        let vpl = fixture("replace").rust(SOURCE).build(&dir).unwrap();
+        let mut manager = PluginManager::new();
+
+        let handle = manager.load(&vpl).unwrap();
+        manager.begin(handle).unwrap();
+        /* The loaded library is the extracted copy, never the package itself. */
+        fixture("replace").rust(SOURCE).version("0.2.0").build(&dir).unwrap();
+
+        let updated = manager.load(&vpl).unwrap();
+        assert_eq!(manager.plugin(updated).unwrap().metadata.version, "0.2.0");
+        manager.terminate(handle).unwrap();
+}
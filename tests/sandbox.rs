@@ -0,0 +1,114 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+
+/*
+ * Plugins running in a sandbox process. The sandbox process is this test started again,
+ * which has to serve the plugin before doing anything else: the tests run from their own
+ * `main` instead of the test harness (`harness = false`).
+ */
+
+use std::path::PathBuf;
+use std::time::Duration;
+use vplugin::{PluginManager, PluginState, VPluginError};
+use vplugin::sandbox::SandboxedPlugin;
+use vplugin::testing::fixture;
+
+const SOURCE: &str = r#"
+        type Write = unsafe extern "C" fn(*mut u8, *const u8, usize);
+
+        #[no_mangle] pub extern "C" fn vplugin_init() -> i32 { 0 }
+        #[no_mangle] pub extern "C" fn vplugin_exit() {}
+        #[no_mangle] pub unsafe extern "C" fn shout(input: *const u8, len: usize, write: Write, context: *mut u8) -> i32 {
+                let upper = std::slice::from_raw_parts(input, len).to_ascii_uppercase();
+                write(context, upper.as_ptr(), upper.len());
+                0
+        }
+        #[no_mangle] pub unsafe extern "C" fn refuse(_: *const u8, _: usize, _: Write, _: *mut u8) -> i32 { 3 }
+        #[no_mangle] pub unsafe extern "C" fn crash(_: *const u8, _: usize, _: Write, _: *mut u8) -> i32 {
+                std::ptr::write_volatile(std::ptr::null_mut::<i32>(), 1);
+                0
+        }
+        #[no_mangle] pub unsafe extern "C" fn hang(_: *const u8, _: usize, _: Write, _: *mut u8) -> i32 {
+                loop { std::thread::sleep(std::time::Duration::from_secs(1)); }
+        }
+"#;
+
+fn main() {
+        vplugin::sandbox::serve_if_child();
+
+        let tests: [(&str, fn()); 4] = [
+                ("sandboxed_hooks", sandboxed_hooks),
+                ("failing_hook", failing_hook),
+                ("crashing_hook", crashing_hook),
+                ("hanging_hook", hanging_hook),
+        ];
+        for (name, test) in tests {
+                println!("test {} ...", name);
+                test();
+        }
+        println!("test result: ok. {} passed", tests.len());
+}
+
+fn sandboxed(test: &str) -> SandboxedPlugin {
+        let dir = std::env::temp_dir().join(format!("vplugin-tests-sandbox-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let vpl: PathBuf = fixture("sandboxed").rust(SOURCE).build(&dir).unwrap();
+
+        let mut plugin = PluginManager::new().load_plugin_sandboxed(&vpl).unwrap();
+        assert_eq!(plugin.get_metadata().name, "sandboxed");
+        plugin.begin().unwrap();
+        plugin
+}
+
+fn sandboxed_hooks() {
+        let mut plugin = sandboxed("hooks");
+        assert_eq!(plugin.call("shout", b"hello").unwrap(), b"HELLO");
+        assert_eq!(plugin.call("shout", b"").unwrap(), b"");
+        plugin.terminate().unwrap();
+        assert!(matches!(plugin.call("shout", b"hello"), Err(VPluginError::InvalidState { .. })));
+        if !cfg!(feature = "non_reusable_plugins") {
+                plugin.begin().unwrap();
+                assert_eq!(plugin.call("shout", b"again").unwrap(), b"AGAIN");
+        }
+}
+
+fn failing_hook() {
+        let mut plugin = sandboxed("failing");
+        assert!(matches!(plugin.call("refuse", b""), Err(VPluginError::SandboxFailed { .. })));
+        assert!(matches!(plugin.call("missing", b""), Err(VPluginError::SandboxFailed { .. })));
+        /* The process keeps serving the plugin. */
+        assert_eq!(plugin.call("shout", b"still here").unwrap(), b"STILL HERE");
+}
+
+fn crashing_hook() {
+        let mut plugin = sandboxed("crashing");
+        assert!(matches!(plugin.call("crash", b""), Err(VPluginError::SandboxCrashed { .. })));
+        assert_eq!(plugin.state(), PluginState::Invalid);
+        assert!(!plugin.is_alive());
+        assert!(plugin.call("shout", b"hello").is_err());
+}
+
+fn hanging_hook() {
+        let mut plugin = sandboxed("hanging");
+        plugin.set_timeout(Duration::from_millis(500));
+        match plugin.call("hang", b"") {
+                Err(VPluginError::SandboxFailed { reason, .. }) => assert!(reason.contains("no answer within"), "{}", reason),
+                other                                           => panic!("{:?}", other)
+        }
+        assert_eq!(plugin.state(), PluginState::Invalid);
+        assert!(!plugin.is_alive());
+}
@@ -0,0 +1,76 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+
+/* Values sent to plugins with `#[derive(Shareable)]`, received by hooks of a fake backend. */
+
+use std::sync::Mutex;
+use vplugin::{Plugin, PluginManager, PluginMetadata, Shareable};
+use vplugin::shareable::PlainData;
+use vplugin::testing::FakeBackend;
+
+#[derive(Shareable, Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
+struct Viewport {
+        origin: [i32; 2],
+        scale : f32,
+        flags : u32,
+}
+
+#[derive(Shareable, Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
+#[shareable(hook = "take_pair")]
+struct Pair<T: PlainData> {
+        first : T,
+        second: T,
+}
+
+extern "C" fn init() -> i32 {
+        0
+}
+
+static RECEIVED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+unsafe extern "C" fn receive_viewport(data: *const u8, len: usize) -> i32 {
+        RECEIVED.lock().unwrap().push(format!("{:?}", Viewport::receive(data, len)));
+        assert!(Viewport::receive(data, len - 1).is_err());
+        0
+}
+
+unsafe extern "C" fn receive_pair(data: *const u8, len: usize) -> i32 {
+        RECEIVED.lock().unwrap().push(format!("{:?}", Pair::<u16>::receive(data, len)));
+        0
+}
+
+#[test]
+fn plain_data() {
+        let backend = FakeBackend::new()
+                .with_symbol("vplugin_init", init as *const ())
+                .with_symbol(Viewport::SHAREABLE_HOOK, receive_viewport as *const ())
+                .with_symbol(Pair::<u16>::SHAREABLE_HOOK, receive_pair as *const ());
+        let mut manager = PluginManager::new();
+        let handle = manager.insert(Plugin::with_backend(PluginMetadata { name: "receiver".into(), ..Default::default() }, backend));
+        manager.begin(handle).unwrap();
+        let plugin = manager.plugin(handle).unwrap();
+
+        Viewport { origin: [-4, 2], scale: 1.5, flags: 7 }.send(plugin);
+        Pair { first: 1u16, second: 2 }.send(plugin);
+        assert_eq!(Viewport::SHAREABLE_HOOK, "vplugin_receive_viewport");
+        assert_eq!(*RECEIVED.lock().unwrap(), [
+                "Ok(Viewport { origin: [-4, 2], scale: 1.5, flags: 7 })",
+                "Ok(Pair { first: 1, second: 2 })",
+        ]);
+}
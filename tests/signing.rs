@@ -0,0 +1,110 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+
+/* Packages signed with `Fixture::signed_with`, loaded by a manager trusting some keys. */
+
+use std::path::PathBuf;
+use vplugin::{PluginManager, VPluginError};
+use vplugin::signing::public_key;
+use vplugin::testing::fixture;
+
+const SOURCE: &str = r#"
+        #[no_mangle] pub extern "C" fn vplugin_init() -> i32 { 0 }
+        #[no_mangle] pub extern "C" fn vplugin_exit() {}
+"#;
+
+const PUBLISHER: [u8; 32] = [1; 32];
+const STRANGER : [u8; 32] = [2; 32];
+
+fn output_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vplugin-tests-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+}
+
+fn trusting_manager() -> PluginManager {
+        let mut manager = PluginManager::new();
+        manager.add_trusted_key(public_key(&PUBLISHER)).unwrap();
+        manager
+}
+
+fn assert_refused(result: Result<vplugin::Plugin, VPluginError>, reason: &str) {
+        match result {
+                Err(VPluginError::SignatureInvalid { reason: r }) => assert!(r.contains(reason), "{}", r),
+                Err(e) => panic!("expected SignatureInvalid, got {:?}", e),
+                Ok (_) => panic!("the package was loaded")
+        }
+}
+
+#[test]
+fn signed_packages() {
+        let dir = output_dir("signing-signed");
+        let signed = fixture("signed").rust(SOURCE).signed_with(PUBLISHER);
+        let vpl = signed.build(&dir).unwrap();
+        let package = dir.join("signed");
+
+        let mut manager = trusting_manager();
+        manager.load_plugin(&vpl).unwrap();
+        manager.load_plugin(&package).unwrap();
+        manager.load_plugin_from_bytes(&std::fs::read(&vpl).unwrap()).unwrap();
+}
+
+#[test]
+fn unsigned_packages() {
+        let dir = output_dir("signing-unsigned");
+        let unsigned = fixture("unsigned").rust(SOURCE);
+        let vpl = unsigned.build(&dir).unwrap();
+
+        /* Nothing is checked until a key is trusted. */
+        PluginManager::new().load_plugin(&vpl).unwrap();
+
+        let mut manager = trusting_manager();
+        assert_refused(manager.load_plugin(&vpl), "isn't signed");
+        assert_refused(manager.load_plugin(dir.join("unsigned")), "isn't signed");
+        assert_refused(manager.load_plugin_from_bytes(&std::fs::read(&vpl).unwrap()), "isn't signed");
+
+        let object = dir.join("unsigned").join(format!("plugin.{}", std::env::consts::DLL_EXTENSION));
+        assert_refused(manager.load_plugin(object), "can't be signed");
+}
+
+#[test]
+fn untrusted_key() {
+        let dir = output_dir("signing-untrusted");
+        let vpl = fixture("untrusted").rust(SOURCE).signed_with(STRANGER).build(&dir).unwrap();
+
+        let mut manager = trusting_manager();
+        assert_refused(manager.load_plugin(&vpl), "isn't signed by a trusted key");
+
+        manager.add_trusted_key(public_key(&STRANGER)).unwrap();
+        manager.load_plugin(&vpl).unwrap();
+}
+
+#[test]
+fn modified_package() {
+        let dir = output_dir("signing-modified");
+        let package = fixture("modified").rust(SOURCE).signed_with(PUBLISHER).build_directory(&dir).unwrap();
+        trusting_manager().load_plugin(&package).unwrap();
+
+        let metadata = package.join("metadata.toml");
+        let mut contents = std::fs::read_to_string(&metadata).unwrap();
+        contents.push_str("description = \"Changed after signing\"\n");
+        std::fs::write(&metadata, contents).unwrap();
+        assert_refused(trusting_manager().load_plugin(&package), "was modified");
+
+        std::fs::write(package.join("package.sig"), b"not a signature").unwrap();
+        assert_refused(trusting_manager().load_plugin(&package), "isn't a valid signature");
+}
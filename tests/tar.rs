@@ -0,0 +1,105 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+
+/* Plugins packed as tarballs compressed with gzip or zstd instead of ZIP files. */
+
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use vplugin::{PluginManager, VPluginError};
+use vplugin::testing::fixture;
+
+const SOURCE: &str = r#"
+        #[no_mangle] pub extern "C" fn vplugin_init() -> i32 { 0 }
+        #[no_mangle] pub extern "C" fn vplugin_exit() {}
+        #[no_mangle] pub extern "C" fn add_one(x: i32) -> i32 { x + 1 }
+"#;
+
+fn output_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vplugin-tests-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+}
+
+/* Packs the files of `package` into a tarball written to `output`. */
+fn pack<W: Write>(package: &Path, output: W) -> W {
+        let mut builder = tar::Builder::new(output);
+        for entry in std::fs::read_dir(package).unwrap() {
+                let path = entry.unwrap().path();
+                builder.append_path_with_name(&path, path.file_name().unwrap()).unwrap();
+        }
+        builder.into_inner().unwrap()
+}
+
+fn tar_gz(package: &Path, archive: &Path) {
+        let encoder = flate2::write::GzEncoder::new(File::create(archive).unwrap(), flate2::Compression::default());
+        pack(package, encoder).finish().unwrap();
+}
+
+fn tar_zst(package: &Path, archive: &Path) {
+        let encoder = zstd::stream::write::Encoder::new(File::create(archive).unwrap(), 0).unwrap();
+        pack(package, encoder).finish().unwrap();
+}
+
+fn check_plugin(manager: &mut PluginManager, archive: &Path) {
+        let handle = manager.load(archive).unwrap();
+        assert_eq!(manager.plugin(handle).unwrap().metadata.name, "tarball");
+        manager.begin(handle).unwrap();
+        let add_one = manager.custom_hook::<i32, i32>(handle, "add_one").unwrap();
+        assert_eq!(unsafe { add_one(41) }, 42);
+        manager.terminate(handle).unwrap();
+}
+
+#[test]
+fn compressed_tarballs() {
+        let dir = output_dir("tar-compressed");
+        let package = fixture("tarball").rust(SOURCE).build_directory(&dir).unwrap();
+        let mut manager = PluginManager::new();
+
+        /* The format is told apart from the contents, not the extension. */
+        tar_gz(&package, &dir.join("gzip.vpl"));
+        check_plugin(&mut manager, &dir.join("gzip.vpl"));
+        tar_zst(&package, &dir.join("zstd.vpl"));
+        check_plugin(&mut manager, &dir.join("zstd.vpl"));
+
+        let vpl = std::fs::read(dir.join("zstd.vpl")).unwrap();
+        let plugin = manager.load_plugin_from_bytes(&vpl).unwrap();
+        assert_eq!(plugin.metadata.name, "tarball");
+}
+
+#[test]
+fn corrupt_tarballs() {
+        let dir = output_dir("tar-corrupt");
+        let package = fixture("tarball").rust(SOURCE).build_directory(&dir).unwrap();
+        tar_gz(&package, &dir.join("gzip.vpl"));
+        tar_zst(&package, &dir.join("zstd.vpl"));
+
+        let mut manager = PluginManager::new();
+        for name in ["gzip.vpl", "zstd.vpl"] {
+                let archive = std::fs::read(dir.join(name)).unwrap();
+                let truncated = dir.join(format!("truncated-{}", name));
+                std::fs::write(&truncated, &archive[..archive.len() / 2]).unwrap();
+                assert!(manager.load(&truncated).is_err());
+
+                /* The magic bytes of the format, followed by garbage. */
+                let mut garbage = archive[..4].to_vec();
+                garbage.extend_from_slice(&[0x55; 256]);
+                let garbage_file = dir.join(format!("garbage-{}", name));
+                std::fs::write(&garbage_file, garbage).unwrap();
+                assert!(matches!(manager.load(&garbage_file), Err(VPluginError::InvalidPlugin)));
+        }
+}
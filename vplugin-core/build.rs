@@ -0,0 +1,34 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+/* Records the version of the compiler building the SDK, see `sdk::RUSTC_VERSION`. */
+
+use std::env;
+use std::process::Command;
+
+fn main() {
+        let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+        let version = Command::new(rustc)
+                .arg("--version")
+                .output()
+                .ok()
+                .and_then(|output| String::from_utf8(output.stdout).ok())
+                .map(|version| version.trim().to_owned())
+                .unwrap_or_default();
+
+        println!("cargo:rustc-env=VPLUGIN_RUSTC_VERSION={}", version);
+        println!("cargo:rerun-if-env-changed=RUSTC");
+}
@@ -0,0 +1,158 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Generation of the code on both ends of a [`HookManifest`], meant to be called from
+//! build scripts so that the host and the plugin can't disagree on the hooks:
+//! - [`host_hooks`] generates the [`declare_hooks!`](https://docs.rs/vplugin/latest/vplugin/macro.declare_hooks.html)
+//!   struct the host resolves the hooks with.
+//! - [`plugin_hooks`] generates a trait with one function per hook, and the `extern "C"`
+//!   functions exporting them from the type implementing it.
+//!
+//! ## Example
+//! In the `main` function of the host's `build.rs`:
+//! ```rust,no_run
+//! vplugin_core::codegen::write_host_hooks("plugin/hooks.toml", "EditorHooks").unwrap();
+//! ```
+//! And the host itself (Not compiled as a test, since only build scripts set `OUT_DIR`):
+//! ```rust,ignore
+//! include!(concat!(env!("OUT_DIR"), "/vplugin_hooks.rs"));
+//! ```
+//! The plugin does the same with [`write_plugin_hooks`], and implements the generated trait.
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::error::VPluginError;
+use crate::manifest::{HookManifest, HookSignature};
+
+/// The file generated inside `OUT_DIR` by [`write_host_hooks`] and [`write_plugin_hooks`].
+pub const GENERATED_FILE: &str = "vplugin_hooks.rs";
+
+/// Generates a [`declare_hooks!`](https://docs.rs/vplugin/latest/vplugin/macro.declare_hooks.html)
+/// invocation declaring the public struct `name`, with a method for every hook of `manifest`.
+pub fn host_hooks(manifest: &HookManifest, name: &str) -> Result<String, VPluginError> {
+        let hooks = signatures(manifest)?;
+        let mut code = String::new();
+        code.push_str("vplugin::declare_hooks! {\n");
+        code.push_str("    /// The hooks declared in the `hooks.toml` of the plugin.\n");
+        let _ = writeln!(code, "    pub struct {} {{", name);
+        for (hook, signature, docs) in &hooks {
+                for line in docs {
+                        let _ = writeln!(code, "        {}", line);
+                }
+                let _ = writeln!(code, "        fn {}({}){};", hook, arguments(signature), returns(signature));
+        }
+        code.push_str("    }\n}\n");
+        Ok(code)
+}
+
+/// Generates the public trait `name`, with a function for every hook of `manifest`, and
+/// the `#[no_mangle] extern "C"` functions exporting the hooks of `implementor`, the type
+/// of the plugin implementing the trait. A hook left unimplemented is a compile error,
+/// so the plugin can't be built without exporting every hook it declares.
+pub fn plugin_hooks(manifest: &HookManifest, name: &str, implementor: &str) -> Result<String, VPluginError> {
+        let hooks = signatures(manifest)?;
+        let mut code = String::new();
+        code.push_str("/// The hooks declared in the `hooks.toml` of the plugin.\n");
+        let _ = writeln!(code, "pub trait {} {{", name);
+        for (hook, signature, docs) in &hooks {
+                for line in docs {
+                        let _ = writeln!(code, "    {}", line);
+                }
+                let _ = writeln!(code, "    fn {}({}){};", hook, arguments(signature), returns(signature));
+        }
+        code.push_str("}\n");
+        for (hook, signature, _) in &hooks {
+                let names: Vec<&str> = signature.arguments.iter().map(|(name, _)| name.as_str()).collect();
+                let _ = write!(
+                        code,
+                        "\n#[no_mangle]\npub extern \"C\" fn {hook}({}){} {{\n    <{implementor} as {name}>::{hook}({})\n}}\n",
+                        arguments(signature),
+                        returns(signature),
+                        names.join(", ")
+                );
+        }
+        Ok(code)
+}
+
+/// Reads the manifest at `manifest` and writes the code generated by [`host_hooks`] to
+/// [`GENERATED_FILE`], inside `OUT_DIR`. Only meant to be called from build scripts.
+pub fn write_host_hooks<P: AsRef<Path>>(manifest: P, name: &str) -> Result<PathBuf, VPluginError> {
+        let code = host_hooks(&read(manifest.as_ref())?, name)?;
+        write(&code)
+}
+
+/// Reads the manifest at `manifest` and writes the code generated by [`plugin_hooks`] to
+/// [`GENERATED_FILE`], inside `OUT_DIR`. Only meant to be called from build scripts.
+pub fn write_plugin_hooks<P: AsRef<Path>>(manifest: P, name: &str, implementor: &str) -> Result<PathBuf, VPluginError> {
+        let code = plugin_hooks(&read(manifest.as_ref())?, name, implementor)?;
+        write(&code)
+}
+
+/* Reads the manifest at `path`, rerunning the build script whenever it changes. */
+fn read(path: &Path) -> Result<HookManifest, VPluginError> {
+        println!("cargo:rerun-if-changed={}", path.display());
+        let contents = fs::read_to_string(path).map_err(|e| VPluginError::from_io(&e))?;
+        HookManifest::parse(&contents)
+}
+
+fn write(code: &str) -> Result<PathBuf, VPluginError> {
+        let directory = env::var_os("OUT_DIR").ok_or_else(|| VPluginError::InternalError {
+                err: "OUT_DIR is not set, hooks can only be generated from build scripts".into()
+        })?;
+        let path = Path::new(&directory).join(GENERATED_FILE);
+        fs::write(&path, code).map_err(|e| VPluginError::from_io(&e))?;
+        Ok(path)
+}
+
+/* A hook to generate code for: its name, parsed signature and documentation comments. */
+type Hook<'a> = (&'a str, HookSignature, Vec<String>);
+
+fn signatures(manifest: &HookManifest) -> Result<Vec<Hook<'_>>, VPluginError> {
+        manifest.validate()?;
+        manifest.hooks
+                .iter()
+                .map(|(name, hook)| {
+                        let signature = hook.parse_signature().map_err(|reason| VPluginError::InvalidHookManifest { reason })?;
+                        let mut docs: Vec<String> = hook.description
+                                .iter()
+                                .flat_map(|description| description.lines())
+                                .map(|line| format!("/// {}", line).trim_end().to_owned())
+                                .collect();
+                        if !docs.is_empty() {
+                                docs.push("///".into());
+                        }
+                        docs.push(format!("/// Version {} of the hook.", hook.version));
+                        Ok((name.as_str(), signature, docs))
+                })
+                .collect()
+}
+
+fn arguments(signature: &HookSignature) -> String {
+        signature.arguments
+                .iter()
+                .map(|(name, ty)| format!("{}: {}", name, ty))
+                .collect::<Vec<_>>()
+                .join(", ")
+}
+
+fn returns(signature: &HookSignature) -> String {
+        match &signature.returns {
+                Some(ty) => format!(" -> {}", ty),
+                None     => String::new()
+        }
+}
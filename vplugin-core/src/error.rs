@@ -0,0 +1,225 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+extern crate thiserror;
+use thiserror::Error;
+use crate::state::PluginState;
+
+/// ## **Generic error code enum**
+/// 
+/// This enum represents possible errors that can occur while using
+/// VPlugin. They are usually an `Err` value on a `Result` enum returned
+/// by the API's functions.
+/// 
+/// ## Error Handling
+/// If a function from VPlugin returned an `Err` with this enum, then you are
+/// advised to see what the error is (There is a `#derive(Debug)` also used there).
+/// If an `InternalError` is returned, then take a look at the `String` parameter instead.
+/// 
+/// ## Layout
+/// The enum is `#[repr(C)]`, so new variants are only ever added at the end: the
+/// discriminants of the existing ones never change.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub enum VPluginError {
+        /// Invalid parameters passed to the function,
+        /// only useful for FFI calls.
+        #[error("Passed invalid parameters")]
+        ParametersError,
+        /// The plugin given is not valid
+        /// for this operation.
+        #[error("Invalid plugin requested")]
+        InvalidPlugin,
+        /// The file requested is not available.
+        #[error("File requested couldn't be accessed")]
+        NoSuchFile,
+        /// You do not have permission to access something
+        /// on the host system.
+        #[error("Access denied on requested permissions")]
+        PermissionDenied,
+        /// The symbol requested is not present in the raw
+        /// object file.
+        #[error("Symbol requested is not present in the plugin")]
+        MissingSymbol,
+        /// The plugin failed to initialize.
+        #[error("Plugin failed to initialize")]
+        FailedToInitialize,
+        /// Internal error: See the `String` parameter
+        /// to determine what the error is.
+        #[error("Internal error: {err:?}")]
+        InternalError {err: String},
+        /// The operating system refused to load the plugin because
+        /// of its code signature (Gatekeeper / AMFI on macOS).
+        #[error("Code signature rejected: {reason}")]
+        CodeSignatureRejected { reason: String },
+        /// The host process can't load shared libraries at all
+        /// (Statically linked, sandboxed, ...). The `reason`
+        /// explains what was detected and how to fix it.
+        #[error("Dynamic loading is not available: {reason}")]
+        DynamicLoadingUnavailable { reason: String },
+        /// The plugin archive contains two entries whose
+        /// paths only differ by case, which would overwrite
+        /// each other on case-insensitive filesystems.
+        #[error("Archive entries '{first}' and '{second}' only differ by case")]
+        ConflictingEntries { first: String, second: String },
+        /// The operation requires the plugin to be in a different
+        /// state (For example, starting a plugin that was already
+        /// started).
+        #[error("Plugin can't go from {current:?} to {requested:?}")]
+        InvalidState { current: PluginState, requested: PluginState },
+        /// The plugin can't be terminated because hooks
+        /// obtained through `Plugin::hook_guard` are still
+        /// alive.
+        #[error("Plugin is still in use by {pins} hook guard(s)")]
+        PluginInUse { pins: usize },
+        /// The `PluginHandle` given doesn't refer to a plugin
+        /// owned by the manager (Anymore), or the `ObjectHandle`
+        /// given was never handed out by the object registry.
+        #[error("Invalid handle")]
+        InvalidHandle,
+        /// A version string (Usually the `version` field of a
+        /// plugin's metadata) is not a valid version.
+        #[error("'{version}' is not a valid version")]
+        InvalidVersion { version: String },
+        /// The plugin doesn't support the version of the host's
+        /// API, or asked for a newer one.
+        #[error("Plugin is incompatible with host API version {host}")]
+        IncompatibleApi { host: String },
+        /// The plugin was built against a version of VPlugin
+        /// that isn't compatible with the host's.
+        #[error("Plugin was built with VPlugin {plugin_sdk}, which is incompatible with the host's VPlugin {host_sdk}")]
+        SdkVersionMismatch { plugin_sdk: String, host_sdk: String },
+        /// The plugin uses the Rust ABI but was built with a
+        /// different compiler than the host, or the layout of
+        /// the types they share differs.
+        #[error("Plugin was built with '{plugin}', which is incompatible with the host's '{host}'")]
+        ToolchainMismatch { plugin: String, host: String },
+        /// A version requirement between the host and a plugin
+        /// isn't met. `constraint` says which one (For example
+        /// the `requires_host` field of the plugin).
+        #[error("{constraint} requires '{requirement}', but the version is {version}")]
+        UnsatisfiedVersion { constraint: String, requirement: String, version: String },
+        /// A value doesn't fit the setting `key` of the plugin
+        /// (See the `[settings]` section of its metadata), or
+        /// the plugin has no such setting.
+        #[error("Invalid value for setting '{key}': {reason}")]
+        InvalidSetting { key: String, reason: String },
+        /// The license of the plugin (`NONE` if it doesn't
+        /// declare one) isn't accepted by the license policy
+        /// of the host.
+        #[error("Plugin '{plugin}' is licensed under '{expression}', which the license policy doesn't accept")]
+        LicensePolicyViolation { plugin: String, expression: String },
+        /// Native libraries the plugin needs (See the `needs`
+        /// field of its metadata) can't be found on the system.
+        #[error("Plugin needs libraries that can't be found: {}", libraries.join(", "))]
+        MissingLibraries { libraries: Vec<String> },
+        /// The `hooks.toml` file of the plugin is invalid.
+        #[error("Invalid hook manifest: {reason}")]
+        InvalidHookManifest { reason: String },
+        /// The `ObjectHandle` given refers to an object that was
+        /// released, even if its slot was reused since.
+        #[error("Handle {handle:#x} refers to an object that was released")]
+        StaleHandle { handle: u64 },
+        /// The object the `ObjectHandle` refers to is borrowed,
+        /// and can't be used until it's given back.
+        #[error("The object of handle {handle:#x} is borrowed")]
+        HandleBorrowed { handle: u64 },
+        /// The plugin didn't acknowledge a stop request in time,
+        /// and was terminated by force.
+        #[error("Plugin didn't stop within {timeout_ms} ms")]
+        StopTimedOut { timeout_ms: u64 },
+        /// The plugin panicked, and reported it to the host (See
+        /// `vplugin_core::panic`) or the panic unwound into the host.
+        /// `location` is empty if unknown.
+        #[error("Plugin panicked at '{location}': {message}")]
+        PluginPanicked { message: String, location: String },
+        /// A plugin the application can't run without failed to
+        /// start, with `error`. See `PluginManager::begin_all`.
+        #[error("Required plugin '{plugin}' failed to start: {error}")]
+        RequiredPluginFailed { plugin: String, error: Box<VPluginError> },
+        /// The application's entitlement check refused the plugin,
+        /// usually because the user didn't buy it. `reason` is
+        /// what the check returned, meant to be shown to the user.
+        #[error("Not entitled to plugin '{plugin}': {reason}")]
+        NotEntitled { plugin: String, reason: String },
+        /// An interceptor of the host refused a call into the
+        /// plugin (`call` says which one). See the `intercept`
+        /// module of VPlugin.
+        #[error("Call to the {call} of '{plugin}' refused: {reason}")]
+        CallRefused { plugin: String, call: String, reason: String },
+        /// The process running a sandboxed plugin exited (Usually
+        /// because the plugin crashed), with `status`.
+        #[error("The process of sandboxed plugin '{plugin}' exited ({status})")]
+        SandboxCrashed { plugin: String, status: String },
+        /// A sandboxed plugin couldn't be started, failed a
+        /// request or stopped answering, see `reason`.
+        #[error("Sandboxed plugin '{plugin}' failed: {reason}")]
+        SandboxFailed { plugin: String, reason: String },
+        /// The plugin depends on `dependency` (See the
+        /// `[dependencies]` section of its metadata), but the
+        /// manager has no plugin with that name and a version
+        /// matching `requirement`.
+        #[error("Plugin '{plugin}' depends on '{dependency}' ({requirement}), which isn't loaded")]
+        MissingDependency { plugin: String, dependency: String, requirement: String },
+        /// The dependencies of the plugins in `cycle` depend on
+        /// each other, so none of them can be started first.
+        #[error("Cyclic dependency between plugins: {}", cycle.join(" -> "))]
+        CyclicDependency { cycle: Vec<String> },
+        /// The package of a plugin isn't signed by a key the
+        /// host trusts, or was modified after being signed. See
+        /// the `signing` module of VPlugin.
+        #[error("Invalid signature: {reason}")]
+        SignatureInvalid { reason: String },
+        /// The plugin crashed with `signal` (`"SIGSEGV"`, ...) during a
+        /// call, and was quarantined. Only caught with the `crash_guard`
+        /// feature of VPlugin, on Unix.
+        #[error("Plugin crashed with {signal}")]
+        PluginCrashed { signal: String },
+        /// Loading the plugin was cancelled by the progress
+        /// callback of the application.
+        #[error("Loading the plugin was cancelled")]
+        Cancelled,
+        /// A symbol of the plugin isn't what the host would call
+        /// it as: the signature its package declares is another
+        /// one, or it's not even a function. `reason` says which.
+        #[error("Symbol '{symbol}' doesn't match the expected signature: {reason}")]
+        SignatureMismatch { symbol: String, reason: String },
+        /// The plugin declares capabilities (See the `[permissions]`
+        /// section of its metadata) the permission policy of the
+        /// host doesn't allow.
+        #[error("Plugin '{plugin}' asks for capabilities the application doesn't grant: {}", capabilities.join(", "))]
+        CapabilitiesRefused { plugin: String, capabilities: Vec<String> },
+        /// The plugin was built against version `found` of the
+        /// host's API (The `api_version` field of its metadata),
+        /// which version `expected` of the host doesn't provide.
+        #[error("Plugin was built against API version {found}, but the host provides {expected}")]
+        IncompatibleVersion { expected: String, found: String },
+}
+
+impl VPluginError {
+        /// Translates an I/O error into the closest matching `VPluginError`.
+        pub fn from_io(e: &std::io::Error) -> Self {
+                use std::io::ErrorKind::*;
+                match e.kind() {
+                        PermissionDenied => VPluginError::PermissionDenied,
+                        NotFound         => VPluginError::NoSuchFile,
+                        Interrupted      => VPluginError::InvalidPlugin,
+                        UnexpectedEof    => VPluginError::InvalidPlugin,
+                        OutOfMemory      => VPluginError::InternalError { err: "Host is out of memory".into() },
+                        _                => VPluginError::InternalError { err: e.to_string() },
+                }
+        }
+}
\ No newline at end of file
@@ -0,0 +1,420 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! `#[repr(C)]` types for passing data between hosts and plugins, instead of naked
+//! pointers whose length, lifetime and owner are only known by convention.
+//!
+//! - [`VStr`] and [`VSlice`] borrow a string or a slice for the duration of a call.
+//! - [`VString`] and [`VVec`] are owned, and carry the function freeing them. Whichever
+//!   side drops them, the memory goes back to the allocator of the side that allocated it,
+//!   so they can be moved freely between a host and a plugin built with different allocators.
+//! - [`VOption`] and [`VResult`] are the C equivalents of `Option` and `Result`.
+//!
+//! From C, owned values are dropped with [`vplugin_string_free`] and [`vplugin_bytes_free`].
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
+use std::ops::Deref;
+use std::str::Utf8Error;
+
+/// ## VStr
+/// A borrowed UTF-8 string, the equivalent of `&str`. Unlike C strings it's not
+/// nul-terminated, its length is passed along instead.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct VStr<'a> {
+        ptr   : *const u8,
+        len   : usize,
+        _str  : PhantomData<&'a str>,
+}
+
+impl<'a> VStr<'a> {
+        /// Returns the bytes of the string.
+        pub fn as_bytes(&self) -> &'a [u8] {
+                raw_slice(self.ptr, self.len)
+        }
+
+        /// Returns the string, if it's valid UTF-8. Strings created from Rust always are,
+        /// the check is there for those coming from C.
+        pub fn to_str(&self) -> Result<&'a str, Utf8Error> {
+                std::str::from_utf8(self.as_bytes())
+        }
+}
+
+impl<'a> From<&'a str> for VStr<'a> {
+        fn from(string: &'a str) -> Self {
+                Self { ptr: string.as_ptr(), len: string.len(), _str: PhantomData }
+        }
+}
+
+impl fmt::Debug for VStr<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Debug::fmt(&String::from_utf8_lossy(self.as_bytes()), f)
+        }
+}
+
+impl PartialEq for VStr<'_> {
+        fn eq(&self, other: &Self) -> bool {
+                self.as_bytes() == other.as_bytes()
+        }
+}
+
+unsafe impl Send for VStr<'_> {}
+unsafe impl Sync for VStr<'_> {}
+
+/// ## VSlice
+/// A borrowed slice, the equivalent of `&[T]`.
+#[repr(C)]
+pub struct VSlice<'a, T> {
+        ptr   : *const T,
+        len   : usize,
+        _slice: PhantomData<&'a [T]>,
+}
+
+impl<'a, T> VSlice<'a, T> {
+        /// Returns the slice.
+        pub fn as_slice(&self) -> &'a [T] {
+                raw_slice(self.ptr, self.len)
+        }
+}
+
+impl<'a, T> From<&'a [T]> for VSlice<'a, T> {
+        fn from(slice: &'a [T]) -> Self {
+                Self { ptr: slice.as_ptr(), len: slice.len(), _slice: PhantomData }
+        }
+}
+
+impl<T> Deref for VSlice<'_, T> {
+        type Target = [T];
+
+        fn deref(&self) -> &[T] {
+                self.as_slice()
+        }
+}
+
+impl<T> Clone for VSlice<'_, T> {
+        fn clone(&self) -> Self {
+                *self
+        }
+}
+
+impl<T> Copy for VSlice<'_, T> {}
+
+impl<T: fmt::Debug> fmt::Debug for VSlice<'_, T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Debug::fmt(self.as_slice(), f)
+        }
+}
+
+unsafe impl<T: Sync> Send for VSlice<'_, T> {}
+unsafe impl<T: Sync> Sync for VSlice<'_, T> {}
+
+/// ## VVec
+/// An owned vector, the equivalent of `Vec<T>`. It's freed by the side that allocated it,
+/// through the function it carries.
+#[repr(C)]
+pub struct VVec<T> {
+        ptr     : *mut T,
+        len     : usize,
+        capacity: usize,
+        free    : unsafe extern "C" fn(*mut T, usize, usize),
+}
+
+impl<T> VVec<T> {
+        /// Returns the elements of the vector.
+        pub fn as_slice(&self) -> &[T] {
+                raw_slice(self.ptr, self.len)
+        }
+
+        /// Borrows the vector as a [`VSlice`].
+        pub fn as_vslice(&self) -> VSlice<'_, T> {
+                self.as_slice().into()
+        }
+}
+
+impl<T: Clone> VVec<T> {
+        /// Copies the elements into a `Vec` allocated by the caller, freeing the vector.
+        pub fn into_vec(self) -> Vec<T> {
+                self.as_slice().to_vec()
+        }
+}
+
+impl<T> From<Vec<T>> for VVec<T> {
+        fn from(vec: Vec<T>) -> Self {
+                let mut vec = ManuallyDrop::new(vec);
+                Self { ptr: vec.as_mut_ptr(), len: vec.len(), capacity: vec.capacity(), free: free_vec::<T> }
+        }
+}
+
+impl<T> Drop for VVec<T> {
+        fn drop(&mut self) {
+                unsafe { (self.free)(self.ptr, self.len, self.capacity) }
+        }
+}
+
+impl<T> Deref for VVec<T> {
+        type Target = [T];
+
+        fn deref(&self) -> &[T] {
+                self.as_slice()
+        }
+}
+
+impl<T: Clone> Clone for VVec<T> {
+        fn clone(&self) -> Self {
+                self.as_slice().to_vec().into()
+        }
+}
+
+impl<T: fmt::Debug> fmt::Debug for VVec<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Debug::fmt(self.as_slice(), f)
+        }
+}
+
+impl<T: PartialEq> PartialEq for VVec<T> {
+        fn eq(&self, other: &Self) -> bool {
+                self.as_slice() == other.as_slice()
+        }
+}
+
+unsafe impl<T: Send> Send for VVec<T> {}
+unsafe impl<T: Sync> Sync for VVec<T> {}
+
+/// ## VString
+/// An owned UTF-8 string, the equivalent of `String`. Like [`VVec`], it's freed by the
+/// side that allocated it.
+#[repr(transparent)]
+#[derive(Clone, PartialEq)]
+pub struct VString(VVec<u8>);
+
+impl VString {
+        /// Returns the bytes of the string.
+        pub fn as_bytes(&self) -> &[u8] {
+                self.0.as_slice()
+        }
+
+        /// Returns the string, if it's valid UTF-8. Strings created from Rust always are,
+        /// the check is there for those coming from C.
+        pub fn to_str(&self) -> Result<&str, Utf8Error> {
+                std::str::from_utf8(self.as_bytes())
+        }
+
+        /// Borrows the string as a [`VStr`].
+        pub fn as_vstr(&self) -> VStr<'_> {
+                VStr { ptr: self.0.ptr, len: self.0.len, _str: PhantomData }
+        }
+
+        /// Copies the string into a `String` allocated by the caller, freeing it. Invalid
+        /// UTF-8 is replaced with `U+FFFD`.
+        pub fn into_string(self) -> String {
+                String::from_utf8_lossy(self.as_bytes()).into_owned()
+        }
+}
+
+impl From<String> for VString {
+        fn from(string: String) -> Self {
+                Self(string.into_bytes().into())
+        }
+}
+
+impl From<&str> for VString {
+        fn from(string: &str) -> Self {
+                string.to_owned().into()
+        }
+}
+
+impl fmt::Debug for VString {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Debug::fmt(&String::from_utf8_lossy(self.as_bytes()), f)
+        }
+}
+
+impl fmt::Display for VString {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Display::fmt(&String::from_utf8_lossy(self.as_bytes()), f)
+        }
+}
+
+/// ## VOption
+/// An optional value, the equivalent of `Option<T>`. Its layout is the one of a C struct
+/// holding a `uint8_t` tag (`0` for `None`) followed by a union of the variants.
+#[repr(C, u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VOption<T> {
+        None,
+        Some(T),
+}
+
+impl<T> VOption<T> {
+        /// Converts into an `Option`.
+        pub fn into_option(self) -> Option<T> {
+                self.into()
+        }
+}
+
+impl<T> From<Option<T>> for VOption<T> {
+        fn from(option: Option<T>) -> Self {
+                match option {
+                        Some(v) => VOption::Some(v),
+                        None    => VOption::None
+                }
+        }
+}
+
+impl<T> From<VOption<T>> for Option<T> {
+        fn from(option: VOption<T>) -> Self {
+                match option {
+                        VOption::Some(v) => Some(v),
+                        VOption::None    => None
+                }
+        }
+}
+
+/// ## VResult
+/// The result of an operation, the equivalent of `Result<T, E>`. Its layout is the one of
+/// a C struct holding a `uint8_t` tag (`0` for `Ok`) followed by a union of the variants.
+#[repr(C, u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VResult<T, E> {
+        Ok(T),
+        Err(E),
+}
+
+impl<T, E> VResult<T, E> {
+        /// Converts into a `Result`.
+        pub fn into_result(self) -> Result<T, E> {
+                self.into()
+        }
+}
+
+impl<T, E> From<Result<T, E>> for VResult<T, E> {
+        fn from(result: Result<T, E>) -> Self {
+                match result {
+                        Ok (v) => VResult::Ok(v),
+                        Err(e) => VResult::Err(e)
+                }
+        }
+}
+
+impl<T, E> From<VResult<T, E>> for Result<T, E> {
+        fn from(result: VResult<T, E>) -> Self {
+                match result {
+                        VResult::Ok (v) => Ok(v),
+                        VResult::Err(e) => Err(e)
+                }
+        }
+}
+
+/// Frees a [`VString`] received from Rust code, from C.
+#[no_mangle]
+pub extern "C" fn vplugin_string_free(string: VString) {
+        drop(string)
+}
+
+/// Frees a [`VVec`] of bytes received from Rust code, from C.
+#[no_mangle]
+pub extern "C" fn vplugin_bytes_free(bytes: VVec<u8>) {
+        drop(bytes)
+}
+
+/* Frees the vector with the allocator of the side that created it, since this is compiled into it. */
+unsafe extern "C" fn free_vec<T>(ptr: *mut T, len: usize, capacity: usize) {
+        drop(Vec::from_raw_parts(ptr, len, capacity))
+}
+
+/* Empty slices coming from C may have a null pointer, which `from_raw_parts` doesn't accept. */
+fn raw_slice<'a, T>(ptr: *const T, len: usize) -> &'a [T] {
+        match ptr.is_null() || len == 0 {
+                true  => &[],
+                false => unsafe { std::slice::from_raw_parts(ptr, len) }
+        }
+}
+
+#[cfg(test)]
+mod tests {
+        use super::*;
+        use std::rc::Rc;
+
+        #[test]
+        fn borrowed_values() {
+                let string = VStr::from("hello");
+                assert_eq!(string.to_str(), Ok("hello"));
+                assert_eq!(string, VStr::from("hello"));
+                assert_eq!(format!("{:?}", string), "\"hello\"");
+
+                let slice = VSlice::from(&[1, 2, 3][..]);
+                assert_eq!(slice.as_slice(), &[1, 2, 3]);
+                assert_eq!(slice.len(), 3);
+
+                /* Values coming from C may use a null pointer for empty strings. */
+                let empty = VStr { ptr: std::ptr::null(), len: 0, _str: PhantomData };
+                assert_eq!(empty.to_str(), Ok(""));
+        }
+
+        #[test]
+        fn invalid_utf8() {
+                let bytes = [b'a', 0xff, b'b'];
+                let string = VStr { ptr: bytes.as_ptr(), len: bytes.len(), _str: PhantomData };
+                assert!(string.to_str().is_err());
+
+                let string = VString(bytes.to_vec().into());
+                assert!(string.to_str().is_err());
+                assert_eq!(string.to_string(), "a\u{fffd}b");
+                assert_eq!(string.into_string(), "a\u{fffd}b");
+        }
+
+        #[test]
+        fn owned_values() {
+                let vec = VVec::from(vec![1, 2, 3]);
+                assert_eq!(vec.as_vslice().as_slice(), &[1, 2, 3]);
+                assert_eq!(vec.clone(), vec);
+                assert_eq!(vec.into_vec(), vec![1, 2, 3]);
+
+                let string = VString::from("hello");
+                assert_eq!(string.to_str(), Ok("hello"));
+                assert_eq!(string.as_vstr(), VStr::from("hello"));
+                assert_eq!(string.to_string(), "hello");
+                assert_eq!(string.clone().into_string(), "hello");
+                vplugin_string_free(string);
+                vplugin_bytes_free(b"bytes".to_vec().into());
+        }
+
+        #[test]
+        fn frees_the_elements() {
+                let counter = Rc::new(());
+                let vec = VVec::from(vec![counter.clone(), counter.clone()]);
+                assert_eq!(Rc::strong_count(&counter), 3);
+
+                let copy = vec.clone().into_vec();
+                assert_eq!(Rc::strong_count(&counter), 5);
+                drop(copy);
+                drop(vec);
+                assert_eq!(Rc::strong_count(&counter), 1);
+        }
+
+        #[test]
+        fn options_and_results() {
+                assert_eq!(VOption::from(Some(1)).into_option(), Some(1));
+                assert_eq!(VOption::<i32>::from(None), VOption::None);
+                assert_eq!(Option::from(VOption::Some("a")), Some("a"));
+
+                assert_eq!(VResult::<i32, ()>::from(Ok(1)).into_result(), Ok(1));
+                assert_eq!(VResult::<(), &str>::from(Err("failed")), VResult::Err("failed"));
+                assert_eq!(Result::from(VResult::<(), _>::Err(2)), Err(2));
+        }
+}
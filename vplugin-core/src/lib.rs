@@ -0,0 +1,64 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+#![warn(clippy::all)]
+
+//! # VPlugin Core
+//! The types shared between applications hosting plugins with [VPlugin](https://crates.io/crates/vplugin)
+//! and the plugins themselves, like the error type and plugin metadata.
+//! 
+//! Plugins only need this crate, not the whole loader (Which pulls `zip`, `libloading` and more).
+//! Hosts don't need to depend on it directly, everything here is reexported by `vplugin`.
+
+use std::ffi::{c_int, c_void};
+
+pub mod codegen;
+pub mod error;
+pub mod ffi;
+pub mod manifest;
+pub mod metadata;
+pub mod panic;
+pub mod sdk;
+pub mod settings;
+pub mod state;
+
+pub use error::VPluginError;
+pub use manifest::{HookDeclaration, HookManifest, HookSignature};
+pub use metadata::{MetadataDiagnostic, PluginMetadata};
+pub use settings::{Setting, SettingKind, SettingsSchema};
+pub use state::PluginState;
+
+/// A `Result` whose error defaults to [`VPluginError`](crate::error::VPluginError).
+///
+/// It's the standard `Result`, so it has all of its combinators (`map`, `and_then`, `ok`,
+/// ...), works with `?` next to any other `Result`, and `main` returning an `Err` exits
+/// with a failure code.
+pub type Result<T, E = VPluginError> = std::result::Result<T, E>;
+
+/// ## VHook
+/// The `VHook` is a type to represent a generic function by VPlugin.
+/// There is only a generic parameter available, a standard `void*`
+/// which can then be translated into the actual struct (Expected to
+/// be provided by your application or library). The return value is always
+/// an integer, to indicate success or failure. To save data, you should make
+/// a field available to the struct you pass.
+/// ## Safety
+/// Generally, using void pointers is unsafe by miles, and you should avoid it.
+/// If you can, use Rust alternatives instead, such as generics or simply getting
+/// the functions yourself.
+/// In any case, you should only use VHook if you are ready to deal with type mismatches
+/// and a ton of other issues.
+pub type VHook = unsafe extern "C" fn(*mut c_void) -> c_int;
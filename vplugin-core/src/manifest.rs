@@ -0,0 +1,240 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+
+//! The hooks a plugin declares in the `hooks.toml` file of its package, turning the
+//! symbols it exports into a contract the application can check.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use serde_derive::{Deserialize, Serialize};
+use crate::error::VPluginError;
+
+/// The file hooks are declared in, next to `metadata.toml`.
+pub const HOOK_MANIFEST_FILE: &str = "hooks.toml";
+
+/// The signature VPlugin calls the entry point of plugins with.
+pub const ENTRY_POINT_SIGNATURE: &str = "fn() -> i32";
+
+/// ## HookManifest
+/// The hooks a plugin exports, by name, as declared in its `hooks.toml`:
+/// ```toml
+/// [hooks.on_frame]
+/// signature   = "fn(dt: f32) -> i32"
+/// version     = "1.2.0"
+/// description = "Called before every frame is drawn"
+///
+/// [hooks.on_close]
+/// signature = "fn()"
+/// version   = "1.0.0"
+/// ```
+/// Every hook must be exported by the plugin's object file, otherwise the plugin isn't loaded.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HookManifest {
+        #[serde(default)]
+        pub hooks: BTreeMap<String, HookDeclaration>,
+}
+
+/// A single hook of a [`HookManifest`](crate::manifest::HookManifest).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HookDeclaration {
+        /// The signature of the hook, written like a Rust function pointer with named
+        /// arguments (`fn(dt: f32) -> i32`). See [`HookSignature`].
+        pub signature  : String,
+        /// The version of the hook (`major.minor.patch`), bumped by the plugin like any
+        /// other API: a new major version when the signature or the meaning changes.
+        pub version    : String,
+        /// What the hook does.
+        pub description: Option<String>,
+}
+
+/// The parsed signature of a [`HookDeclaration`]. Types are kept as they're written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookSignature {
+        /// The arguments of the hook, as `(name, type)`.
+        pub arguments: Vec<(String, String)>,
+        /// What the hook returns, `None` if nothing.
+        pub returns  : Option<String>,
+}
+
+impl HookManifest {
+        /// Parses the contents of a `hooks.toml` file, checking every declaration.
+        pub fn parse(contents: &str) -> Result<Self, VPluginError> {
+                let manifest: Self = toml::from_str(contents).map_err(|e| VPluginError::InvalidHookManifest {
+                        reason: e.message().to_owned()
+                })?;
+                manifest.validate()?;
+                Ok(manifest)
+        }
+
+        /// Checks that every hook has a valid name, signature and version.
+        pub fn validate(&self) -> Result<(), VPluginError> {
+                for (name, hook) in &self.hooks {
+                        let invalid = |reason: String| VPluginError::InvalidHookManifest {
+                                reason: format!("hook '{}': {}", name, reason)
+                        };
+                        if !is_identifier(name) {
+                                return Err(invalid("not a valid symbol name".into()));
+                        }
+                        hook.parse_signature().map_err(invalid)?;
+                        hook.parse_version().map_err(invalid)?;
+                }
+                Ok(())
+        }
+
+        /// Returns whether the manifest declares no hooks.
+        pub fn is_empty(&self) -> bool {
+                self.hooks.is_empty()
+        }
+
+        /// Returns the hook `name`, if declared.
+        pub fn get(&self, name: &str) -> Option<&HookDeclaration> {
+                self.hooks.get(name)
+        }
+}
+
+impl HookDeclaration {
+        /// Parses the signature of the hook.
+        pub fn parse_signature(&self) -> Result<HookSignature, String> {
+                self.signature.parse()
+        }
+
+        /// Parses the version of the hook into `(major, minor, patch)`.
+        pub fn parse_version(&self) -> Result<(u64, u64, u64), String> {
+                let parts: Vec<&str> = self.version.split('.').collect();
+                match parts.as_slice() {
+                        [major, minor, patch] => {
+                                let number = |part: &str| part
+                                        .parse::<u64>()
+                                        .map_err(|_| format!("'{}' is not a valid version (major.minor.patch)", self.version));
+                                Ok((number(major)?, number(minor)?, number(patch)?))
+                        },
+                        _ => Err(format!("'{}' is not a valid version (major.minor.patch)", self.version))
+                }
+        }
+}
+
+impl std::str::FromStr for HookSignature {
+        type Err = String;
+
+        fn from_str(signature: &str) -> Result<Self, String> {
+                let invalid = || format!("'{}' is not a valid signature, like 'fn(dt: f32) -> i32'", signature);
+                let rest = signature.trim().strip_prefix("fn").ok_or_else(invalid)?.trim_start();
+                let rest = rest.strip_prefix('(').ok_or_else(invalid)?;
+                let close = closing_parenthesis(rest).ok_or_else(invalid)?;
+                let (arguments, rest) = (&rest[..close], rest[close + 1..].trim());
+
+                let returns = match rest {
+                        "" => None,
+                        _  => match rest.strip_prefix("->").map(str::trim) {
+                                Some("()") => None,
+                                Some(ty) if !ty.is_empty() => Some(ty.to_owned()),
+                                _ => return Err(invalid())
+                        }
+                };
+                let arguments = split_arguments(arguments)
+                        .into_iter()
+                        .map(|argument| match argument.split_once(':') {
+                                Some((name, ty)) if is_identifier(name.trim()) && !ty.trim().is_empty() => {
+                                        Ok((name.trim().to_owned(), ty.trim().to_owned()))
+                                },
+                                _ => Err(format!("argument '{}' of '{}' must be written 'name: type'", argument, signature))
+                        })
+                        .collect::<Result<_, _>>()?;
+                Ok(Self { arguments, returns })
+        }
+}
+
+impl HookSignature {
+        /// Hashes the types of the signature, leaving out the names of the arguments (Which
+        /// don't matter to callers) and whitespace: this is the FNV-1a hash of `fn(f32)->i32`
+        /// for `fn(dt: f32) -> i32`. Packaging tools write it in the `entry_signature` field
+        /// of the metadata of plugins, see the specification.
+        pub fn hash(&self) -> u64 {
+                let types: Vec<&str> = self.arguments.iter().map(|(_, ty)| ty.as_str()).collect();
+                let mut normalized = format!("fn({})", types.join(","));
+                if let Some(returns) = &self.returns {
+                        normalized.push_str("->");
+                        normalized.push_str(returns);
+                }
+
+                /* FNV-1a */
+                let mut hash: u64 = 0xcbf29ce484222325;
+                for byte in normalized.bytes().filter(|b| !b.is_ascii_whitespace()) {
+                        hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+                }
+                hash
+        }
+}
+
+/// Prints the signature back, normalized (`fn(dt: f32) -> i32`).
+impl fmt::Display for HookSignature {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let arguments: Vec<String> = self.arguments
+                        .iter()
+                        .map(|(name, ty)| format!("{}: {}", name, ty))
+                        .collect();
+                write!(f, "fn({})", arguments.join(", "))?;
+                match &self.returns {
+                        Some(ty) => write!(f, " -> {}", ty),
+                        None     => Ok(())
+                }
+        }
+}
+
+/* Whether `name` can be the name of a symbol, or of an argument. */
+fn is_identifier(name: &str) -> bool {
+        let mut chars = name.chars();
+        matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/* The index of the parenthesis closing the one `s` follows. */
+fn closing_parenthesis(s: &str) -> Option<usize> {
+        let mut depth = 0;
+        for (i, c) in s.char_indices() {
+                match c {
+                        '(' => depth += 1,
+                        ')' if depth == 0 => return Some(i),
+                        ')' => depth -= 1,
+                        _   => ()
+                }
+        }
+        None
+}
+
+/* Splits arguments on the commas that aren't nested in a type (`[u8; 4]`, `fn(a, b)`, ...). */
+fn split_arguments(arguments: &str) -> Vec<&str> {
+        let mut split = Vec::new();
+        let mut depth = 0;
+        let mut start = 0;
+        for (i, c) in arguments.char_indices() {
+                match c {
+                        '(' | '[' | '<' => depth += 1,
+                        ')' | ']' | '>' if !arguments[..i].ends_with('-') => depth -= 1,
+                        ',' if depth == 0 => {
+                                split.push(arguments[start..i].trim());
+                                start = i + 1;
+                        },
+                        _ => ()
+                }
+        }
+        split.push(arguments[start..].trim());
+        split.retain(|argument| !argument.is_empty());
+        split
+}
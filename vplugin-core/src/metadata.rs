@@ -0,0 +1,503 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+use std::path::PathBuf;
+use serde_derive::{Deserialize, Serialize};
+use toml::Spanned;
+use crate::error::VPluginError;
+use crate::settings::{Setting, SettingsSchema};
+
+/// A struct that represents metadata about
+/// a single plugin, like its version and name.
+/// 
+/// This struct is normally returned by VPlugin while loading a plugin,
+/// hosts rarely have to create one themselves.
+/// 
+/// The metadata can be serialized with serde, so hosts can store or transmit it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[repr(C)]
+pub struct PluginMetadata {
+        pub description: Option<String>,
+        pub version    : String,
+        pub name       : String,
+        pub filename   : PathBuf,
+        pub objfile    : String,
+        /// The versions of the application the plugin works with, as a semantic
+        /// version requirement (`">=2.1, <3"`). `None` if the plugin doesn't say.
+        /// A `min_host_version` in the metadata is added to it as a `>=` requirement.
+        pub requires_host: Option<String>,
+        /// The version of the host's API the plugin was built against, as `major.minor`
+        /// (`"1.2"`). `None` if the plugin doesn't say.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub api_version: Option<String>,
+        /// The license of the plugin, as an SPDX expression (`"MIT OR Apache-2.0"`).
+        /// `None` if the plugin doesn't say.
+        pub license    : Option<String>,
+        /// Who publishes the plugin (`"Acme Audio Ltd."`), for commercial plugins.
+        /// `None` if the plugin doesn't say.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub publisher  : Option<String>,
+        /// The identifier of the product the plugin belongs to in the publisher's
+        /// store, which entitlements are checked against. `None` if the plugin
+        /// doesn't say.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub product_id : Option<String>,
+        /// The authors of the plugin (`["Jane Doe <jane@example.com>"]`).
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        pub authors    : Vec<String>,
+        /// The URL of the plugin's website. `None` if the plugin doesn't say.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub homepage   : Option<String>,
+        /// Keywords describing the plugin (`["audio", "reverb"]`), for applications and
+        /// stores to categorize and search plugins.
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        pub tags       : Vec<String>,
+        /// The hash of the signature of the plugin's entry point (16 hexadecimal digits),
+        /// written by `vplugin-package`. See [`HookSignature::hash`](crate::manifest::HookSignature::hash).
+        /// `None` if the package doesn't say.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub entry_signature: Option<String>,
+        /// The native libraries the object file of the plugin links to
+        /// (`["libssl.so.3", "vulkan-1.dll"]`), checked before it's loaded.
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        pub needs      : Vec<String>,
+        /// The plugins this plugin depends on, from the `[dependencies]` section of its
+        /// `metadata.toml`: their names, with the versions of them it works with as a
+        /// semantic version requirement (`"^1.2"`).
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        pub dependencies: BTreeMap<String, String>,
+        /// The capabilities the plugin needs, from the `[permissions]` section of its
+        /// `metadata.toml` (`"network"`, `"filesystem"`, ...), each with why it needs it.
+        /// Applications can refuse plugins asking for capabilities they don't grant, and
+        /// show the rest to the user.
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        pub permissions: BTreeMap<String, String>,
+        /// The settings the plugin declares in the `[settings]` section of its
+        /// `metadata.toml`. See [`SettingsSchema`](crate::settings::SettingsSchema).
+        #[serde(skip_serializing_if = "SettingsSchema::is_empty")]
+        pub settings   : SettingsSchema,
+        /// The default configuration of the plugin, from the `[config]` section of its
+        /// `metadata.toml`. It's handed to the plugin when it starts, overridden by the
+        /// configuration the application gives it.
+        #[serde(skip_serializing_if = "toml::Table::is_empty")]
+        pub config     : toml::Table,
+        /// Keys of the `metadata` table VPlugin doesn't know about, and those of the
+        /// `metadata.extra` table. See [`extra_iter`](crate::metadata::PluginMetadata::extra_iter).
+        #[serde(flatten)]
+        pub extra      : toml::Table
+}
+
+impl PluginMetadata {
+        /// Reads a metadata.toml file or returns an error. This is useful
+        /// for libraries that wish to make use of VPlugin's internals.
+        pub fn read_from_str<T: for<'a> serde::Deserialize<'a>>(string: &str) -> Result<T, VPluginError> {
+                let data: T = match toml::from_str(string) {
+                        Ok (t) => t,
+                        Err(e) => {
+                                log::error!("Couldn't read metadata file: {}", e);
+                                return Err(VPluginError::ParametersError)
+                        }
+                };
+
+                Ok(data)
+
+        }
+
+        /// Parses the contents of a `metadata.toml` file, like
+        /// [`read_from_str`](crate::metadata::PluginMetadata::read_from_str), but instead of
+        /// stopping at the first problem, every violation of the format is returned, along
+        /// with where it is in `string`. Packaging tools can use these to show precise errors.
+        /// 
+        /// The `filename` of the returned metadata is empty.
+        /// 
+        /// ## Example
+        /// ```rust
+        /// # use vplugin_core::metadata::PluginMetadata;
+        /// # let contents = "name = \"plugin\"\nversion = \"1.0.0\"\nobjfile = \"plugin.so\"";
+        /// match PluginMetadata::parse_with_diagnostics(contents) {
+        ///     Ok (metadata)    => println!("{}", metadata),
+        ///     Err(diagnostics) => for d in diagnostics {
+        ///         eprintln!("metadata.toml:{}", d);
+        ///     }
+        /// }
+        /// ```
+        pub fn parse_with_diagnostics(string: &str) -> Result<PluginMetadata, Vec<MetadataDiagnostic>> {
+                let document: Document = match toml::from_str(string) {
+                        Ok (document) => document,
+                        Err(e)        => return Err(vec![MetadataDiagnostic::new(string, e.message().to_owned(), e.span())])
+                };
+
+                let mut diagnostics = Vec::new();
+                let table = match document.metadata {
+                        Some(table) => table,
+                        None        => {
+                                diagnostics.push(MetadataDiagnostic::new(string, "Missing the `metadata` table".into(), None));
+                                return Err(diagnostics);
+                        }
+                };
+                let span   = table.span();
+                let mut fields = table.into_inner();
+
+                let mut required = |key: &str, check_spaces: bool| -> String {
+                        match fields.remove(key) {
+                                Some(value) => {
+                                        let span = value.span();
+                                        match value.into_inner() {
+                                                toml::Value::String(v) if v.is_empty() => {
+                                                        diagnostics.push(MetadataDiagnostic::new(string, format!("`{}` can't be empty", key), Some(span)));
+                                                        v
+                                                },
+                                                toml::Value::String(v) if check_spaces && v.contains(' ') => {
+                                                        diagnostics.push(MetadataDiagnostic::new(string, format!("`{}` can't contain spaces", key), Some(span)));
+                                                        v
+                                                },
+                                                toml::Value::String(v) => v,
+                                                other => {
+                                                        diagnostics.push(MetadataDiagnostic::new(
+                                                                string,
+                                                                format!("`{}` must be a string, found {}", key, other.type_str()),
+                                                                Some(span)
+                                                        ));
+                                                        String::new()
+                                                }
+                                        }
+                                },
+                                None => {
+                                        diagnostics.push(MetadataDiagnostic::new(string, format!("Missing the required `{}` field", key), Some(span.clone())));
+                                        String::new()
+                                }
+                        }
+                };
+                let name    = required("name", true);
+                let version = required("version", true);
+                let objfile = required("objfile", false);
+
+                let api_version_span = fields.get("api_version").map(|v| v.span());
+                let entry_signature_span = fields.get("entry_signature").map(|v| v.span());
+                let mut optional = |key: &str| -> Option<String> {
+                        match fields.remove(key).map(|v| (v.span(), v.into_inner())) {
+                                None                                  => None,
+                                Some((_, toml::Value::String(value))) => Some(value),
+                                Some((span, other)) => {
+                                        diagnostics.push(MetadataDiagnostic::new(
+                                                string,
+                                                format!("`{}` must be a string, found {}", key, other.type_str()),
+                                                Some(span)
+                                        ));
+                                        None
+                                }
+                        }
+                };
+                let description   = optional("description");
+                let requires_host = optional("requires_host");
+                let api_version   = optional("api_version");
+                let license       = optional("license");
+                let publisher     = optional("publisher");
+                let product_id    = optional("product_id");
+                let homepage      = optional("homepage");
+                let min_host_version = optional("min_host_version");
+                let entry_signature  = optional("entry_signature");
+
+                /* `min_host_version` is a shorthand for a `>=` requirement. */
+                let requires_host = match (requires_host, min_host_version) {
+                        (Some(requirement), Some(minimum)) => Some(format!(">={}, {}", minimum, requirement)),
+                        (None, Some(minimum))              => Some(format!(">={}", minimum)),
+                        (requirement, None)                => requirement
+                };
+
+                if let Some(api_version) = &api_version {
+                        let valid = api_version
+                                .split_once('.')
+                                .is_some_and(|(major, minor)| major.parse::<u16>().is_ok() && minor.parse::<u16>().is_ok());
+                        if !valid {
+                                diagnostics.push(MetadataDiagnostic::new(
+                                        string,
+                                        format!("`api_version` must be `major.minor` (Like \"1.2\"), found \"{}\"", api_version),
+                                        api_version_span
+                                ));
+                        }
+                }
+
+                if let Some(entry_signature) = &entry_signature {
+                        if entry_signature.len() != 16 || !entry_signature.bytes().all(|b| b.is_ascii_hexdigit()) {
+                                diagnostics.push(MetadataDiagnostic::new(
+                                        string,
+                                        format!("`entry_signature` must be 16 hexadecimal digits, found \"{}\"", entry_signature),
+                                        entry_signature_span
+                                ));
+                        }
+                }
+
+                let mut strings = |key: &str| -> Vec<String> {
+                        match fields.remove(key).map(|v| (v.span(), v.into_inner())) {
+                                None                                => Vec::new(),
+                                Some((span, toml::Value::Array(a))) => a
+                                        .into_iter()
+                                        .filter_map(|value| match value {
+                                                toml::Value::String(value) => Some(value),
+                                                other                      => {
+                                                        diagnostics.push(MetadataDiagnostic::new(
+                                                                string,
+                                                                format!("`{}` must only contain strings, found {}", key, other.type_str()),
+                                                                Some(span.clone())
+                                                        ));
+                                                        None
+                                                }
+                                        })
+                                        .collect(),
+                                Some((span, other)) => {
+                                        diagnostics.push(MetadataDiagnostic::new(
+                                                string,
+                                                format!("`{}` must be an array, found {}", key, other.type_str()),
+                                                Some(span)
+                                        ));
+                                        Vec::new()
+                                }
+                        }
+                };
+                let needs   = strings("needs");
+                let authors = strings("authors");
+                let tags    = strings("tags");
+
+                let extra_table = match fields.remove("extra").map(|v| (v.span(), v.into_inner())) {
+                        None                                => None,
+                        Some((span, toml::Value::Table(t))) => Some((span, t)),
+                        Some((span, other))                 => {
+                                diagnostics.push(MetadataDiagnostic::new(
+                                        string,
+                                        format!("`extra` must be a table, found {}", other.type_str()),
+                                        Some(span)
+                                ));
+                                None
+                        }
+                };
+                let mut extra: toml::Table = fields.into_iter().map(|(key, value)| (key, value.into_inner())).collect();
+                if let Some((span, table)) = extra_table {
+                        for (key, value) in table {
+                                if extra.contains_key(&key) {
+                                        diagnostics.push(MetadataDiagnostic::new(
+                                                string,
+                                                format!("`{}` is both in `metadata` and `metadata.extra`", key),
+                                                Some(span.clone())
+                                        ));
+                                        continue;
+                                }
+                                extra.insert(key, value);
+                        }
+                }
+
+                let mut dependencies = BTreeMap::new();
+                for (dependency, requirement) in document.dependencies.unwrap_or_default() {
+                        let span = requirement.span();
+                        match requirement.into_inner() {
+                                toml::Value::String(requirement) => { dependencies.insert(dependency, requirement); },
+                                other                            => diagnostics.push(MetadataDiagnostic::new(
+                                        string,
+                                        format!("Dependency `{}` must be a version requirement string, found {}", dependency, other.type_str()),
+                                        Some(span)
+                                ))
+                        }
+                }
+
+                let mut permissions = BTreeMap::new();
+                for (capability, reason) in document.permissions.unwrap_or_default() {
+                        let span = reason.span();
+                        match reason.into_inner() {
+                                toml::Value::String(reason) => { permissions.insert(capability, reason); },
+                                other                       => diagnostics.push(MetadataDiagnostic::new(
+                                        string,
+                                        format!("Permission `{}` must be a string saying why it's needed, found {}", capability, other.type_str()),
+                                        Some(span)
+                                ))
+                        }
+                }
+
+                let mut settings = SettingsSchema::default();
+                for (key, value) in document.settings.unwrap_or_default() {
+                        let span = value.span();
+                        let parsed = value
+                                .into_inner()
+                                .try_into::<Setting>()
+                                .map_err(|e| e.message().to_owned())
+                                .and_then(|setting| setting.check(&setting.default).map(|_| setting).map_err(|e| format!("invalid default: {}", e)));
+                        match parsed {
+                                Ok (setting) => { settings.settings.insert(key, setting); },
+                                Err(message) => diagnostics.push(MetadataDiagnostic::new(
+                                        string,
+                                        format!("Invalid setting `{}`: {}", key, message),
+                                        Some(span)
+                                ))
+                        }
+                }
+
+                if !diagnostics.is_empty() {
+                        return Err(diagnostics);
+                }
+                Ok(PluginMetadata {
+                        description,
+                        version,
+                        name,
+                        filename: PathBuf::new(),
+                        objfile,
+                        requires_host,
+                        api_version,
+                        license,
+                        publisher,
+                        product_id,
+                        authors,
+                        homepage,
+                        tags,
+                        entry_signature,
+                        needs,
+                        dependencies,
+                        permissions,
+                        settings,
+                        config  : document.config.unwrap_or_default(),
+                        extra
+                })
+        }
+
+        /// Returns an iterator over the keys of the `metadata` table that VPlugin doesn't
+        /// use itself, along with their values. Applications can use these to store their
+        /// own information about a plugin:
+        /// ```toml
+        /// [metadata]
+        /// name    = "ExamplePlugin"
+        /// version = "1.4.5"
+        /// objfile = "plugin.so"
+        /// 
+        /// myapp-category = "filters"
+        /// myapp-hidden   = false
+        /// ```
+        /// The keys of a `[metadata.extra]` table are returned the same way, which keeps
+        /// them apart from the fields VPlugin may add in the future.
+        pub fn extra_iter(&self) -> impl Iterator<Item = (&str, &toml::Value)> {
+                self.extra.iter().map(|(key, value)| (key.as_str(), value))
+        }
+
+        /// Returns the value of an application-specific key, if present.
+        pub fn extra(&self, key: &str) -> Option<&toml::Value> {
+                self.extra.get(key)
+        }
+
+        /// Returns the value of an application-specific key, if present and a string.
+        pub fn extra_str(&self, key: &str) -> Option<&str> {
+                self.extra(key).and_then(toml::Value::as_str)
+        }
+
+        /// Returns the value of an application-specific key, if present and a boolean.
+        pub fn extra_bool(&self, key: &str) -> Option<bool> {
+                self.extra(key).and_then(toml::Value::as_bool)
+        }
+
+        /// Returns the value of an application-specific key, if present and an integer.
+        pub fn extra_integer(&self, key: &str) -> Option<i64> {
+                self.extra(key).and_then(toml::Value::as_integer)
+        }
+}
+
+/// Prints a one-line summary of the plugin (`name v1.2.3 — description`).
+/// The alternate form (`{:#}`) prints every field on its own line instead.
+impl fmt::Display for PluginMetadata {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{} v{}", self.name, self.version)?;
+                if f.alternate() {
+                        writeln!(f)?;
+                        writeln!(f, "  Description: {}", self.description.as_deref().unwrap_or("-"))?;
+                        writeln!(f, "  Object file: {}", self.objfile)?;
+                        writeln!(f, "  Requires host: {}", self.requires_host.as_deref().unwrap_or("-"))?;
+                        writeln!(f, "  API version: {}", self.api_version.as_deref().unwrap_or("-"))?;
+                        writeln!(f, "  License: {}", self.license.as_deref().unwrap_or("-"))?;
+                        match self.authors.is_empty() {
+                                true  => writeln!(f, "  Authors: -")?,
+                                false => writeln!(f, "  Authors: {}", self.authors.join(", "))?
+                        }
+                        writeln!(f, "  Homepage: {}", self.homepage.as_deref().unwrap_or("-"))?;
+                        match self.tags.is_empty() {
+                                true  => writeln!(f, "  Tags: -")?,
+                                false => writeln!(f, "  Tags: {}", self.tags.join(", "))?
+                        }
+                        writeln!(f, "  Publisher: {}", self.publisher.as_deref().unwrap_or("-"))?;
+                        writeln!(f, "  Product: {}", self.product_id.as_deref().unwrap_or("-"))?;
+                        match self.needs.is_empty() {
+                                true  => writeln!(f, "  Needs: -")?,
+                                false => writeln!(f, "  Needs: {}", self.needs.join(", "))?
+                        }
+                        if self.dependencies.is_empty() {
+                                writeln!(f, "  Dependencies: -")?;
+                        } else {
+                                let dependencies: Vec<String> = self.dependencies
+                                        .iter()
+                                        .map(|(name, requirement)| format!("{} {}", name, requirement))
+                                        .collect();
+                                writeln!(f, "  Dependencies: {}", dependencies.join(", "))?;
+                        }
+                        match self.permissions.is_empty() {
+                                true  => write!(f, "  Permissions: -"),
+                                false => write!(f, "  Permissions: {}", self.permissions.keys().cloned().collect::<Vec<_>>().join(", "))
+                        }
+                } else {
+                        match &self.description {
+                                Some(description) => write!(f, " — {}", description),
+                                None              => Ok(())
+                        }
+                }
+        }
+}
+
+/* The layout of a metadata.toml file, keeping where every value is. */
+#[derive(Deserialize)]
+struct Document {
+        metadata: Option<Spanned<BTreeMap<String, Spanned<toml::Value>>>>,
+        dependencies: Option<BTreeMap<String, Spanned<toml::Value>>>,
+        permissions: Option<BTreeMap<String, Spanned<toml::Value>>>,
+        settings: Option<BTreeMap<String, Spanned<toml::Value>>>,
+        config: Option<toml::Table>,
+}
+
+/// A problem found by [`PluginMetadata::parse_with_diagnostics`](crate::metadata::PluginMetadata::parse_with_diagnostics).
+///
+/// `Display` prints it as `line:column: message`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataDiagnostic {
+        /// What is wrong.
+        pub message: String,
+        /// The byte range of the string the problem is in, if known.
+        pub span   : Option<Range<usize>>,
+        /// The line the problem starts at, starting from 1.
+        pub line   : usize,
+        /// The column the problem starts at, in characters and starting from 1.
+        pub column : usize,
+}
+
+impl MetadataDiagnostic {
+        fn new(string: &str, message: String, span: Option<Range<usize>>) -> Self {
+                let start  = span.as_ref().map_or(0, |span| span.start.min(string.len()));
+                let before = string.get(..start).unwrap_or("");
+                let line   = before.matches('\n').count() + 1;
+                let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+                Self { message, span, line, column }
+        }
+}
+
+impl fmt::Display for MetadataDiagnostic {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}:{}: {}", self.line, self.column, self.message)
+        }
+}
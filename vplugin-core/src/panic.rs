@@ -0,0 +1,113 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! Forwarding the panics of Rust plugins to the host, instead of them only being printed
+//! to stderr. Plugins invoke [`forward_panics!`](crate::forward_panics) once, which exports
+//! the symbol through which the host hands them its reporter:
+//! ```rust
+//! vplugin_core::export_plugin!();
+//! vplugin_core::forward_panics!();
+//!
+//! #[no_mangle]
+//! pub extern "C" fn vplugin_init() -> i32 {
+//!     vplugin_core::panic::catch(|| {
+//!         /* ... */
+//!         0
+//!     })
+//! }
+//! ```
+//! The message and location of every panic are then reported to the host before the usual
+//! panic message is printed, along with a backtrace of the plugin when built with the
+//! `backtrace` feature. Since unwinding out of an `extern "C"` function aborts the
+//! process, exported functions should also catch panics with [`catch`], so the host gets
+//! an error (`VPluginError::PluginPanicked`) instead.
+//!
+//! The panic hook is global to the copy of the standard library it's installed in, so this
+//! is only meant for plugins built as their own shared library.
+
+use std::ffi::{c_int, c_void};
+use std::panic::{self, UnwindSafe};
+use std::sync::{Mutex, Once};
+use crate::ffi::VStr;
+
+/// The symbol through which plugins receive the reporter of the host.
+pub const SET_PANIC_REPORTER_SYMBOL: &str = "vplugin_set_panic_reporter";
+
+/// Reports a panic to the host: its context, the panic message, the file, line and
+/// column it happened at (An empty file if unknown) and the backtrace of the panicking
+/// thread (Empty unless the plugin was built with the `backtrace` feature).
+pub type PanicReporter = unsafe extern "C" fn(*mut c_void, VStr<'_>, VStr<'_>, u32, u32, VStr<'_>);
+
+/* The reporter of the host and its context, stored as an address to be `Send`. */
+static REPORTER: Mutex<Option<(PanicReporter, usize)>> = Mutex::new(None);
+static INSTALL : Once = Once::new();
+
+/// Installs a panic hook reporting panics to `reporter`, before running the hook that was
+/// installed until now. Usually called through [`forward_panics!`](crate::forward_panics).
+pub fn install(reporter: PanicReporter, context: *mut c_void) {
+        *REPORTER.lock().unwrap_or_else(|e| e.into_inner()) = Some((reporter, context as usize));
+        INSTALL.call_once(|| {
+                let previous = panic::take_hook();
+                panic::set_hook(Box::new(move |info| {
+                        report(info);
+                        previous(info);
+                }));
+        });
+}
+
+/// Runs `f`, returning `-1` if it panicked instead of unwinding into the host. The panic
+/// is still reported to the host.
+pub fn catch<F: FnOnce() -> c_int + UnwindSafe>(f: F) -> c_int {
+        panic::catch_unwind(f).unwrap_or(-1)
+}
+
+#[allow(deprecated)] /* `PanicInfo` was renamed `PanicHookInfo`, which older compilers don't have. */
+fn report(info: &panic::PanicInfo<'_>) {
+        let (reporter, context) = match *REPORTER.lock().unwrap_or_else(|e| e.into_inner()) {
+                Some(reporter) => reporter,
+                None           => return
+        };
+        let payload = info.payload();
+        let message = payload
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("Box<dyn Any>");
+        let (file, line, column) = match info.location() {
+                Some(location) => (location.file(), location.line(), location.column()),
+                None           => ("", 0, 0)
+        };
+        #[cfg(feature = "backtrace")]
+        let backtrace = std::backtrace::Backtrace::force_capture().to_string();
+        #[cfg(not(feature = "backtrace"))]
+        let backtrace = String::new();
+        unsafe { reporter(context as *mut c_void, message.into(), file.into(), line, column, backtrace.as_str().into()) }
+}
+
+/// Exports `vplugin_set_panic_reporter`, through which the host hands the plugin its panic
+/// reporter. See the [module documentation](crate::panic).
+#[macro_export]
+macro_rules! forward_panics {
+        () => {
+                #[no_mangle]
+                pub unsafe extern "C" fn vplugin_set_panic_reporter(
+                        reporter: $crate::panic::PanicReporter,
+                        context : *mut ::std::ffi::c_void
+                ) {
+                        $crate::panic::install(reporter, context)
+                }
+        };
+}
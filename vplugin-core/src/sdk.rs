@@ -0,0 +1,211 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! What plugins export so hosts can check they were built against a compatible
+//! version of VPlugin. Plugins only have to invoke [`export_plugin!`](crate::export_plugin)
+//! once.
+
+/// The version of the SDK (This crate) a host or plugin was built against.
+pub const SDK_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// [`SDK_VERSION`], nul-terminated, as returned to the host.
+#[doc(hidden)]
+pub const SDK_VERSION_NUL: &str = concat!(env!("CARGO_PKG_VERSION"), "\0");
+
+/// The symbol through which a plugin reports its [`SDK_VERSION`].
+pub const SDK_VERSION_SYMBOL: &str = "vplugin_sdk_version";
+
+/// The `rustc --version` of the compiler that built the SDK, and so the plugin or host
+/// using it. Empty if it couldn't be determined.
+pub const RUSTC_VERSION: &str = env!("VPLUGIN_RUSTC_VERSION");
+
+/// [`RUSTC_VERSION`], nul-terminated, as returned to the host.
+#[doc(hidden)]
+pub const RUSTC_VERSION_NUL: &str = concat!(env!("VPLUGIN_RUSTC_VERSION"), "\0");
+
+/// The symbols through which a Rust ABI plugin reports its toolchain: the compiler
+/// version and the [`layout_hash`].
+pub const RUSTC_VERSION_SYMBOL: &str = "vplugin_rustc_version";
+pub const LAYOUT_HASH_SYMBOL: &str = "vplugin_layout_hash";
+
+/// The symbol of the [`SdkInfo`] a plugin exports.
+pub const SDK_INFO_SYMBOL: &str = "vplugin_sdk_info";
+
+/// ## SdkInfo
+/// Everything [`export_plugin!`](crate::export_plugin) reports, exported as data rather
+/// than functions so hosts can read it from the object file of a plugin without loading
+/// it, and so without running any of its code. Strings are nul-padded.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SdkInfo {
+        /// [`SdkInfo::MAGIC`], to tell the structure apart from anything else.
+        pub magic        : [u8; 8],
+        /// The [`SDK_VERSION`] of the plugin.
+        pub sdk_version  : [u8; 32],
+        /// The [`RUSTC_VERSION`] of Rust ABI plugins, empty for others.
+        pub rustc_version: [u8; 96],
+        /// The [`layout_hash`] of Rust ABI plugins, 0 for others.
+        pub layout_hash  : u64,
+}
+
+impl SdkInfo {
+        /// The first bytes of every `SdkInfo`. The last one is the version of the structure.
+        pub const MAGIC: [u8; 8] = *b"VPLSDK\0\x01";
+
+        /// The information of a plugin (Or host) built with this version of the SDK, which
+        /// uses the Rust ABI if `rust_abi` is set.
+        pub const fn new(rust_abi: bool) -> Self {
+                Self {
+                        magic        : Self::MAGIC,
+                        sdk_version  : padded(SDK_VERSION),
+                        rustc_version: if rust_abi { padded(RUSTC_VERSION) } else { [0; 96] },
+                        layout_hash  : if rust_abi { layout_hash() } else { 0 },
+                }
+        }
+
+        /// Reads an `SdkInfo` from `bytes`, the contents of the symbol in an object file whose
+        /// byte order is little endian if `little_endian` is set. `None` is returned if `bytes`
+        /// doesn't hold one.
+        pub fn from_bytes(bytes: &[u8], little_endian: bool) -> Option<Self> {
+                if bytes.len() < std::mem::size_of::<Self>() || bytes[..8] != Self::MAGIC {
+                        return None;
+                }
+                let layout_hash: [u8; 8] = bytes[136..144].try_into().ok()?;
+                Some(Self {
+                        magic        : Self::MAGIC,
+                        sdk_version  : bytes[8..40].try_into().ok()?,
+                        rustc_version: bytes[40..136].try_into().ok()?,
+                        layout_hash  : match little_endian {
+                                true  => u64::from_le_bytes(layout_hash),
+                                false => u64::from_be_bytes(layout_hash)
+                        },
+                })
+        }
+
+        /// The version of the SDK the plugin was built against.
+        pub fn sdk_version(&self) -> String {
+                unpadded(&self.sdk_version)
+        }
+
+        /// The compiler version and layout hash of Rust ABI plugins, `None` for others.
+        pub fn toolchain(&self) -> Option<(String, u64)> {
+                match self.layout_hash {
+                        0 => None,
+                        _ => Some((unpadded(&self.rustc_version), self.layout_hash))
+                }
+        }
+}
+
+/* `string` as a nul-padded array, truncated if it's too long. */
+const fn padded<const N: usize>(string: &str) -> [u8; N] {
+        let bytes = string.as_bytes();
+        let mut array = [0; N];
+        let mut i = 0;
+        while i < bytes.len() && i + 1 < N {
+                array[i] = bytes[i];
+                i += 1;
+        }
+        array
+}
+
+fn unpadded(array: &[u8]) -> String {
+        let end = array.iter().position(|b| *b == 0).unwrap_or(array.len());
+        String::from_utf8_lossy(&array[..end]).into_owned()
+}
+
+/// Hashes the size and alignment of the types commonly passed between hosts and Rust
+/// ABI plugins. Those aren't guaranteed to be the same between two compilers (Or two
+/// sets of compiler flags), so a plugin and host reporting different hashes can't
+/// exchange them safely.
+pub const fn layout_hash() -> u64 {
+        use std::mem::{align_of, size_of};
+        use crate::{PluginMetadata, PluginState, VPluginError};
+
+        let layouts = [
+                (size_of::<String>(), align_of::<String>()),
+                (size_of::<Vec<u8>>(), align_of::<Vec<u8>>()),
+                (size_of::<&str>(), align_of::<&str>()),
+                (size_of::<Option<Box<u8>>>(), align_of::<Option<Box<u8>>>()),
+                (size_of::<Box<dyn std::any::Any>>(), align_of::<Box<dyn std::any::Any>>()),
+                (size_of::<PluginMetadata>(), align_of::<PluginMetadata>()),
+                (size_of::<PluginState>(), align_of::<PluginState>()),
+                (size_of::<VPluginError>(), align_of::<VPluginError>()),
+                (size_of::<Result<(), VPluginError>>(), align_of::<Result<(), VPluginError>>()),
+        ];
+
+        /* FNV-1a */
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let mut i = 0;
+        while i < layouts.len() {
+                hash = (hash ^ layouts[i].0 as u64).wrapping_mul(0x100000001b3);
+                hash = (hash ^ layouts[i].1 as u64).wrapping_mul(0x100000001b3);
+                i += 1;
+        }
+        hash
+}
+
+/// Exports the information VPlugin checks when loading a plugin, like the version
+/// of the SDK the plugin was built against. Hosts refuse plugins built against an
+/// incompatible SDK with `VPluginError::SdkVersionMismatch`, instead of crashing
+/// once they exchange data whose layout changed.
+///
+/// Invoke it once, at the root of the plugin's crate:
+/// ```rust
+/// vplugin_core::export_plugin!();
+///
+/// #[no_mangle]
+/// pub extern "C" fn vplugin_init() -> i32 {
+///     0
+/// }
+/// ```
+///
+/// Plugins exchanging Rust types (`String`, `Vec`, trait objects, ...) with the host
+/// instead of only using the C ABI should use `export_plugin!(rust_abi)`. The compiler
+/// version and the layout of common types are then exported too, and hosts refuse the
+/// plugin with `VPluginError::ToolchainMismatch` if they don't match their own, since
+/// the layout of Rust types is only stable within the same compiler.
+///
+/// Both also export a [`SdkInfo`] holding the same information, which hosts read without
+/// loading the plugin.
+#[macro_export]
+macro_rules! export_plugin {
+        () => {
+                $crate::export_plugin!(@sdk false);
+        };
+        (rust_abi) => {
+                $crate::export_plugin!(@sdk true);
+
+                #[no_mangle]
+                pub extern "C" fn vplugin_rustc_version() -> *const ::std::ffi::c_char {
+                        $crate::sdk::RUSTC_VERSION_NUL.as_ptr().cast()
+                }
+
+                #[no_mangle]
+                pub extern "C" fn vplugin_layout_hash() -> u64 {
+                        $crate::sdk::layout_hash()
+                }
+        };
+        (@sdk $rust_abi:expr) => {
+                #[no_mangle]
+                pub extern "C" fn vplugin_sdk_version() -> *const ::std::ffi::c_char {
+                        $crate::sdk::SDK_VERSION_NUL.as_ptr().cast()
+                }
+
+                #[no_mangle]
+                #[allow(non_upper_case_globals)]
+                pub static vplugin_sdk_info: $crate::sdk::SdkInfo = $crate::sdk::SdkInfo::new($rust_abi);
+        };
+}
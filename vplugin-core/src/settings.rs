@@ -0,0 +1,158 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+//! The settings a plugin declares in the `[settings]` section of its `metadata.toml`,
+//! so the application can show and edit them without knowing the plugin.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use serde_derive::{Deserialize, Serialize};
+use crate::error::VPluginError;
+
+/// ## SettingsSchema
+/// The settings of a plugin, by key, as declared in its `metadata.toml`:
+/// ```toml
+/// [settings.font_size]
+/// type        = "integer"
+/// default     = 12
+/// min         = 6
+/// max         = 72
+/// description = "Size of the text, in points"
+///
+/// [settings.theme]
+/// type    = "string"
+/// default = "dark"
+/// choices = ["dark", "light"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SettingsSchema {
+        pub settings: BTreeMap<String, Setting>
+}
+
+/// A single setting of a [`SettingsSchema`](crate::settings::SettingsSchema).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Setting {
+        /// The type of the values of the setting.
+        #[serde(rename = "type")]
+        pub kind       : SettingKind,
+        /// The value used until the user changes it.
+        pub default    : toml::Value,
+        /// What the setting does, for the application to show next to it.
+        pub description: Option<String>,
+        /// The smallest value allowed, for numeric settings.
+        pub min        : Option<f64>,
+        /// The largest value allowed, for numeric settings.
+        pub max        : Option<f64>,
+        /// The only values allowed. Empty if any value of the right type is.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub choices    : Vec<toml::Value>,
+}
+
+/// The type of a [`Setting`](crate::settings::Setting).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SettingKind {
+        Boolean,
+        Integer,
+        /// Integers are accepted too.
+        Float,
+        String,
+}
+
+impl fmt::Display for SettingKind {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(match self {
+                        SettingKind::Boolean => "boolean",
+                        SettingKind::Integer => "integer",
+                        SettingKind::Float   => "float",
+                        SettingKind::String  => "string",
+                })
+        }
+}
+
+impl Setting {
+        /// Checks that `value` can be given to the setting, returning why not otherwise.
+        pub fn check(&self, value: &toml::Value) -> Result<(), String> {
+                let number = match (self.kind, value) {
+                        (SettingKind::Boolean, toml::Value::Boolean(_)) => None,
+                        (SettingKind::String,  toml::Value::String(_))  => None,
+                        (SettingKind::Integer, toml::Value::Integer(v)) => Some(*v as f64),
+                        (SettingKind::Float,   toml::Value::Integer(v)) => Some(*v as f64),
+                        (SettingKind::Float,   toml::Value::Float(v))   => Some(*v),
+                        (kind, other) => return Err(format!("expected {}, found {}", kind, other.type_str()))
+                };
+                if let Some(number) = number {
+                        if let Some(min) = self.min.filter(|min| number < *min) {
+                                return Err(format!("{} is below the minimum of {}", value, min));
+                        }
+                        if let Some(max) = self.max.filter(|max| number > *max) {
+                                return Err(format!("{} is above the maximum of {}", value, max));
+                        }
+                }
+                if !self.choices.is_empty() && !self.choices.contains(value) {
+                        return Err(format!("{} is not one of the allowed choices", value));
+                }
+                Ok(())
+        }
+}
+
+impl SettingsSchema {
+        /// Returns whether the plugin has no settings.
+        pub fn is_empty(&self) -> bool {
+                self.settings.is_empty()
+        }
+
+        /// Returns the setting `key`, if the plugin has it.
+        pub fn get(&self, key: &str) -> Option<&Setting> {
+                self.settings.get(key)
+        }
+
+        /// Returns the default value of every setting.
+        pub fn defaults(&self) -> toml::Table {
+                self.settings
+                        .iter()
+                        .map(|(key, setting)| (key.clone(), setting.default.clone()))
+                        .collect()
+        }
+
+        /// Checks that the default of every setting is valid for it, returning the first
+        /// one that isn't as `VPluginError::InvalidSetting`.
+        pub fn validate(&self) -> Result<(), VPluginError> {
+                for (key, setting) in &self.settings {
+                        if let Err(reason) = setting.check(&setting.default) {
+                                return Err(VPluginError::InvalidSetting {
+                                        key   : key.clone(),
+                                        reason: format!("invalid default: {}", reason)
+                                });
+                        }
+                }
+                Ok(())
+        }
+
+        /// Checks that `key` is a setting of the plugin and `value` is valid for it.
+        pub fn check(&self, key: &str, value: &toml::Value) -> Result<(), VPluginError> {
+                let reason = match self.get(key) {
+                        Some(setting) => match setting.check(value) {
+                                Ok (_)      => return Ok(()),
+                                Err(reason) => reason
+                        },
+                        None => "no such setting".into()
+                };
+                Err(VPluginError::InvalidSetting { key: key.into(), reason })
+        }
+}
@@ -0,0 +1,83 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+use std::fmt;
+use serde_derive::{Deserialize, Serialize};
+
+/// ## PluginState
+/// The lifecycle of a plugin. A plugin starts as `Loaded`, becomes `Started`
+/// once its entry point succeeds and `Terminated` after its destructor ran.
+/// A terminated plugin can be started again, unless VPlugin was built with
+/// the `non_reusable_plugins` feature.
+/// 
+/// `Invalid` and `Quarantined` plugins can't be used anymore: The former
+/// failed to load (Or was terminated and can't be reused), while the latter
+/// was isolated by the host or VPlugin after misbehaving.
+/// 
+/// Allowed transitions:
+/// ```text
+/// Loaded ──► Started ──► Terminated ──► Started ...
+///    │          │            │
+///    └──────────┴────────────┴──► Invalid / Quarantined
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[repr(C)]
+pub enum PluginState {
+        /// The plugin is loaded but its entry point hasn't been called yet.
+        Loaded,
+        /// The plugin's entry point was called successfully.
+        Started,
+        /// The plugin's destructor was called.
+        Terminated,
+        /// The plugin can't be used.
+        Invalid,
+        /// The plugin was isolated after misbehaving and can't be used.
+        Quarantined,
+}
+
+impl PluginState {
+        /// Returns whether a plugin in this state may move to `next`.
+        pub fn can_transition_to(self, next: PluginState) -> bool {
+                use PluginState::*;
+                match (self, next) {
+                        (Loaded, Started)     => true,
+                        (Terminated, Started) => true,
+                        (Started, Terminated) => true,
+                        (Quarantined, _)      => false,
+                        (_, Invalid)          => true,
+                        (_, Quarantined)      => true,
+                        _                     => false
+                }
+        }
+
+        /// Returns whether the plugin can still be used in any way.
+        pub fn is_usable(self) -> bool {
+                !matches!(self, PluginState::Invalid | PluginState::Quarantined)
+        }
+}
+
+impl fmt::Display for PluginState {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let name = match self {
+                        PluginState::Loaded      => "loaded",
+                        PluginState::Started     => "started",
+                        PluginState::Terminated  => "terminated",
+                        PluginState::Invalid     => "invalid",
+                        PluginState::Quarantined => "quarantined",
+                };
+                f.pad(name)
+        }
+}
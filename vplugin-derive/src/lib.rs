@@ -0,0 +1,188 @@
+/*
+ * Copyright 2022-2023 Aggelos Tselios.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+*/
+
+#![warn(clippy::all)]
+
+//! # VPlugin Derive
+//! The derive macros of [VPlugin](https://crates.io/crates/vplugin), reexported by it with
+//! the `derive` feature. Don't depend on this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, LitStr};
+
+/// Implements `vplugin::Shareable`, sending the value to the plugin through a hook
+/// taking its bytes and their length:
+/// ```c
+/// int vplugin_receive_editor_config(const uint8_t* data, size_t len);
+/// ```
+/// The hook returns `0` if it accepted the value. Plugins decode it with the generated
+/// `receive()` function, which checks the length before reading anything.
+///
+/// ## Attributes
+/// - `#[shareable(hook = "name")]` - The hook receiving the value, `vplugin_receive_`
+///   followed by the name of the type in snake case by default.
+/// - `#[shareable(serde)]` - Sends the value serialized (As TOML) instead of its bytes. The
+///   type must implement `Serialize` and `Deserialize`. Without it, the type must be plain
+///   old data: a `#[repr(C)]` struct whose fields all implement `vplugin::shareable::PlainData`
+///   (Integers, floats or arrays of them), without any padding between them. Every bit pattern is then a valid value, and
+///   every byte sent is initialized. Anything else is a compile error.
+#[proc_macro_derive(Shareable, attributes(shareable))]
+pub fn derive_shareable(input: TokenStream) -> TokenStream {
+        let input = parse_macro_input!(input as DeriveInput);
+        match shareable(&input) {
+                Ok (tokens) => tokens.into(),
+                Err(e)      => e.to_compile_error().into()
+        }
+}
+
+/* How the value is sent. */
+enum Encoding {
+        Pod,
+        Serde,
+}
+
+fn shareable(input: &DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+        let name = &input.ident;
+        let mut hook = LitStr::new(&format!("vplugin_receive_{}", snake_case(&name.to_string())), Span::call_site());
+        let mut encoding = Encoding::Pod;
+
+        for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("shareable")) {
+                attr.parse_nested_meta(|meta| {
+                        if meta.path.is_ident("hook") {
+                                hook = meta.value()?.parse()?;
+                                Ok(())
+                        } else if meta.path.is_ident("serde") {
+                                encoding = Encoding::Serde;
+                                Ok(())
+                        } else {
+                                Err(meta.error("expected `hook = \"...\"` or `serde`"))
+                        }
+                })?;
+        }
+
+        let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+        let private = quote!(::vplugin::shareable::__private);
+        let (send, receive, checks, padding) = match encoding {
+                Encoding::Pod => {
+                        let fields = pod_fields(input)?;
+                        let message = format!("`{}` has padding, which can't be shared as plain old data", name);
+                        (
+                                quote! {
+                                        let () = Self::__VPLUGIN_NO_PADDING;
+                                        unsafe { #private::send_pod(self, plugin, #hook) }
+                                },
+                                quote! {
+                                        let () = Self::__VPLUGIN_NO_PADDING;
+                                        #private::receive_pod(data, len)
+                                },
+                                quote! {
+                                        const _: () = {
+                                                #[allow(dead_code)]
+                                                fn assert_plain_data #impl_generics () #where_clause {
+                                                        fn plain_data<T: ::vplugin::shareable::PlainData>() {}
+                                                        #( plain_data::<#fields>(); )*
+                                                }
+                                        };
+                                },
+                                quote! {
+                                        #[doc(hidden)]
+                                        const __VPLUGIN_NO_PADDING: () = ::std::assert!(
+                                                ::std::mem::size_of::<Self>() == 0 #( + ::std::mem::size_of::<#fields>() )*,
+                                                #message
+                                        );
+                                }
+                        )
+                },
+                Encoding::Serde => (
+                        quote!(#private::send_serialized(self, plugin, #hook)),
+                        quote!(#private::receive_serialized(data, len)),
+                        quote!(),
+                        quote!()
+                )
+        };
+
+        Ok(quote! {
+                #checks
+
+                impl #impl_generics ::vplugin::Shareable for #name #ty_generics #where_clause {
+                        fn send(&mut self, plugin: &::vplugin::Plugin) {
+                                #send
+                        }
+
+                        unsafe fn send_ptr(ptr: *mut Self, plugin: &::vplugin::Plugin) {
+                                <Self as ::vplugin::Shareable>::send(&mut *ptr, plugin)
+                        }
+                }
+
+                impl #impl_generics #name #ty_generics #where_clause {
+                        /// The hook the value is sent to.
+                        pub const SHAREABLE_HOOK: &'static str = #hook;
+
+                        #padding
+
+                        /// Decodes a value sent by the host, from the hook receiving it.
+                        ///
+                        /// # Safety
+                        /// `data` must point to `len` readable bytes, sent by `Shareable::send`.
+                        pub unsafe fn receive(data: *const u8, len: usize) -> ::std::result::Result<Self, ::vplugin::VPluginError> {
+                                #receive
+                        }
+                }
+        })
+}
+
+/* The types of the fields of a plain old data struct, which must be `#[repr(C)]`. */
+fn pod_fields(input: &DeriveInput) -> Result<Vec<&syn::Type>, Error> {
+        let fields = match &input.data {
+                Data::Struct(data) => &data.fields,
+                _ => return Err(Error::new_spanned(&input.ident, "only structs can be shared as plain old data, use #[shareable(serde)]"))
+        };
+        let mut repr_c = false;
+        for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("repr")) {
+                attr.parse_nested_meta(|meta| {
+                        if meta.path.is_ident("C") || meta.path.is_ident("transparent") {
+                                repr_c = true;
+                        }
+                        Ok(())
+                })?;
+        }
+        if !repr_c {
+                return Err(Error::new_spanned(&input.ident, "plain old data must be #[repr(C)], or use #[shareable(serde)]"));
+        }
+        Ok(match fields {
+                Fields::Named(fields)   => fields.named.iter().map(|field| &field.ty).collect(),
+                Fields::Unnamed(fields) => fields.unnamed.iter().map(|field| &field.ty).collect(),
+                Fields::Unit            => Vec::new()
+        })
+}
+
+/* `EditorConfig` -> `editor_config` */
+fn snake_case(name: &str) -> String {
+        let mut snake = String::new();
+        for (i, c) in name.char_indices() {
+                if c.is_uppercase() {
+                        if i > 0 && !name[..i].ends_with('_') {
+                                snake.push('_');
+                        }
+                        snake.extend(c.to_lowercase());
+                } else {
+                        snake.push(c);
+                }
+        }
+        snake
+}